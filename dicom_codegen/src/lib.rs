@@ -276,6 +276,165 @@ pub fn dicom_tag_map_accessors(input: TokenStream) -> TokenStream {
 }
 
 
+/// 根据字段上的 `#[dicom(tag = tags::PATIENT_ID)]` 生成:
+/// - `TAGS`: 所有字段对应的 tag
+/// - `new(obj)`: 从 DICOM 对象读取各字段, 可用 `default = 1` 指定缺省值
+/// - `map_key(tag)`: tag 对应的 JSON key, 形如 "x00100020"
+/// - 以 tag 为 key 的 `Serialize` / `Deserialize`
+///
+/// 生成的代码依赖 `crate::dcmobj` 中的 `get_tag_value`, `json_key`, `json_key_tag`.
+#[proc_macro_derive(DicomTagFields, attributes(dicom))]
+pub fn dicom_tag_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_dicom_tag_fields(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+struct DicomField {
+    ident: syn::Ident,
+    tag: syn::Expr,
+    default: Option<syn::Expr>,
+}
+
+fn parse_dicom_fields(input: &DeriveInput) -> syn::Result<Vec<DicomField>> {
+    let syn::Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "DicomTagFields only supports structs",
+        ));
+    };
+    let mut fields = Vec::new();
+    for field in &data.fields {
+        let Some(ident) = field.ident.clone() else {
+            return Err(syn::Error::new_spanned(
+                field,
+                "DicomTagFields requires named fields",
+            ));
+        };
+        let mut tag = None;
+        let mut default = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("dicom")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    tag = Some(meta.value()?.parse::<syn::Expr>()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("default") {
+                    default = Some(meta.value()?.parse::<syn::Expr>()?);
+                    return Ok(());
+                }
+                Err(meta.error("expected `tag = ...` or `default = ...`"))
+            })?;
+        }
+        let Some(tag) = tag else {
+            return Err(syn::Error::new_spanned(
+                &ident,
+                "missing #[dicom(tag = ...)] attribute",
+            ));
+        };
+        fields.push(DicomField {
+            ident,
+            tag,
+            default,
+        });
+    }
+    Ok(fields)
+}
+
+fn expand_dicom_tag_fields(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let fields = parse_dicom_fields(input)?;
+    let count = fields.len();
+    let idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+    let tags: Vec<_> = fields.iter().map(|f| &f.tag).collect();
+    let defaults: Vec<_> = fields
+        .iter()
+        .map(|f| match &f.default {
+            Some(default) => quote! { #default },
+            None => quote! { ::core::default::Default::default() },
+        })
+        .collect();
+
+    Ok(quote! {
+        impl #name {
+            /// 所有字段对应的 tag, 按字段顺序
+            pub const TAGS: &'static [::dicom::core::Tag] = &[#(#tags),*];
+
+            pub fn new(obj: &::dicom::object::DefaultDicomObject) -> Self {
+                Self {
+                    #(#idents: crate::dcmobj::get_tag_value(#tags, obj, #defaults),)*
+                }
+            }
+
+            /// tag 对应的 JSON key, 不属于本结构的 tag 返回 None
+            pub fn map_key(tag: ::dicom::core::Tag) -> Option<String> {
+                Self::TAGS
+                    .contains(&tag)
+                    .then(|| crate::dcmobj::json_key(tag))
+            }
+        }
+
+        impl ::serde::Serialize for #name {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                use ::serde::ser::SerializeMap;
+                let mut state = s.serialize_map(Some(#count))?;
+                #(state.serialize_entry(&crate::dcmobj::json_key(#tags), &self.#idents)?;)*
+                state.end()
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for #name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                struct FieldsVisitor;
+
+                impl<'de> ::serde::de::Visitor<'de> for FieldsVisitor {
+                    type Value = #name;
+
+                    fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                        formatter.write_str(concat!("struct ", #name_str))
+                    }
+
+                    fn visit_map<V>(self, mut map: V) -> Result<#name, V::Error>
+                    where
+                        V: ::serde::de::MapAccess<'de>,
+                    {
+                        #(let mut #idents = None;)*
+                        while let Some(key) = map.next_key::<String>()? {
+                            match crate::dcmobj::json_key_tag(&key) {
+                                #(Some(tag) if tag == #tags => {
+                                    #idents = Some(map.next_value()?);
+                                })*
+                                _ => {
+                                    let _: ::serde::de::IgnoredAny = map.next_value()?;
+                                }
+                            }
+                        }
+                        Ok(#name {
+                            #(#idents: #idents.ok_or_else(|| {
+                                <V::Error as ::serde::de::Error>::custom(format!(
+                                    "missing field `{}`",
+                                    crate::dcmobj::json_key(#tags)
+                                ))
+                            })?,)*
+                        })
+                    }
+                }
+
+                deserializer.deserialize_map(FieldsVisitor)
+            }
+        }
+    })
+}
+
 #[proc_macro_attribute]
 pub fn log_execution(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let input_fn = parse_macro_input!(item as ItemFn);
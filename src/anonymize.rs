@@ -0,0 +1,981 @@
+use crate::error::DcmToolsError;
+use crate::uid_map::UidMap;
+use chrono::{Duration, NaiveDate};
+use clap::ValueEnum;
+use dicom::core::value::{DataSetSequence, PrimitiveValue, Value};
+use dicom::core::header::Header;
+use dicom::core::{DataElement, Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom_object::mem::InMemElement;
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// PS3.15 Table E.1-1 中的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// 替换为与 VR 相符的非空虚拟值
+    D,
+    /// 替换为空值
+    Z,
+    /// 删除
+    X,
+    /// 保留 (序列中的属性仍按本表处理)
+    K,
+    /// 清理: 文本中去掉身份信息, 日期按偏移量平移
+    C,
+    /// 替换为新的 UID, 同一个原 UID 总是得到同一个新 UID (见 UidMap)
+    U,
+}
+
+/// PS3.15 E.3 中可选的 Profile Option (不含像素清理与 Retain Safe Private)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum ProfileOption {
+    RetainUids,
+    RetainDeviceIdentity,
+    RetainInstitutionIdentity,
+    RetainPatientCharacteristics,
+    RetainLongitudinalFullDates,
+    RetainLongitudinalModifiedDates,
+    CleanDescriptors,
+    CleanStructuredContent,
+    CleanGraphics,
+}
+
+impl ProfileOption {
+    /// CID 7050 中的代码 (DCM)
+    fn code(self) -> (&'static str, &'static str) {
+        match self {
+            ProfileOption::RetainUids => ("113110", "Retain UIDs Option"),
+            ProfileOption::RetainDeviceIdentity => ("113109", "Retain Device Identity Option"),
+            ProfileOption::RetainInstitutionIdentity => ("113112", "Retain Institution Identity Option"),
+            ProfileOption::RetainPatientCharacteristics => ("113108", "Retain Patient Characteristics Option"),
+            ProfileOption::RetainLongitudinalFullDates => {
+                ("113106", "Retain Longitudinal Temporal Information Full Dates Option")
+            }
+            ProfileOption::RetainLongitudinalModifiedDates => {
+                ("113107", "Retain Longitudinal Temporal Information Modified Dates Option")
+            }
+            ProfileOption::CleanDescriptors => ("113105", "Clean Descriptors Option"),
+            ProfileOption::CleanStructuredContent => ("113104", "Clean Structured Content Option"),
+            ProfileOption::CleanGraphics => ("113103", "Clean Graphics Option"),
+        }
+    }
+}
+
+/// Table E.1-1 的一行: Basic Profile 动作, 以及选中某个 Option 时的动作
+pub struct ProfileEntry {
+    pub tag: Tag,
+    pub action: Action,
+    pub options: &'static [(ProfileOption, Action)],
+}
+
+const fn e(group: u16, element: u16, action: Action, options: &'static [(ProfileOption, Action)]) -> ProfileEntry {
+    ProfileEntry { tag: Tag(group, element), action, options }
+}
+
+use Action::{C, D, K, U, X, Z};
+use ProfileOption::*;
+
+const NONE: &[(ProfileOption, Action)] = &[];
+const DESC: &[(ProfileOption, Action)] = &[(CleanDescriptors, C)];
+const DATE: &[(ProfileOption, Action)] = &[(RetainLongitudinalFullDates, K), (RetainLongitudinalModifiedDates, C)];
+const DEVICE: &[(ProfileOption, Action)] = &[(RetainDeviceIdentity, K)];
+const INSTITUTION: &[(ProfileOption, Action)] = &[(RetainInstitutionIdentity, K)];
+const PATIENT: &[(ProfileOption, Action)] = &[(RetainPatientCharacteristics, K)];
+const PATIENT_DESC: &[(ProfileOption, Action)] = &[(RetainPatientCharacteristics, C), (CleanDescriptors, C)];
+const STRUCTURED: &[(ProfileOption, Action)] = &[(CleanStructuredContent, C)];
+const GRAPHICS: &[(ProfileOption, Action)] = &[(CleanGraphics, C)];
+const UID: &[(ProfileOption, Action)] = &[(RetainUids, K)];
+const DEVICE_UID: &[(ProfileOption, Action)] = &[(RetainDeviceIdentity, K), (RetainUids, K)];
+
+/// PS3.15 Table E.1-1 (Basic Profile 及 Option 列).
+/// 组合动作按不破坏 IOD 的方式取舍: Z/D → D, X/Z、X/D、X/Z/D → X, X/Z/U* → U;
+/// 60xx 组的 overlay 属性以 6000 组登记
+pub static BASIC_PROFILE: &[ProfileEntry] = &[
+    e(0x0008, 0x0012, X, DATE),  // Instance Creation Date
+    e(0x0008, 0x0013, X, DATE),  // Instance Creation Time
+    e(0x0008, 0x0014, U, UID),   // Instance Creator UID
+    e(0x0008, 0x0015, X, DATE),  // Instance Coercion DateTime
+    e(0x0008, 0x0017, U, UID),   // Acquisition UID
+    e(0x0008, 0x0018, U, UID),   // SOP Instance UID
+    e(0x0008, 0x0019, U, UID),   // Pyramid UID
+    e(0x0008, 0x0020, Z, DATE),  // Study Date
+    e(0x0008, 0x0021, X, DATE),  // Series Date
+    e(0x0008, 0x0022, X, DATE),  // Acquisition Date
+    e(0x0008, 0x0023, D, DATE),  // Content Date
+    e(0x0008, 0x0024, X, DATE),  // Overlay Date
+    e(0x0008, 0x0025, X, DATE),  // Curve Date
+    e(0x0008, 0x002A, X, DATE),  // Acquisition DateTime
+    e(0x0008, 0x0030, Z, DATE),  // Study Time
+    e(0x0008, 0x0031, X, DATE),  // Series Time
+    e(0x0008, 0x0032, X, DATE),  // Acquisition Time
+    e(0x0008, 0x0033, D, DATE),  // Content Time
+    e(0x0008, 0x0034, X, DATE),  // Overlay Time
+    e(0x0008, 0x0035, X, DATE),  // Curve Time
+    e(0x0008, 0x0050, Z, NONE),  // Accession Number
+    e(0x0008, 0x0051, X, NONE),  // Issuer of Accession Number Sequence
+    e(0x0008, 0x0058, U, UID),   // Failed SOP Instance UID List
+    e(0x0008, 0x0080, X, INSTITUTION), // Institution Name
+    e(0x0008, 0x0081, X, INSTITUTION), // Institution Address
+    e(0x0008, 0x0082, X, INSTITUTION), // Institution Code Sequence
+    e(0x0008, 0x0090, Z, NONE),  // Referring Physician's Name
+    e(0x0008, 0x0092, X, NONE),  // Referring Physician's Address
+    e(0x0008, 0x0094, X, NONE),  // Referring Physician's Telephone Numbers
+    e(0x0008, 0x0096, X, NONE),  // Referring Physician Identification Sequence
+    e(0x0008, 0x009C, Z, NONE),  // Consulting Physician's Name
+    e(0x0008, 0x009D, X, NONE),  // Consulting Physician Identification Sequence
+    e(0x0008, 0x010D, U, UID),   // Context Group Extension Creator UID
+    e(0x0008, 0x0201, X, DATE),  // Timezone Offset From UTC
+    e(0x0008, 0x1010, X, DEVICE), // Station Name
+    e(0x0008, 0x1030, X, DESC),  // Study Description
+    e(0x0008, 0x103E, X, DESC),  // Series Description
+    e(0x0008, 0x1040, X, INSTITUTION), // Institutional Department Name
+    e(0x0008, 0x1041, X, INSTITUTION), // Institutional Department Type Code Sequence
+    e(0x0008, 0x1048, X, NONE),  // Physician(s) of Record
+    e(0x0008, 0x1049, X, NONE),  // Physician(s) of Record Identification Sequence
+    e(0x0008, 0x1050, X, NONE),  // Performing Physician's Name
+    e(0x0008, 0x1052, X, NONE),  // Performing Physician Identification Sequence
+    e(0x0008, 0x1060, X, NONE),  // Name of Physician(s) Reading Study
+    e(0x0008, 0x1062, X, NONE),  // Physician(s) Reading Study Identification Sequence
+    e(0x0008, 0x1070, X, NONE),  // Operators' Name
+    e(0x0008, 0x1072, X, NONE),  // Operator Identification Sequence
+    e(0x0008, 0x1080, X, PATIENT_DESC), // Admitting Diagnoses Description
+    e(0x0008, 0x1084, X, PATIENT_DESC), // Admitting Diagnoses Code Sequence
+    e(0x0008, 0x1110, X, NONE),  // Referenced Study Sequence
+    e(0x0008, 0x1111, X, NONE),  // Referenced Performed Procedure Step Sequence
+    e(0x0008, 0x1120, X, NONE),  // Referenced Patient Sequence
+    e(0x0008, 0x1140, U, NONE),  // Referenced Image Sequence
+    e(0x0008, 0x1155, U, UID),   // Referenced SOP Instance UID
+    e(0x0008, 0x1195, U, UID),   // Transaction UID
+    e(0x0008, 0x2111, X, DESC),  // Derivation Description
+    e(0x0008, 0x2112, U, NONE),  // Source Image Sequence
+    e(0x0008, 0x3010, U, UID),   // Irradiation Event UID
+    e(0x0008, 0x4000, X, DESC),  // Identifying Comments
+    e(0x0008, 0x9123, U, UID),   // Creator-Version UID
+    e(0x0010, 0x0010, Z, NONE),  // Patient's Name
+    e(0x0010, 0x0020, Z, NONE),  // Patient ID
+    e(0x0010, 0x0021, X, NONE),  // Issuer of Patient ID
+    e(0x0010, 0x0024, X, NONE),  // Issuer of Patient ID Qualifiers Sequence
+    e(0x0010, 0x0026, X, NONE),  // Source Patient Group Identification Sequence
+    e(0x0010, 0x0027, X, NONE),  // Group of Patients Identification Sequence
+    e(0x0010, 0x0030, Z, NONE),  // Patient's Birth Date
+    e(0x0010, 0x0032, X, NONE),  // Patient's Birth Time
+    e(0x0010, 0x0033, X, NONE),  // Patient's Birth Date in Alternative Calendar
+    e(0x0010, 0x0034, X, NONE),  // Patient's Death Date in Alternative Calendar
+    e(0x0010, 0x0035, X, NONE),  // Patient's Alternative Calendar
+    e(0x0010, 0x0040, Z, PATIENT), // Patient's Sex
+    e(0x0010, 0x0050, X, NONE),  // Patient's Insurance Plan Code Sequence
+    e(0x0010, 0x0101, X, NONE),  // Patient's Primary Language Code Sequence
+    e(0x0010, 0x0102, X, NONE),  // Patient's Primary Language Modifier Code Sequence
+    e(0x0010, 0x1000, X, NONE),  // Other Patient IDs
+    e(0x0010, 0x1001, X, NONE),  // Other Patient Names
+    e(0x0010, 0x1002, X, NONE),  // Other Patient IDs Sequence
+    e(0x0010, 0x1005, X, NONE),  // Patient's Birth Name
+    e(0x0010, 0x1010, X, PATIENT), // Patient's Age
+    e(0x0010, 0x1020, X, PATIENT), // Patient's Size
+    e(0x0010, 0x1030, X, PATIENT), // Patient's Weight
+    e(0x0010, 0x1040, X, NONE),  // Patient's Address
+    e(0x0010, 0x1050, X, NONE),  // Insurance Plan Identification
+    e(0x0010, 0x1060, X, NONE),  // Patient's Mother's Birth Name
+    e(0x0010, 0x1080, X, NONE),  // Military Rank
+    e(0x0010, 0x1081, X, NONE),  // Branch of Service
+    e(0x0010, 0x1090, X, NONE),  // Medical Record Locator
+    e(0x0010, 0x1100, X, NONE),  // Referenced Patient Photo Sequence
+    e(0x0010, 0x2000, X, PATIENT_DESC), // Medical Alerts
+    e(0x0010, 0x2110, X, PATIENT_DESC), // Allergies
+    e(0x0010, 0x2150, X, NONE),  // Country of Residence
+    e(0x0010, 0x2152, X, NONE),  // Region of Residence
+    e(0x0010, 0x2154, X, NONE),  // Patient's Telephone Numbers
+    e(0x0010, 0x2155, X, NONE),  // Patient's Telecom Information
+    e(0x0010, 0x2160, X, PATIENT), // Ethnic Group
+    e(0x0010, 0x2180, X, PATIENT_DESC), // Occupation
+    e(0x0010, 0x21A0, X, PATIENT), // Smoking Status
+    e(0x0010, 0x21B0, X, PATIENT_DESC), // Additional Patient History
+    e(0x0010, 0x21C0, X, PATIENT), // Pregnancy Status
+    e(0x0010, 0x21D0, X, DATE),  // Last Menstrual Date
+    e(0x0010, 0x21F0, X, NONE),  // Patient's Religious Preference
+    e(0x0010, 0x2203, X, PATIENT), // Patient's Sex Neutered
+    e(0x0010, 0x2297, X, NONE),  // Responsible Person
+    e(0x0010, 0x2299, X, NONE),  // Responsible Organization
+    e(0x0010, 0x4000, X, DESC),  // Patient Comments
+    e(0x0012, 0x0010, D, NONE),  // Clinical Trial Sponsor Name
+    e(0x0012, 0x0020, D, NONE),  // Clinical Trial Protocol ID
+    e(0x0012, 0x0021, Z, NONE),  // Clinical Trial Protocol Name
+    e(0x0012, 0x0030, Z, NONE),  // Clinical Trial Site ID
+    e(0x0012, 0x0031, Z, NONE),  // Clinical Trial Site Name
+    e(0x0012, 0x0040, D, NONE),  // Clinical Trial Subject ID
+    e(0x0012, 0x0042, D, NONE),  // Clinical Trial Subject Reading ID
+    e(0x0012, 0x0050, Z, NONE),  // Clinical Trial Time Point ID
+    e(0x0012, 0x0051, X, DESC),  // Clinical Trial Time Point Description
+    e(0x0012, 0x0060, Z, NONE),  // Clinical Trial Coordinating Center Name
+    e(0x0012, 0x0071, X, NONE),  // Clinical Trial Series ID
+    e(0x0012, 0x0072, X, DESC),  // Clinical Trial Series Description
+    e(0x0012, 0x0081, D, NONE),  // Clinical Trial Protocol Ethics Committee Name
+    e(0x0012, 0x0082, Z, NONE),  // Clinical Trial Protocol Ethics Committee Approval Number
+    e(0x0014, 0x407C, X, DATE),  // Calibration Time
+    e(0x0014, 0x407E, X, DATE),  // Calibration Date
+    e(0x0016, 0x002B, X, NONE),  // Maker Note
+    e(0x0016, 0x004B, X, NONE),  // Device Setting Description
+    e(0x0016, 0x004D, X, NONE),  // Camera Owner Name
+    e(0x0016, 0x004E, X, NONE),  // Lens Specification
+    e(0x0016, 0x004F, X, NONE),  // Lens Make
+    e(0x0016, 0x0050, X, NONE),  // Lens Model
+    e(0x0016, 0x0051, X, NONE),  // Lens Serial Number
+    e(0x0016, 0x0070, X, NONE),  // GPS Version ID
+    e(0x0016, 0x0071, X, NONE),  // GPS Latitude Ref
+    e(0x0016, 0x0072, X, NONE),  // GPS Latitude
+    e(0x0016, 0x0073, X, NONE),  // GPS Longitude Ref
+    e(0x0016, 0x0074, X, NONE),  // GPS Longitude
+    e(0x0016, 0x0075, X, NONE),  // GPS Altitude Ref
+    e(0x0016, 0x0076, X, NONE),  // GPS Altitude
+    e(0x0016, 0x0077, X, NONE),  // GPS Time Stamp
+    e(0x0016, 0x0078, X, NONE),  // GPS Satellites
+    e(0x0016, 0x0079, X, NONE),  // GPS Status
+    e(0x0016, 0x007A, X, NONE),  // GPS Measure Mode
+    e(0x0016, 0x007B, X, NONE),  // GPS DOP
+    e(0x0016, 0x007C, X, NONE),  // GPS Speed Ref
+    e(0x0016, 0x007D, X, NONE),  // GPS Speed
+    e(0x0016, 0x007E, X, NONE),  // GPS Track Ref
+    e(0x0016, 0x007F, X, NONE),  // GPS Track
+    e(0x0016, 0x0080, X, NONE),  // GPS Img Direction Ref
+    e(0x0016, 0x0081, X, NONE),  // GPS Img Direction
+    e(0x0016, 0x0082, X, NONE),  // GPS Map Datum
+    e(0x0016, 0x0083, X, NONE),  // GPS Dest Latitude Ref
+    e(0x0016, 0x0084, X, NONE),  // GPS Dest Latitude
+    e(0x0016, 0x0085, X, NONE),  // GPS Dest Longitude Ref
+    e(0x0016, 0x0086, X, NONE),  // GPS Dest Longitude
+    e(0x0016, 0x0087, X, NONE),  // GPS Dest Bearing Ref
+    e(0x0016, 0x0088, X, NONE),  // GPS Dest Bearing
+    e(0x0016, 0x0089, X, NONE),  // GPS Dest Distance Ref
+    e(0x0016, 0x008A, X, NONE),  // GPS Dest Distance
+    e(0x0016, 0x008B, X, NONE),  // GPS Processing Method
+    e(0x0016, 0x008C, X, NONE),  // GPS Area Information
+    e(0x0016, 0x008D, X, NONE),  // GPS Date Stamp
+    e(0x0016, 0x008E, X, NONE),  // GPS Differential
+    e(0x0018, 0x0010, D, DESC),  // Contrast/Bolus Agent
+    e(0x0018, 0x1000, X, DEVICE), // Device Serial Number
+    e(0x0018, 0x1002, U, DEVICE_UID), // Device UID
+    e(0x0018, 0x1004, X, DEVICE), // Plate ID
+    e(0x0018, 0x1005, X, DEVICE), // Generator ID
+    e(0x0018, 0x1007, X, DEVICE), // Cassette ID
+    e(0x0018, 0x1008, X, DEVICE), // Gantry ID
+    e(0x0018, 0x1009, X, DEVICE), // Unique Device Identifier
+    e(0x0018, 0x100A, X, DEVICE), // UDI Sequence
+    e(0x0018, 0x100B, U, DEVICE_UID), // Manufacturer's Device Class UID
+    e(0x0018, 0x1010, X, DEVICE), // Secondary Capture Device ID
+    e(0x0018, 0x1011, X, DEVICE), // Hardcopy Creation Device ID
+    e(0x0018, 0x1030, X, DESC),  // Protocol Name
+    e(0x0018, 0x1078, X, DATE),  // Radiopharmaceutical Start DateTime
+    e(0x0018, 0x1079, X, DATE),  // Radiopharmaceutical Stop DateTime
+    e(0x0018, 0x1200, X, DATE),  // Date of Last Calibration
+    e(0x0018, 0x1201, X, DATE),  // Time of Last Calibration
+    e(0x0018, 0x1202, X, DATE),  // DateTime of Last Calibration
+    e(0x0018, 0x1400, X, DESC),  // Acquisition Device Processing Description
+    e(0x0018, 0x2042, U, UID),   // Target UID
+    e(0x0018, 0x4000, X, DESC),  // Acquisition Comments
+    e(0x0018, 0x5011, X, DEVICE), // Transducer Identification Sequence
+    e(0x0018, 0x700A, X, DEVICE), // Detector ID
+    e(0x0018, 0x700C, X, DATE),  // Date of Last Detector Calibration
+    e(0x0018, 0x700E, X, DATE),  // Time of Last Detector Calibration
+    e(0x0018, 0x9074, X, DATE),  // Frame Acquisition DateTime
+    e(0x0018, 0x9151, X, DATE),  // Frame Reference DateTime
+    e(0x0018, 0x9367, X, DEVICE), // X-Ray Source ID
+    e(0x0018, 0x9371, X, DEVICE), // X-Ray Detector ID
+    e(0x0018, 0x9373, X, DEVICE), // X-Ray Detector Label
+    e(0x0018, 0x937B, X, DESC),  // Multi-energy Acquisition Description
+    e(0x0018, 0x937F, X, DESC),  // Decomposition Description
+    e(0x0018, 0x9424, X, DESC),  // Acquisition Protocol Description
+    e(0x0018, 0x9516, X, DATE),  // Start Acquisition DateTime
+    e(0x0018, 0x9517, X, DATE),  // End Acquisition DateTime
+    e(0x0018, 0x9623, X, DATE),  // Functional Sync Pulse
+    e(0x0018, 0x9701, X, DATE),  // Decay Correction DateTime
+    e(0x0018, 0x9804, X, DATE),  // Exclusion Start DateTime
+    e(0x0018, 0x9919, X, DATE),  // Instruction Performed DateTime
+    e(0x0018, 0x9937, X, DESC),  // Requested Series Description
+    e(0x0018, 0xA002, X, DATE),  // Contribution DateTime
+    e(0x0018, 0xA003, X, DESC),  // Contribution Description
+    e(0x0020, 0x000D, U, UID),   // Study Instance UID
+    e(0x0020, 0x000E, U, UID),   // Series Instance UID
+    e(0x0020, 0x0010, Z, NONE),  // Study ID
+    e(0x0020, 0x0052, U, UID),   // Frame of Reference UID
+    e(0x0020, 0x0200, U, UID),   // Synchronization Frame of Reference UID
+    e(0x0020, 0x3401, X, DEVICE), // Modifying Device ID
+    e(0x0020, 0x3404, X, DEVICE), // Modifying Device Manufacturer
+    e(0x0020, 0x3406, X, DESC),  // Modified Image Description
+    e(0x0020, 0x4000, X, DESC),  // Image Comments
+    e(0x0020, 0x9158, X, DESC),  // Frame Comments
+    e(0x0020, 0x9161, U, UID),   // Concatenation UID
+    e(0x0020, 0x9164, U, UID),   // Dimension Organization UID
+    e(0x0028, 0x1199, U, UID),   // Palette Color Lookup Table UID
+    e(0x0028, 0x1214, U, UID),   // Large Palette Color Lookup Table UID
+    e(0x0028, 0x4000, X, DESC),  // Image Presentation Comments
+    e(0x0032, 0x0012, X, NONE),  // Study ID Issuer
+    e(0x0032, 0x0032, X, DATE),  // Study Verified Date
+    e(0x0032, 0x0033, X, DATE),  // Study Verified Time
+    e(0x0032, 0x0034, X, DATE),  // Study Read Date
+    e(0x0032, 0x0035, X, DATE),  // Study Read Time
+    e(0x0032, 0x1000, X, DATE),  // Scheduled Study Start Date
+    e(0x0032, 0x1001, X, DATE),  // Scheduled Study Start Time
+    e(0x0032, 0x1010, X, DATE),  // Scheduled Study Stop Date
+    e(0x0032, 0x1011, X, DATE),  // Scheduled Study Stop Time
+    e(0x0032, 0x1020, X, NONE),  // Scheduled Study Location
+    e(0x0032, 0x1021, X, NONE),  // Scheduled Study Location AE Title
+    e(0x0032, 0x1030, X, DESC),  // Reason for Study
+    e(0x0032, 0x1032, X, NONE),  // Requesting Physician
+    e(0x0032, 0x1033, X, NONE),  // Requesting Service
+    e(0x0032, 0x1040, X, DATE),  // Study Arrival Date
+    e(0x0032, 0x1041, X, DATE),  // Study Arrival Time
+    e(0x0032, 0x1050, X, DATE),  // Study Completion Date
+    e(0x0032, 0x1051, X, DATE),  // Study Completion Time
+    e(0x0032, 0x1060, X, DESC),  // Requested Procedure Description
+    e(0x0032, 0x1066, X, DESC),  // Reason for Visit
+    e(0x0032, 0x1067, X, DESC),  // Reason for Visit Code Sequence
+    e(0x0032, 0x1070, X, DESC),  // Requested Contrast Agent
+    e(0x0032, 0x4000, X, DESC),  // Study Comments
+    e(0x0038, 0x0004, X, NONE),  // Referenced Patient Alias Sequence
+    e(0x0038, 0x0010, X, NONE),  // Admission ID
+    e(0x0038, 0x0011, X, NONE),  // Issuer of Admission ID
+    e(0x0038, 0x0014, X, NONE),  // Issuer of Admission ID Sequence
+    e(0x0038, 0x001A, X, DATE),  // Scheduled Admission Date
+    e(0x0038, 0x001B, X, DATE),  // Scheduled Admission Time
+    e(0x0038, 0x001C, X, DATE),  // Scheduled Discharge Date
+    e(0x0038, 0x001D, X, DATE),  // Scheduled Discharge Time
+    e(0x0038, 0x001E, X, NONE),  // Scheduled Patient Institution Residence
+    e(0x0038, 0x0020, X, DATE),  // Admitting Date
+    e(0x0038, 0x0021, X, DATE),  // Admitting Time
+    e(0x0038, 0x0030, X, DATE),  // Discharge Date
+    e(0x0038, 0x0032, X, DATE),  // Discharge Time
+    e(0x0038, 0x0040, X, PATIENT_DESC), // Discharge Diagnosis Description
+    e(0x0038, 0x0044, X, PATIENT_DESC), // Discharge Diagnosis Code Sequence
+    e(0x0038, 0x0050, X, PATIENT_DESC), // Special Needs
+    e(0x0038, 0x0060, X, NONE),  // Service Episode ID
+    e(0x0038, 0x0061, X, NONE),  // Issuer of Service Episode ID
+    e(0x0038, 0x0062, X, DESC),  // Service Episode Description
+    e(0x0038, 0x0064, X, NONE),  // Issuer of Service Episode ID Sequence
+    e(0x0038, 0x0300, X, NONE),  // Current Patient Location
+    e(0x0038, 0x0400, X, NONE),  // Patient's Institution Residence
+    e(0x0038, 0x0500, X, PATIENT_DESC), // Patient State
+    e(0x0038, 0x4000, X, DESC),  // Visit Comments
+    e(0x003A, 0x0310, U, UID),   // Multiplex Group UID
+    e(0x0040, 0x0001, X, DEVICE), // Scheduled Station AE Title
+    e(0x0040, 0x0002, X, DATE),  // Scheduled Procedure Step Start Date
+    e(0x0040, 0x0003, X, DATE),  // Scheduled Procedure Step Start Time
+    e(0x0040, 0x0004, X, DATE),  // Scheduled Procedure Step End Date
+    e(0x0040, 0x0005, X, DATE),  // Scheduled Procedure Step End Time
+    e(0x0040, 0x0006, X, NONE),  // Scheduled Performing Physician's Name
+    e(0x0040, 0x0007, X, DESC),  // Scheduled Procedure Step Description
+    e(0x0040, 0x000B, X, NONE),  // Scheduled Performing Physician Identification Sequence
+    e(0x0040, 0x0010, X, DEVICE), // Scheduled Station Name
+    e(0x0040, 0x0011, X, NONE),  // Scheduled Procedure Step Location
+    e(0x0040, 0x0012, X, PATIENT_DESC), // Pre-Medication
+    e(0x0040, 0x0241, X, DEVICE), // Performed Station AE Title
+    e(0x0040, 0x0242, X, DEVICE), // Performed Station Name
+    e(0x0040, 0x0243, X, NONE),  // Performed Location
+    e(0x0040, 0x0244, X, DATE),  // Performed Procedure Step Start Date
+    e(0x0040, 0x0245, X, DATE),  // Performed Procedure Step Start Time
+    e(0x0040, 0x0248, X, DEVICE), // Performed Station Name Code Sequence
+    e(0x0040, 0x0250, X, DATE),  // Performed Procedure Step End Date
+    e(0x0040, 0x0251, X, DATE),  // Performed Procedure Step End Time
+    e(0x0040, 0x0253, X, NONE),  // Performed Procedure Step ID
+    e(0x0040, 0x0254, X, DESC),  // Performed Procedure Step Description
+    e(0x0040, 0x0275, X, NONE),  // Request Attributes Sequence
+    e(0x0040, 0x0280, X, DESC),  // Comments on the Performed Procedure Step
+    e(0x0040, 0x050A, X, NONE),  // Specimen Accession Number
+    e(0x0040, 0x051A, X, DESC),  // Container Description
+    e(0x0040, 0x0554, U, UID),   // Specimen UID
+    e(0x0040, 0x0555, X, STRUCTURED), // Acquisition Context Sequence
+    e(0x0040, 0x0600, X, DESC),  // Specimen Short Description
+    e(0x0040, 0x0602, X, DESC),  // Specimen Detailed Description
+    e(0x0040, 0x06FA, X, NONE),  // Slide Identifier
+    e(0x0040, 0x1001, X, NONE),  // Requested Procedure ID
+    e(0x0040, 0x1004, X, NONE),  // Patient Transport Arrangements
+    e(0x0040, 0x1005, X, NONE),  // Requested Procedure Location
+    e(0x0040, 0x1010, X, NONE),  // Names of Intended Recipients of Results
+    e(0x0040, 0x1011, X, NONE),  // Intended Recipients of Results Identification Sequence
+    e(0x0040, 0x1101, D, NONE),  // Person Identification Code Sequence
+    e(0x0040, 0x1102, X, NONE),  // Person's Address
+    e(0x0040, 0x1103, X, NONE),  // Person's Telephone Numbers
+    e(0x0040, 0x1104, X, NONE),  // Person's Telecom Information
+    e(0x0040, 0x1400, X, DESC),  // Requested Procedure Comments
+    e(0x0040, 0x2001, X, DESC),  // Reason for the Imaging Service Request
+    e(0x0040, 0x2008, X, NONE),  // Order Entered By
+    e(0x0040, 0x2009, X, NONE),  // Order Enterer's Location
+    e(0x0040, 0x2010, X, NONE),  // Order Callback Phone Number
+    e(0x0040, 0x2016, Z, NONE),  // Placer Order Number / Imaging Service Request
+    e(0x0040, 0x2017, Z, NONE),  // Filler Order Number / Imaging Service Request
+    e(0x0040, 0x2400, X, DESC),  // Imaging Service Request Comments
+    e(0x0040, 0x3001, X, NONE),  // Confidentiality Constraint on Patient Data Description
+    e(0x0040, 0x4005, X, DATE),  // Scheduled Procedure Step Start DateTime
+    e(0x0040, 0x4008, X, DATE),  // Scheduled Procedure Step Expiration DateTime
+    e(0x0040, 0x4010, X, DATE),  // Scheduled Procedure Step Modification DateTime
+    e(0x0040, 0x4011, X, DATE),  // Expected Completion DateTime
+    e(0x0040, 0x4023, U, UID),   // Referenced General Purpose Scheduled Procedure Step Transaction UID
+    e(0x0040, 0x4025, X, DEVICE), // Scheduled Station Name Code Sequence
+    e(0x0040, 0x4027, X, DEVICE), // Scheduled Station Geographic Location Code Sequence
+    e(0x0040, 0x4030, X, DEVICE), // Performed Station Geographic Location Code Sequence
+    e(0x0040, 0x4034, X, NONE),  // Scheduled Human Performers Sequence
+    e(0x0040, 0x4035, X, NONE),  // Actual Human Performers Sequence
+    e(0x0040, 0x4036, X, NONE),  // Human Performer's Organization
+    e(0x0040, 0x4037, X, NONE),  // Human Performer's Name
+    e(0x0040, 0x4050, X, DATE),  // Performed Procedure Step Start DateTime
+    e(0x0040, 0x4051, X, DATE),  // Performed Procedure Step End DateTime
+    e(0x0040, 0x4052, X, DATE),  // Procedure Step Cancellation DateTime
+    e(0x0040, 0xA027, X, NONE),  // Verifying Organization
+    e(0x0040, 0xA030, X, DATE),  // Verification DateTime
+    e(0x0040, 0xA032, X, DATE),  // Observation DateTime
+    e(0x0040, 0xA073, D, NONE),  // Verifying Observer Sequence
+    e(0x0040, 0xA075, D, NONE),  // Verifying Observer Name
+    e(0x0040, 0xA078, X, NONE),  // Author Observer Sequence
+    e(0x0040, 0xA07A, X, NONE),  // Participant Sequence
+    e(0x0040, 0xA07C, X, NONE),  // Custodial Organization Sequence
+    e(0x0040, 0xA082, X, DATE),  // Participation DateTime
+    e(0x0040, 0xA088, Z, NONE),  // Verifying Observer Identification Code Sequence
+    e(0x0040, 0xA120, X, DATE),  // DateTime
+    e(0x0040, 0xA121, X, DATE),  // Date
+    e(0x0040, 0xA122, X, DATE),  // Time
+    e(0x0040, 0xA123, D, NONE),  // Person Name
+    e(0x0040, 0xA124, U, UID),   // UID
+    e(0x0040, 0xA13A, X, DATE),  // Referenced DateTime
+    e(0x0040, 0xA171, U, UID),   // Observation UID
+    e(0x0040, 0xA172, U, UID),   // Referenced Observation UID (Trial)
+    e(0x0040, 0xA192, X, DATE),  // Observation Date (Trial)
+    e(0x0040, 0xA193, X, DATE),  // Observation Time (Trial)
+    e(0x0040, 0xA307, X, NONE),  // Current Observer (Trial)
+    e(0x0040, 0xA352, X, NONE),  // Verbal Source (Trial)
+    e(0x0040, 0xA353, X, NONE),  // Address (Trial)
+    e(0x0040, 0xA354, X, NONE),  // Telephone Number (Trial)
+    e(0x0040, 0xA358, X, NONE),  // Verbal Source Identifier Code Sequence (Trial)
+    e(0x0040, 0xA402, U, UID),   // Observation Subject UID (Trial)
+    e(0x0040, 0xA730, X, STRUCTURED), // Content Sequence
+    e(0x0040, 0xDB0C, U, UID),   // Template Extension Organization UID
+    e(0x0040, 0xDB0D, U, UID),   // Template Extension Creator UID
+    e(0x0040, 0xE004, X, DATE),  // HL7 Document Effective Time
+    e(0x0050, 0x001B, X, NONE),  // Container Component ID
+    e(0x0050, 0x0020, X, DESC),  // Device Description
+    e(0x0050, 0x0021, X, DESC),  // Long Device Description
+    e(0x0062, 0x0021, U, UID),   // Tracking UID
+    e(0x0064, 0x0003, U, UID),   // Source Frame of Reference UID
+    e(0x0068, 0x6226, X, DATE),  // Effective DateTime
+    e(0x0068, 0x6270, X, DATE),  // Information Issue DateTime
+    e(0x0070, 0x0001, D, GRAPHICS), // Graphic Annotation Sequence
+    e(0x0070, 0x0084, Z, NONE),  // Content Creator's Name
+    e(0x0070, 0x0086, X, NONE),  // Content Creator's Identification Code Sequence
+    e(0x0070, 0x031A, U, UID),   // Fiducial UID
+    e(0x0070, 0x1101, U, UID),   // Presentation Display Collection UID
+    e(0x0070, 0x1102, U, UID),   // Presentation Sequence Collection UID
+    e(0x0072, 0x000A, X, DATE),  // Hanging Protocol Creation DateTime
+    e(0x0074, 0x100A, X, NONE),  // Contact URI
+    e(0x0074, 0x100C, X, NONE),  // Contact Display Name
+    e(0x0074, 0x1234, X, NONE),  // Receiving AE
+    e(0x0074, 0x1236, X, NONE),  // Requesting AE
+    e(0x0088, 0x0140, U, UID),   // Storage Media File-set UID
+    e(0x0088, 0x0200, X, NONE),  // Icon Image Sequence
+    e(0x0088, 0x0904, X, NONE),  // Topic Title
+    e(0x0088, 0x0906, X, NONE),  // Topic Subject
+    e(0x0088, 0x0910, X, NONE),  // Topic Author
+    e(0x0088, 0x0912, X, NONE),  // Topic Keywords
+    e(0x0100, 0x0420, X, DATE),  // SOP Authorization DateTime
+    e(0x0100, 0x0424, X, DESC),  // SOP Authorization Comment
+    e(0x0100, 0x0426, X, NONE),  // Authorization Equipment Certification Number
+    e(0x0400, 0x0100, X, NONE),  // Digital Signature UID
+    e(0x0400, 0x0105, X, DATE),  // Digital Signature DateTime
+    e(0x0400, 0x0115, X, NONE),  // Certificate of Signer
+    e(0x0400, 0x0120, X, NONE),  // Signature
+    e(0x0400, 0x0310, X, NONE),  // Certified Timestamp
+    e(0x0400, 0x0402, X, NONE),  // Referenced Digital Signature Sequence
+    e(0x0400, 0x0403, X, NONE),  // Referenced SOP Instance MAC Sequence
+    e(0x0400, 0x0404, X, NONE),  // MAC
+    e(0x0400, 0x0550, X, NONE),  // Modified Attributes Sequence
+    e(0x0400, 0x0561, X, NONE),  // Original Attributes Sequence
+    e(0x0400, 0x0562, X, DATE),  // Attribute Modification DateTime
+    e(0x0400, 0x0563, X, NONE),  // Modifying System
+    e(0x0400, 0x0564, X, NONE),  // Source of Previous Values
+    e(0x0400, 0x0565, X, NONE),  // Reason for the Attribute Modification
+    e(0x2030, 0x0020, X, NONE),  // Text String
+    e(0x3006, 0x0002, D, NONE),  // Structure Set Label
+    e(0x3006, 0x0004, X, NONE),  // Structure Set Name
+    e(0x3006, 0x0006, X, DESC),  // Structure Set Description
+    e(0x3006, 0x0008, X, DATE),  // Structure Set Date
+    e(0x3006, 0x0009, X, DATE),  // Structure Set Time
+    e(0x3006, 0x0024, U, UID),   // Referenced Frame of Reference UID
+    e(0x3006, 0x0026, Z, NONE),  // ROI Name
+    e(0x3006, 0x0028, X, DESC),  // ROI Description
+    e(0x3006, 0x0038, X, DESC),  // ROI Generation Description
+    e(0x3006, 0x00A6, Z, NONE),  // ROI Interpreter
+    e(0x3006, 0x00C2, U, UID),   // Related Frame of Reference UID
+    e(0x300A, 0x0002, D, NONE),  // RT Plan Label
+    e(0x300A, 0x0003, X, NONE),  // RT Plan Name
+    e(0x300A, 0x0004, X, DESC),  // RT Plan Description
+    e(0x300A, 0x0006, X, DATE),  // RT Plan Date
+    e(0x300A, 0x0007, X, DATE),  // RT Plan Time
+    e(0x300A, 0x0013, U, UID),   // Dose Reference UID
+    e(0x300A, 0x0016, X, DESC),  // Dose Reference Description
+    e(0x300A, 0x0083, U, UID),   // Referenced Dose Reference UID
+    e(0x300A, 0x00C3, X, DESC),  // Beam Description
+    e(0x300A, 0x0650, U, UID),   // Patient Setup UID
+    e(0x300A, 0x0700, U, UID),   // Treatment Session UID
+    e(0x300C, 0x0113, X, DESC),  // Reason for Omission Description
+    e(0x300C, 0x0127, X, DATE),  // Beam Hold Transition DateTime
+    e(0x300E, 0x0004, X, DATE),  // Review Date
+    e(0x300E, 0x0005, X, DATE),  // Review Time
+    e(0x300E, 0x0008, X, NONE),  // Reviewer Name
+    e(0x3010, 0x0006, U, UID),   // Conceptual Volume UID
+    e(0x3010, 0x000B, U, UID),   // Referenced Conceptual Volume UID
+    e(0x3010, 0x0013, U, UID),   // Constituent Conceptual Volume UID
+    e(0x3010, 0x0015, U, UID),   // Source Conceptual Volume UID
+    e(0x3010, 0x0031, U, UID),   // Referenced Fiducials UID
+    e(0x3010, 0x003B, U, UID),   // RT Treatment Phase UID
+    e(0x3010, 0x006E, U, UID),   // Dosimetric Objective UID
+    e(0x3010, 0x006F, U, UID),   // Referenced Dosimetric Objective UID
+    e(0x4000, 0x0010, X, NONE),  // Arbitrary
+    e(0x4000, 0x4000, X, DESC),  // Text Comments
+    e(0x4008, 0x0040, X, NONE),  // Results ID
+    e(0x4008, 0x0042, X, NONE),  // Results ID Issuer
+    e(0x4008, 0x0100, X, DATE),  // Interpretation Recorded Date
+    e(0x4008, 0x0101, X, DATE),  // Interpretation Recorded Time
+    e(0x4008, 0x0102, X, NONE),  // Interpretation Recorder
+    e(0x4008, 0x0108, X, DATE),  // Interpretation Transcription Date
+    e(0x4008, 0x0109, X, DATE),  // Interpretation Transcription Time
+    e(0x4008, 0x010A, X, NONE),  // Interpretation Transcriber
+    e(0x4008, 0x010B, X, DESC),  // Interpretation Text
+    e(0x4008, 0x010C, X, NONE),  // Interpretation Author
+    e(0x4008, 0x0111, X, NONE),  // Interpretation Approver Sequence
+    e(0x4008, 0x0112, X, DATE),  // Interpretation Approval Date
+    e(0x4008, 0x0113, X, DATE),  // Interpretation Approval Time
+    e(0x4008, 0x0114, X, NONE),  // Physician Approving Interpretation
+    e(0x4008, 0x0115, X, DESC),  // Interpretation Diagnosis Description
+    e(0x4008, 0x0118, X, NONE),  // Results Distribution List Sequence
+    e(0x4008, 0x0119, X, NONE),  // Distribution Name
+    e(0x4008, 0x011A, X, NONE),  // Distribution Address
+    e(0x4008, 0x0202, X, NONE),  // Interpretation ID Issuer
+    e(0x4008, 0x0300, X, DESC),  // Impressions
+    e(0x4008, 0x4000, X, DESC),  // Results Comments
+    e(0x6000, 0x3000, X, GRAPHICS), // Overlay Data
+    e(0x6000, 0x4000, X, DESC),  // Overlay Comments
+    e(0xFFFA, 0xFFFA, X, NONE),  // Digital Signatures Sequence
+];
+
+/// 按 tag 查找 Table E.1-1 的行; 60xx 组按 6000 组查找
+pub fn profile_entry(tag: Tag) -> Option<&'static ProfileEntry> {
+    static TABLE: OnceLock<HashMap<Tag, &'static ProfileEntry>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| BASIC_PROFILE.iter().map(|entry| (entry.tag, entry)).collect());
+    let tag = match tag.group() {
+        group if group & 0xFF01 == 0x6000 => Tag(0x6000, tag.element()),
+        _ => tag,
+    };
+    table.get(&tag).copied()
+}
+
+/// 按 Basic Profile 及选中的 Option 去除身份信息
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    pub options: HashSet<ProfileOption>,
+    /// Retain Longitudinal Temporal Information Modified Dates: 日期平移的天数
+    pub date_shift: Option<i64>,
+    /// 替换后的 Patient ID / Patient's Name, 如研究中的受试者编号
+    pub patient_id: Option<String>,
+    pub patient_name: Option<String>,
+    pub uids: UidMap,
+}
+
+impl Anonymizer {
+    pub fn new(options: &[ProfileOption], date_shift: Option<i64>) -> Result<Self, String> {
+        let options: HashSet<ProfileOption> = options.iter().copied().collect();
+        let modified = options.contains(&RetainLongitudinalModifiedDates);
+        if modified && options.contains(&RetainLongitudinalFullDates) {
+            return Err("retain-longitudinal-full-dates and retain-longitudinal-modified-dates are exclusive".into());
+        }
+        if modified != date_shift.is_some() {
+            return Err("--date-shift is required with, and only with, retain-longitudinal-modified-dates".into());
+        }
+        Ok(Anonymizer { options, date_shift, ..Default::default() })
+    }
+
+    pub fn with_patient(mut self, patient_id: Option<String>, patient_name: Option<String>) -> Self {
+        self.patient_id = patient_id;
+        self.patient_name = patient_name;
+        self
+    }
+
+    /// 使用持久化的 UID 映射, 多次运行之间保持一致
+    pub fn with_uid_map(mut self, uids: UidMap) -> Self {
+        self.uids = uids;
+        self
+    }
+
+    /// 选中的 Option 中保留 (K) 优先于清理 (C), 否则使用 Basic Profile 的动作
+    pub fn action(&self, entry: &ProfileEntry) -> Action {
+        let selected: Vec<Action> = entry
+            .options
+            .iter()
+            .filter(|(option, _)| self.options.contains(option))
+            .map(|(_, action)| *action)
+            .collect();
+        if selected.contains(&K) {
+            K
+        } else if selected.contains(&C) {
+            C
+        } else {
+            entry.action
+        }
+    }
+
+    /// 去除文件中的身份信息, 并更新文件元信息中的 SOP Instance UID
+    pub fn anonymize(&self, mut obj: DefaultDicomObject) -> Result<DefaultDicomObject, DcmToolsError> {
+        let identifiers = identifiers(&obj);
+        let dataset = std::mem::replace(&mut *obj, InMemDicomObject::new_empty());
+        *obj = self.dataset(dataset, &identifiers)?;
+
+        obj.put(DataElement::new(tags::PATIENT_IDENTITY_REMOVED, VR::CS, PrimitiveValue::from("YES")));
+        obj.put(DataElement::new(
+            tags::DEIDENTIFICATION_METHOD,
+            VR::LO,
+            PrimitiveValue::from("Basic Application Confidentiality Profile"),
+        ));
+        let mut codes = vec![("113100", "Basic Application Confidentiality Profile")];
+        codes.extend(self.selected_options().into_iter().map(ProfileOption::code));
+        let items: Vec<InMemDicomObject> = codes
+            .into_iter()
+            .map(|(value, meaning)| {
+                InMemDicomObject::from_element_iter([
+                    DataElement::new(tags::CODE_VALUE, VR::SH, PrimitiveValue::from(value)),
+                    DataElement::new(tags::CODING_SCHEME_DESIGNATOR, VR::SH, PrimitiveValue::from("DCM")),
+                    DataElement::new(tags::CODE_MEANING, VR::LO, PrimitiveValue::from(meaning)),
+                ])
+            })
+            .collect();
+        obj.put(DataElement::new(tags::DEIDENTIFICATION_METHOD_CODE_SEQUENCE, VR::SQ, DataSetSequence::from(items)));
+        let temporal = if self.options.contains(&RetainLongitudinalFullDates) {
+            "UNMODIFIED"
+        } else if self.options.contains(&RetainLongitudinalModifiedDates) {
+            "MODIFIED"
+        } else {
+            "REMOVED"
+        };
+        obj.put(DataElement::new(
+            tags::LONGITUDINAL_TEMPORAL_INFORMATION_MODIFIED,
+            VR::CS,
+            PrimitiveValue::from(temporal),
+        ));
+        if let Some(id) = &self.patient_id {
+            obj.put(DataElement::new(tags::PATIENT_ID, VR::LO, PrimitiveValue::from(id.as_str())));
+        }
+        if let Some(name) = &self.patient_name {
+            obj.put(DataElement::new(tags::PATIENT_NAME, VR::PN, PrimitiveValue::from(name.as_str())));
+        }
+
+        let sop_uid = obj
+            .element_opt(tags::SOP_INSTANCE_UID)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_str().ok())
+            .map(|s| s.trim_end_matches('\0').to_string());
+        let meta_uid = match sop_uid {
+            Some(uid) => uid,
+            None if self.options.contains(&RetainUids) => obj.meta().media_storage_sop_instance_uid().to_string(),
+            None => self.uids.map(obj.meta().media_storage_sop_instance_uid().trim_end_matches('\0'))?,
+        };
+        obj.update_meta(|meta| meta.media_storage_sop_instance_uid = meta_uid);
+        self.uids.flush()?;
+        Ok(obj)
+    }
+
+    fn selected_options(&self) -> Vec<ProfileOption> {
+        ProfileOption::value_variants()
+            .iter()
+            .copied()
+            .filter(|option| self.options.contains(option))
+            .collect()
+    }
+
+    fn dataset(&self, mut obj: InMemDicomObject, identifiers: &[String]) -> Result<InMemDicomObject, DcmToolsError> {
+        let element_tags: Vec<Tag> = obj.tags().collect();
+        for tag in element_tags {
+            let Ok(element) = obj.take_element(tag) else { continue };
+            // 私有属性 (含 Private Creator) 与 50xx 组的 curve 一律删除
+            if tag.group() % 2 == 1 || tag.group() & 0xFF00 == 0x5000 {
+                continue;
+            }
+            let action = profile_entry(tag).map(|entry| self.action(entry)).unwrap_or(K);
+            if let Some(element) = self.apply(element, action, identifiers)? {
+                obj.put(element);
+            }
+        }
+        Ok(obj)
+    }
+
+    fn apply(
+        &self,
+        element: InMemElement,
+        action: Action,
+        identifiers: &[String],
+    ) -> Result<Option<InMemElement>, DcmToolsError> {
+        let tag = element.tag();
+        let vr = element.vr();
+        if vr == VR::SQ {
+            return match action {
+                X => Ok(None),
+                Z => Ok(Some(DataElement::new(tag, vr, DataSetSequence::<InMemDicomObject>::empty()))),
+                // 保留的序列中, 条目仍按本表处理
+                _ => {
+                    let items = match element.into_value() {
+                        Value::Sequence(seq) => seq.into_items().into_vec(),
+                        _ => Vec::new(),
+                    };
+                    let items = items
+                        .into_iter()
+                        .map(|item| self.dataset(item, identifiers))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Some(DataElement::new(tag, vr, DataSetSequence::from(items))))
+                }
+            };
+        }
+        Ok(match action {
+            X => None,
+            Z => Some(DataElement::empty(tag, vr)),
+            K => Some(element),
+            D => Some(self.dummy(&element)),
+            U if vr == VR::UI => {
+                let uids = element
+                    .to_multi_str()
+                    .map(|uids| uids.to_vec())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|uid| self.uids.map(uid.trim_end_matches('\0')))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some(DataElement::new(tag, vr, PrimitiveValue::Strs(uids.into())))
+            }
+            U => Some(self.dummy(&element)),
+            C => Some(self.clean(element, identifiers)),
+        })
+    }
+
+    fn dummy(&self, element: &InMemElement) -> InMemElement {
+        let tag = element.tag();
+        let vr = element.vr();
+        let value = match vr {
+            VR::PN | VR::LO | VR::SH | VR::CS | VR::AE | VR::LT | VR::ST | VR::UT | VR::UC => {
+                PrimitiveValue::from("ANONYMIZED")
+            }
+            VR::DA => PrimitiveValue::from("19000101"),
+            VR::TM => PrimitiveValue::from("000000"),
+            VR::DT => PrimitiveValue::from("19000101000000"),
+            VR::AS => PrimitiveValue::from("000Y"),
+            VR::IS | VR::DS => PrimitiveValue::from("0"),
+            VR::UI => PrimitiveValue::from(dicom_gen_uid::gen_uid()),
+            VR::US => PrimitiveValue::from(0_u16),
+            VR::SS => PrimitiveValue::from(0_i16),
+            VR::UL => PrimitiveValue::from(0_u32),
+            VR::SL => PrimitiveValue::from(0_i32),
+            VR::FL => PrimitiveValue::from(0_f32),
+            VR::FD => PrimitiveValue::from(0_f64),
+            _ => PrimitiveValue::Empty,
+        };
+        DataElement::new(tag, vr, value)
+    }
+
+    /// 清理: 日期按 --date-shift 平移, 时间保留, 文本中去掉患者姓名/ID 等标识
+    fn clean(&self, element: InMemElement, identifiers: &[String]) -> InMemElement {
+        let tag = element.tag();
+        let vr = element.vr();
+        let Ok(values) = element.to_multi_str() else { return element };
+        let values: Vec<String> = match vr {
+            VR::DA | VR::DT => values.iter().map(|v| shift_date(v, self.date_shift.unwrap_or(0))).collect(),
+            VR::TM => return element,
+            VR::PN | VR::LO | VR::SH | VR::LT | VR::ST | VR::UT | VR::UC => {
+                values.iter().map(|v| remove_identifiers(v, identifiers)).collect()
+            }
+            _ => return element,
+        };
+        DataElement::new(tag, vr, PrimitiveValue::Strs(values.into()))
+    }
+
+    /// 去除单个文件的身份信息, 写入 output/study/series/sop.dcm (使用新的 UID), 返回输出文件
+    pub fn anonymize_into_tree(&self, file: &Path, output: &Path) -> Result<PathBuf, DcmToolsError> {
+        let obj = dicom_object::open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+        warn_burned_in(file, &obj);
+        write_into_tree(&self.anonymize(obj)?, output)
+    }
+}
+
+/// 写入 output/study/series/sop.dcm, 返回输出文件
+pub fn write_into_tree(obj: &DefaultDicomObject, output: &Path) -> Result<PathBuf, DcmToolsError> {
+    let uid = |tag| {
+        obj.element_opt(tag)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_str().ok())
+            .map(|s| s.trim_end_matches('\0').to_string())
+            .unwrap_or_default()
+    };
+    let target = output
+        .join(uid(tags::STUDY_INSTANCE_UID))
+        .join(uid(tags::SERIES_INSTANCE_UID))
+        .join(format!("{}.dcm", obj.meta().media_storage_sop_instance_uid().trim_end_matches('\0')));
+    let dir = target.parent().unwrap_or(output);
+    std::fs::create_dir_all(dir).map_err(|e| DcmToolsError::write(dir, e))?;
+    obj.write_to_file(&target).map_err(|e| DcmToolsError::write(&target, e))?;
+    Ok(target)
+}
+
+/// 像素中烧入的文字不在本 Profile 的处理范围内, 需要提醒
+pub fn warn_burned_in(file: &Path, obj: &InMemDicomObject) {
+    let burned_in = obj
+        .element_opt(tags::BURNED_IN_ANNOTATION)
+        .ok()
+        .flatten()
+        .and_then(|e| e.to_str().ok())
+        .is_some_and(|v| v.trim() == "YES");
+    if burned_in {
+        eprintln!("Warning: {:?} has burned in annotation, pixel data is not cleaned", file);
+    }
+}
+
+/// 文本清理时要去掉的标识: 患者姓名的各部分, Patient ID, Accession Number
+fn identifiers(obj: &InMemDicomObject) -> Vec<String> {
+    let mut identifiers = Vec::new();
+    for tag in [tags::PATIENT_NAME, tags::PATIENT_ID, tags::ACCESSION_NUMBER] {
+        let Some(values) = obj.element_opt(tag).ok().flatten().and_then(|e| e.to_multi_str().ok()) else {
+            continue;
+        };
+        for value in values.iter() {
+            identifiers.extend(
+                value
+                    .split(['^', '=', ' '])
+                    .map(|part| part.trim_matches(['\0', ' ']).to_string())
+                    .filter(|part| part.len() >= 2),
+            );
+        }
+    }
+    // 先去掉长的, 避免只去掉其中一部分
+    identifiers.sort_by_key(|part| std::cmp::Reverse(part.len()));
+    identifiers.dedup();
+    identifiers
+}
+
+/// 不区分 ASCII 大小写地去掉文本中的标识
+fn remove_identifiers(text: &str, identifiers: &[String]) -> String {
+    let mut text = text.to_string();
+    for identifier in identifiers {
+        let needle = identifier.to_ascii_uppercase();
+        while let Some(pos) = text.to_ascii_uppercase().find(&needle) {
+            text.replace_range(pos..pos + needle.len(), "");
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// DA (YYYYMMDD) 或 DT (YYYYMMDD...) 的日期部分平移若干天; 无法解析时清空
+fn shift_date(value: &str, days: i64) -> String {
+    let value = value.trim_end_matches(['\0', ' ']);
+    let Some(date) = value.get(..8) else {
+        return String::new();
+    };
+    match NaiveDate::parse_from_str(date, "%Y%m%d") {
+        Ok(date) => format!("{}{}", (date + Duration::days(days)).format("%Y%m%d"), &value[8..]),
+        Err(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> DefaultDicomObject {
+        let mut obj = dicom_object::open_file("./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm").unwrap();
+        obj.put(DataElement::new(tags::PATIENT_NAME, VR::PN, PrimitiveValue::from("ZHANG^SAN")));
+        obj.put(DataElement::new(tags::PATIENT_ID, VR::LO, PrimitiveValue::from("P12345")));
+        obj.put(DataElement::new(tags::STUDY_DATE, VR::DA, PrimitiveValue::from("20240115")));
+        obj.put(DataElement::new(tags::STATION_NAME, VR::SH, PrimitiveValue::from("CT01")));
+        obj.put(DataElement::new(tags::SECONDARY_CAPTURE_DEVICE_ID, VR::LO, PrimitiveValue::from("SC-WS-07")));
+        obj.put(DataElement::new(tags::STUDY_DESCRIPTION, VR::LO, PrimitiveValue::from("Chest CT zhang P12345")));
+        obj.put(DataElement::new(Tag(0x0009, 0x0010), VR::LO, PrimitiveValue::from("VENDOR")));
+        obj.put(DataElement::new(Tag(0x0009, 0x1001), VR::LO, PrimitiveValue::from("secret")));
+        let referenced = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::REFERENCED_SOP_CLASS_UID, VR::UI, PrimitiveValue::from("1.2.840.10008.5.1.4.1.1.2")),
+            DataElement::new(tags::REFERENCED_SOP_INSTANCE_UID, VR::UI, PrimitiveValue::from("1.2.3.4")),
+            DataElement::new(tags::PATIENT_NAME, VR::PN, PrimitiveValue::from("ZHANG^SAN")),
+        ]);
+        obj.put(DataElement::new(tags::SOURCE_IMAGE_SEQUENCE, VR::SQ, DataSetSequence::from(vec![referenced])));
+        obj
+    }
+
+    fn text(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+        obj.element_opt(tag)
+            .ok()
+            .flatten()
+            .map(|e| e.to_str().unwrap_or_default().trim_end_matches(['\0', ' ']).to_string())
+    }
+
+    #[test]
+    fn test_basic_profile() {
+        let original = sample();
+        let original_study = text(&original, tags::STUDY_INSTANCE_UID).unwrap();
+        let anonymizer = Anonymizer::new(&[], None).unwrap();
+        let obj = anonymizer.anonymize(original).unwrap();
+
+        assert_eq!(text(&obj, tags::PATIENT_NAME).as_deref(), Some(""));
+        assert_eq!(text(&obj, tags::PATIENT_ID).as_deref(), Some(""));
+        assert_eq!(text(&obj, tags::STUDY_DATE).as_deref(), Some(""));
+        assert_eq!(text(&obj, tags::STATION_NAME), None);
+        assert_eq!(text(&obj, tags::SECONDARY_CAPTURE_DEVICE_ID), None);
+        assert_eq!(text(&obj, tags::STUDY_DESCRIPTION), None);
+        assert!(obj.element_opt(Tag(0x0009, 0x0010)).unwrap().is_none());
+        assert!(obj.element_opt(Tag(0x0009, 0x1001)).unwrap().is_none());
+        assert_eq!(text(&obj, tags::PATIENT_IDENTITY_REMOVED).as_deref(), Some("YES"));
+        assert_eq!(text(&obj, tags::LONGITUDINAL_TEMPORAL_INFORMATION_MODIFIED).as_deref(), Some("REMOVED"));
+
+        let study = text(&obj, tags::STUDY_INSTANCE_UID).unwrap();
+        assert_ne!(study, original_study);
+        assert_eq!(anonymizer.uids.map(&original_study).unwrap(), study);
+        assert_eq!(text(&obj, tags::SOP_INSTANCE_UID).unwrap(), obj.meta().media_storage_sop_instance_uid());
+
+        // 序列中的属性同样处理: 引用的 UID 被替换, 姓名被清空
+        let item = &obj.element(tags::SOURCE_IMAGE_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(text(item, tags::REFERENCED_SOP_INSTANCE_UID), Some(anonymizer.uids.map("1.2.3.4").unwrap()));
+        assert_eq!(text(item, tags::REFERENCED_SOP_CLASS_UID).as_deref(), Some("1.2.840.10008.5.1.4.1.1.2"));
+        assert_eq!(text(item, tags::PATIENT_NAME).as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_profile_options() {
+        let original = sample();
+        let original_study = text(&original, tags::STUDY_INSTANCE_UID).unwrap();
+        let options = [RetainDeviceIdentity, RetainLongitudinalModifiedDates, CleanDescriptors, RetainUids];
+        assert!(Anonymizer::new(&options, None).is_err());
+        assert!(Anonymizer::new(&[RetainLongitudinalFullDates, RetainLongitudinalModifiedDates], Some(1)).is_err());
+        let anonymizer = Anonymizer::new(&options, Some(-20))
+            .unwrap()
+            .with_patient(Some("SUBJ-001".to_string()), None);
+        let obj = anonymizer.anonymize(original).unwrap();
+
+        assert_eq!(text(&obj, tags::STATION_NAME).as_deref(), Some("CT01"));
+        assert_eq!(text(&obj, tags::SECONDARY_CAPTURE_DEVICE_ID).as_deref(), Some("SC-WS-07"));
+        assert_eq!(text(&obj, tags::STUDY_DATE).as_deref(), Some("20231226"));
+        assert_eq!(text(&obj, tags::STUDY_DESCRIPTION).as_deref(), Some("Chest CT"));
+        assert_eq!(text(&obj, tags::STUDY_INSTANCE_UID), Some(original_study));
+        assert_eq!(text(&obj, tags::PATIENT_ID).as_deref(), Some("SUBJ-001"));
+        assert_eq!(text(&obj, tags::LONGITUDINAL_TEMPORAL_INFORMATION_MODIFIED).as_deref(), Some("MODIFIED"));
+        let codes: Vec<String> = obj
+            .element(tags::DEIDENTIFICATION_METHOD_CODE_SEQUENCE)
+            .unwrap()
+            .items()
+            .unwrap()
+            .iter()
+            .map(|item| text(item, tags::CODE_VALUE).unwrap())
+            .collect();
+        assert_eq!(codes, ["113100", "113110", "113109", "113107", "113105"]);
+    }
+
+    #[test]
+    fn test_shift_date() {
+        assert_eq!(shift_date("20240115", -20), "20231226");
+        assert_eq!(shift_date("20240301120000.5", -1), "20240229120000.5");
+        assert_eq!(shift_date("2024", 1), "");
+        // 非 ASCII 字符落在第 8 字节处时按无效日期清空, 不会 panic
+        assert_eq!(shift_date("2024011é", 1), "");
+        assert_eq!(shift_date("2024年1月15日", 1), "");
+    }
+}
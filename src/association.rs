@@ -0,0 +1,726 @@
+use dicom::dictionary_std::tags;
+use dicom_ul::pdu::{
+    AbortRQSource, AssociationAC, AssociationRJ, AssociationRJResult, AssociationRJSource,
+    AssociationRJServiceUserReason, AssociationRQ, PDataValue, PDataValueType, Pdu,
+    PresentationContextProposed, PresentationContextResult, PresentationContextResultReason,
+    UserVariableItem,
+};
+use dicom_ul::pdu::{DEFAULT_MAX_PDU, MAXIMUM_PDU_SIZE, MINIMUM_PDU_SIZE, read_pdu, write_pdu};
+use dicom_object::InMemDicomObject;
+use dicom_transfer_syntax_registry::entries::IMPLICIT_VR_LITTLE_ENDIAN;
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+use std::net::TcpStream;
+
+/// DICOM 应用上下文名称
+pub const APPLICATION_CONTEXT_NAME: &str = "1.2.840.10008.3.1.1.1";
+pub const IMPLEMENTATION_CLASS_UID: &str = "1.2.826.0.1.3680043.10.1526.1";
+pub const IMPLEMENTATION_VERSION_NAME: &str = "DCMTOOLS_010";
+/// Verification SOP Class
+pub const VERIFICATION_SOP_CLASS: &str = "1.2.840.10008.1.1";
+/// 默认的传输语法: Explicit VR Little Endian, Implicit VR Little Endian
+pub const DEFAULT_TRANSFER_SYNTAXES: &[&str] = &["1.2.840.10008.1.2.1", "1.2.840.10008.1.2"];
+
+/// 网络操作的错误
+#[derive(Debug)]
+pub enum NetError {
+    /// 读写 socket 失败, 包括超时
+    Io(std::io::Error),
+    /// PDU 编解码失败
+    Pdu(String),
+    /// 对端拒绝了关联请求
+    Rejected(String),
+    /// 对端中止了关联
+    Aborted,
+    /// 对端关闭了连接
+    Closed,
+    /// 收到不符合协议的 PDU 或 DIMSE 消息
+    Protocol(String),
+    /// TLS 证书或配置错误
+    Tls(String),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(e) => write!(f, "network error: {}", e),
+            NetError::Pdu(e) => write!(f, "invalid PDU: {}", e),
+            NetError::Rejected(reason) => write!(f, "association rejected: {}", reason),
+            NetError::Aborted => write!(f, "association aborted by peer"),
+            NetError::Closed => write!(f, "connection closed by peer"),
+            NetError::Protocol(e) => write!(f, "protocol error: {}", e),
+            NetError::Tls(e) => write!(f, "TLS error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for NetError {
+    fn from(e: std::io::Error) -> Self {
+        NetError::Io(e)
+    }
+}
+
+/// AE Title: 1-16 个字符, 不能全是空格, 不能包含反斜杠和控制字符 (用作 clap value_parser)
+pub fn parse_ae_title(value: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() || value.len() > 16 {
+        return Err(format!("AE title must be 1-16 characters: {:?}", value));
+    }
+    if value.chars().any(|c| c == '\\' || c.is_control() || !c.is_ascii()) {
+        return Err(format!("invalid character in AE title: {:?}", value));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// 协商成功的表示上下文
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresentationContext {
+    pub id: u8,
+    pub abstract_syntax: String,
+    pub transfer_syntax: String,
+}
+
+/// 发起关联 (SCU) 的参数
+#[derive(Debug, Clone)]
+pub struct RequestOptions {
+    pub calling_ae: String,
+    pub called_ae: String,
+    pub max_pdu: u32,
+    /// (abstract syntax, transfer syntaxes), 每项对应一个表示上下文
+    pub contexts: Vec<(String, Vec<String>)>,
+    /// 请求方同时担任 SCP 的 SOP Class (C-GET 时接收 C-STORE)
+    pub scp_roles: Vec<String>,
+}
+
+impl RequestOptions {
+    pub fn new(calling_ae: &str, called_ae: &str) -> Self {
+        Self {
+            calling_ae: calling_ae.to_string(),
+            called_ae: called_ae.to_string(),
+            max_pdu: DEFAULT_MAX_PDU,
+            contexts: Vec::new(),
+            scp_roles: Vec::new(),
+        }
+    }
+
+    pub fn with_context(mut self, abstract_syntax: &str, transfer_syntaxes: &[&str]) -> Self {
+        self.contexts.push((
+            abstract_syntax.to_string(),
+            transfer_syntaxes.iter().map(|ts| ts.to_string()).collect(),
+        ));
+        self
+    }
+}
+
+/// 接受关联 (SCP) 的参数
+#[derive(Debug, Clone)]
+pub struct AcceptOptions {
+    pub ae_title: String,
+    /// 为 true 时只接受 Called AE Title 与 ae_title 一致的请求
+    pub strict_called_ae: bool,
+    /// 允许的 Calling AE Title, 为空时不限制
+    pub allowed_calling_aes: Vec<String>,
+    pub max_pdu: u32,
+    /// 支持的 abstract syntax
+    pub abstract_syntaxes: Vec<String>,
+    /// 按 UID 前缀接受的 abstract syntax, 如全部 Storage SOP Class
+    pub abstract_syntax_prefixes: Vec<String>,
+    /// 支持的 transfer syntax, 按请求方给出的顺序选择第一个支持的
+    pub transfer_syntaxes: Vec<String>,
+}
+
+/// 收到的 DIMSE 消息: 命令集 + 可选的数据集 (按协商的传输语法编码的原始字节)
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub presentation_context_id: u8,
+    pub command: InMemDicomObject,
+    pub data: Option<Vec<u8>>,
+}
+
+/// 等待消息时可能收到的内容
+#[derive(Debug)]
+pub enum Incoming {
+    Message(Message),
+    /// 对端请求释放关联
+    Release,
+}
+
+/// 建立在任意字节流 (TCP 或 TLS) 上的 DICOM 关联
+pub struct Association<S> {
+    stream: S,
+    read_buffer: Vec<u8>,
+    /// 对端可接收的最大 PDU
+    peer_max_pdu: u32,
+    /// 本端可接收的最大 PDU
+    max_pdu: u32,
+    pub calling_ae: String,
+    pub called_ae: String,
+    pub contexts: Vec<PresentationContext>,
+    /// 等待其他响应时收到的 C-CANCEL-RQ 所取消请求的 Message ID
+    pub cancelled: Vec<u16>,
+}
+
+/// 可以不阻塞地检查有无待读数据的字节流, SCP 在子操作之间借此检查 C-CANCEL-RQ
+pub trait PollRead {
+    /// 有待读的数据或连接已关闭时返回 true
+    fn has_input(&mut self) -> io::Result<bool>;
+}
+
+impl PollRead for TcpStream {
+    fn has_input(&mut self) -> io::Result<bool> {
+        self.set_nonblocking(true)?;
+        let result = match self.peek(&mut [0u8; 1]) {
+            // 读到 0 字节表示对端已关闭, 留给 receive 报告
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        };
+        self.set_nonblocking(false)?;
+        result
+    }
+}
+
+/// SCP/SCU Role Selection 子项 (PS3.7 D.3.3.4), dicom-ul 以 Unknown 项读写
+const ROLE_SELECTION_ITEM: u8 = 0x54;
+
+fn role_selection(sop_class: &str, scu: bool, scp: bool) -> UserVariableItem {
+    let mut data = Vec::with_capacity(sop_class.len() + 4);
+    data.extend_from_slice(&(sop_class.len() as u16).to_be_bytes());
+    data.extend_from_slice(sop_class.as_bytes());
+    data.push(scu as u8);
+    data.push(scp as u8);
+    UserVariableItem::Unknown(ROLE_SELECTION_ITEM, data)
+}
+
+/// 解析 Role Selection 子项: (SOP Class, SCU 角色, SCP 角色)
+fn parse_role_selection(item: &UserVariableItem) -> Option<(String, bool, bool)> {
+    let UserVariableItem::Unknown(ROLE_SELECTION_ITEM, data) = item else {
+        return None;
+    };
+    let len = u16::from_be_bytes([*data.first()?, *data.get(1)?]) as usize;
+    let uid = std::str::from_utf8(data.get(2..2 + len)?).ok()?;
+    Some((trim_uid(uid), *data.get(2 + len)? == 1, *data.get(3 + len)? == 1))
+}
+
+fn user_variables(max_pdu: u32) -> Vec<UserVariableItem> {
+    vec![
+        UserVariableItem::MaxLength(max_pdu),
+        UserVariableItem::ImplementationClassUID(IMPLEMENTATION_CLASS_UID.to_string()),
+        UserVariableItem::ImplementationVersionName(IMPLEMENTATION_VERSION_NAME.to_string()),
+    ]
+}
+
+fn peer_max_pdu(variables: &[UserVariableItem]) -> u32 {
+    variables
+        .iter()
+        .find_map(|v| match v {
+            UserVariableItem::MaxLength(len) => Some(*len),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_MAX_PDU)
+}
+
+fn trim_uid(uid: &str) -> String {
+    uid.trim_end_matches(['\0', ' ']).to_string()
+}
+
+fn rejection_reason(rj: &AssociationRJ) -> String {
+    let result = match rj.result {
+        AssociationRJResult::Permanent => "permanent",
+        AssociationRJResult::Transient => "transient",
+    };
+    format!("{}, {:?}", result, rj.source)
+}
+
+impl<S: Read + Write> Association<S> {
+    /// 发送 A-ASSOCIATE-RQ, 等待对端接受
+    pub fn request(stream: S, options: &RequestOptions) -> Result<Self, NetError> {
+        let max_pdu = options.max_pdu.clamp(MINIMUM_PDU_SIZE, MAXIMUM_PDU_SIZE);
+        let proposed: Vec<PresentationContextProposed> = options
+            .contexts
+            .iter()
+            .enumerate()
+            .map(|(i, (abstract_syntax, transfer_syntaxes))| PresentationContextProposed {
+                id: (i * 2 + 1) as u8,
+                abstract_syntax: abstract_syntax.clone(),
+                transfer_syntaxes: transfer_syntaxes.clone(),
+            })
+            .collect();
+        if proposed.is_empty() || proposed.len() > 128 {
+            return Err(NetError::Protocol(format!(
+                "1-128 presentation contexts required, got {}",
+                proposed.len()
+            )));
+        }
+        let mut assoc = Association {
+            stream,
+            read_buffer: Vec::new(),
+            peer_max_pdu: DEFAULT_MAX_PDU,
+            max_pdu,
+            calling_ae: options.calling_ae.clone(),
+            called_ae: options.called_ae.clone(),
+            contexts: Vec::new(),
+            cancelled: Vec::new(),
+        };
+        assoc.send(&Pdu::AssociationRQ(AssociationRQ {
+            protocol_version: 1,
+            calling_ae_title: options.calling_ae.clone(),
+            called_ae_title: options.called_ae.clone(),
+            application_context_name: APPLICATION_CONTEXT_NAME.to_string(),
+            presentation_contexts: proposed.clone(),
+            user_variables: user_variables(max_pdu)
+                .into_iter()
+                .chain(options.scp_roles.iter().map(|uid| role_selection(uid, true, true)))
+                .collect(),
+        }))?;
+        match assoc.receive()? {
+            Pdu::AssociationAC(ac) => {
+                assoc.peer_max_pdu = peer_max_pdu(&ac.user_variables);
+                assoc.contexts = ac
+                    .presentation_contexts
+                    .iter()
+                    .filter(|pc| pc.reason == PresentationContextResultReason::Acceptance)
+                    .filter_map(|pc| {
+                        let abstract_syntax = proposed.iter().find(|p| p.id == pc.id)?;
+                        Some(PresentationContext {
+                            id: pc.id,
+                            abstract_syntax: trim_uid(&abstract_syntax.abstract_syntax),
+                            transfer_syntax: trim_uid(&pc.transfer_syntax),
+                        })
+                    })
+                    .collect();
+                if assoc.contexts.is_empty() {
+                    let _ = assoc.abort();
+                    return Err(NetError::Rejected(
+                        "no presentation context accepted".to_string(),
+                    ));
+                }
+                Ok(assoc)
+            }
+            Pdu::AssociationRJ(rj) => Err(NetError::Rejected(rejection_reason(&rj))),
+            Pdu::AbortRQ { .. } => Err(NetError::Aborted),
+            pdu => Err(NetError::Protocol(format!(
+                "unexpected PDU during association: {}",
+                pdu.short_description()
+            ))),
+        }
+    }
+
+    /// 等待 A-ASSOCIATE-RQ 并按 options 协商
+    pub fn accept(stream: S, options: &AcceptOptions) -> Result<Self, NetError> {
+        let max_pdu = options.max_pdu.clamp(MINIMUM_PDU_SIZE, MAXIMUM_PDU_SIZE);
+        let mut assoc = Association {
+            stream,
+            read_buffer: Vec::new(),
+            peer_max_pdu: DEFAULT_MAX_PDU,
+            max_pdu,
+            calling_ae: String::new(),
+            called_ae: String::new(),
+            contexts: Vec::new(),
+            cancelled: Vec::new(),
+        };
+        let rq = match assoc.receive()? {
+            Pdu::AssociationRQ(rq) => rq,
+            Pdu::AbortRQ { .. } => return Err(NetError::Aborted),
+            pdu => {
+                let _ = assoc.abort();
+                return Err(NetError::Protocol(format!(
+                    "expected A-ASSOCIATE-RQ, got {}",
+                    pdu.short_description()
+                )));
+            }
+        };
+        assoc.calling_ae = rq.calling_ae_title.trim().to_string();
+        assoc.called_ae = rq.called_ae_title.trim().to_string();
+        assoc.peer_max_pdu = peer_max_pdu(&rq.user_variables);
+
+        let reject = if rq.application_context_name.trim_end_matches('\0') != APPLICATION_CONTEXT_NAME {
+            Some(AssociationRJServiceUserReason::ApplicationContextNameNotSupported)
+        } else if options.strict_called_ae && assoc.called_ae != options.ae_title {
+            Some(AssociationRJServiceUserReason::CalledAETitleNotRecognized)
+        } else if !options.allowed_calling_aes.is_empty()
+            && !options.allowed_calling_aes.contains(&assoc.calling_ae)
+        {
+            Some(AssociationRJServiceUserReason::CallingAETitleNotRecognized)
+        } else {
+            None
+        };
+        if let Some(reason) = reject {
+            assoc.send(&Pdu::AssociationRJ(AssociationRJ {
+                result: AssociationRJResult::Permanent,
+                source: AssociationRJSource::ServiceUser(reason.clone()),
+            }))?;
+            return Err(NetError::Rejected(format!(
+                "{:?} (calling {}, called {})",
+                reason, assoc.calling_ae, assoc.called_ae
+            )));
+        }
+
+        let results: Vec<PresentationContextResult> = rq
+            .presentation_contexts
+            .iter()
+            .map(|pc| negotiate(pc, options))
+            .collect();
+        assoc.contexts = results
+            .iter()
+            .filter(|r| r.reason == PresentationContextResultReason::Acceptance)
+            .filter_map(|r| {
+                let pc = rq.presentation_contexts.iter().find(|pc| pc.id == r.id)?;
+                Some(PresentationContext {
+                    id: r.id,
+                    abstract_syntax: trim_uid(&pc.abstract_syntax),
+                    transfer_syntax: trim_uid(&r.transfer_syntax),
+                })
+            })
+            .collect();
+        assoc.send(&Pdu::AssociationAC(AssociationAC {
+            protocol_version: 1,
+            calling_ae_title: rq.calling_ae_title.clone(),
+            called_ae_title: rq.called_ae_title.clone(),
+            application_context_name: APPLICATION_CONTEXT_NAME.to_string(),
+            presentation_contexts: results,
+            // 接受请求方提议的角色, C-GET 时本端通过同一关联发送 C-STORE
+            user_variables: user_variables(max_pdu)
+                .into_iter()
+                .chain(
+                    rq.user_variables
+                        .iter()
+                        .filter_map(parse_role_selection)
+                        .map(|(uid, scu, scp)| role_selection(&uid, scu, scp)),
+                )
+                .collect(),
+        }))?;
+        Ok(assoc)
+    }
+
+    /// 查找指定 abstract syntax 的上下文
+    pub fn find_context(&self, abstract_syntax: &str) -> Option<&PresentationContext> {
+        self.contexts
+            .iter()
+            .find(|pc| pc.abstract_syntax == abstract_syntax)
+    }
+
+    pub fn send(&mut self, pdu: &Pdu) -> Result<(), NetError> {
+        let mut buffer = Vec::new();
+        write_pdu(&mut buffer, pdu).map_err(|e| NetError::Pdu(e.to_string()))?;
+        self.stream.write_all(&buffer)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    pub fn receive(&mut self) -> Result<Pdu, NetError> {
+        let mut chunk = [0u8; 16 * 1024];
+        loop {
+            let mut cursor = Cursor::new(&self.read_buffer[..]);
+            if let Some(pdu) =
+                read_pdu(&mut cursor, self.max_pdu, false).map_err(|e| NetError::Pdu(e.to_string()))?
+            {
+                let consumed = cursor.position() as usize;
+                self.read_buffer.drain(..consumed);
+                return Ok(pdu);
+            }
+            let n = self.stream.read(&mut chunk)?;
+            if n == 0 {
+                return Err(NetError::Closed);
+            }
+            self.read_buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// 发送一条 DIMSE 消息, 按对端的最大 PDU 分片
+    pub fn send_message(
+        &mut self,
+        presentation_context_id: u8,
+        command: &InMemDicomObject,
+        data: Option<&[u8]>,
+    ) -> Result<(), NetError> {
+        let command = encode_command(command)?;
+        self.send_fragments(presentation_context_id, PDataValueType::Command, &command)?;
+        if let Some(data) = data {
+            self.send_fragments(presentation_context_id, PDataValueType::Data, data)?;
+        }
+        Ok(())
+    }
+
+    fn send_fragments(
+        &mut self,
+        presentation_context_id: u8,
+        value_type: PDataValueType,
+        bytes: &[u8],
+    ) -> Result<(), NetError> {
+        // PDV item 头部 6 字节: 长度 (4) + 上下文 ID (1) + 控制头 (1)
+        let peer_max = if self.peer_max_pdu == 0 {
+            MAXIMUM_PDU_SIZE
+        } else {
+            self.peer_max_pdu
+        };
+        let chunk_size = (peer_max.max(MINIMUM_PDU_SIZE) - 6) as usize;
+        let mut chunks = bytes.chunks(chunk_size).peekable();
+        if chunks.peek().is_none() {
+            return self.send(&Pdu::PData {
+                data: vec![PDataValue {
+                    presentation_context_id,
+                    value_type,
+                    is_last: true,
+                    data: Vec::new(),
+                }],
+            });
+        }
+        while let Some(chunk) = chunks.next() {
+            let is_last = chunks.peek().is_none();
+            self.send(&Pdu::PData {
+                data: vec![PDataValue {
+                    presentation_context_id,
+                    value_type: value_type.clone(),
+                    is_last,
+                    data: chunk.to_vec(),
+                }],
+            })?;
+        }
+        Ok(())
+    }
+
+    /// 接收下一条 DIMSE 消息, 对端请求释放时返回 Incoming::Release
+    pub fn receive_message(&mut self) -> Result<Incoming, NetError> {
+        let mut presentation_context_id = None;
+        let mut command_bytes = Vec::new();
+        let mut command: Option<InMemDicomObject> = None;
+        let mut data = Vec::new();
+        loop {
+            let values = match self.receive()? {
+                Pdu::PData { data } => data,
+                Pdu::ReleaseRQ => return Ok(Incoming::Release),
+                Pdu::AbortRQ { .. } => return Err(NetError::Aborted),
+                pdu => {
+                    return Err(NetError::Protocol(format!(
+                        "unexpected PDU: {}",
+                        pdu.short_description()
+                    )));
+                }
+            };
+            for value in values {
+                if *presentation_context_id.get_or_insert(value.presentation_context_id)
+                    != value.presentation_context_id
+                {
+                    return Err(NetError::Protocol(
+                        "presentation context changed within a message".to_string(),
+                    ));
+                }
+                match value.value_type {
+                    PDataValueType::Command => {
+                        command_bytes.extend_from_slice(&value.data);
+                        if value.is_last {
+                            let cmd = decode_command(&command_bytes)?;
+                            if !has_data_set(&cmd) {
+                                return Ok(Incoming::Message(Message {
+                                    presentation_context_id: value.presentation_context_id,
+                                    command: cmd,
+                                    data: None,
+                                }));
+                            }
+                            command = Some(cmd);
+                        }
+                    }
+                    PDataValueType::Data => {
+                        data.extend_from_slice(&value.data);
+                        if value.is_last {
+                            let command = command.take().ok_or_else(|| {
+                                NetError::Protocol("data set received before command".to_string())
+                            })?;
+                            return Ok(Incoming::Message(Message {
+                                presentation_context_id: value.presentation_context_id,
+                                command,
+                                data: Some(data),
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 不阻塞地检查是否有新消息; 有待读数据时读取完整的一条消息
+    pub fn poll_message(&mut self) -> Result<Option<Incoming>, NetError>
+    where
+        S: PollRead,
+    {
+        if self.read_buffer.is_empty() && !self.stream.has_input()? {
+            return Ok(None);
+        }
+        self.receive_message().map(Some)
+    }
+
+    /// SCU 释放关联: 发送 A-RELEASE-RQ, 等待 A-RELEASE-RP
+    pub fn release(mut self) -> Result<(), NetError> {
+        self.send(&Pdu::ReleaseRQ)?;
+        loop {
+            match self.receive()? {
+                Pdu::ReleaseRP => return Ok(()),
+                Pdu::AbortRQ { .. } => return Err(NetError::Aborted),
+                // 释放冲突时对端也可能发送 A-RELEASE-RQ
+                Pdu::ReleaseRQ => self.send(&Pdu::ReleaseRP)?,
+                Pdu::PData { .. } => continue,
+                pdu => {
+                    return Err(NetError::Protocol(format!(
+                        "expected A-RELEASE-RP, got {}",
+                        pdu.short_description()
+                    )));
+                }
+            }
+        }
+    }
+
+    /// SCP 响应对端的释放请求
+    pub fn confirm_release(mut self) -> Result<(), NetError> {
+        self.send(&Pdu::ReleaseRP)
+    }
+
+    pub fn abort(mut self) -> Result<(), NetError> {
+        self.send(&Pdu::AbortRQ {
+            source: AbortRQSource::ServiceUser,
+        })
+    }
+}
+
+fn negotiate(pc: &PresentationContextProposed, options: &AcceptOptions) -> PresentationContextResult {
+    let abstract_syntax = trim_uid(&pc.abstract_syntax);
+    let supported = options.abstract_syntaxes.contains(&abstract_syntax)
+        || options
+            .abstract_syntax_prefixes
+            .iter()
+            .any(|prefix| abstract_syntax.starts_with(prefix.as_str()));
+    if !supported {
+        return PresentationContextResult {
+            id: pc.id,
+            reason: PresentationContextResultReason::AbstractSyntaxNotSupported,
+            transfer_syntax: pc.transfer_syntaxes.first().cloned().unwrap_or_default(),
+        };
+    }
+    match pc
+        .transfer_syntaxes
+        .iter()
+        .find(|ts| options.transfer_syntaxes.contains(&trim_uid(ts)))
+    {
+        Some(ts) => PresentationContextResult {
+            id: pc.id,
+            reason: PresentationContextResultReason::Acceptance,
+            transfer_syntax: trim_uid(ts),
+        },
+        None => PresentationContextResult {
+            id: pc.id,
+            reason: PresentationContextResultReason::TransferSyntaxesNotSupported,
+            transfer_syntax: pc.transfer_syntaxes.first().cloned().unwrap_or_default(),
+        },
+    }
+}
+
+/// 命令集总是以 Implicit VR Little Endian 编码, 并带有 CommandGroupLength
+pub fn encode_command(command: &InMemDicomObject) -> Result<Vec<u8>, NetError> {
+    let mut command = command.clone();
+    command.remove_element(tags::COMMAND_GROUP_LENGTH);
+    let ts = IMPLICIT_VR_LITTLE_ENDIAN.erased();
+    let mut body = Vec::new();
+    command
+        .write_dataset_with_ts(&mut body, &ts)
+        .map_err(|e| NetError::Pdu(e.to_string()))?;
+    command.put(dicom::core::DataElement::new(
+        tags::COMMAND_GROUP_LENGTH,
+        dicom::core::VR::UL,
+        dicom::core::PrimitiveValue::from(body.len() as u32),
+    ));
+    let mut bytes = Vec::with_capacity(body.len() + 12);
+    command
+        .write_dataset_with_ts(&mut bytes, &ts)
+        .map_err(|e| NetError::Pdu(e.to_string()))?;
+    Ok(bytes)
+}
+
+pub fn decode_command(bytes: &[u8]) -> Result<InMemDicomObject, NetError> {
+    InMemDicomObject::read_dataset_with_ts(bytes, &IMPLICIT_VR_LITTLE_ENDIAN.erased())
+        .map_err(|e| NetError::Protocol(format!("invalid command set: {}", e)))
+}
+
+/// CommandDataSetType 为 0x0101 时表示没有数据集
+fn has_data_set(command: &InMemDicomObject) -> bool {
+    command
+        .get(tags::COMMAND_DATA_SET_TYPE)
+        .and_then(|e| e.to_int::<u16>().ok())
+        .is_some_and(|t| t != 0x0101)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ae_title() {
+        assert_eq!(parse_ae_title("DCM-TOOLS ").unwrap(), "DCM-TOOLS");
+        assert!(parse_ae_title("").is_err());
+        assert!(parse_ae_title("    ").is_err());
+        assert!(parse_ae_title("A_VERY_LONG_AE_TITLE").is_err());
+        assert!(parse_ae_title("BAD\\AE").is_err());
+    }
+
+    #[test]
+    fn test_negotiate_prefers_requestor_order() {
+        let options = AcceptOptions {
+            ae_title: "SCP".to_string(),
+            strict_called_ae: false,
+            allowed_calling_aes: Vec::new(),
+            max_pdu: DEFAULT_MAX_PDU,
+            abstract_syntaxes: vec![VERIFICATION_SOP_CLASS.to_string()],
+            abstract_syntax_prefixes: Vec::new(),
+            transfer_syntaxes: DEFAULT_TRANSFER_SYNTAXES.iter().map(|s| s.to_string()).collect(),
+        };
+        let pc = PresentationContextProposed {
+            id: 1,
+            abstract_syntax: VERIFICATION_SOP_CLASS.to_string(),
+            transfer_syntaxes: vec!["1.2.840.10008.1.2.4.50".to_string(), "1.2.840.10008.1.2".to_string()],
+        };
+        let result = negotiate(&pc, &options);
+        assert_eq!(result.reason, PresentationContextResultReason::Acceptance);
+        assert_eq!(result.transfer_syntax, "1.2.840.10008.1.2");
+
+        let pc = PresentationContextProposed {
+            id: 3,
+            abstract_syntax: "1.2.840.10008.5.1.4.1.1.2".to_string(),
+            transfer_syntaxes: vec!["1.2.840.10008.1.2".to_string()],
+        };
+        assert_eq!(
+            negotiate(&pc, &options).reason,
+            PresentationContextResultReason::AbstractSyntaxNotSupported
+        );
+        let options = AcceptOptions {
+            abstract_syntax_prefixes: vec!["1.2.840.10008.5.1.4.1.1.".to_string()],
+            ..options
+        };
+        assert_eq!(
+            negotiate(&pc, &options).reason,
+            PresentationContextResultReason::Acceptance
+        );
+    }
+
+    #[test]
+    fn test_role_selection_round_trip() {
+        let item = role_selection("1.2.840.10008.5.1.4.1.1.2", false, true);
+        let UserVariableItem::Unknown(ROLE_SELECTION_ITEM, data) = &item else {
+            panic!("expected role selection item");
+        };
+        assert_eq!(&data[..2], &[0, 25]);
+        assert_eq!(
+            parse_role_selection(&item),
+            Some(("1.2.840.10008.5.1.4.1.1.2".to_string(), false, true))
+        );
+        assert_eq!(parse_role_selection(&UserVariableItem::MaxLength(16384)), None);
+        assert_eq!(parse_role_selection(&UserVariableItem::Unknown(ROLE_SELECTION_ITEM, vec![0, 9, b'1'])), None);
+    }
+}
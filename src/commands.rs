@@ -0,0 +1,1188 @@
+use crate::anonymize::{Anonymizer, ProfileOption, write_into_tree};
+use crate::dcmobj::{
+    BatchSummary, ManifestOptions, build_manifest, change_transfer_syntax_iter, convert_ts_with_gdcm,
+    file_exists, generate_json_file, get_string, walk_directory, walk_input,
+};
+use crate::dicom_info::DicomInfo;
+use crate::dicom_json::{
+    BulkDataOptions, DicomJsonOptions, from_dicom_json, to_dicom_json, to_file_object,
+};
+use crate::edit::{Editor, TagPath, parse_assignment, parse_tag_path};
+use crate::error::DcmToolsError;
+use crate::geometry::{SeriesGeometry, Volume, load_series};
+use crate::dicomdir::{MEDIA_STORAGE_DIRECTORY, check_record, copy_with_iso_names, file_id, write_dicomdir};
+use crate::index::{IndexRecord, MetadataIndex};
+use crate::index_db::IndexDb;
+use crate::multiframe::{group_by_series, merge_files, split_file};
+use crate::nifti::export_series;
+use crate::render::{Preset, Renderer, Window, parse_window};
+use crate::scp::{MoveDestination, ScpArgs, ScpService, parse_move_destination};
+use crate::scu::{ScuArgs, SendItem, echo, plan_associations, store_batch};
+use crate::image_info::ImageInfo;
+use crate::patient_info::PatientInfo;
+use crate::series_info::SeriesInfo;
+use crate::study_info::StudyInfo;
+use crate::thumbnail::{DEFAULT_THUMBNAIL_SIZE, ThumbnailFormat, ThumbnailOptions, ThumbnailTarget};
+use crate::uid_map::UidMap;
+use crate::validate::validate_file;
+use crate::transcode::{
+    LossyParams, Photometric, TranscodeOptions, parse_transfer_syntax, transfer_syntax_uid,
+};
+use clap::builder::TypedValueParser;
+use clap::{Args, Subcommand, ValueEnum};
+use dicom::dictionary_std::tags;
+use dicom_object::OpenFileOptions;
+use serde::Deserialize;
+use rayon::prelude::*;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// 退出码: 全部成功
+pub const EXIT_OK: u8 = 0;
+/// 退出码: 参数错误或致命错误, 没有任何文件被处理
+pub const EXIT_FAILURE: u8 = 1;
+/// 退出码: 批处理中部分文件失败 (2 由 clap 用于命令行参数错误)
+pub const EXIT_PARTIAL: u8 = 3;
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// 扫描目录, 生成 series/sop 清单 JSON
+    Json(JsonArgs),
+    /// 转换传输语法 (单个文件或目录)
+    Transcode(TranscodeArgs),
+    /// 打印单个 DICOM 文件的内容
+    Dump(DumpArgs),
+    /// 导出为 DICOM JSON (PS3.18 Annex F)
+    DicomJson(DicomJsonArgs),
+    /// 从 DICOM JSON 或 DicomInfo JSON 生成 DICOM 文件
+    FromJson(FromJsonArgs),
+    /// 发送 C-ECHO, 验证与对端的连通性
+    Echo(EchoArgs),
+    /// 启动 Verification SCP, 响应 C-ECHO
+    EchoScp(EchoScpArgs),
+    /// 启动 Storage SCP, 接收的实例写入 output/patient/study/series/sop.dcm
+    StoreScp(StoreScpArgs),
+    /// 发送单个文件或目录到远端 AE (C-STORE)
+    Send(SendArgs),
+    /// 启动 Query/Retrieve SCP: 接收存储, 并按目录索引响应 C-FIND/C-MOVE/C-GET
+    QrScp(QrScpArgs),
+    /// 扫描目录, 将元数据写入 SQLite 索引; 再次运行时只解析新增或修改的文件
+    Index(IndexArgs),
+    /// 为目录中的实例生成 DICOMDIR (PATIENT/STUDY/SERIES/实例记录)
+    Dicomdir(DicomdirArgs),
+    /// 按 PS3.15 Basic Application Confidentiality Profile 去除身份信息
+    Anonymize(AnonymizeArgs),
+    /// 按持久化的映射表替换 UID (含序列中的引用); --reverse 时换回原 UID
+    RemapUids(RemapUidsArgs),
+    /// 修改单个文件或目录中的属性 (原地写入, 保持传输语法)
+    Edit(EditArgs),
+    /// 按 SOP Class 对应的 IOD 模块检查实例, 输出逐个文件的 JSON 报告
+    Validate(ValidateArgs),
+    /// 将图像帧渲染为 PNG (Modality LUT, VOI LUT 或窗宽窗位, MONOCHROME1 反转)
+    Render(RenderArgs),
+    /// 按 Image Position/Orientation (Patient) 排序切片, 检查层间距、缺层、重复、倾斜与方向, 输出逐个 series 的 JSON 报告
+    Geometry(GeometryArgs),
+    /// 将 series 组装为体数据并导出 NIfTI-1 (.nii.gz), 附带 JSON 附属文件
+    ToNifti(ToNiftiArgs),
+    /// 将多帧实例拆分为单帧实例 (新 SOP Instance UID), 功能组中的值写入顶层属性
+    SplitFrames(SplitFramesArgs),
+    /// 将同一 series 的单帧 CT/MR/PET 实例合并为 Legacy Converted Enhanced 多帧实例
+    MergeFrames(MergeFramesArgs),
+}
+
+/// 清单默认有效期: 24 小时
+pub const DEFAULT_MANIFEST_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Args)]
+pub struct JsonArgs {
+    /// DICOM 文件所在目录
+    #[arg(short = 'i', long = "input", required = true)]
+    pub input: PathBuf,
+    /// 输出 JSON 文件, 不指定时输出到标准输出
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+    /// 配置文件 (JSON), 字段: hiscode, token, ttl, output; 命令行参数优先
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
+    /// 医院编码
+    #[arg(long = "hiscode")]
+    pub hiscode: Option<String>,
+    /// 访问令牌
+    #[arg(long = "token")]
+    pub token: Option<String>,
+    /// 有效期, 如 3600 / 30m / 12h / 7d, 默认 24h
+    #[arg(long = "ttl", value_parser = parse_ttl)]
+    pub ttl: Option<Duration>,
+    /// SQLite 索引文件: 先增量更新, 再由索引生成清单, 不必重新解析全部文件
+    #[arg(long = "db")]
+    pub db: Option<PathBuf>,
+    /// 为每个 series 的中间实例生成缩略图, 写入该目录, 清单中以 "thumbnail" 记录路径
+    #[arg(long = "thumbnail-dir", conflicts_with = "inline_thumbnails")]
+    pub thumbnail_dir: Option<PathBuf>,
+    /// 缩略图以 base64 data URI 内嵌在清单的 "thumbnail" 中
+    #[arg(long = "inline-thumbnails")]
+    pub inline_thumbnails: bool,
+    /// 缩略图最长边 (像素)
+    #[arg(long = "thumbnail-size", default_value_t = DEFAULT_THUMBNAIL_SIZE, value_parser = clap::value_parser!(u32).range(16..=1024))]
+    pub thumbnail_size: u32,
+    /// 缩略图格式
+    #[arg(long = "thumbnail-format", value_enum, default_value_t = ThumbnailFormat::Jpeg)]
+    pub thumbnail_format: ThumbnailFormat,
+    /// 输入目录中有 DICOMDIR 时 (如患者光盘) 按其记录列出引用的文件; 默认列出目录中的全部文件.
+    /// 与 --db 同时使用时无效: 索引总是扫描目录中的全部文件
+    #[arg(long = "follow-dicomdir", conflicts_with = "db")]
+    pub follow_dicomdir: bool,
+}
+
+/// json 子命令的配置文件
+#[derive(Debug, Default, Deserialize)]
+pub struct ManifestConfig {
+    pub hiscode: Option<String>,
+    pub token: Option<String>,
+    pub ttl: Option<String>,
+    pub output: Option<PathBuf>,
+}
+
+impl JsonArgs {
+    /// 合并配置文件与命令行参数
+    pub fn options(&self) -> Result<ManifestOptions, Box<dyn std::error::Error>> {
+        let config = match &self.config {
+            Some(path) => serde_json::from_str::<ManifestConfig>(&std::fs::read_to_string(path)?)?,
+            None => ManifestConfig::default(),
+        };
+        let ttl = match (&self.ttl, &config.ttl) {
+            (Some(ttl), _) => *ttl,
+            (None, Some(ttl)) => parse_ttl(ttl)?,
+            (None, None) => DEFAULT_MANIFEST_TTL,
+        };
+        Ok(ManifestOptions {
+            output: self.output.clone().or(config.output),
+            hiscode: self
+                .hiscode
+                .clone()
+                .or(config.hiscode)
+                .ok_or("hiscode is required (--hiscode or config file)")?,
+            token: self
+                .token
+                .clone()
+                .or(config.token)
+                .ok_or("token is required (--token or config file)")?,
+            ttl,
+            thumbnails: self.thumbnail_options(),
+            follow_dicomdir: self.follow_dicomdir,
+        })
+    }
+
+    fn thumbnail_options(&self) -> Option<ThumbnailOptions> {
+        let target = match &self.thumbnail_dir {
+            Some(dir) => ThumbnailTarget::Directory(dir.clone()),
+            None if self.inline_thumbnails => ThumbnailTarget::Inline,
+            None => return None,
+        };
+        Some(ThumbnailOptions {
+            target,
+            format: self.thumbnail_format,
+            size: self.thumbnail_size,
+        })
+    }
+}
+
+/// 解析有效期: 纯数字为秒, 支持 s/m/h/d 后缀
+pub fn parse_ttl(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => value.split_at(pos),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid ttl: {}", value))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("invalid ttl unit: {}", value)),
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+#[derive(Debug, Args)]
+pub struct TranscodeArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input", required = true)]
+    pub input: PathBuf,
+    /// 输出文件 (输入为文件时) 或输出根目录 (输入为目录时)
+    #[arg(short = 'o', long = "output", required = true)]
+    pub output: PathBuf,
+    /// 目标传输语法, 名称或 UID, 如 jpeg2000-lossless / 1.2.840.10008.1.2.4.90
+    #[arg(
+        short = 't',
+        long = "transfer-syntax",
+        default_value = "jpeg2000-lossless",
+        value_parser = parse_transfer_syntax
+    )]
+    pub transfer_syntax: gdcm_conv::TransferSyntax,
+    /// 光度解释转换, 在第一段传输语法之后执行
+    #[arg(short = 'p', long = "photometric", value_enum, ignore_case = true)]
+    pub photometric: Option<Photometric>,
+    /// 第二段传输语法, 在光度解释转换之后执行
+    #[arg(long = "then", value_parser = parse_transfer_syntax)]
+    pub second_transfer_syntax: Option<gdcm_conv::TransferSyntax>,
+    /// 输入目录中有 DICOMDIR 时 (如患者光盘) 按其记录列出引用的文件; 默认列出目录中的全部文件
+    #[arg(long = "follow-dicomdir")]
+    pub follow_dicomdir: bool,
+    #[command(flatten)]
+    pub lossy: LossyParams,
+}
+
+impl TranscodeArgs {
+    pub fn options(&self) -> Result<TranscodeOptions, String> {
+        let options = TranscodeOptions {
+            transfer_syntax: self.lossy.apply(self.transfer_syntax),
+            photometric: self.photometric,
+            second_transfer_syntax: self
+                .second_transfer_syntax
+                .map(|ts| self.lossy.apply(ts))
+                .unwrap_or(gdcm_conv::TransferSyntax::None),
+        };
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct DumpArgs {
+    /// DICOM 文件
+    #[arg(short = 'i', long = "input", required = true)]
+    pub input: PathBuf,
+    /// 输出格式
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = DumpFormat::Text)]
+    pub format: DumpFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    /// 完整的数据元素列表
+    Text,
+    /// DicomInfo JSON
+    Json,
+    /// 按 patient/study/series/image 分级的 JSON
+    Levels,
+}
+
+#[derive(Debug, Args)]
+pub struct DicomJsonArgs {
+    /// DICOM 文件或目录
+    #[arg(short = 'i', long = "input", required = true)]
+    pub input: PathBuf,
+    /// 输出文件 (输入为文件时, 不指定则输出到标准输出) 或输出目录 (输入为目录时, 必须指定)
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+    /// 超过该字节数的二进制数据输出为 BulkDataURI, 需同时指定 --bulk-dir
+    #[arg(long = "bulk-threshold", requires = "bulk_dir")]
+    pub bulk_threshold: Option<usize>,
+    /// bulk data 文件的保存目录
+    #[arg(long = "bulk-dir", requires = "bulk_threshold")]
+    pub bulk_dir: Option<PathBuf>,
+    /// BulkDataURI 前缀, 默认为 bulk data 目录的 file:// URI
+    #[arg(long = "bulk-uri")]
+    pub bulk_uri: Option<String>,
+}
+
+impl DicomJsonArgs {
+    /// 目录输入时每个实例的 bulk data 放在以 SOP Instance UID 命名的子目录
+    pub fn options(&self, sub_dir: Option<&str>) -> DicomJsonOptions {
+        let bulk_data = match (self.bulk_threshold, &self.bulk_dir) {
+            (Some(threshold), Some(dir)) => {
+                let mut base_uri = self
+                    .bulk_uri
+                    .clone()
+                    .unwrap_or_else(|| format!("file://{}/", dir.display()));
+                if !base_uri.ends_with('/') {
+                    base_uri.push('/');
+                }
+                let mut dir = dir.clone();
+                if let Some(sub_dir) = sub_dir {
+                    dir.push(sub_dir);
+                    base_uri = format!("{}{}/", base_uri, sub_dir);
+                }
+                Some(BulkDataOptions {
+                    threshold,
+                    dir,
+                    base_uri,
+                })
+            }
+            _ => None,
+        };
+        DicomJsonOptions { bulk_data }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct FromJsonArgs {
+    /// DICOM JSON (PS3.18) 或 DicomInfo 格式的 JSON 文件
+    #[arg(short = 'i', long = "input", required = true)]
+    pub input: PathBuf,
+    /// 输出的 DICOM 文件
+    #[arg(short = 'o', long = "output", required = true)]
+    pub output: PathBuf,
+    /// 输出文件的传输语法, 只支持未压缩的传输语法
+    #[arg(
+        short = 't',
+        long = "transfer-syntax",
+        default_value = "explicit-vr-little-endian",
+        value_parser = parse_native_transfer_syntax
+    )]
+    pub transfer_syntax: String,
+    /// 相对 BulkDataURI 的根目录, 默认为输入文件所在目录
+    #[arg(long = "bulk-base")]
+    pub bulk_base: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct EchoArgs {
+    #[command(flatten)]
+    pub scu: ScuArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct EchoScpArgs {
+    #[command(flatten)]
+    pub scp: ScpArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct StoreScpArgs {
+    #[command(flatten)]
+    pub scp: ScpArgs,
+    /// 存储根目录
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct QrScpArgs {
+    #[command(flatten)]
+    pub scp: ScpArgs,
+    /// 存储根目录, 启动时扫描建立索引
+    #[arg(short = 'd', long = "dir")]
+    pub dir: PathBuf,
+    /// C-MOVE 目的地 AE=host:port (TLS 为 AE=tls://host:port), 可多次指定; 未配置的目的地返回 0xA801
+    #[arg(long = "move-dest", value_parser = parse_move_destination)]
+    pub move_destinations: Vec<MoveDestination>,
+    /// SQLite 索引文件, 启动时增量更新后加载, 代替完整扫描
+    #[arg(long = "db")]
+    pub db: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DicomdirArgs {
+    /// DICOM 文件所在目录, 不重命名时 DICOMDIR 写在该目录下
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 复制到 --output 并按层级重命名为 8 字符的 ISO 9660 文件名 (PAT00001/STU00001/SER00001/IMG00001)
+    #[arg(long = "rename", requires = "output")]
+    pub rename: bool,
+    /// 重命名模式的输出目录
+    #[arg(short = 'o', long = "output", requires = "rename")]
+    pub output: Option<PathBuf>,
+    /// File-set ID (0004,1130)
+    #[arg(long = "file-set-id", default_value = "DCMTOOLS", value_parser = parse_file_set_id)]
+    pub file_set_id: String,
+}
+
+/// File-set ID: 最多 16 个大写字母、数字、下划线或空格 (CS)
+fn parse_file_set_id(value: &str) -> Result<String, String> {
+    let valid = value.len() <= 16
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_' || b == b' ');
+    if !valid {
+        return Err(format!("invalid file-set ID '{}': up to 16 of A-Z 0-9 _ and space", value));
+    }
+    Ok(value.to_string())
+}
+
+#[derive(Debug, Args)]
+pub struct IndexArgs {
+    /// DICOM 文件所在目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// SQLite 索引文件, 不存在时创建
+    #[arg(long = "db")]
+    pub db: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct AnonymizeArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 输出文件 (输入为文件时) 或输出根目录 (输入为目录时, 写入 study/series/sop.dcm)
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+    /// PS3.15 Profile Option, 可多次指定
+    #[arg(long = "option", value_enum)]
+    pub options: Vec<ProfileOption>,
+    /// retain-longitudinal-modified-dates 时日期平移的天数, 可为负数
+    #[arg(long = "date-shift", allow_negative_numbers = true)]
+    pub date_shift: Option<i64>,
+    /// 替换后的 Patient ID, 如受试者编号
+    #[arg(long = "patient-id")]
+    pub patient_id: Option<String>,
+    /// 替换后的 Patient's Name
+    #[arg(long = "patient-name")]
+    pub patient_name: Option<String>,
+    /// UID 映射文件 (SQLite), 不存在时创建; 同一患者的新数据再次运行时得到相同的新 UID
+    #[arg(long = "uid-map")]
+    pub uid_map: Option<PathBuf>,
+    /// 输入目录中有 DICOMDIR 时 (如患者光盘) 按其记录列出引用的文件; 默认列出目录中的全部文件
+    #[arg(long = "follow-dicomdir")]
+    pub follow_dicomdir: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RemapUidsArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 输出文件 (输入为文件时) 或输出根目录 (输入为目录时, 写入 study/series/sop.dcm)
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+    /// UID 映射文件 (SQLite), 不存在时创建
+    #[arg(long = "uid-map")]
+    pub uid_map: PathBuf,
+    /// 由新 UID 换回原 UID (重新识别), 映射中没有的 UID 保持不变
+    #[arg(long = "reverse")]
+    pub reverse: bool,
+}
+
+/// 属性路径: 0010,0010 / (0010,0010) / PatientName / 0040,0275[0].0040,1001 / 0008,1140[*].0008,1155;
+/// 按 --copy → --delete → --insert → --set 的顺序执行
+#[derive(Debug, Args)]
+pub struct EditArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 设置属性值 PATH=VALUE, 多值以 "\\" 分隔; 路径中的序列必须存在, 属性不存在时创建
+    #[arg(long = "set", value_parser = parse_assignment)]
+    pub sets: Vec<(TagPath, String)>,
+    /// 删除属性, 不存在时忽略
+    #[arg(long = "delete", value_parser = parse_tag_path)]
+    pub deletes: Vec<TagPath>,
+    /// 插入属性 PATH=VALUE, 创建路径中缺少的序列与条目 (下标等于条目数时追加)
+    #[arg(long = "insert", value_parser = parse_assignment)]
+    pub inserts: Vec<(TagPath, String)>,
+    /// 从该文件复制 --copy 指定的属性
+    #[arg(long = "copy-from", requires = "copies")]
+    pub copy_from: Option<PathBuf>,
+    /// 要复制的属性路径
+    #[arg(long = "copy", value_parser = parse_tag_path, requires = "copy_from")]
+    pub copies: Vec<TagPath>,
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 报告写入该文件, 默认输出到 stdout
+    #[arg(long = "report")]
+    pub report: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct GeometryArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 报告写入该文件, 默认输出到 stdout
+    #[arg(long = "report")]
+    pub report: Option<PathBuf>,
+    /// 将可组装的 series 写为 {series_uid}.raw (float32 小端, 经 Rescale, 列变化最快) 和记录尺寸、仿射矩阵的 {series_uid}.json
+    #[arg(long = "volume")]
+    pub volume: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ToNiftiArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 输出目录, 文件以 Series Instance UID 命名; 只有一个 series 时也可以是 .nii.gz 文件
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct SplitFramesArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 输出目录, 文件以新的 SOP Instance UID 命名
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeFramesArgs {
+    /// 输入目录, 按 Series Instance UID 分组合并
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 输出目录, 文件以新的 SOP Instance UID 命名; 只有一个 series 时也可以是 .dcm 文件
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct RenderArgs {
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 输出目录, 文件以 SOP Instance UID 命名; 单个文件只输出一帧时也可以是 .png 文件
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+    /// 只渲染该帧 (从 1 开始), 默认渲染全部帧
+    #[arg(long = "frame", value_parser = clap::value_parser!(u32).range(1..))]
+    pub frame: Option<u32>,
+    /// 窗位与窗宽 CENTER,WIDTH, 替代文件中的 VOI LUT 与窗口; 负窗位写作 --window=-600,1500
+    #[arg(long = "window", value_parser = parse_window, conflicts_with = "preset")]
+    pub window: Option<Window>,
+    /// 预设的 CT 窗
+    #[arg(long = "preset", value_enum)]
+    pub preset: Option<Preset>,
+    /// 输出 PNG 的位深
+    #[arg(
+        long = "bits",
+        default_value = "8",
+        value_parser = clap::builder::PossibleValuesParser::new(["8", "16"]).map(|s| s.parse::<u8>().unwrap())
+    )]
+    pub bits: u8,
+}
+
+#[derive(Debug, Args)]
+pub struct SendArgs {
+    #[command(flatten)]
+    pub scu: ScuArgs,
+    /// 输入文件或目录
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+    /// 并行的关联数
+    #[arg(long = "associations", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=64))]
+    pub associations: u16,
+    /// 对端不接受原传输语法时, 不使用 gdcm 解压后重发
+    #[arg(long = "no-transcode")]
+    pub no_transcode: bool,
+    /// 逐个文件的发送结果写入 JSON 报告
+    #[arg(long = "report")]
+    pub report: Option<PathBuf>,
+    /// 输入目录中有 DICOMDIR 时 (如患者光盘) 按其记录列出引用的文件; 默认列出目录中的全部文件
+    #[arg(long = "follow-dicomdir")]
+    pub follow_dicomdir: bool,
+}
+
+/// 解析未压缩的传输语法, 返回 UID
+fn parse_native_transfer_syntax(value: &str) -> Result<String, String> {
+    let ts = parse_transfer_syntax(value)?;
+    match ts {
+        gdcm_conv::TransferSyntax::ImplicitVRLittleEndian
+        | gdcm_conv::TransferSyntax::ExplicitVRLittleEndian
+        | gdcm_conv::TransferSyntax::ExplicitVRBigEndian => Ok(transfer_syntax_uid(ts)
+            .unwrap_or_default()
+            .to_string()),
+        _ => Err(format!("not an uncompressed transfer syntax: {}", value)),
+    }
+}
+
+impl Commands {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Json(_) => "json",
+            Commands::Transcode(_) => "transcode",
+            Commands::Dump(_) => "dump",
+            Commands::DicomJson(_) => "dicom-json",
+            Commands::FromJson(_) => "from-json",
+            Commands::Echo(_) => "echo",
+            Commands::EchoScp(_) => "echo-scp",
+            Commands::StoreScp(_) => "store-scp",
+            Commands::Send(_) => "send",
+            Commands::QrScp(_) => "qr-scp",
+            Commands::Index(_) => "index",
+            Commands::Dicomdir(_) => "dicomdir",
+            Commands::Anonymize(_) => "anonymize",
+            Commands::RemapUids(_) => "remap-uids",
+            Commands::Edit(_) => "edit",
+            Commands::Validate(_) => "validate",
+            Commands::Render(_) => "render",
+            Commands::Geometry(_) => "geometry",
+            Commands::ToNifti(_) => "to-nifti",
+            Commands::SplitFrames(_) => "split-frames",
+            Commands::MergeFrames(_) => "merge-frames",
+        }
+    }
+
+    pub fn run(&self) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+        match self {
+            Commands::Json(args) => run_json(args),
+            Commands::Transcode(args) => run_transcode(args),
+            Commands::Dump(args) => run_dump(args),
+            Commands::DicomJson(args) => run_dicom_json(args),
+            Commands::FromJson(args) => run_from_json(args),
+            Commands::Echo(args) => run_echo(args),
+            Commands::EchoScp(args) => run_echo_scp(args),
+            Commands::StoreScp(args) => run_store_scp(args),
+            Commands::Send(args) => run_send(args),
+            Commands::QrScp(args) => run_qr_scp(args),
+            Commands::Index(args) => run_index(args),
+            Commands::Dicomdir(args) => run_dicomdir(args),
+            Commands::Anonymize(args) => run_anonymize(args),
+            Commands::RemapUids(args) => run_remap_uids(args),
+            Commands::Edit(args) => run_edit(args),
+            Commands::Validate(args) => run_validate(args),
+            Commands::Render(args) => run_render(args),
+            Commands::Geometry(args) => run_geometry(args),
+            Commands::ToNifti(args) => run_to_nifti(args),
+            Commands::SplitFrames(args) => run_split_frames(args),
+            Commands::MergeFrames(args) => run_merge_frames(args),
+        }
+    }
+}
+
+/// 根据执行结果计算进程退出码, 供脚本判断
+pub fn exit_code(result: &Result<BatchSummary, Box<dyn std::error::Error>>) -> ExitCode {
+    match result {
+        Ok(summary) if summary.failed() == 0 => ExitCode::from(EXIT_OK),
+        Ok(summary) if summary.succeeded > 0 => ExitCode::from(EXIT_PARTIAL),
+        _ => ExitCode::from(EXIT_FAILURE),
+    }
+}
+
+fn ensure_exists(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    if !file_exists(path) {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("File or Directory does not exist: {:?}", path),
+        )));
+    }
+    Ok(())
+}
+
+fn run_json(args: &JsonArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let options = args.options()?;
+    let manifest = match &args.db {
+        Some(db) => {
+            let mut db = IndexDb::open(db)?;
+            db.update(&args.input)?;
+            build_manifest(db.manifest_entries(Some(&args.input))?, &options)?
+        }
+        None => generate_json_file(&args.input, &options)?,
+    };
+    if options.output.is_none() {
+        println!("{}", manifest);
+    }
+    Ok(BatchSummary::ok(1))
+}
+
+fn run_transcode(args: &TranscodeArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let options = args.options()?;
+    println!(
+        "Target transfer syntax: {}",
+        options.output_uid().unwrap_or("unknown")
+    );
+    if args.input.is_dir() {
+        Ok(change_transfer_syntax_iter(&args.input, &args.output, &options, None, args.follow_dicomdir)?)
+    } else {
+        convert_ts_with_gdcm(&args.input, &args.output, &options)?;
+        Ok(BatchSummary::ok(1))
+    }
+}
+
+fn run_dump(args: &DumpArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    if args.format == DumpFormat::Text {
+        let obj = dicom_object::open_file(&args.input)?;
+        dicom::dump::dump_file(&obj)?;
+        return Ok(BatchSummary::ok(1));
+    }
+    let obj = OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .open_file(&args.input)?;
+    let value = match args.format {
+        DumpFormat::Levels => json!({
+            "patient": PatientInfo::new(&obj),
+            "study": StudyInfo::new(&obj),
+            "series": SeriesInfo::new(&obj),
+            "image": ImageInfo::new(&obj),
+        }),
+        _ => serde_json::to_value(DicomInfo::new(&obj))?,
+    };
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(BatchSummary::ok(1))
+}
+
+fn run_dicom_json(args: &DicomJsonArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    if !args.input.is_dir() {
+        let obj = dicom_object::open_file(&args.input)
+            .map_err(|e| DcmToolsError::open(&args.input, e))?;
+        let value = to_dicom_json(&obj, &args.options(None))
+            .map_err(|e| dicom_json_error(&args.input, &args.input, e))?;
+        match &args.output {
+            Some(output) => std::fs::write(output, value.to_string())
+                .map_err(|e| DcmToolsError::write(output, e))?,
+            None => println!("{}", value),
+        }
+        return Ok(BatchSummary::ok(1));
+    }
+    let output = args
+        .output
+        .as_ref()
+        .ok_or("--output is required when the input is a directory")?;
+    std::fs::create_dir_all(output).map_err(|e| DcmToolsError::write(output, e))?;
+    let files = walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let results: Vec<Result<(), DcmToolsError>> = files
+        .par_iter()
+        .map(|file| export_dicom_json(file, output, args))
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+/// 导出单个文件为 output/<sop>.json
+fn export_dicom_json(file: &Path, output: &Path, args: &DicomJsonArgs) -> Result<(), DcmToolsError> {
+    let obj = dicom_object::open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+    let sop_uid = get_string(tags::SOP_INSTANCE_UID, &obj);
+    let target = output.join(format!("{}.json", sop_uid));
+    let value = to_dicom_json(&obj, &args.options(Some(&sop_uid)))
+        .map_err(|e| dicom_json_error(file, &target, e))?;
+    std::fs::write(&target, value.to_string()).map_err(|e| DcmToolsError::write(&target, e))
+}
+
+/// 无法表示的数据 (封装的像素数据) 归为转换错误, 其余为写入 bulk data 失败
+fn dicom_json_error(input: &Path, target: &Path, e: std::io::Error) -> DcmToolsError {
+    match e.kind() {
+        std::io::ErrorKind::Unsupported => DcmToolsError::convert(input, e.to_string()),
+        _ => DcmToolsError::write(target, e),
+    }
+}
+
+fn run_from_json(args: &FromJsonArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let text = std::fs::read_to_string(&args.input)
+        .map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| DcmToolsError::parse(&args.input, e))?;
+    let bulk_base = match &args.bulk_base {
+        Some(dir) => dir.clone(),
+        None => args
+            .input
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    };
+    let obj = from_dicom_json(&value, &bulk_base)
+        .and_then(|obj| to_file_object(obj, &args.transfer_syntax))
+        .map_err(|e| DcmToolsError::parse(&args.input, e))?;
+    obj.write_to_file(&args.output)
+        .map_err(|e| DcmToolsError::write(&args.output, e))?;
+    Ok(BatchSummary::ok(1))
+}
+
+fn run_echo(args: &EchoArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    let status = echo(&args.scu)?;
+    println!(
+        "C-ECHO {}@{}:{} status: 0x{:04X}",
+        args.scu.called_ae, args.scu.host, args.scu.port, status
+    );
+    if status != crate::dimse::status::SUCCESS {
+        return Err(format!("C-ECHO failed with status 0x{:04X}", status).into());
+    }
+    Ok(BatchSummary::ok(1))
+}
+
+fn run_echo_scp(args: &EchoScpArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    let listener = args.scp.listen()?;
+    std::sync::Arc::new(ScpService::new(args.scp.clone())).serve(listener)?;
+    Ok(BatchSummary::default())
+}
+
+fn run_store_scp(args: &StoreScpArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(&args.output).map_err(|e| DcmToolsError::write(&args.output, e))?;
+    let listener = args.scp.listen()?;
+    let service = ScpService::new(args.scp.clone()).with_storage(args.output.clone());
+    std::sync::Arc::new(service).serve(listener)?;
+    Ok(BatchSummary::default())
+}
+
+fn run_send(args: &SendArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let files = walk_input(&args.input, args.follow_dicomdir).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    if files.is_empty() {
+        eprintln!("No DICOM files found in the directory: {:?}", args.input);
+        return Ok(BatchSummary::default());
+    }
+    let mut results: Vec<(PathBuf, Result<u16, DcmToolsError>)> = Vec::with_capacity(files.len());
+    let mut items = Vec::with_capacity(files.len());
+    for file in files {
+        match SendItem::open(&file) {
+            Ok(item) => items.push(item),
+            Err(e) => results.push((file, Err(e))),
+        }
+    }
+    // 按 --associations 轮流分配文件, 每份再按上下文上限拆成多个关联
+    let lanes = usize::from(args.associations).min(items.len().max(1));
+    let mut groups = vec![Vec::new(); lanes];
+    for (i, item) in items.into_iter().enumerate() {
+        groups[i % lanes].push(item);
+    }
+    let sent: Vec<Vec<(PathBuf, Result<u16, DcmToolsError>)>> = groups
+        .into_par_iter()
+        .map(|group| {
+            plan_associations(group)
+                .into_iter()
+                .flat_map(|batch| {
+                    let statuses = store_batch(&args.scu, &batch, !args.no_transcode);
+                    batch.items.into_iter().map(|item| item.path).zip(statuses).collect::<Vec<_>>()
+                })
+                .collect()
+        })
+        .collect();
+    results.extend(sent.into_iter().flatten());
+
+    if let Some(report) = &args.report {
+        let entries: Vec<serde_json::Value> = results
+            .iter()
+            .map(|(path, result)| match result {
+                Ok(status) => json!({ "path": path, "status": format!("0x{:04X}", status) }),
+                Err(e) => json!({ "path": path, "error": e.to_string() }),
+            })
+            .collect();
+        std::fs::write(report, serde_json::to_string_pretty(&entries)?)
+            .map_err(|e| DcmToolsError::write(report, e))?;
+    }
+    Ok(BatchSummary::from_results(
+        results.into_iter().map(|(_, result)| result.map(|_| ())),
+    ))
+}
+
+fn run_qr_scp(args: &QrScpArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(&args.dir).map_err(|e| DcmToolsError::write(&args.dir, e))?;
+    let (index, summary) = match &args.db {
+        Some(db) => {
+            let mut db = IndexDb::open(db)?;
+            let (_, summary) = db.update(&args.dir)?;
+            (MetadataIndex::from_records(db.records(Some(&args.dir))?), summary)
+        }
+        None => MetadataIndex::scan(&args.dir)?,
+    };
+    eprintln!(
+        "[{}] indexed {} instances, {} unreadable",
+        args.scp.ae_title,
+        index.len(),
+        summary.failed()
+    );
+    let listener = args.scp.listen()?;
+    let service = ScpService::new(args.scp.clone())
+        .with_storage(args.dir.clone())
+        .with_index(std::sync::Arc::new(index))
+        .with_move_destinations(args.move_destinations.clone());
+    std::sync::Arc::new(service).serve(listener)?;
+    Ok(BatchSummary::default())
+}
+
+fn run_index(args: &IndexArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let mut db = IndexDb::open(&args.db)?;
+    let (stats, summary) = db.update(&args.input)?;
+    eprintln!(
+        "[index] added: {}, updated: {}, unchanged: {}, removed: {}",
+        stats.added, stats.updated, stats.unchanged, stats.removed
+    );
+    Ok(summary)
+}
+
+fn run_dicomdir(args: &DicomdirArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    // 重新生成 DICOMDIR 时要包含尚未被引用的新文件
+    let files = walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let opened: Vec<Result<IndexRecord, DcmToolsError>> =
+        files.par_iter().map(|file| IndexRecord::open(file)).collect();
+    let mut records = Vec::new();
+    let mut results = Vec::new();
+    for result in opened {
+        match result {
+            // 已有的 DICOMDIR 不是实例
+            Ok(record) if record.sop_class_uid == MEDIA_STORAGE_DIRECTORY => {}
+            Ok(record) => match check_record(&record) {
+                Ok(()) => {
+                    records.push(record);
+                    results.push(Ok(()));
+                }
+                Err(e) => results.push(Err(e)),
+            },
+            Err(e) => results.push(Err(e)),
+        }
+    }
+    let summary = BatchSummary::from_results(results);
+    let (root, records) = match &args.output {
+        Some(output) => {
+            std::fs::create_dir_all(output).map_err(|e| DcmToolsError::write(output, e))?;
+            (output.as_path(), copy_with_iso_names(&records, output)?)
+        }
+        None => {
+            let invalid: Vec<&IndexRecord> =
+                records.iter().filter(|r| file_id(&args.input, &r.path).is_none()).collect();
+            if let Some(first) = invalid.first() {
+                return Err(format!(
+                    "{} file names are not ISO 9660 compliant (e.g. {:?}), use --rename -o <dir>",
+                    invalid.len(),
+                    first.path
+                )
+                .into());
+            }
+            (args.input.as_path(), records)
+        }
+    };
+    let path = write_dicomdir(root, &records, &args.file_set_id)?;
+    println!("{}: {} instances", path.display(), records.len());
+    Ok(summary)
+}
+
+fn run_anonymize(args: &AnonymizeArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let mut anonymizer = Anonymizer::new(&args.options, args.date_shift)?
+        .with_patient(args.patient_id.clone(), args.patient_name.clone());
+    if let Some(path) = &args.uid_map {
+        anonymizer = anonymizer.with_uid_map(UidMap::open(path)?);
+    }
+    if !args.input.is_dir() {
+        let obj = dicom_object::open_file(&args.input)
+            .map_err(|e| DcmToolsError::open(&args.input, e))?;
+        crate::anonymize::warn_burned_in(&args.input, &obj);
+        anonymizer
+            .anonymize(obj)?
+            .write_to_file(&args.output)
+            .map_err(|e| DcmToolsError::write(&args.output, e))?;
+        return Ok(BatchSummary::ok(1));
+    }
+    let files = walk_input(&args.input, args.follow_dicomdir)
+        .map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let results: Vec<Result<(), DcmToolsError>> = files
+        .par_iter()
+        .map(|file| anonymizer.anonymize_into_tree(file, &args.output).map(|_| ()))
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn run_remap_uids(args: &RemapUidsArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let uids = UidMap::open(&args.uid_map)?;
+    let remap = |file: &Path| {
+        let obj = dicom_object::open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+        uids.remap_file(obj, args.reverse)
+    };
+    if !args.input.is_dir() {
+        remap(&args.input)?
+            .write_to_file(&args.output)
+            .map_err(|e| DcmToolsError::write(&args.output, e))?;
+        return Ok(BatchSummary::ok(1));
+    }
+    let files = walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let results: Vec<Result<(), DcmToolsError>> = files
+        .par_iter()
+        .map(|file| remap(file).and_then(|obj| write_into_tree(&obj, &args.output)).map(|_| ()))
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn run_edit(args: &EditArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let source = match &args.copy_from {
+        Some(path) => Some(
+            dicom_object::open_file(path)
+                .map_err(|e| DcmToolsError::open(path, e))?
+                .into_inner(),
+        ),
+        None => None,
+    };
+    let editor = Editor {
+        source,
+        copies: args.copies.clone(),
+        deletes: args.deletes.clone(),
+        inserts: args.inserts.clone(),
+        sets: args.sets.clone(),
+    };
+    if editor.is_empty() {
+        return Err("no edits given (--set, --delete, --insert or --copy)".into());
+    }
+    if !args.input.is_dir() {
+        editor.edit_file(&args.input)?;
+        return Ok(BatchSummary::ok(1));
+    }
+    let files = walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let results: Vec<Result<(), DcmToolsError>> = files.par_iter().map(|file| editor.edit_file(file)).collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn run_validate(args: &ValidateArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let files = if args.input.is_dir() {
+        walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?
+    } else {
+        vec![args.input.clone()]
+    };
+    let results: Vec<_> = files.par_iter().map(|file| validate_file(file)).collect();
+
+    let entries: Vec<serde_json::Value> = results
+        .iter()
+        .zip(&files)
+        .map(|(result, path)| match result {
+            Ok(report) => serde_json::to_value(report).unwrap_or_default(),
+            Err(e) => json!({ "path": path, "error": e.to_string() }),
+        })
+        .collect();
+    let output = serde_json::to_string_pretty(&entries)?;
+    match &args.report {
+        Some(report) => std::fs::write(report, output).map_err(|e| DcmToolsError::write(report, e))?,
+        None => println!("{}", output),
+    }
+    // 有错误的实例计为失败, 只有警告的计为通过
+    Ok(BatchSummary::from_results(results.into_iter().map(|result| {
+        result.and_then(|report| match report.errors {
+            0 => Ok(()),
+            errors => Err(DcmToolsError::invalid(report.path, errors)),
+        })
+    })))
+}
+
+fn run_render(args: &RenderArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let renderer = Renderer {
+        window: args.window.or(args.preset.map(Preset::window)),
+        bits: args.bits,
+    };
+    if !args.input.is_dir() {
+        renderer.render_file(&args.input, &args.output, args.frame)?;
+        return Ok(BatchSummary::ok(1));
+    }
+    if args.output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+        return Err("output must be a directory when input is a directory".into());
+    }
+    let files = walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let results: Vec<Result<(), DcmToolsError>> = files
+        .par_iter()
+        .map(|file| renderer.render_file(file, &args.output, args.frame).map(|_| ()))
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn run_geometry(args: &GeometryArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let files = if args.input.is_dir() {
+        walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?
+    } else {
+        vec![args.input.clone()]
+    };
+    let series = load_series(&files);
+    let output = serde_json::to_string_pretty(&series)?;
+    match &args.report {
+        Some(report) => std::fs::write(report, output).map_err(|e| DcmToolsError::write(report, e))?,
+        None => println!("{}", output),
+    }
+    // 有致命问题的 series 计为失败; 指定 --volume 时组装失败也计为失败
+    let results: Vec<Result<(), DcmToolsError>> = series
+        .iter()
+        .map(|geometry| match &args.volume {
+            Some(dir) => write_raw_volume(geometry, dir),
+            None if geometry.is_volume() => Ok(()),
+            None => {
+                let issues = geometry.issues.iter().filter(|issue| issue.is_fatal()).count();
+                Err(DcmToolsError::geometry(geometry.path(), format!("{} blocking issue(s)", issues)))
+            }
+        })
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn write_raw_volume(geometry: &SeriesGeometry, dir: &Path) -> Result<(), DcmToolsError> {
+    let volume = Volume::assemble(geometry)?;
+    std::fs::create_dir_all(dir).map_err(|e| DcmToolsError::write(dir, e))?;
+    let target = dir.join(format!("{}.raw", geometry.series_uid));
+    let bytes: Vec<u8> = volume.values().iter().flat_map(|v| v.to_le_bytes()).collect();
+    std::fs::write(&target, bytes).map_err(|e| DcmToolsError::write(&target, e))?;
+    // 尺寸与仿射矩阵 (体素索引 -> LPS mm) 写在同名 .json 中
+    let header = json!({ "dimensions": volume.dimensions, "affine": volume.affine, "dtype": "float32" });
+    let target = target.with_extension("json");
+    std::fs::write(&target, header.to_string()).map_err(|e| DcmToolsError::write(&target, e))
+}
+
+fn run_to_nifti(args: &ToNiftiArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let files = if args.input.is_dir() {
+        walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?
+    } else {
+        vec![args.input.clone()]
+    };
+    let series = load_series(&files);
+    let single_file = args.output.to_string_lossy().ends_with(".nii.gz");
+    if single_file && series.len() > 1 {
+        return Err(format!("{} series found, output must be a directory", series.len()).into());
+    }
+    let results: Vec<Result<(), DcmToolsError>> = series
+        .iter()
+        .map(|geometry| {
+            let output = if single_file {
+                args.output.clone()
+            } else {
+                args.output.join(format!("{}.nii.gz", geometry.series_uid))
+            };
+            export_series(geometry, &output)
+        })
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn run_split_frames(args: &SplitFramesArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let files = if args.input.is_dir() {
+        walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?
+    } else {
+        vec![args.input.clone()]
+    };
+    let results: Vec<Result<(), DcmToolsError>> = files
+        .par_iter()
+        .map(|file| split_file(file, &args.output).map(|_| ()))
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+fn run_merge_frames(args: &MergeFramesArgs) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    ensure_exists(&args.input)?;
+    let files = walk_directory(&args.input).map_err(|e| DcmToolsError::read(&args.input, e))?;
+    let series = group_by_series(&files);
+    let single_file = args.output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dcm"));
+    if single_file && series.len() > 1 {
+        return Err(format!("{} series found, output must be a directory", series.len()).into());
+    }
+    let results: Vec<Result<(), DcmToolsError>> = series
+        .values()
+        .map(|files| {
+            let merged = merge_files(files)?;
+            let target = if single_file {
+                args.output.clone()
+            } else {
+                let sop_uid = merged.meta().media_storage_sop_instance_uid().trim_end_matches('\0').to_string();
+                args.output.join(format!("{}.dcm", sop_uid))
+            };
+            if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir).map_err(|e| DcmToolsError::write(dir, e))?;
+            }
+            merged.write_to_file(&target).map_err(|e| DcmToolsError::write(&target, e))
+        })
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
@@ -4,7 +4,7 @@ use clap::Parser;
 use dicom_codegen::{DicomTagAccessors, DicomTagMapAccessors, TagMapAccessors};
 use dicom_core::Tag;
 use dicom_object::DefaultDicomObject;
-use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde::de::{MapAccess, Visitor};
 // use dicom::dictionary_std::tags;
 
@@ -20,6 +20,7 @@ pub struct DcmMeta {
     #[dicom_tag(group(0x0008), element(0x0008))]
     pub image_type: String,
 }
+#[allow(dead_code)]
 impl DcmMeta {
     pub fn new(obj: &DefaultDicomObject) -> Self {
         Self {
@@ -42,6 +43,7 @@ pub struct DcmMapMeta {
     pub high_bit: u16,
 }
 
+#[allow(dead_code)]
 impl DcmMapMeta {
     pub fn new(obj: &DefaultDicomObject) -> Self {
         Self {
@@ -51,20 +53,23 @@ impl DcmMapMeta {
         }
     }
 
-    // 私有函数示例：验证位数是否合法
-    fn validate_bits(&self) -> Result<(), String> {
-        if self.bit_allocated <= 0 || self.bit_allocated > 32 {
+    /// 验证位数是否合法 (Image Pixel Module), validate 子命令复用
+    pub(crate) fn validate_bits(&self) -> Result<(), String> {
+        if self.bit_allocated == 0 || self.bit_allocated > 32 {
             return Err("bit_allocated is between (0,32]".to_string());
         }
-        if self.bit_allocated % 8 != 0 {
-            return Err("bit_allocated must be a multiple of 8".to_string());
+        if self.bit_allocated != 1 && !self.bit_allocated.is_multiple_of(8) {
+            return Err("bit_allocated must be 1 or a multiple of 8".to_string());
+        }
+        if self.bits_stored == 0 {
+            return Err("bits_stored must be > 0".to_string());
         }
         if self.bit_allocated < self.bits_stored {
             return Err("bit_allocated must be >= bits_stored".to_string());
         }
 
-        if self.high_bit >= self.bits_stored {
-            return Err("high_bit must be < bits_stored".to_string());
+        if self.high_bit != self.bits_stored - 1 {
+            return Err("high_bit must be bits_stored - 1".to_string());
         }
         Ok(())
     }
@@ -101,6 +106,7 @@ pub struct DcmEntityBaseMeta {
 }
 
 
+#[allow(dead_code)]
 impl DcmEntityBaseMeta {
     pub fn new(obj: &DefaultDicomObject) -> Self {
         Self {
@@ -2,25 +2,57 @@ use dicom::core::Tag;
 use dicom::dictionary_std::{tags, uids};
 use dicom::object::DefaultDicomObject;
 use dicom::pixeldata::Transcode;
-use dicom_core::Length;
-use dicom_core::header::HasLength;
-use dicom_encoding::snafu::Report;
-use dicom_object::{
-    FileDicomObject, FileMetaTableBuilder, InMemDicomObject, OpenFileOptions, open_file,
-};
-use gdcm_conv::PhotometricInterpretation;
-use log::warn;
+use dicom_object::{OpenFileOptions, open_file};
 use rayon::iter::ParallelIterator;
-use rayon::prelude::IntoParallelRefIterator;
-use rayon::prelude::*;
-use serde_json::{Map, Value, json};
+use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator};
+use serde_json::{Value, json};
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::{Cursor, Write};
 use std::path::{Path, PathBuf};
-use std::ptr::write;
-use crate::dcm_meta::{DcmEntityBaseMeta};
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use crate::dicomdir::{find_dicomdir, read_dicomdir};
+use crate::error::DcmToolsError;
+use crate::thumbnail::ThumbnailOptions;
+use crate::transcode::TranscodeOptions;
+
+/// 批处理结果统计, 失败的文件连同错误原因一起保留
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    pub errors: Vec<DcmToolsError>,
+}
+
+impl BatchSummary {
+    /// 全部成功
+    pub fn ok(succeeded: usize) -> Self {
+        Self {
+            succeeded,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn failed(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// 汇总每个文件的处理结果, 失败的文件立即输出到 stderr
+    pub fn from_results(results: impl IntoIterator<Item = Result<(), DcmToolsError>>) -> Self {
+        let mut summary = Self::default();
+        for result in results {
+            match result {
+                Ok(()) => summary.succeeded += 1,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    summary.errors.push(e);
+                }
+            }
+        }
+        summary
+    }
+}
 
 pub fn get_tag_value<T>(tag: Tag, obj: &DefaultDicomObject, def_value: T) -> T
 where
@@ -44,16 +76,16 @@ where
         .ok()
         .flatten()
         .and_then(|e| e.to_str().ok())
-        .and_then(|s|  {
+        .map(|s| {
             let mut result = vec![];
             for s in s.trim_end().split("\\") {
                 if let Ok(v) = s.parse::<T>() {
                     result.push(v);
                 }
             }
-            Some(result)
+            result
         })
-        .unwrap_or_else(|| vec![])
+        .unwrap_or_default()
 }
 
 pub fn get_string(tag: Tag, obj: &DefaultDicomObject) -> String {
@@ -69,22 +101,51 @@ pub fn json_key(tag: Tag) -> String {
     format!("x{:04X}{:04X}", group, elem)
 }
 
+/// json_key 的逆操作: "x00100020" -> (0010,0020)
+pub fn json_key_tag(key: &str) -> Option<Tag> {
+    let hex = key.strip_prefix('x')?;
+    if hex.len() != 8 || !hex.is_ascii() {
+        return None;
+    }
+    let group = u16::from_str_radix(&hex[..4], 16).ok()?;
+    let elem = u16::from_str_radix(&hex[4..], 16).ok()?;
+    Some(Tag(group, elem))
+}
+
 pub fn file_exists(p0: &PathBuf) -> bool {
     fs::metadata(p0).is_ok()
 }
 /// 递归遍历目录下的所有文件
 pub fn walk_directory<P: Into<PathBuf>>(start_path: P) -> Result<Vec<PathBuf>, std::io::Error> {
-    let start_path = start_path.into();
+    walk(start_path.into(), false)
+}
+
+/// 列出输入目录中的文件; follow_dicomdir 为 true 且目录中有 DICOMDIR 时 (如患者光盘),
+/// 按其记录层级列出引用的文件, 跳过光盘上的其他文件
+pub fn walk_input<P: Into<PathBuf>>(
+    start_path: P,
+    follow_dicomdir: bool,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    walk(start_path.into(), follow_dicomdir)
+}
+
+fn walk(start_path: PathBuf, follow_dicomdir: bool) -> Result<Vec<PathBuf>, std::io::Error> {
     let mut file_paths = Vec::new();
 
     if start_path.is_dir() {
+        if follow_dicomdir && let Some(dicomdir) = find_dicomdir(&start_path) {
+            match read_dicomdir(&dicomdir) {
+                Ok(entries) => return Ok(entries.into_iter().map(|entry| entry.path).collect()),
+                Err(e) => eprintln!("Ignoring DICOMDIR, listing files instead: {}", e),
+            }
+        }
         for entry in fs::read_dir(start_path)? {
             let entry = entry?;
             let path = entry.path();
 
             if path.is_dir() {
                 // 递归处理子目录
-                file_paths.extend(walk_directory(path)?);
+                file_paths.extend(walk(path, follow_dicomdir)?);
             } else {
                 // 收集文件路径
                 file_paths.push(path);
@@ -100,289 +161,151 @@ pub fn walk_directory<P: Into<PathBuf>>(start_path: P) -> Result<Vec<PathBuf>, s
 
 pub fn change_transfer_syntax_iter(
     src: &PathBuf,
-    dest: &PathBuf,
-    ts: gdcm_conv::TransferSyntax,
+    dest: &Path,
+    options: &TranscodeOptions,
     test_name: Option<&str>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    follow_dicomdir: bool,
+) -> Result<BatchSummary, DcmToolsError> {
     if !file_exists(src) {
-        eprintln!("File does not exist: {:?}", src);
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("File or Directory does not exist: {:?}", src),
-        )));
+        return Err(DcmToolsError::not_found(src));
     }
+    // 先在目标目录写入一个测试文件, 确认有写权限
     let test_root_name = test_name.unwrap_or("starSky14kWAKAME2k10X");
-    let target_root = format!("{}/{}", dest.to_str().unwrap(), test_root_name);
-
-    let target_path2 = format!(
-        "{}/{}/{}/{}/{}.txt",
-        dest.to_str().unwrap(),
-        test_root_name,
-        "2222",
-        "3333",
-        "12345.99"
-    );
+    let target_root = dest.join(test_root_name);
+    let target_path2 = target_root.join("2222").join("3333").join("12345.99.txt");
     // 获取父目录路径
-    let target2_dir = Path::new(&target_path2).parent().unwrap();
-    if fs::create_dir_all(&target2_dir).is_err() {
-        eprintln!("Error creating directory: {:?}", dest);
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::PermissionDenied,
-            format!("Error creating directory: {:?}", dest),
-        )));
-    }
-    if fs::write(&target_path2, "test").is_err() {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::PermissionDenied,
-            format!(
-                "Error write file in Directory: {:?}-->{:?}",
-                target_path2, target2_dir
-            ),
-        )));
-    }
-    // if fs::remove_file(&target_path2).is_err() {
-    //     return Err(Box::new(std::io::Error::new(
-    //         std::io::ErrorKind::PermissionDenied,
-    //         format!(
-    //             "Error remove file in Directory: {:?}-->{:?}",
-    //             target_path2, target2_dir
-    //         ),
-    //     )));
-    // }
-    if fs::remove_dir_all(&target_root).is_err() {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::PermissionDenied,
-            format!("Error remove  Directory: {:?}", target_root),
-        )));
-    }
+    let target2_dir = target_path2.parent().unwrap_or(&target_root);
+    fs::create_dir_all(target2_dir).map_err(|e| DcmToolsError::write(target2_dir, e))?;
+    fs::write(&target_path2, "test").map_err(|e| DcmToolsError::write(&target_path2, e))?;
+    fs::remove_dir_all(&target_root).map_err(|e| DcmToolsError::write(&target_root, e))?;
 
-    let files = walk_directory(src)?;
+    let files = walk_input(src, follow_dicomdir).map_err(|e| DcmToolsError::read(src, e))?;
     if files.is_empty() {
         eprintln!("No DICOM files found in the directory: {:?}", src);
-        return Ok(());
+        return Ok(BatchSummary::default());
     }
 
-    files.par_iter().for_each(|file| {
-        match open_file(file) {
-            Ok(obj) => {
-                let patient_id = get_string(tags::PATIENT_ID, &obj);
-                let study_uid = get_string(tags::STUDY_INSTANCE_UID, &obj);
-                let series_uid = get_string(tags::SERIES_INSTANCE_UID, &obj);
-                let sop_uid = get_string(tags::SOP_INSTANCE_UID, &obj);
-                let target_path = format!(
-                    "{:?}/{:?}/{:?}/{:?}/{:?}.dcm",
-                    dest, patient_id, study_uid, series_uid, sop_uid
-                );
-                // 获取父目录路径
-                let target_dir = Path::new(&target_path).parent().unwrap();
-                // let file_size= file.metadata().unwrap().len();
-
-                // 递归创建目录（如果不存在）
-                fs::create_dir_all(target_dir).unwrap();
-
-                let mut input_buffer = Vec::with_capacity(512 * 512);
-                // 将 DICOM 对象写入缓冲区,如果出错,则内存分配失败,直接退出
-                obj.write_all(&mut input_buffer)
-                    .expect("DefaultDicomObject write to buffer failed");
-
-                // if let Err(e) = obj.write_all(&mut input_buffer) {
-                //     eprintln!(
-                //         "DefaultDicomObject write to buffer failed: {}",
-                //         Report::from_error(e)
-                //     );
-                //     return;
-                // }
-
-                // obj.write_all(&mut buffer)
-                //     .unwrap_or_else(|e| {
-                //         eprintln!("Error writing to buffer: {}", Report::from_error(e));
-                //     });
-                match gdcm_conv::pipeline(
-                    // Input DICOM file buffer
-                    input_buffer,
-                    // Estimated Length
-                    None,
-                    // First Transfer Syntax conversion
-                    ts,
-                    // Photometric conversion
-                    PhotometricInterpretation::None,
-                    // Second Transfer Syntax conversion
-                    gdcm_conv::TransferSyntax::None,
-                ) {
-                    Ok(buffer) => {
-                        let mut output_file = File::create(target_path).unwrap();
-                        output_file.write_all(&buffer).unwrap();
-                    }
-                    Err(e) => {
-                        eprintln!("Error during transcoding: {}", e);
-                    }
-                };
-            }
-            Err(e) => {
-                eprintln!("Error opening file: {}", e);
-                return;
-            }
-        };
-    });
-    Ok(())
+    let results: Vec<Result<(), DcmToolsError>> = files
+        .par_iter()
+        .map(|file| transcode_into_tree(file, dest, options))
+        .collect();
+    Ok(BatchSummary::from_results(results))
+}
+
+/// 转换单个文件, 写入 dest/patient/study/series/sop.dcm
+fn transcode_into_tree(
+    file: &Path,
+    dest: &Path,
+    options: &TranscodeOptions,
+) -> Result<(), DcmToolsError> {
+    let obj = open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+    let patient_id = get_string(tags::PATIENT_ID, &obj);
+    let study_uid = get_string(tags::STUDY_INSTANCE_UID, &obj);
+    let series_uid = get_string(tags::SERIES_INSTANCE_UID, &obj);
+    let sop_uid = get_string(tags::SOP_INSTANCE_UID, &obj);
+    let target_path = dest
+        .join(&patient_id)
+        .join(&study_uid)
+        .join(&series_uid)
+        .join(format!("{}.dcm", sop_uid));
+    // 获取父目录路径
+    let target_dir = target_path.parent().unwrap_or(dest);
+    // 递归创建目录（如果不存在）
+    fs::create_dir_all(target_dir).map_err(|e| DcmToolsError::write(target_dir, e))?;
+
+    let mut input_buffer = Vec::with_capacity(512 * 512);
+    // 将 DICOM 对象写入缓冲区
+    obj.write_all(&mut input_buffer)
+        .map_err(|e| DcmToolsError::parse(file, e))?;
+    let buffer = options
+        .run(input_buffer)
+        .map_err(|e| DcmToolsError::transcode(file, e))?;
+    let mut output_file =
+        File::create(&target_path).map_err(|e| DcmToolsError::write(&target_path, e))?;
+    output_file
+        .write_all(&buffer)
+        .map_err(|e| DcmToolsError::write(&target_path, e))
 }
 
-#[warn(dead_code)]
+#[allow(dead_code)]
 pub fn change_transfer_syntax(
-    src: &PathBuf,
-    dest: &PathBuf,
-    ts: gdcm_conv::TransferSyntax,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut dicoms: Vec<(FileDicomObject<InMemDicomObject>, PathBuf)> = Vec::new();
-
-    match walk_directory(src) {
-        Ok(files) => {
-            for file in files {
-                let obj = match open_file(&file) {
-                    Ok(obj) => obj,
-                    Err(e) => {
-                        eprintln!("Error opening file: {}", e);
-                        continue;
-                    }
-                };
-                dicoms.push((obj, file));
-            }
-        }
-        Err(e) => eprintln!("Error walking directory: {}", e),
-    }
-    for (obj, path) in dicoms.iter() {
+    src: &Path,
+    dest: &Path,
+    options: &TranscodeOptions,
+) -> Result<BatchSummary, DcmToolsError> {
+    let files = walk_directory(src).map_err(|e| DcmToolsError::read(src, e))?;
+    let mut results = Vec::with_capacity(files.len());
+    for path in files {
         println!("Processing file: {:?}", path);
-        let mut ibuffer = Vec::new();
-        obj.write_all(&mut ibuffer).unwrap_or_else(|e| {
-            eprintln!("Error writing to buffer: {}", Report::from_error(e));
-        });
-        match gdcm_conv::pipeline(
-            // Input DICOM file buffer
-            ibuffer,
-            // Estimated Length
-            None,
-            // First Transfer Syntax conversion
-            ts,
-            // Photometric conversion
-            PhotometricInterpretation::None,
-            // Second Transfer Syntax conversion
-            gdcm_conv::TransferSyntax::None,
-        ) {
-            Ok(buffer) => {
-                let cursor = Cursor::new(buffer);
-                // Read the DICOM object from the cursor
-                let obj = dicom_object::from_reader(cursor).unwrap_or_else(|e| {
-                    eprintln!("Error reading DICOM object: {}", e);
-                    std::process::exit(1);
-                });
-                println!(
-                    "Patient Name: {:?}",
-                    get_string(tags::SOP_INSTANCE_UID, &obj)
-                );
-                let target_path = format!(
-                    "{}/{}.dcm",
-                    dest.to_str().unwrap(),
-                    path.file_stem().unwrap().to_str().unwrap()
-                );
-                obj.write_to_file(target_path).unwrap_or_else(|e| {
-                    eprintln!("{}", Report::from_error(e));
-                });
-            }
-            Err(e) => {
-                eprintln!("Error during transcoding: {}", e);
-                return Err(Box::new(e));
-            }
-        };
+        results.push(transcode_to_dir(&path, dest, options));
     }
-    Ok(())
+    Ok(BatchSummary::from_results(results))
+}
+
+fn transcode_to_dir(
+    path: &Path,
+    dest: &Path,
+    options: &TranscodeOptions,
+) -> Result<(), DcmToolsError> {
+    let obj = open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+    let mut ibuffer = Vec::new();
+    obj.write_all(&mut ibuffer)
+        .map_err(|e| DcmToolsError::parse(path, e))?;
+    let buffer = options
+        .run(ibuffer)
+        .map_err(|e| DcmToolsError::transcode(path, e))?;
+    let cursor = Cursor::new(buffer);
+    // Read the DICOM object from the cursor
+    let obj = dicom_object::from_reader(cursor).map_err(|e| DcmToolsError::transcode(path, e))?;
+    let file_stem = path.file_stem().unwrap_or(path.as_os_str());
+    let target_path = dest.join(file_stem).with_extension("dcm");
+    obj.write_to_file(&target_path)
+        .map_err(|e| DcmToolsError::write(&target_path, e))
 }
 
 pub fn convert_ts_with_gdcm(
     p0: &PathBuf,
     output_path: &PathBuf,
-) -> Result<(), Box<dyn std::error::Error>> {
+    options: &TranscodeOptions,
+) -> Result<(), DcmToolsError> {
     // 步骤 1: 读取 DICOM 文件
-    use gdcm_conv::{PhotometricInterpretation, TransferSyntax};
-    use std::fs::File;
-    use std::io::prelude::*;
-
-    // Read input file
-    let mut ibuffer = Vec::new();
-    let mut ifile = File::open(p0).unwrap();
-    ifile.read_to_end(&mut ibuffer).unwrap();
+    let ibuffer = fs::read(p0).map_err(|e| DcmToolsError::read(p0, e))?;
 
     // Transcode DICOM file
-    let buffer = match gdcm_conv::pipeline(
-        // Input DICOM file buffer
-        ibuffer,
-        // Estimated Length
-        None,
-        // First Transfer Syntax conversion
-        TransferSyntax::JPEG2000Lossless,
-        // Photometric conversion
-        PhotometricInterpretation::None,
-        // Second Transfer Syntax conversion
-        TransferSyntax::None,
-    ) {
-        Ok(buffer) => buffer,
-        Err(e) => {
-            eprintln!("Error during transcoding: {}", e);
-            return Err(Box::new(e));
-        }
-    };
+    let buffer = options
+        .run(ibuffer)
+        .map_err(|e| DcmToolsError::transcode(p0, e))?;
 
     // 转换完毕后执行验证
     // Wrap Vec<u8> in a Cursor to implement Read
     let cursor = Cursor::new(buffer);
 
     // Read the DICOM object from the cursor
-    let obj = dicom_object::from_reader(cursor)?;
-
-    // Now you can use `obj` as a DICOM object
-    println!("Patient Name: {:?}", get_string(tags::PATIENT_NAME, &obj));
-    println!("Patient Name: {:?}", get_string(tags::PATIENT_ID, &obj));
-    println!(
-        "Patient Name: {:?}",
-        get_string(tags::STUDY_DESCRIPTION, &obj)
-    );
+    let obj = dicom_object::from_reader(cursor).map_err(|e| DcmToolsError::transcode(p0, e))?;
 
-    obj.write_to_file(output_path).unwrap_or_else(|e| {
-        eprintln!("{}", Report::from_error(e));
-    });
-    //  直接写入文件的方式
-    // let mut output_file = File::create(output_path).unwrap();
-    // output_file.write_all(&buffer).unwrap();
-
-    Ok(())
+    obj.write_to_file(output_path)
+        .map_err(|e| DcmToolsError::write(output_path, e))
 }
 
 // 此修改文件传输语法,存在限制.
-#[warn(dead_code)]
+#[allow(dead_code)]
 pub fn convert_ts_with_pixel_data(
     file: &PathBuf,
     output_path: String,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), DcmToolsError> {
     // 步骤 1: 读取 DICOM 文件
-    let mut obj = open_file(file).unwrap_or_else(|e| {
-        eprintln!("open file error :{}", Report::from_error(e));
-        std::process::exit(1);
-    });
-    assert_eq!(
-        obj.meta().transfer_syntax(),
-        uids::IMPLICIT_VR_LITTLE_ENDIAN
-    );
+    let mut obj = open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+    if obj.meta().transfer_syntax() != uids::IMPLICIT_VR_LITTLE_ENDIAN {
+        return Err(DcmToolsError::transcode(
+            file,
+            format!(
+                "source transfer syntax must be {}, got {}",
+                uids::IMPLICIT_VR_LITTLE_ENDIAN,
+                obj.meta().transfer_syntax()
+            ),
+        ));
+    }
 
-    // Transcode DICOM file
-    // let ts = dicom_transfer_syntax_registry::TransferSyntaxRegistry
-    //     .get(uids::JPEG_BASELINE8_BIT)
-    //     .unwrap();
-
-    // let mut options = EncodeOptions::default();
-    // options.quality = Some(100);
-    // options.effort = Some(100);
-    // obj.transcode_with_options(ts, options).unwrap_or_else(|e| {
-    //     eprintln!("{}", Report::from_error(e));
-    // });
     // transcode to JPEG baseline
     let target_ts = dicom_transfer_syntax_registry::entries::JPIP_HTJ2K_REFERENCED_DEFLATE;
     println!(
@@ -390,174 +313,184 @@ pub fn convert_ts_with_pixel_data(
         target_ts.name(),
         target_ts.is_codec_free()
     );
-    if target_ts.is_codec_free() {
-        // transcode without codec
-        obj.transcode(&target_ts.erased())
-            .expect("Should have transcode successfully");
-        obj.update_meta(|meta| {
-            meta.implementation_class_uid = dicom_object::IMPLEMENTATION_CLASS_UID.to_string();
-            meta.implementation_version_name =
-                Some(dicom_object::IMPLEMENTATION_VERSION_NAME.to_string());
-        });
-
-        obj.write_to_file(output_path).unwrap_or_else(|e| {
-            eprintln!("{}", Report::from_error(e));
-        });
-    } else {
+    if !target_ts.is_codec_free() {
         // 添加错误提示
-        eprintln!("Error: 不支持的传输语法: {}", target_ts.uid());
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
+        return Err(DcmToolsError::transcode(
+            file,
             format!("不支持的传输语法:{:?}", target_ts.uid()),
-        )));
+        ));
     }
-    Ok(())
+    // transcode without codec
+    obj.transcode(&target_ts.erased())
+        .map_err(|e| DcmToolsError::transcode(file, e))?;
+    obj.update_meta(|meta| {
+        meta.implementation_class_uid = dicom_object::IMPLEMENTATION_CLASS_UID.to_string();
+        meta.implementation_version_name =
+            Some(dicom_object::IMPLEMENTATION_VERSION_NAME.to_string());
+    });
+
+    obj.write_to_file(&output_path)
+        .map_err(|e| DcmToolsError::write(&output_path, e))
+}
+
+/// generate_json_file 的调用参数
+#[derive(Debug, Clone)]
+pub struct ManifestOptions {
+    /// 输出文件, 为 None 时只返回 JSON 不写文件
+    pub output: Option<PathBuf>,
+    pub hiscode: String,
+    pub token: String,
+    /// 有效期, expires = 当前时间 + ttl
+    pub ttl: Duration,
+    /// 为每个 series 生成缩略图, 为 None 时不生成
+    pub thumbnails: Option<ThumbnailOptions>,
+    /// 输入目录中有 DICOMDIR 时按其记录列出文件
+    pub follow_dicomdir: bool,
 }
 
-pub fn generate_json_file(file: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    if !file_exists(file) {
-        eprintln!("File does not exist: {:?}", file);
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("File or Directory does not exist: {:?}", file),
-        )));
+impl ManifestOptions {
+    /// 过期时间, 格式如 2025-06-20T13-05-16
+    pub fn expires_at(&self, now: DateTime<Local>) -> String {
+        let ttl = chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::zero());
+        let expires = now.checked_add_signed(ttl).unwrap_or(now);
+        expires.format("%Y-%m-%dT%H-%M-%S").to_string()
     }
-    if !file.is_dir() {
-        // 递归遍历目录下的所有文件
-        eprintln!("File does not exist: {:?}", file);
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("File or Directory does not exist: {:?}", file),
-        )));
+}
+
+/// (series_uid, series_number) -> series json
+type SeriesJsonMap = HashMap<(String, u32), Value>;
+/// (series_uid, sop_uid, instance_number) -> (文件路径, sop json)
+type SopJsonMap = HashMap<(String, String, u32), (PathBuf, Value)>;
+
+/// 清单中一个实例对应的条目: series 级与 sop 级的 JSON
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    pub series_uid: String,
+    pub series_json: Value,
+    pub sop_json: Value,
+}
+
+impl ManifestEntry {
+    pub fn new(path: &Path, obj: &DefaultDicomObject) -> Self {
+        let series_uid = get_string(tags::SERIES_INSTANCE_UID, obj);
+        let sn = get_string(tags::SERIES_NUMBER, obj);
+        let sex = get_string(tags::PATIENT_SEX, obj);
+        let age = get_string(tags::PATIENT_AGE, obj);
+        let name = get_string(tags::PATIENT_NAME, obj);
+        let paid = get_string(tags::PATIENT_ID, obj);
+        let birth_date = get_string(tags::PATIENT_BIRTH_DATE, obj);
+        let modality = get_string(tags::MODALITY, obj);
+        let body_part = get_string(tags::BODY_PART_EXAMINED, obj);
+        let study_date = get_string(tags::STUDY_DATE, obj);
+        let study_time = get_string(tags::STUDY_TIME, obj);
+        let acc_num = get_string(tags::ACCESSION_NUMBER, obj);
+        let manufacturer = get_string(tags::MANUFACTURER, obj);
+        let institution_address = get_string(tags::INSTITUTION_ADDRESS, obj);
+        let institution_name = get_string(tags::INSTITUTION_NAME, obj);
+        let series_json = json!({
+              "00100040": sex,
+              "00101010": age,
+              "0020000E": series_uid,
+              "00100010": name,
+              "00100020": paid,
+              "00100030": birth_date,
+              "00180015": body_part,
+              "00200011": sn ,
+              "00080020": study_date,
+              "00080030": study_time,
+              "00080050": acc_num,
+              "00080060": modality,
+              "00080070": manufacturer,
+              "00080081": institution_address,
+              "00080080": institution_name,
+        });
+
+        let series_desc = get_string(tags::SERIES_DESCRIPTION, obj);
+        let px_spacing_vec: Vec<String> =get_tag_values(tags::PIXEL_SPACING, obj);
+        let rows = get_string(tags::ROWS, obj);
+        let columns = get_string(tags::COLUMNS, obj);
+        let image_type_vec:  Vec<String> =get_tag_values(tags::IMAGE_TYPE, obj);
+        let pixel_representation = get_string(tags::PIXEL_REPRESENTATION, obj);
+        let patient_position = get_string(tags::PATIENT_POSITION, obj);
+        let image_position_patient_vec:  Vec<String> =get_tag_values(tags::IMAGE_POSITION_PATIENT, obj);
+        let image_orientation_patient_vec:  Vec<String> =get_tag_values(tags::IMAGE_ORIENTATION_PATIENT, obj);
+        let instance_num = get_string(tags::INSTANCE_NUMBER, obj);
+        let slice_thickness = get_string(tags::SLICE_THICKNESS, obj);
+        let sop_uid = get_string(tags::SOP_INSTANCE_UID, obj);
+        let sop_json = json!({
+          "0008103E": series_desc ,
+          "00280030": px_spacing_vec,
+          "00280010": rows  ,
+          "00280011": columns,
+          "00180015": body_part,
+          "00080008": image_type_vec,
+          "00280103": pixel_representation,
+          "00185100": patient_position,
+          "00200032": image_position_patient_vec,
+          "00180050": slice_thickness,
+          "00200013": instance_num,
+          "00200037": image_orientation_patient_vec,
+          "00080018": sop_uid
+        });
+        Self {
+            path: path.to_path_buf(),
+            series_uid,
+            series_json,
+            sop_json,
+        }
+    }
+}
+
+/// JSON 中的编号字符串, 无法解析时为 0
+fn json_number(json: &Value, key: &str) -> u32 {
+    json[key].as_str().and_then(|s| s.parse::<u32>().ok()).unwrap_or(0)
+}
+
+pub fn generate_json_file(
+    file: &PathBuf,
+    options: &ManifestOptions,
+) -> Result<Value, DcmToolsError> {
+    if !file_exists(file) || !file.is_dir() {
+        return Err(DcmToolsError::not_found(file));
     }
-    let files = walk_directory(file)?;
+    let files = walk_input(file, options.follow_dicomdir).map_err(|e| DcmToolsError::read(file, e))?;
     if files.is_empty() {
         eprintln!("No DICOM files found in the directory: {:?}", file);
-        return Ok(());
     }
-    let media_storage_sop_instance_uid = "DHZ.1.2.25.280986007.1.65029756031778";
-    let empty_meta = FileMetaTableBuilder::new()
-        .transfer_syntax(
-            dicom_transfer_syntax_registry::entries::EXPLICIT_VR_LITTLE_ENDIAN.uid(),
-        )
-        .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.1")
-        .media_storage_sop_instance_uid( media_storage_sop_instance_uid)
-        .implementation_class_uid("1.2.345.6.7890.1.234")
-        .build()
-        .unwrap();
-    let results: Vec<(
-        HashMap<(String, u32), Value>,
-        HashMap<(String, String, u32), Value>,
-    )> =
-        files
-            .par_iter()
-            .map(|file| {
-                let mut local_seris_map = HashMap::new();
-                let mut local_sop_map = HashMap::new();
-                let obj =
-                    OpenFileOptions::new()
-                        .read_until(tags::PIXEL_DATA)
-                        .open_file(&file)
-                        .unwrap_or_else(|_| {
-                            FileDicomObject::new_empty_with_meta(empty_meta.clone())
-                        });
-
-
-                let mj = DcmEntityBaseMeta::new(&obj);
-
-                println!("{:?}", mj);
-
-                if get_string(tags::MEDIA_STORAGE_SOP_INSTANCE_UID, &obj) != media_storage_sop_instance_uid {
-
-
-
-                    let series_uid = get_string(tags::SERIES_INSTANCE_UID, &obj);
-                    let sn = get_string(tags::SERIES_NUMBER, &obj);
-                    let series_num = sn.parse::<u32>().unwrap_or(0);
-                    // if !local_seris_map.contains_key(&(series_uid.clone(), series_num))
-                    {
-                        let sex = get_string(tags::PATIENT_SEX, &obj);
-                        let age = get_string(tags::PATIENT_AGE, &obj);
-                        let name = get_string(tags::PATIENT_NAME, &obj);
-                        let paid = get_string(tags::PATIENT_ID, &obj);
-                        let birth_date = get_string(tags::PATIENT_BIRTH_DATE, &obj);
-                        let modality = get_string(tags::MODALITY, &obj);
-                        let body_part = get_string(tags::BODY_PART_EXAMINED, &obj);
-                        let study_date = get_string(tags::STUDY_DATE, &obj);
-                        let study_time = get_string(tags::STUDY_TIME, &obj);
-                        let acc_num = get_string(tags::ACCESSION_NUMBER, &obj);
-                        let manufacturer = get_string(tags::MANUFACTURER, &obj);
-                        let institution_address = get_string(tags::INSTITUTION_ADDRESS, &obj);
-                        let institution_name = get_string(tags::INSTITUTION_NAME, &obj);
-                        let series_json = json!({
-                              "00100040": sex,
-                              "00101010": age,
-                              "0020000E": series_uid,
-                              "00100010": name,
-                              "00100020": paid,
-                              "00100030": birth_date,
-                              "00180015": body_part,
-                              "00200011": sn ,
-                              "00080020": study_date,
-                              "00080030": study_time,
-                              "00080050": acc_num,
-                              "00080060": modality,
-                              "00080070": manufacturer,
-                              "00080081": institution_address,
-                              "00080080": institution_name,
-                        });
-                        local_seris_map.insert((series_uid.clone(), series_num), series_json);
-                    }
-                    // else{
-                    //     println!("Series already exists: {:?}", series_uid);
-                    // }
-
-                    let series_desc = get_string(tags::SERIES_DESCRIPTION, &obj);
-
-                    let px_spacing_vec: Vec<String> =get_tag_values(tags::PIXEL_SPACING, &obj);
-
-                    let rows = get_string(tags::ROWS, &obj);
-                    let columns = get_string(tags::COLUMNS, &obj);
-                    let body_part = get_string(tags::BODY_PART_EXAMINED, &obj);
-
-                    let image_type_vec:  Vec<String> =get_tag_values(tags::IMAGE_TYPE, &obj);
-                    let pixel_representation = get_string(tags::PIXEL_REPRESENTATION, &obj);
-                    let patient_position = get_string(tags::PATIENT_POSITION, &obj);
-                    let image_position_patient_vec:  Vec<String> =get_tag_values(tags::IMAGE_POSITION_PATIENT, &obj);
-
-                    let image_orientation_patient_vec:  Vec<String> =get_tag_values(tags::IMAGE_ORIENTATION_PATIENT, &obj);
-
-                    let instance_num = get_string(tags::INSTANCE_NUMBER, &obj);
-                    let slice_thickness = get_string(tags::SLICE_THICKNESS, &obj);
-                    let sop_uid = get_string(tags::SOP_INSTANCE_UID, &obj);
-                    let inst_num = instance_num.parse::<u32>().unwrap_or(0);
-                    let sop_json = json!({
-                      "0008103E": series_desc ,
-                      "00280030": px_spacing_vec,
-                      "00280010": rows  ,
-                      "00280011": columns,
-                      "00180015": body_part,
-                      "00080008": image_type_vec,
-                      "00280103": pixel_representation,
-                      "00185100": patient_position,
-                      "00200032": image_position_patient_vec,
-                      "00180050": slice_thickness,
-                      "00200013": instance_num,
-                      "00200037": image_orientation_patient_vec,
-                      "00080018": sop_uid
-                    });
-                    local_sop_map.insert((series_uid, sop_uid, inst_num), sop_json);
+    let entries: Vec<ManifestEntry> = files
+        .par_iter()
+        .filter_map(|file| {
+            // 非 DICOM 文件或损坏的文件直接跳过
+            match OpenFileOptions::new()
+                .read_until(tags::PIXEL_DATA)
+                .open_file(file)
+            {
+                Ok(obj) => Some(ManifestEntry::new(file, &obj)),
+                Err(e) => {
+                    eprintln!("Skipping: {}", DcmToolsError::open(file, e));
+                    None
                 }
-                (local_seris_map, local_sop_map)
-            })
-            .collect();
+            }
+        })
+        .collect();
+    build_manifest(entries, options)
+}
 
+/// 按 series 汇总条目生成清单, 指定输出文件时写入磁盘
+pub fn build_manifest(
+    entries: impl IntoIterator<Item = ManifestEntry>,
+    options: &ManifestOptions,
+) -> Result<Value, DcmToolsError> {
     //结果进行合并
-    let mut seris_map = HashMap::new();
-    let mut sop_map = HashMap::new();
-    for (local_seris, local_sop) in results {
-        seris_map.extend(local_seris);
-        sop_map.extend(local_sop);
+    let mut seris_map: SeriesJsonMap = HashMap::new();
+    let mut sop_map: SopJsonMap = HashMap::new();
+    for entry in entries {
+        let series_num = json_number(&entry.series_json, "00200011");
+        let sop_uid = entry.sop_json["00080018"].as_str().unwrap_or_default().to_string();
+        let inst_num = json_number(&entry.sop_json, "00200013");
+        seris_map.insert((entry.series_uid.clone(), series_num), entry.series_json);
+        sop_map.insert((entry.series_uid, sop_uid, inst_num), (entry.path, entry.sop_json));
     }
     let mut study_vec = Vec::new();
     // 排序后的 series (series_uid, series_num, series_json)
@@ -566,22 +499,20 @@ pub fn generate_json_file(file: &PathBuf) -> Result<(), Box<dyn std::error::Erro
 
     for ((series_uid, _series_num), series_json) in seris_vec {
         // 1. 收集、排序
-        let mut sop_list: Vec<(u32, &Value)> = sop_map
+        let mut sop_list: Vec<(u32, &PathBuf, &Value)> = sop_map
             .iter()
             .filter(|((s_uid, _, _), _)| s_uid == series_uid)
-            .map(|((_, _, inst_num), sop_json)| (*inst_num, sop_json))
+            .map(|((_, _, inst_num), (path, sop_json))| (*inst_num, path, sop_json))
             .collect();
-        sop_list.sort_by_key(|(inst_num, _)| *inst_num);
-        let sop_vec: Vec<&Value> = sop_list.into_iter().map(|(_, v)| v).collect();
-
-        // let mut sop_vec = Vec::new();
-        // for (s_uid, sop_json) in sop_map.iter() {
-        //     if s_uid.0 == *series_uid {
-        //         sop_vec.push(sop_json);
-        //     }
-        // }
+        sop_list.sort_by_key(|(inst_num, _, _)| *inst_num);
+        // 按 Instance Number 排序后的中间实例用于缩略图
+        let middle = sop_list.get(sop_list.len() / 2).map(|(_, path, _)| (*path).clone());
+        let sop_vec: Vec<&Value> = sop_list.into_iter().map(|(_, _, v)| v).collect();
+
         // 2. 组装 series
-        let series_json = series_json.as_object().unwrap();
+        let Some(series_json) = series_json.as_object() else {
+            continue;
+        };
         let json_str = json!({
               "00100040": series_json["00100040"],
               "00101010": series_json["00101010"],
@@ -600,17 +531,34 @@ pub fn generate_json_file(file: &PathBuf) -> Result<(), Box<dyn std::error::Erro
               "00080080": series_json["00080080"],
               "sopData":sop_vec
         });
-        study_vec.push(json_str);
+        study_vec.push((json_str, middle));
     }
+    // 缩略图渲染较慢, 各 series 并行生成; 失败时只告警, 清单中不带缩略图
+    let study_vec: Vec<Value> = study_vec
+        .into_par_iter()
+        .map(|(mut series, middle)| {
+            if let (Some(thumbnails), Some(path)) = (&options.thumbnails, middle) {
+                let series_uid = series["0020000E"].as_str().unwrap_or_default().to_string();
+                match thumbnails.thumbnail(&path, &series_uid) {
+                    Ok(thumbnail) => series["thumbnail"] = Value::String(thumbnail),
+                    Err(e) => eprintln!("Skipping thumbnail: {}", e),
+                }
+            }
+            series
+        })
+        .collect();
 
     let study_json = json!({
        "seriesData": study_vec,
-       "hiscode":"89269",
-       "expires":"2025-06-20T13-05-16",
-       "token":"cbcbc2c203fe3877737c0befd6a769fa"
+       "hiscode": options.hiscode,
+       "expires": options.expires_at(Local::now()),
+       "token": options.token
     });
-    write_json_file2(&study_json.to_string(), "./tst22-A.json");
-    return Ok(());
+    if let Some(output) = &options.output {
+        write_json_file2(&study_json.to_string(), output)
+            .map_err(|e| DcmToolsError::write(output, e))?;
+    }
+    Ok(study_json)
 }
 
 // fn write_json_file(p0: &mut Map<String, Value>, p1: &str) {
@@ -623,9 +571,7 @@ pub fn generate_json_file(file: &PathBuf) -> Result<(), Box<dyn std::error::Erro
 //             .unwrap();
 //     }
 // }
-fn write_json_file2(json: &String, p1: &str) {
-    let file = File::create(p1);
-    if (file.is_ok()) {
-        file.unwrap().write_all(json.as_bytes()).unwrap();
-    }
+fn write_json_file2(json: &str, p1: &Path) -> std::io::Result<()> {
+    let mut file = File::create(p1)?;
+    file.write_all(json.as_bytes())
 }
@@ -398,7 +398,6 @@ impl<'de> Deserialize<'de> for DicomInfo {
 
 #[cfg(test)]
 mod tests {
-    use serde_json;
     use dicom::dictionary_std::uids;
     // use dicom_core::value::C;
     use crate::dicom_info::DicomInfo;
@@ -511,7 +510,7 @@ mod tests {
         // ));
 
         //推荐的方式
-        let _c = vec!["0.5", "0.5"];
+        let _c = ["0.5", "0.5"];
         obj.put(DataElement::new(
             tags::PIXEL_SPACING,
             VR::DS,
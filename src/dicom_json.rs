@@ -0,0 +1,518 @@
+use crate::dcmobj::json_key_tag;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use dicom::core::dictionary::DataDictionary;
+use dicom::core::value::{DataSetSequence, PrimitiveValue, Value as DicomValue};
+use dicom::core::{DataElement, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags};
+use dicom_object::{DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// DICOM JSON (PS3.18 Annex F) 导出参数
+#[derive(Debug, Clone, Default)]
+pub struct DicomJsonOptions {
+    /// 不指定时二进制数据全部以 InlineBinary 输出
+    pub bulk_data: Option<BulkDataOptions>,
+}
+
+/// 超过阈值的二进制数据写入单独的文件, JSON 中只保留 BulkDataURI
+#[derive(Debug, Clone)]
+pub struct BulkDataOptions {
+    /// 字节数大于该值的二进制数据输出为 BulkDataURI
+    pub threshold: usize,
+    /// bulk data 文件的保存目录
+    pub dir: PathBuf,
+    /// URI 前缀, 与文件名拼接得到 BulkDataURI
+    pub base_uri: String,
+}
+
+/// PS3.18 中 tag 的 key 形式, 如 "00100020"
+pub fn tag_key(tag: Tag) -> String {
+    format!("{:04X}{:04X}", tag.group(), tag.element())
+}
+
+/// 将数据集转换为 DICOM JSON, 不包含文件元信息 (0002 组); 封装的像素数据返回 Unsupported
+pub fn to_dicom_json(obj: &InMemDicomObject, options: &DicomJsonOptions) -> std::io::Result<Value> {
+    if let Some(bulk) = &options.bulk_data {
+        fs::create_dir_all(&bulk.dir)?;
+    }
+    dataset_to_json(obj, options, "")
+}
+
+fn dataset_to_json(
+    obj: &InMemDicomObject,
+    options: &DicomJsonOptions,
+    prefix: &str,
+) -> std::io::Result<Value> {
+    let mut map = Map::new();
+    for elem in obj {
+        let tag = elem.header().tag;
+        if tag.group() == 0x0002 {
+            continue;
+        }
+        let key = tag_key(tag);
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        let vr = elem.vr();
+        let mut attr = Map::new();
+        attr.insert("vr".to_string(), json!(vr.to_string()));
+        match elem.value() {
+            DicomValue::Primitive(PrimitiveValue::Empty) => {}
+            DicomValue::Primitive(value) => {
+                if is_binary(vr) {
+                    binary_to_json(&value.to_bytes(), options, &path, &mut attr)?;
+                } else {
+                    attr.insert("Value".to_string(), Value::Array(primitive_to_json(vr, value)));
+                }
+            }
+            DicomValue::Sequence(seq) => {
+                let mut items = Vec::with_capacity(seq.items().len());
+                for (i, item) in seq.items().iter().enumerate() {
+                    items.push(dataset_to_json(item, options, &format!("{}.{}", path, i))?);
+                }
+                attr.insert("Value".to_string(), Value::Array(items));
+            }
+            DicomValue::PixelSequence(_) => {
+                // 拼接 fragment 会丢失 Basic Offset Table 与帧边界, 无法还原封装的像素数据
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "encapsulated Pixel Data cannot be exported, transcode to a native transfer syntax first",
+                ));
+            }
+        }
+        map.insert(key, Value::Object(attr));
+    }
+    Ok(Value::Object(map))
+}
+
+pub fn is_binary(vr: VR) -> bool {
+    matches!(
+        vr,
+        VR::OB | VR::OD | VR::OF | VR::OL | VR::OV | VR::OW | VR::UN
+    )
+}
+
+fn binary_to_json(
+    bytes: &[u8],
+    options: &DicomJsonOptions,
+    path: &str,
+    attr: &mut Map<String, Value>,
+) -> std::io::Result<()> {
+    match &options.bulk_data {
+        Some(bulk) if bytes.len() > bulk.threshold => {
+            let file_name = format!("{}.bin", path);
+            fs::write(bulk.dir.join(&file_name), bytes)?;
+            attr.insert(
+                "BulkDataURI".to_string(),
+                json!(format!("{}{}", bulk.base_uri, file_name)),
+            );
+        }
+        _ => {
+            attr.insert("InlineBinary".to_string(), json!(STANDARD.encode(bytes)));
+        }
+    }
+    Ok(())
+}
+
+fn primitive_to_json(vr: VR, value: &PrimitiveValue) -> Vec<Value> {
+    let strings = value.to_multi_str();
+    strings
+        .iter()
+        .map(|s| {
+            let s = s.trim_end_matches(['\0', ' ']);
+            if s.is_empty() {
+                return Value::Null;
+            }
+            match vr {
+                VR::PN => person_name(s),
+                VR::AT => json!(s.replace([',', '(', ')'], "").to_uppercase()),
+                VR::IS | VR::SS | VR::SL | VR::SV | VR::US | VR::UL => s
+                    .trim()
+                    .parse::<i64>()
+                    .map(Value::from)
+                    .unwrap_or_else(|_| json!(s)),
+                VR::UV => s
+                    .trim()
+                    .parse::<u64>()
+                    .map(Value::from)
+                    .unwrap_or_else(|_| json!(s)),
+                VR::DS | VR::FL | VR::FD => s
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(Value::Number)
+                    .unwrap_or_else(|| json!(s)),
+                _ => json!(s),
+            }
+        })
+        .collect()
+}
+
+/// PN 按 "=" 拆分为 Alphabetic / Ideographic / Phonetic
+fn person_name(s: &str) -> Value {
+    let mut name = Map::new();
+    for (group, key) in s.split('=').zip(["Alphabetic", "Ideographic", "Phonetic"]) {
+        if !group.is_empty() {
+            name.insert(key.to_string(), json!(group));
+        }
+    }
+    Value::Object(name)
+}
+
+/// 从 JSON 构建数据集. 支持 PS3.18 DICOM JSON ("00100020": {"vr": .., "Value": [..]})
+/// 与 DicomInfo 形式 ("x00100020": "..", VR 取自标准字典).
+/// 文件元信息 (0002 组) 被忽略, 由 to_file_object 重新生成.
+/// 相对路径或 file:// 形式的 BulkDataURI 相对 bulk_base 读取.
+pub fn from_dicom_json(value: &Value, bulk_base: &Path) -> Result<InMemDicomObject, String> {
+    let map = match value {
+        Value::Object(map) => map,
+        // QIDO-RS / WADO-RS metadata 返回的是数据集数组
+        Value::Array(items) if items.len() == 1 => items[0]
+            .as_object()
+            .ok_or("expected a JSON object")?,
+        _ => return Err("expected a JSON object or an array with one object".to_string()),
+    };
+    dataset_from_json(map, bulk_base)
+}
+
+fn dataset_from_json(map: &Map<String, Value>, bulk_base: &Path) -> Result<InMemDicomObject, String> {
+    let mut obj = InMemDicomObject::new_empty();
+    for (key, attr) in map {
+        let elem = match (parse_tag_key(key), json_key_tag(key)) {
+            (Some(tag), _) => element_from_json(tag, attr, bulk_base)?,
+            (None, Some(tag)) => element_from_tag_keyed(tag, attr)?,
+            (None, None) => return Err(format!("invalid attribute key: {}", key)),
+        };
+        if elem.header().tag.group() != 0x0002 {
+            obj.put(elem);
+        }
+    }
+    Ok(obj)
+}
+
+fn parse_tag_key(key: &str) -> Option<Tag> {
+    if key.len() != 8 || !key.is_ascii() {
+        return None;
+    }
+    let group = u16::from_str_radix(&key[..4], 16).ok()?;
+    let elem = u16::from_str_radix(&key[4..], 16).ok()?;
+    Some(Tag(group, elem))
+}
+
+fn element_from_json(
+    tag: Tag,
+    attr: &Value,
+    bulk_base: &Path,
+) -> Result<DataElement<InMemDicomObject>, String> {
+    let key = tag_key(tag);
+    let attr = attr
+        .as_object()
+        .ok_or_else(|| format!("{}: expected an attribute object", key))?;
+    let vr = attr
+        .get("vr")
+        .and_then(Value::as_str)
+        .and_then(|vr| VR::from_str(vr).ok())
+        .ok_or_else(|| format!("{}: missing or invalid vr", key))?;
+
+    if vr == VR::SQ {
+        let mut items = Vec::new();
+        for item in attr.get("Value").and_then(Value::as_array).into_iter().flatten() {
+            let item = item
+                .as_object()
+                .ok_or_else(|| format!("{}: sequence items must be objects", key))?;
+            items.push(dataset_from_json(item, bulk_base)?);
+        }
+        return Ok(DataElement::new(tag, vr, DataSetSequence::from(items)));
+    }
+    let value = if let Some(inline) = attr.get("InlineBinary").and_then(Value::as_str) {
+        let bytes = STANDARD
+            .decode(inline)
+            .map_err(|e| format!("{}: invalid InlineBinary: {}", key, e))?;
+        PrimitiveValue::U8(bytes.into())
+    } else if let Some(uri) = attr.get("BulkDataURI").and_then(Value::as_str) {
+        let path = bulk_base.join(uri.strip_prefix("file://").unwrap_or(uri));
+        if uri.contains("://") && !uri.starts_with("file://") {
+            return Err(format!("{}: unsupported BulkDataURI: {}", key, uri));
+        }
+        let bytes = fs::read(&path).map_err(|e| format!("{}: {:?}: {}", key, path, e))?;
+        PrimitiveValue::U8(bytes.into())
+    } else {
+        let values = attr.get("Value").and_then(Value::as_array).map(Vec::as_slice);
+        primitive_from_json(vr, values.unwrap_or_default()).map_err(|e| format!("{}: {}", key, e))?
+    };
+    Ok(DataElement::new(tag, vr, value))
+}
+
+/// DicomInfo 的值是字符串 (多值以 "\\" 分隔) 或数字
+pub fn element_from_tag_keyed(tag: Tag, value: &Value) -> Result<DataElement<InMemDicomObject>, String> {
+    let vr = StandardDataDictionary
+        .by_tag(tag)
+        .map(|entry| entry.vr.relaxed())
+        .unwrap_or(VR::UN);
+    if vr == VR::SQ {
+        return Err(format!("{}: sequences are not supported in tag-keyed JSON", tag_key(tag)));
+    }
+    let values: Vec<Value> = match value {
+        Value::Null => Vec::new(),
+        Value::String(s) if s.is_empty() => Vec::new(),
+        Value::String(s) => s.split('\\').map(|v| json!(v)).collect(),
+        Value::Array(items) => items.clone(),
+        other => vec![other.clone()],
+    };
+    let value = if is_binary(vr) {
+        // 字典之外的私有 tag 按字符串原样保存
+        let text: Vec<String> = values.iter().map(json_to_string).collect();
+        PrimitiveValue::U8(text.join("\\").into_bytes().into())
+    } else {
+        primitive_from_json(vr, &values).map_err(|e| format!("{}: {}", tag_key(tag), e))?
+    };
+    Ok(DataElement::new(tag, vr, value))
+}
+
+/// 命令行中的值 (多值以 "\\" 分隔) 按 VR 转换
+pub fn primitive_from_text(vr: VR, text: &str) -> Result<PrimitiveValue, String> {
+    if text.is_empty() {
+        return Ok(PrimitiveValue::Empty);
+    }
+    let values: Vec<Value> = text.split('\\').map(|v| json!(v)).collect();
+    primitive_from_json(vr, &values)
+}
+
+fn json_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn numbers<T: FromStr>(values: &[Value]) -> Result<Vec<T>, String> {
+    values
+        .iter()
+        .map(|v| {
+            let s = json_to_string(v);
+            s.trim()
+                .parse::<T>()
+                .map_err(|_| format!("invalid number: {}", s))
+        })
+        .collect()
+}
+
+fn primitive_from_json(vr: VR, values: &[Value]) -> Result<PrimitiveValue, String> {
+    if values.is_empty() {
+        return Ok(PrimitiveValue::Empty);
+    }
+    let value = match vr {
+        VR::US => PrimitiveValue::U16(numbers(values)?.into()),
+        VR::SS => PrimitiveValue::I16(numbers(values)?.into()),
+        VR::UL => PrimitiveValue::U32(numbers(values)?.into()),
+        VR::SL => PrimitiveValue::I32(numbers(values)?.into()),
+        VR::UV => PrimitiveValue::U64(numbers(values)?.into()),
+        VR::SV => PrimitiveValue::I64(numbers(values)?.into()),
+        VR::FL => PrimitiveValue::F32(numbers(values)?.into()),
+        VR::FD => PrimitiveValue::F64(numbers(values)?.into()),
+        VR::AT => {
+            let tags: Result<Vec<Tag>, String> = values
+                .iter()
+                .map(|v| {
+                    let s = json_to_string(v);
+                    parse_tag_key(&s).ok_or_else(|| format!("invalid AT value: {}", s))
+                })
+                .collect();
+            PrimitiveValue::Tags(tags?.into())
+        }
+        VR::PN => PrimitiveValue::Strs(values.iter().map(person_name_to_string).collect()),
+        _ => PrimitiveValue::Strs(values.iter().map(json_to_string).collect()),
+    };
+    Ok(value)
+}
+
+/// {"Alphabetic": .., "Ideographic": .., "Phonetic": ..} -> "A=I=P"
+fn person_name_to_string(value: &Value) -> String {
+    let Value::Object(name) = value else {
+        return json_to_string(value);
+    };
+    let groups: Vec<&str> = ["Alphabetic", "Ideographic", "Phonetic"]
+        .iter()
+        .map(|key| name.get(*key).and_then(Value::as_str).unwrap_or(""))
+        .collect();
+    groups.join("=").trim_end_matches('=').to_string()
+}
+
+/// 生成文件元信息, 缺少 SOP Instance UID 时自动生成一个
+pub fn to_file_object(
+    mut obj: InMemDicomObject,
+    transfer_syntax: &str,
+) -> Result<DefaultDicomObject, String> {
+    if obj.get(tags::SOP_CLASS_UID).is_none() {
+        return Err("missing SOP Class UID (0008,0016)".to_string());
+    }
+    if obj.get(tags::SOP_INSTANCE_UID).is_none() {
+        let uid = dicom_gen_uid::gen_uid();
+        obj.put(DataElement::new(
+            tags::SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from(uid),
+        ));
+    }
+    obj.with_meta(FileMetaTableBuilder::new().transfer_syntax(transfer_syntax))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::{DataElement, dicom_value};
+    use dicom::core::value::DataSetSequence;
+    use dicom::dictionary_std::tags;
+
+    fn sample() -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            dicom_value!(Str, "Yamada^Tarou=山田^太郎=やまだ^たろう"),
+        ));
+        obj.put(DataElement::new(tags::PATIENT_ID, VR::LO, dicom_value!(Str, "12345 ")));
+        obj.put(DataElement::new(tags::ROWS, VR::US, dicom_value!(U16, [512])));
+        obj.put(DataElement::new(
+            tags::PIXEL_SPACING,
+            VR::DS,
+            dicom_value!(Strs, ["0.5", "0.25"]),
+        ));
+        obj.put(DataElement::new(tags::STUDY_DESCRIPTION, VR::LO, PrimitiveValue::Empty));
+        let mut item = InMemDicomObject::new_empty();
+        item.put(DataElement::new(tags::CODE_VALUE, VR::SH, dicom_value!(Str, "T-D1100")));
+        obj.put(DataElement::new(
+            tags::ANATOMIC_REGION_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![item]),
+        ));
+        obj.put(DataElement::new(tags::PIXEL_DATA, VR::OW, dicom_value!(U8, [1, 2, 3, 4])));
+        obj
+    }
+
+    #[test]
+    fn test_dicom_json_model() {
+        let value = to_dicom_json(&sample(), &DicomJsonOptions::default()).unwrap();
+        assert_eq!(
+            value["00100010"],
+            json!({"vr": "PN", "Value": [{
+                "Alphabetic": "Yamada^Tarou",
+                "Ideographic": "山田^太郎",
+                "Phonetic": "やまだ^たろう"
+            }]})
+        );
+        assert_eq!(value["00100020"], json!({"vr": "LO", "Value": ["12345"]}));
+        assert_eq!(value["00280010"], json!({"vr": "US", "Value": [512]}));
+        assert_eq!(value["00280030"], json!({"vr": "DS", "Value": [0.5, 0.25]}));
+        assert_eq!(value["00081030"], json!({"vr": "LO"}));
+        assert_eq!(
+            value["00082218"],
+            json!({"vr": "SQ", "Value": [{"00080100": {"vr": "SH", "Value": ["T-D1100"]}}]})
+        );
+        assert_eq!(value["7FE00010"], json!({"vr": "OW", "InlineBinary": "AQIDBA=="}));
+    }
+
+    #[test]
+    fn test_unparseable_decimal_keeps_string() {
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SLICE_THICKNESS, VR::DS, PrimitiveValue::from("1,5")),
+            DataElement::new(tags::INSTANCE_NUMBER, VR::IS, PrimitiveValue::from("7a")),
+            DataElement::new(tags::PIXEL_SPACING, VR::DS, PrimitiveValue::Strs(["0.5".to_string(), "abc".to_string()].into())),
+        ]);
+        let value = to_dicom_json(&obj, &DicomJsonOptions::default()).unwrap();
+        assert_eq!(value["00180050"], json!({"vr": "DS", "Value": ["1,5"]}));
+        assert_eq!(value["00200013"], json!({"vr": "IS", "Value": ["7a"]}));
+        assert_eq!(value["00280030"], json!({"vr": "DS", "Value": [0.5, "abc"]}));
+        let back = from_dicom_json(&value, Path::new(".")).unwrap();
+        assert_eq!(back.element(tags::SLICE_THICKNESS).unwrap().to_str().unwrap(), "1,5");
+    }
+
+    #[test]
+    fn test_bulk_data_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = DicomJsonOptions {
+            bulk_data: Some(BulkDataOptions {
+                threshold: 2,
+                dir: dir.path().to_path_buf(),
+                base_uri: "http://localhost/bulk/".to_string(),
+            }),
+        };
+        let value = to_dicom_json(&sample(), &options).unwrap();
+        assert_eq!(
+            value["7FE00010"],
+            json!({"vr": "OW", "BulkDataURI": "http://localhost/bulk/7FE00010.bin"})
+        );
+        assert_eq!(fs::read(dir.path().join("7FE00010.bin")).unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_encapsulated_pixel_data_is_rejected() {
+        use dicom::core::value::PixelFragmentSequence;
+
+        // JPEG Baseline 的两帧: 空的 Basic Offset Table, 每帧一个 fragment
+        let mut obj = sample();
+        let fragments = vec![vec![0xFF, 0xD8, 0xFF, 0xD9], vec![0xFF, 0xD8, 0x00, 0xFF, 0xD9, 0x00]];
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            PixelFragmentSequence::new(Vec::<u32>::new(), fragments),
+        ));
+        let err = to_dicom_json(&obj, &DicomJsonOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        let dir = tempfile::tempdir().unwrap();
+        let options = DicomJsonOptions {
+            bulk_data: Some(BulkDataOptions {
+                threshold: 2,
+                dir: dir.path().to_path_buf(),
+                base_uri: String::new(),
+            }),
+        };
+        assert!(to_dicom_json(&obj, &options).is_err());
+        assert!(!dir.path().join("7FE00010.bin").exists());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = DicomJsonOptions {
+            bulk_data: Some(BulkDataOptions {
+                threshold: 2,
+                dir: dir.path().to_path_buf(),
+                base_uri: String::new(),
+            }),
+        };
+        let exported = to_dicom_json(&sample(), &options).unwrap();
+        let obj = from_dicom_json(&exported, dir.path()).unwrap();
+        assert_eq!(to_dicom_json(&obj, &DicomJsonOptions::default()).unwrap(),
+            to_dicom_json(&sample(), &DicomJsonOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_import_tag_keyed() {
+        let value = json!({
+            "x00080016": "1.2.840.10008.5.1.4.1.1.2",
+            "x00100010": "Zhang^San",
+            "x00280010": 512,
+            "x00280030": "0.5\\0.5",
+            "x00020010": "1.2.840.10008.1.2.1"
+        });
+        let obj = from_dicom_json(&value, Path::new(".")).unwrap();
+        assert_eq!(obj.element(tags::ROWS).unwrap().vr(), VR::US);
+        assert_eq!(obj.element(tags::ROWS).unwrap().to_int::<u16>().unwrap(), 512);
+        assert_eq!(obj.element(tags::PIXEL_SPACING).unwrap().to_multi_float64().unwrap(), [0.5, 0.5]);
+        assert!(obj.get(tags::TRANSFER_SYNTAX_UID).is_none());
+
+        let file = to_file_object(obj, "1.2.840.10008.1.2.1").unwrap();
+        assert_eq!(file.meta().media_storage_sop_class_uid(), "1.2.840.10008.5.1.4.1.1.2");
+        assert!(file.get(tags::SOP_INSTANCE_UID).is_some());
+    }
+}
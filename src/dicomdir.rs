@@ -0,0 +1,713 @@
+use crate::association::{IMPLEMENTATION_CLASS_UID, IMPLEMENTATION_VERSION_NAME};
+use crate::error::DcmToolsError;
+use crate::index::IndexRecord;
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::parser::dataset::DataToken;
+use dicom::parser::dataset::read::DataSetReader;
+use dicom_encoding::TransferSyntaxIndex;
+use dicom_object::InMemDicomObject;
+use dicom_object::meta::FileMetaTableBuilder;
+use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+use dicom_transfer_syntax_registry::entries::EXPLICIT_VR_LITTLE_ENDIAN;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Media Storage Directory Storage (DICOMDIR 的 SOP Class)
+pub const MEDIA_STORAGE_DIRECTORY: &str = "1.2.840.10008.1.3.10";
+/// ISO 9660 (PS3.10 8.5 / PS3.12): 每级最多 8 个字符, 最多 8 级
+const MAX_COMPONENT_LEN: usize = 8;
+const MAX_FILE_ID_DEPTH: usize = 8;
+
+/// 是否为合法的 File ID 组成部分: 1-8 个大写字母、数字或下划线
+pub fn is_iso9660_component(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= MAX_COMPONENT_LEN
+        && s.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// 文件相对于 DICOMDIR 所在目录的 Referenced File ID, 不合规时返回 None
+pub fn file_id(root: &Path, path: &Path) -> Option<Vec<String>> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_str().map(str::to_string))
+        .collect::<Option<_>>()?;
+    let valid = !components.is_empty()
+        && components.len() <= MAX_FILE_ID_DEPTH
+        && components.iter().all(|c| is_iso9660_component(c));
+    valid.then_some(components)
+}
+
+/// patient -> study -> series -> 按 Instance Number 排序的实例
+type Hierarchy<'a> = BTreeMap<&'a str, BTreeMap<&'a str, BTreeMap<&'a str, Vec<&'a IndexRecord>>>>;
+
+fn hierarchy(records: &[IndexRecord]) -> Hierarchy<'_> {
+    let mut tree: Hierarchy = BTreeMap::new();
+    for record in records {
+        tree.entry(record.patient.id.as_str())
+            .or_default()
+            .entry(record.study.study_uid.as_str())
+            .or_default()
+            .entry(record.series.series_uid.as_str())
+            .or_default()
+            .push(record);
+    }
+    for series in tree.values_mut().flat_map(|p| p.values_mut()).flat_map(|s| s.values_mut()) {
+        series.sort_by(|a, b| (a.image.inst_num, &a.image.sop_uid).cmp(&(b.image.inst_num, &b.image.sop_uid)));
+    }
+    tree
+}
+
+/// 将实例复制到 output, 按层级重命名为 PAT00001/STU00001/SER00001/IMG00001, 返回新路径的记录
+pub fn copy_with_iso_names(records: &[IndexRecord], output: &Path) -> Result<Vec<IndexRecord>, DcmToolsError> {
+    let name = |prefix: &str, i: usize, path: &Path| {
+        if i >= 100_000 {
+            return Err(DcmToolsError::write(path, format!("more than 99999 {} entries in one directory", prefix)));
+        }
+        Ok(format!("{}{:05}", prefix, i + 1))
+    };
+    let mut copied = Vec::with_capacity(records.len());
+    for (p, studies) in hierarchy(records).into_values().enumerate() {
+        let patient_dir = output.join(name("PAT", p, output)?);
+        for (st, series) in studies.into_values().enumerate() {
+            let study_dir = patient_dir.join(name("STU", st, &patient_dir)?);
+            for (se, images) in series.into_values().enumerate() {
+                let series_dir = study_dir.join(name("SER", se, &study_dir)?);
+                std::fs::create_dir_all(&series_dir).map_err(|e| DcmToolsError::write(&series_dir, e))?;
+                for (i, record) in images.into_iter().enumerate() {
+                    let target = series_dir.join(name("IMG", i, &series_dir)?);
+                    std::fs::copy(&record.path, &target).map_err(|e| DcmToolsError::write(&target, e))?;
+                    copied.push(record.with_path(target));
+                }
+            }
+        }
+    }
+    Ok(copied)
+}
+
+/// PS3.3 F.5: 实例的 SOP Class 对应的目录记录类型, 未列出的 Storage SOP Class 为 IMAGE
+pub fn record_type(sop_class_uid: &str) -> &'static str {
+    let Some(class) = sop_class_uid.strip_prefix("1.2.840.10008.5.1.4.1.1.") else {
+        return "IMAGE";
+    };
+    let family = |prefix: &str| class.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.'));
+    match class {
+        "88.59" => "KEY OBJECT DOC",
+        "481.2" => "RT DOSE",
+        "481.3" => "RT STRUCTURE SET",
+        "481.5" | "481.8" => "RT PLAN",
+        "481.4" | "481.6" | "481.7" | "481.9" => "RT TREAT RECORD",
+        "4.2" => "SPECTROSCOPY",
+        "66" => "RAW DATA",
+        "66.1" | "66.3" => "REGISTRATION",
+        "66.2" => "FIDUCIAL",
+        "66.5" => "SURFACE",
+        "67" => "VALUE MAP",
+        _ if family("88") => "SR DOCUMENT",
+        _ if family("11") => "PRESENTATION",
+        _ if family("9") => "WAVEFORM",
+        _ if family("104") => "ENCAP DOC",
+        _ => "IMAGE",
+    }
+}
+
+/// PATIENT 记录的 Patient ID 为 Type 1, 为空的实例不能写入 DICOMDIR
+pub fn check_record(record: &IndexRecord) -> Result<(), DcmToolsError> {
+    if record.patient.id.trim().is_empty() {
+        return Err(DcmToolsError::parse(
+            &record.path,
+            "Patient ID (0010,0020) is empty, it is required by the PATIENT directory record",
+        ));
+    }
+    Ok(())
+}
+
+fn put_str(obj: &mut InMemDicomObject, tag: Tag, vr: VR, value: &str) {
+    obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+}
+
+/// 目录记录 (不含偏移量) 及其在记录序列中的链接
+struct DirectoryRecord {
+    keys: InMemDicomObject,
+    /// 同一层级的下一条记录
+    next: Option<usize>,
+    /// 下一层级的第一条记录
+    lower: Option<usize>,
+}
+
+impl DirectoryRecord {
+    fn new(record_type: &str) -> Self {
+        let mut keys = InMemDicomObject::new_empty();
+        put_str(&mut keys, tags::DIRECTORY_RECORD_TYPE, VR::CS, record_type);
+        Self {
+            keys,
+            next: None,
+            lower: None,
+        }
+    }
+
+    /// 带上偏移量编码为 Explicit VR Little Endian 的 item 内容
+    fn encode(&self, offsets: &[u32]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let offset = |index: Option<usize>| index.map(|i| offsets[i]).unwrap_or(0);
+        let mut item = self.keys.clone();
+        item.put(DataElement::new(
+            tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD,
+            VR::UL,
+            PrimitiveValue::from(offset(self.next)),
+        ));
+        item.put(DataElement::new(tags::RECORD_IN_USE_FLAG, VR::US, PrimitiveValue::from(0xFFFF_u16)));
+        item.put(DataElement::new(
+            tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY,
+            VR::UL,
+            PrimitiveValue::from(offset(self.lower)),
+        ));
+        let mut data = Vec::new();
+        item.write_dataset_with_ts(&mut data, &EXPLICIT_VR_LITTLE_ENDIAN.erased())?;
+        Ok(data)
+    }
+}
+
+/// 按深度优先顺序排列的 PATIENT/STUDY/SERIES 及实例记录
+fn directory_records(root: &Path, records: &[IndexRecord]) -> Result<Vec<DirectoryRecord>, DcmToolsError> {
+    let mut out: Vec<DirectoryRecord> = Vec::new();
+    /// 有同层级的上一条记录时填写其 next, 否则作为父记录的第一条下级记录
+    fn link(out: &mut [DirectoryRecord], previous: Option<usize>, parent: Option<usize>, current: usize) {
+        match previous {
+            Some(previous) => out[previous].next = Some(current),
+            None => {
+                if let Some(parent) = parent {
+                    out[parent].lower = Some(current);
+                }
+            }
+        }
+    }
+    for record in records {
+        check_record(record)?;
+    }
+    let mut previous_patient = None;
+    for studies in hierarchy(records).into_values() {
+        let first = studies.values().flat_map(|s| s.values()).flatten().next().expect("non-empty group");
+        let mut patient = DirectoryRecord::new("PATIENT");
+        put_str(&mut patient.keys, tags::PATIENT_NAME, VR::PN, &first.patient.name);
+        put_str(&mut patient.keys, tags::PATIENT_ID, VR::LO, &first.patient.id);
+        let patient_index = out.len();
+        out.push(patient);
+        link(&mut out, previous_patient, None, patient_index);
+        previous_patient = Some(patient_index);
+
+        let mut previous_study = None;
+        for series in studies.into_values() {
+            let first = series.values().flatten().next().expect("non-empty group");
+            let mut study = DirectoryRecord::new("STUDY");
+            put_str(&mut study.keys, tags::STUDY_DATE, VR::DA, &first.study.study_date);
+            put_str(&mut study.keys, tags::STUDY_TIME, VR::TM, &first.study.study_time);
+            put_str(&mut study.keys, tags::STUDY_DESCRIPTION, VR::LO, &first.study.study_desc);
+            put_str(&mut study.keys, tags::STUDY_INSTANCE_UID, VR::UI, &first.study.study_uid);
+            put_str(&mut study.keys, tags::STUDY_ID, VR::SH, &first.study.study_id);
+            put_str(&mut study.keys, tags::ACCESSION_NUMBER, VR::SH, &first.series.accession_number);
+            let study_index = out.len();
+            out.push(study);
+            link(&mut out, previous_study, Some(patient_index), study_index);
+            previous_study = Some(study_index);
+
+            let mut previous_series = None;
+            for images in series.into_values() {
+                let first = images[0];
+                let mut series = DirectoryRecord::new("SERIES");
+                put_str(&mut series.keys, tags::MODALITY, VR::CS, &first.series.modality);
+                put_str(&mut series.keys, tags::SERIES_INSTANCE_UID, VR::UI, &first.series.series_uid);
+                put_str(&mut series.keys, tags::SERIES_NUMBER, VR::IS, &first.series.series_num);
+                let series_index = out.len();
+                out.push(series);
+                link(&mut out, previous_series, Some(study_index), series_index);
+                previous_series = Some(series_index);
+
+                let mut previous_image = None;
+                for record in images {
+                    let components = file_id(root, &record.path).ok_or_else(|| {
+                        DcmToolsError::write(&record.path, "file ID is not ISO 9660 compliant")
+                    })?;
+                    let mut image = DirectoryRecord::new(record_type(&record.sop_class_uid));
+                    image.keys.put(DataElement::new(
+                        tags::REFERENCED_FILE_ID,
+                        VR::CS,
+                        PrimitiveValue::Strs(components.into()),
+                    ));
+                    put_str(&mut image.keys, tags::REFERENCED_SOP_CLASS_UID_IN_FILE, VR::UI, &record.sop_class_uid);
+                    put_str(&mut image.keys, tags::REFERENCED_SOP_INSTANCE_UID_IN_FILE, VR::UI, &record.image.sop_uid);
+                    put_str(
+                        &mut image.keys,
+                        tags::REFERENCED_TRANSFER_SYNTAX_UID_IN_FILE,
+                        VR::UI,
+                        &record.transfer_syntax,
+                    );
+                    put_str(&mut image.keys, tags::INSTANCE_NUMBER, VR::IS, &record.image.inst_num.to_string());
+                    let image_index = out.len();
+                    out.push(image);
+                    link(&mut out, previous_image, Some(series_index), image_index);
+                    previous_image = Some(image_index);
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// 在 root 下写入 DICOMDIR, records 的路径必须位于 root 下且符合 ISO 9660
+pub fn write_dicomdir(root: &Path, records: &[IndexRecord], file_set_id: &str) -> Result<PathBuf, DcmToolsError> {
+    let target = root.join("DICOMDIR");
+    let encode_error = |e: &dyn std::fmt::Display| DcmToolsError::write(&target, e.to_string());
+    let directory = directory_records(root, records)?;
+    let root_records: Vec<usize> = {
+        // 根层级 (PATIENT) 记录: 第一条及其 next 链
+        let mut list = Vec::new();
+        let mut current = (!directory.is_empty()).then_some(0);
+        while let Some(i) = current {
+            list.push(i);
+            current = directory[i].next;
+        }
+        list
+    };
+
+    let sop_instance_uid = dicom_gen_uid::gen_uid();
+    let meta = FileMetaTableBuilder::new()
+        .media_storage_sop_class_uid(MEDIA_STORAGE_DIRECTORY)
+        .media_storage_sop_instance_uid(sop_instance_uid.as_str())
+        .transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+        .implementation_class_uid(IMPLEMENTATION_CLASS_UID)
+        .implementation_version_name(IMPLEMENTATION_VERSION_NAME)
+        .build()
+        .map_err(|e| encode_error(&e))?;
+    let mut header = vec![0u8; 128];
+    header.extend_from_slice(b"DICM");
+    meta.write(&mut header).map_err(|e| encode_error(&e))?;
+
+    // 偏移量均为定长 UL, 先用 0 编码得到各 item 的长度, 再计算实际偏移量
+    let encode_root = |first: u32, last: u32| {
+        let mut obj = InMemDicomObject::new_empty();
+        put_str(&mut obj, tags::FILE_SET_ID, VR::CS, file_set_id);
+        obj.put(DataElement::new(
+            tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY,
+            VR::UL,
+            PrimitiveValue::from(first),
+        ));
+        obj.put(DataElement::new(
+            tags::OFFSET_OF_THE_LAST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY,
+            VR::UL,
+            PrimitiveValue::from(last),
+        ));
+        obj.put(DataElement::new(tags::FILE_SET_CONSISTENCY_FLAG, VR::US, PrimitiveValue::from(0_u16)));
+        let mut data = Vec::new();
+        obj.write_dataset_with_ts(&mut data, &EXPLICIT_VR_LITTLE_ENDIAN.erased())
+            .map(|_| data)
+            .map_err(|e| e.to_string())
+    };
+    let zeros = vec![0u32; directory.len()];
+    let mut offsets = Vec::with_capacity(directory.len());
+    // 12 = Directory Record Sequence 的 tag + VR + 保留字节 + 长度
+    let mut position = header.len() + encode_root(0, 0).map_err(|e| encode_error(&e))?.len() + 12;
+    for record in &directory {
+        offsets.push(position as u32);
+        // 8 = item tag + 长度
+        position += 8 + record.encode(&zeros).map_err(|e| encode_error(&e))?.len();
+    }
+    let root_offset = |i: Option<&usize>| i.map(|i| offsets[*i]).unwrap_or(0);
+    let mut bytes = header;
+    bytes.extend(
+        encode_root(root_offset(root_records.first()), root_offset(root_records.last()))
+            .map_err(|e| encode_error(&e))?,
+    );
+    // (0004,1220) SQ, 未定义长度
+    bytes.extend_from_slice(&[0x04, 0x00, 0x20, 0x12, b'S', b'Q', 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    for record in &directory {
+        let item = record.encode(&offsets).map_err(|e| encode_error(&e))?;
+        bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0]);
+        bytes.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        bytes.extend(item);
+    }
+    // Sequence Delimitation Item
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+
+    let mut file = tempfile::NamedTempFile::new_in(root).map_err(|e| DcmToolsError::write(&target, e))?;
+    file.write_all(&bytes)
+        .and_then(|_| file.as_file().sync_all())
+        .map_err(|e| DcmToolsError::write(&target, e))?;
+    file.persist(&target).map_err(|e| DcmToolsError::write(&target, e))?;
+    Ok(target)
+}
+
+/// DICOMDIR 中引用的一个实例及其所属的 patient/study/series
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DicomdirEntry {
+    pub path: PathBuf,
+    pub patient_id: String,
+    pub study_uid: String,
+    pub series_uid: String,
+    pub sop_instance_uid: String,
+}
+
+/// 统计已读取字节数的 reader, 用于得到 item 在文件中的位置
+struct CountingReader<'a> {
+    inner: &'a [u8],
+    position: Rc<Cell<usize>>,
+}
+
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position.set(self.position.get() + n);
+        Ok(n)
+    }
+}
+
+/// Directory Record Sequence 中各 item 的文件偏移量 (item tag 的第一个字节), 与 item 顺序一致
+fn record_offsets(bytes: &[u8], data_start: usize, ts_uid: &str) -> Result<Vec<u32>, String> {
+    let ts = TransferSyntaxRegistry
+        .get(ts_uid)
+        .ok_or_else(|| format!("unknown transfer syntax {}", ts_uid))?;
+    let position = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: bytes.get(data_start..).ok_or("truncated file")?,
+        position: Rc::clone(&position),
+    };
+    let tokens = DataSetReader::new_with_ts(reader, ts).map_err(|e| e.to_string())?;
+    let mut sequences = Vec::new();
+    let mut offsets = Vec::new();
+    for token in tokens {
+        match token.map_err(|e| e.to_string())? {
+            DataToken::SequenceStart { tag, .. } => sequences.push(tag),
+            DataToken::PixelSequenceStart => sequences.push(tags::PIXEL_DATA),
+            DataToken::SequenceEnd => {
+                sequences.pop();
+            }
+            // 8 = item tag + 长度, 读取 ItemStart 时已经读过
+            DataToken::ItemStart { .. } if sequences == [tags::DIRECTORY_RECORD_SEQUENCE] => {
+                offsets.push((data_start + position.get() - 8) as u32);
+            }
+            _ => {}
+        }
+    }
+    Ok(offsets)
+}
+
+/// 按 File ID 找到文件; 光盘内容复制到大小写敏感的文件系统后, 文件名的大小写可能不同
+fn resolve_file_id(root: &Path, components: &[String]) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in components {
+        let exact = path.join(component);
+        path = if exact.exists() {
+            exact
+        } else {
+            std::fs::read_dir(&path)
+                .ok()?
+                .flatten()
+                .map(|entry| entry.path())
+                .find(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.eq_ignore_ascii_case(component)))?
+        };
+    }
+    Some(path)
+}
+
+/// 按记录之间的偏移量遍历 DICOMDIR, 返回引用的实例 (按记录层级顺序);
+/// 未使用的记录 (Record In-use Flag = 0) 与找不到的文件被跳过
+pub fn read_dicomdir(path: &Path) -> Result<Vec<DicomdirEntry>, DcmToolsError> {
+    let obj = dicom_object::open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+    if obj.meta().media_storage_sop_class_uid().trim_end_matches('\0') != MEDIA_STORAGE_DIRECTORY {
+        return Err(DcmToolsError::parse(path, "not a DICOMDIR"));
+    }
+    let items = match obj.element_opt(tags::DIRECTORY_RECORD_SEQUENCE) {
+        Ok(Some(element)) => element.items().unwrap_or_default().to_vec(),
+        _ => Vec::new(),
+    };
+    let bytes = std::fs::read(path).map_err(|e| DcmToolsError::read(path, e))?;
+    // 128 字节前导 + "DICM" + (0002,0000) 元素 12 字节 + 文件元信息
+    let data_start = 128 + 4 + 12 + obj.meta().information_group_length as usize;
+    let offsets = record_offsets(&bytes, data_start, obj.meta().transfer_syntax().trim_end_matches('\0'))
+        .map_err(|e| DcmToolsError::parse(path, e))?;
+    if offsets.len() != items.len() {
+        return Err(DcmToolsError::parse(path, "cannot locate directory records"));
+    }
+    let by_offset: HashMap<u32, &InMemDicomObject> = offsets.into_iter().zip(items.iter()).collect();
+    let root = path.parent().unwrap_or(Path::new("."));
+    let value = |item: &InMemDicomObject, tag: Tag| {
+        item.element_opt(tag)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_str().ok())
+            .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+            .unwrap_or_default()
+    };
+    let offset = |item: &InMemDicomObject, tag: Tag| {
+        item.element_opt(tag)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_int::<u32>().ok())
+            .filter(|offset| *offset != 0)
+    };
+
+    // (记录偏移量, 上级 patient/study/series), 深度优先
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    let first = offset(&obj, tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY);
+    let mut stack: Vec<(Option<u32>, [String; 3])> = vec![(first, Default::default())];
+    while let Some((current, context)) = stack.pop() {
+        let Some(current) = current else { continue };
+        // 偏移量损坏时可能成环
+        if !visited.insert(current) {
+            continue;
+        }
+        let item = by_offset
+            .get(&current)
+            .ok_or_else(|| DcmToolsError::parse(path, format!("no directory record at offset {}", current)))?;
+        stack.push((offset(item, tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD), context.clone()));
+        let in_use = item
+            .element_opt(tags::RECORD_IN_USE_FLAG)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_int::<u16>().ok())
+            != Some(0);
+        if !in_use {
+            continue;
+        }
+        let mut context = context;
+        match value(item, tags::DIRECTORY_RECORD_TYPE).as_str() {
+            "PATIENT" => context = [value(item, tags::PATIENT_ID), String::new(), String::new()],
+            "STUDY" => context = [context[0].clone(), value(item, tags::STUDY_INSTANCE_UID), String::new()],
+            "SERIES" => context[2] = value(item, tags::SERIES_INSTANCE_UID),
+            _ => {}
+        }
+        let components: Vec<String> = item
+            .element_opt(tags::REFERENCED_FILE_ID)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_multi_str().ok().map(|v| v.iter().map(|s| s.trim().to_string()).collect()))
+            .unwrap_or_default();
+        if !components.is_empty() {
+            match resolve_file_id(root, &components) {
+                Some(file) => entries.push(DicomdirEntry {
+                    path: file,
+                    patient_id: context[0].clone(),
+                    study_uid: context[1].clone(),
+                    series_uid: context[2].clone(),
+                    sop_instance_uid: value(item, tags::REFERENCED_SOP_INSTANCE_UID_IN_FILE),
+                }),
+                None => eprintln!("Missing file referenced by {:?}: {}", path, components.join("\\")),
+            }
+        }
+        stack.push((offset(item, tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY), context));
+    }
+    Ok(entries)
+}
+
+/// 目录中的 DICOMDIR 文件 (文件名不区分大小写)
+pub fn find_dicomdir(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|p| p.is_file() && p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.eq_ignore_ascii_case("DICOMDIR")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso9660_file_id() {
+        assert!(is_iso9660_component("IMG00001"));
+        assert!(!is_iso9660_component("IMG000001"));
+        assert!(!is_iso9660_component("img1"));
+        assert!(!is_iso9660_component("1.2.dcm"));
+        let root = Path::new("/media");
+        assert_eq!(
+            file_id(root, Path::new("/media/PAT1/IMG1")),
+            Some(vec!["PAT1".to_string(), "IMG1".to_string()])
+        );
+        assert_eq!(file_id(root, Path::new("/media/1.2.840/1.2.dcm")), None);
+        assert_eq!(file_id(root, Path::new("/other/IMG1")), None);
+    }
+
+    #[test]
+    fn test_write_dicomdir_with_renaming() {
+        let input = tempfile::tempdir().unwrap();
+        let uid_named = input.path().join("1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm");
+        std::fs::copy("./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm", &uid_named).unwrap();
+        let record = IndexRecord::open(&uid_named).unwrap();
+        let second = IndexRecord::from_parts(
+            &uid_named,
+            record.sop_class_uid.clone(),
+            record.transfer_syntax.clone(),
+            record.patient.clone(),
+            record.study.clone(),
+            record.series.clone(),
+            crate::image_info::ImageInfo {
+                inst_num: record.image.inst_num + 1,
+                sop_uid: format!("{}.1", record.image.sop_uid),
+                ..record.image.clone()
+            },
+        );
+        let records = vec![second, record.clone()];
+
+        let output = tempfile::tempdir().unwrap();
+        // UID 命名的文件不能直接写入 DICOMDIR
+        assert!(write_dicomdir(input.path(), &records, "TEST").is_err());
+        let copied = copy_with_iso_names(&records, output.path()).unwrap();
+        let path = write_dicomdir(output.path(), &copied, "TEST").unwrap();
+        assert!(output.path().join("PAT00001/STU00001/SER00001/IMG00002").is_file());
+
+        let dicomdir = dicom_object::open_file(&path).unwrap();
+        assert_eq!(dicomdir.meta().media_storage_sop_class_uid().trim_end_matches('\0'), MEDIA_STORAGE_DIRECTORY);
+        let items = dicomdir.element(tags::DIRECTORY_RECORD_SEQUENCE).unwrap().items().unwrap().to_vec();
+        let types: Vec<String> = items
+            .iter()
+            .map(|item| item.element(tags::DIRECTORY_RECORD_TYPE).unwrap().to_str().unwrap().trim().to_string())
+            .collect();
+        assert_eq!(types, ["PATIENT", "STUDY", "SERIES", "IMAGE", "IMAGE"]);
+        // 按 Instance Number 排序, 第一个 IMAGE 记录引用原始实例
+        let file_id: Vec<String> = items[3].element(tags::REFERENCED_FILE_ID).unwrap().to_multi_str().unwrap().to_vec();
+        assert_eq!(file_id, ["PAT00001", "STU00001", "SER00001", "IMG00001"]);
+        assert_eq!(
+            items[3].element(tags::REFERENCED_SOP_INSTANCE_UID_IN_FILE).unwrap().to_str().unwrap().trim_end_matches('\0'),
+            record.image.sop_uid
+        );
+
+        // 偏移量指向对应记录的 item 起始位置
+        let bytes = std::fs::read(&path).unwrap();
+        let offset = |obj: &InMemDicomObject, tag: Tag| obj.element(tag).unwrap().to_int::<u32>().unwrap() as usize;
+        let item_at = |offset: usize| {
+            assert_eq!(&bytes[offset..offset + 4], &[0xFE, 0xFF, 0x00, 0xE0]);
+            // item 中第一个元素是 (0004,1400)
+            assert_eq!(&bytes[offset + 8..offset + 12], &[0x04, 0x00, 0x00, 0x14]);
+            offset
+        };
+        let first_patient = item_at(offset(&dicomdir, tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY));
+        assert_eq!(
+            first_patient,
+            offset(&dicomdir, tags::OFFSET_OF_THE_LAST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY)
+        );
+        let study = item_at(offset(&items[0], tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY));
+        let series = item_at(offset(&items[1], tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY));
+        let image = item_at(offset(&items[2], tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY));
+        let next_image = item_at(offset(&items[3], tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD));
+        assert!(first_patient < study && study < series && series < image && image < next_image);
+        assert_eq!(offset(&items[4], tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD), 0);
+        assert_eq!(offset(&items[4], tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY), 0);
+
+        let entries = read_dicomdir(&path).unwrap();
+        let files: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+        assert_eq!(
+            files,
+            [
+                output.path().join("PAT00001/STU00001/SER00001/IMG00001"),
+                output.path().join("PAT00001/STU00001/SER00001/IMG00002")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_type_by_sop_class() {
+        assert_eq!(record_type(uids::CT_IMAGE_STORAGE), "IMAGE");
+        assert_eq!(record_type(uids::ENHANCED_CT_IMAGE_STORAGE), "IMAGE");
+        assert_eq!(record_type(uids::COMPREHENSIVE_SR_STORAGE), "SR DOCUMENT");
+        assert_eq!(record_type(uids::X_RAY_RADIATION_DOSE_SR_STORAGE), "SR DOCUMENT");
+        assert_eq!(record_type(uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE), "KEY OBJECT DOC");
+        assert_eq!(record_type(uids::GRAYSCALE_SOFTCOPY_PRESENTATION_STATE_STORAGE), "PRESENTATION");
+        assert_eq!(record_type(uids::RT_DOSE_STORAGE), "RT DOSE");
+        assert_eq!(record_type(uids::RT_STRUCTURE_SET_STORAGE), "RT STRUCTURE SET");
+        assert_eq!(record_type(uids::RT_PLAN_STORAGE), "RT PLAN");
+        assert_eq!(record_type(uids::RT_IMAGE_STORAGE), "IMAGE");
+        assert_eq!(record_type(uids::ENCAPSULATED_PDF_STORAGE), "ENCAP DOC");
+        assert_eq!(record_type(uids::ENCAPSULATED_CDA_STORAGE), "ENCAP DOC");
+        assert_eq!(record_type(uids::TWELVE_LEAD_ECG_WAVEFORM_STORAGE), "WAVEFORM");
+        assert_eq!(record_type(uids::SEGMENTATION_STORAGE), "IMAGE");
+    }
+
+    #[test]
+    fn test_dicomdir_record_types_and_patient_id() {
+        let source = "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm";
+        let image = IndexRecord::open(Path::new(source)).unwrap();
+        let sr = IndexRecord::from_parts(
+            Path::new(source),
+            uids::COMPREHENSIVE_SR_STORAGE.to_string(),
+            image.transfer_syntax.clone(),
+            image.patient.clone(),
+            image.study.clone(),
+            crate::series_info::SeriesInfo {
+                series_uid: format!("{}.9", image.series.series_uid),
+                modality: "SR".to_string(),
+                ..image.series.clone()
+            },
+            crate::image_info::ImageInfo {
+                sop_uid: format!("{}.9", image.image.sop_uid),
+                ..image.image.clone()
+            },
+        );
+        let output = tempfile::tempdir().unwrap();
+        let copied = copy_with_iso_names(&[image.clone(), sr], output.path()).unwrap();
+        let path = write_dicomdir(output.path(), &copied, "TEST").unwrap();
+        let dicomdir = dicom_object::open_file(&path).unwrap();
+        let types: Vec<String> = dicomdir
+            .element(tags::DIRECTORY_RECORD_SEQUENCE)
+            .unwrap()
+            .items()
+            .unwrap()
+            .iter()
+            .map(|item| item.element(tags::DIRECTORY_RECORD_TYPE).unwrap().to_str().unwrap().trim().to_string())
+            .collect();
+        assert_eq!(types, ["PATIENT", "STUDY", "SERIES", "IMAGE", "SERIES", "SR DOCUMENT"]);
+
+        // PATIENT 记录的 Patient ID 不能为空
+        let anonymous = IndexRecord::from_parts(
+            &copied[0].path,
+            image.sop_class_uid.clone(),
+            image.transfer_syntax.clone(),
+            crate::patient_info::PatientInfo {
+                id: String::new(),
+                ..image.patient.clone()
+            },
+            image.study.clone(),
+            image.series.clone(),
+            image.image.clone(),
+        );
+        assert!(check_record(&anonymous).is_err());
+        assert!(write_dicomdir(output.path(), &[anonymous], "TEST").is_err());
+    }
+
+    #[test]
+    fn test_walk_input_follows_dicomdir() {
+        let input = tempfile::tempdir().unwrap();
+        let source = input.path().join("1.2.dcm");
+        std::fs::copy("./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm", &source).unwrap();
+        let record = IndexRecord::open(&source).unwrap();
+
+        let cd = tempfile::tempdir().unwrap();
+        let copied = copy_with_iso_names(std::slice::from_ref(&record), cd.path()).unwrap();
+        write_dicomdir(cd.path(), &copied, "TEST").unwrap();
+        // 光盘上的其他文件, 以及复制后大小写改变的文件名
+        std::fs::write(cd.path().join("README.TXT"), "viewer").unwrap();
+        std::fs::copy(&source, cd.path().join("PAT00001/EXTRA")).unwrap();
+        let series = cd.path().join("PAT00001/STU00001/SER00001");
+        std::fs::rename(series.join("IMG00001"), series.join("img00001")).unwrap();
+
+        let entries = read_dicomdir(&cd.path().join("DICOMDIR")).unwrap();
+        assert_eq!(
+            entries,
+            [DicomdirEntry {
+                path: series.join("img00001"),
+                patient_id: record.patient.id.clone(),
+                study_uid: record.study.study_uid.clone(),
+                series_uid: record.series.series_uid.clone(),
+                sop_instance_uid: record.image.sop_uid.clone(),
+            }]
+        );
+        let files = crate::dcmobj::walk_input(cd.path(), true).unwrap();
+        assert_eq!(files, [series.join("img00001")]);
+        // 默认不读取 DICOMDIR, 列出全部文件
+        assert_eq!(crate::dcmobj::walk_input(cd.path(), false).unwrap().len(), 4);
+        assert_eq!(crate::dcmobj::walk_directory(cd.path()).unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,252 @@
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom_object::InMemDicomObject;
+
+/// CommandField (0000,0100)
+pub const C_STORE_RQ: u16 = 0x0001;
+pub const C_GET_RQ: u16 = 0x0010;
+pub const C_FIND_RQ: u16 = 0x0020;
+pub const C_MOVE_RQ: u16 = 0x0021;
+pub const C_ECHO_RQ: u16 = 0x0030;
+pub const C_CANCEL_RQ: u16 = 0x0FFF;
+/// 响应的 CommandField = 请求 | 0x8000
+pub const RESPONSE_BIT: u16 = 0x8000;
+
+/// CommandDataSetType: 没有数据集
+pub const NO_DATA_SET: u16 = 0x0101;
+/// CommandDataSetType: 有数据集 (任意非 0x0101 的值)
+pub const HAS_DATA_SET: u16 = 0x0000;
+
+pub const PRIORITY_MEDIUM: u16 = 0x0000;
+
+/// DIMSE 状态码 (PS3.7 Annex C)
+pub mod status {
+    pub const SUCCESS: u16 = 0x0000;
+    /// C-FIND/C-MOVE/C-GET: 还有后续响应
+    pub const PENDING: u16 = 0xFF00;
+    /// C-FIND/C-MOVE/C-GET: 收到 C-CANCEL-RQ 后终止
+    pub const CANCEL: u16 = 0xFE00;
+    pub const UNRECOGNIZED_OPERATION: u16 = 0x0211;
+    pub const PROCESSING_FAILURE: u16 = 0x0110;
+    pub const SOP_CLASS_NOT_SUPPORTED: u16 = 0x0122;
+    /// C-STORE: Refused, Out of Resources
+    pub const OUT_OF_RESOURCES: u16 = 0xA700;
+    /// C-STORE: Error, Data Set does not match SOP Class
+    pub const DATA_SET_DOES_NOT_MATCH: u16 = 0xA900;
+    /// C-STORE: Error, Cannot understand
+    pub const CANNOT_UNDERSTAND: u16 = 0xC000;
+    /// C-FIND/C-MOVE/C-GET: Failed, Unable to process
+    pub const UNABLE_TO_PROCESS: u16 = 0xC001;
+    /// C-MOVE/C-GET: Refused, Out of Resources - Unable to perform sub-operations
+    pub const UNABLE_TO_PERFORM_SUBOPERATIONS: u16 = 0xA702;
+    /// C-MOVE: Refused, Move Destination unknown
+    pub const MOVE_DESTINATION_UNKNOWN: u16 = 0xA801;
+    /// C-MOVE/C-GET: Warning, Sub-operations complete - One or more Failures or Warnings
+    pub const SUBOPERATIONS_WARNING: u16 = 0xB000;
+}
+
+fn put_us(obj: &mut InMemDicomObject, tag: Tag, value: u16) {
+    obj.put(DataElement::new(tag, VR::US, PrimitiveValue::from(value)));
+}
+
+fn put_ui(obj: &mut InMemDicomObject, tag: Tag, value: &str) {
+    obj.put(DataElement::new(tag, VR::UI, PrimitiveValue::from(value)));
+}
+
+/// 请求命令集
+pub fn request(command_field: u16, sop_class: &str, message_id: u16, has_data: bool) -> InMemDicomObject {
+    let mut cmd = InMemDicomObject::new_empty();
+    put_ui(&mut cmd, tags::AFFECTED_SOP_CLASS_UID, sop_class);
+    put_us(&mut cmd, tags::COMMAND_FIELD, command_field);
+    put_us(&mut cmd, tags::MESSAGE_ID, message_id);
+    if command_field != C_ECHO_RQ {
+        put_us(&mut cmd, tags::PRIORITY, PRIORITY_MEDIUM);
+    }
+    put_us(
+        &mut cmd,
+        tags::COMMAND_DATA_SET_TYPE,
+        if has_data { HAS_DATA_SET } else { NO_DATA_SET },
+    );
+    cmd
+}
+
+/// C-STORE-RQ 命令集
+pub fn store_request(sop_class: &str, sop_instance: &str, message_id: u16) -> InMemDicomObject {
+    let mut cmd = request(C_STORE_RQ, sop_class, message_id, true);
+    put_ui(&mut cmd, tags::AFFECTED_SOP_INSTANCE_UID, sop_instance);
+    cmd
+}
+
+/// C-MOVE 产生的 C-STORE-RQ 需带上发起方 AE 与 C-MOVE-RQ 的 Message ID
+pub fn set_move_originator(cmd: &mut InMemDicomObject, ae_title: &str, message_id: u16) {
+    cmd.put(DataElement::new(
+        tags::MOVE_ORIGINATOR_APPLICATION_ENTITY_TITLE,
+        VR::AE,
+        PrimitiveValue::from(ae_title),
+    ));
+    put_us(cmd, tags::MOVE_ORIGINATOR_MESSAGE_ID, message_id);
+}
+
+/// C-MOVE/C-GET 子操作计数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubOperations {
+    pub remaining: u16,
+    pub completed: u16,
+    pub failed: u16,
+    pub warning: u16,
+}
+
+impl SubOperations {
+    pub fn new(total: usize) -> Self {
+        Self {
+            remaining: total.min(u16::MAX as usize) as u16,
+            ..Self::default()
+        }
+    }
+
+    /// 记录一个子操作的 C-STORE 状态 (无响应时按失败处理)
+    pub fn record(&mut self, store_status: Option<u16>) {
+        self.remaining = self.remaining.saturating_sub(1);
+        match store_status {
+            Some(status::SUCCESS) => self.completed = self.completed.saturating_add(1),
+            Some(code) if is_warning(code) => self.warning = self.warning.saturating_add(1),
+            _ => self.failed = self.failed.saturating_add(1),
+        }
+    }
+
+    /// 最终响应的状态: 全部成功, 全部失败, 或部分失败/警告
+    pub fn final_status(&self) -> u16 {
+        if self.failed == 0 && self.warning == 0 {
+            status::SUCCESS
+        } else if self.completed == 0 && self.warning == 0 {
+            status::UNABLE_TO_PERFORM_SUBOPERATIONS
+        } else {
+            status::SUBOPERATIONS_WARNING
+        }
+    }
+
+    /// 写入响应命令集; Pending 响应才带 Remaining
+    pub fn put(&self, cmd: &mut InMemDicomObject, pending: bool) {
+        if pending {
+            put_us(cmd, tags::NUMBER_OF_REMAINING_SUBOPERATIONS, self.remaining);
+        }
+        put_us(cmd, tags::NUMBER_OF_COMPLETED_SUBOPERATIONS, self.completed);
+        put_us(cmd, tags::NUMBER_OF_FAILED_SUBOPERATIONS, self.failed);
+        put_us(cmd, tags::NUMBER_OF_WARNING_SUBOPERATIONS, self.warning);
+    }
+}
+
+/// 响应命令集, 从请求中复制 Affected SOP Class/Instance UID
+pub fn response(rq: &InMemDicomObject, status: u16, has_data: bool) -> InMemDicomObject {
+    let mut cmd = InMemDicomObject::new_empty();
+    for tag in [tags::AFFECTED_SOP_CLASS_UID, tags::AFFECTED_SOP_INSTANCE_UID] {
+        if let Some(uid) = get_str(rq, tag) {
+            put_ui(&mut cmd, tag, &uid);
+        }
+    }
+    put_us(&mut cmd, tags::COMMAND_FIELD, command_field(rq).unwrap_or(0) | RESPONSE_BIT);
+    put_us(&mut cmd, tags::MESSAGE_ID_BEING_RESPONDED_TO, message_id(rq).unwrap_or(0));
+    put_us(
+        &mut cmd,
+        tags::COMMAND_DATA_SET_TYPE,
+        if has_data { HAS_DATA_SET } else { NO_DATA_SET },
+    );
+    put_us(&mut cmd, tags::STATUS, status);
+    cmd
+}
+
+/// 警告状态: Bxxx 类, 以及 C-STORE 的 0001 (Coercion of data elements)
+pub fn is_warning(status: u16) -> bool {
+    status & 0xF000 == 0xB000 || status == 0x0001
+}
+
+pub fn get_u16(cmd: &InMemDicomObject, tag: Tag) -> Option<u16> {
+    cmd.get(tag).and_then(|e| e.to_int::<u16>().ok())
+}
+
+pub fn get_str(cmd: &InMemDicomObject, tag: Tag) -> Option<String> {
+    cmd.get(tag)
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+}
+
+pub fn command_field(cmd: &InMemDicomObject) -> Option<u16> {
+    get_u16(cmd, tags::COMMAND_FIELD)
+}
+
+pub fn message_id(cmd: &InMemDicomObject) -> Option<u16> {
+    get_u16(cmd, tags::MESSAGE_ID)
+}
+
+/// C-CANCEL-RQ 所取消请求的 Message ID
+pub fn cancelled_message_id(cmd: &InMemDicomObject) -> Option<u16> {
+    match command_field(cmd) {
+        Some(C_CANCEL_RQ) => get_u16(cmd, tags::MESSAGE_ID_BEING_RESPONDED_TO),
+        _ => None,
+    }
+}
+
+pub fn status(cmd: &InMemDicomObject) -> Option<u16> {
+    get_u16(cmd, tags::STATUS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::association::{decode_command, encode_command};
+
+    #[test]
+    fn test_echo_round_trip() {
+        let rq = request(C_ECHO_RQ, "1.2.840.10008.1.1", 7, false);
+        let decoded = decode_command(&encode_command(&rq).unwrap()).unwrap();
+        assert_eq!(command_field(&decoded), Some(C_ECHO_RQ));
+        assert_eq!(message_id(&decoded), Some(7));
+        assert_eq!(get_str(&decoded, tags::AFFECTED_SOP_CLASS_UID).as_deref(), Some("1.2.840.10008.1.1"));
+        // CommandGroupLength 为其后所有元素的长度
+        let group_length = decoded.get(tags::COMMAND_GROUP_LENGTH).unwrap().to_int::<u32>().unwrap();
+        assert_eq!(group_length as usize, encode_command(&rq).unwrap().len() - 12);
+
+        let rsp = response(&decoded, status::SUCCESS, false);
+        assert_eq!(command_field(&rsp), Some(C_ECHO_RQ | RESPONSE_BIT));
+        assert_eq!(get_u16(&rsp, tags::MESSAGE_ID_BEING_RESPONDED_TO), Some(7));
+        assert_eq!(status(&rsp), Some(status::SUCCESS));
+    }
+
+    #[test]
+    fn test_sub_operation_status() {
+        let mut ops = SubOperations::new(3);
+        ops.record(Some(status::SUCCESS));
+        assert_eq!(ops.final_status(), status::SUCCESS);
+        ops.record(Some(0xB007));
+        ops.record(None);
+        assert_eq!((ops.remaining, ops.completed, ops.warning, ops.failed), (0, 1, 1, 1));
+        assert_eq!(ops.final_status(), status::SUBOPERATIONS_WARNING);
+        let mut ops = SubOperations::new(1);
+        ops.record(Some(status::OUT_OF_RESOURCES));
+        assert_eq!(ops.final_status(), status::UNABLE_TO_PERFORM_SUBOPERATIONS);
+        let mut rsp = InMemDicomObject::new_empty();
+        ops.put(&mut rsp, false);
+        assert!(rsp.get(tags::NUMBER_OF_REMAINING_SUBOPERATIONS).is_none());
+        assert_eq!(get_u16(&rsp, tags::NUMBER_OF_FAILED_SUBOPERATIONS), Some(1));
+    }
+
+    #[test]
+    fn test_is_warning_by_status_class() {
+        for code in [0x0001, 0xB000, 0xB006, 0xB007, 0xB00F, 0xBFFF] {
+            assert!(is_warning(code), "{code:04X}");
+        }
+        for code in [status::SUCCESS, 0xA700, 0xC000, 0xFF00, 0x0110] {
+            assert!(!is_warning(code), "{code:04X}");
+        }
+    }
+
+    #[test]
+    fn test_sub_operation_counters_saturate() {
+        let mut ops = SubOperations::new(70_000);
+        assert_eq!(ops.remaining, u16::MAX);
+        for _ in 0..70_000 {
+            ops.record(Some(status::SUCCESS));
+        }
+        assert_eq!((ops.remaining, ops.completed), (0, u16::MAX));
+    }
+}
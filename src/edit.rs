@@ -0,0 +1,306 @@
+use crate::dicom_json::{is_binary, primitive_from_text};
+use crate::error::DcmToolsError;
+use dicom::core::dictionary::{DataDictionary, DataDictionaryEntry};
+use dicom::core::value::{DataSetSequence, Value};
+use dicom::core::{DataElement, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags};
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use std::io::Write;
+use std::path::Path;
+
+/// 序列中的条目: 第 n 个 (从 0 开始) 或全部
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+    Index(usize),
+    All,
+}
+
+/// 属性路径, 如 0010,0010 / PatientName / (0040,0275)[0].(0040,1001) / ReferencedImageSequence[*].ReferencedSOPInstanceUID;
+/// 中间的序列省略条目时取第 0 个
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagPath {
+    pub sequences: Vec<(Tag, Item)>,
+    pub tag: Tag,
+}
+
+/// 解析属性路径 (用作 clap value_parser)
+pub fn parse_tag_path(value: &str) -> Result<TagPath, String> {
+    let parse_tag = |s: &str| {
+        StandardDataDictionary
+            .parse_tag(s.trim())
+            .ok_or_else(|| format!("invalid tag or keyword '{}' in {}", s, value))
+    };
+    let mut steps: Vec<&str> = value.split('.').collect();
+    let last = steps.pop().unwrap_or_default();
+    if last.ends_with(']') {
+        return Err(format!("path must end with an attribute: {}", value));
+    }
+    let mut sequences = Vec::new();
+    for step in steps {
+        let (tag, item) = match step.split_once('[') {
+            Some((tag, item)) => {
+                let item = match item.strip_suffix(']') {
+                    Some("*") => Item::All,
+                    Some(n) => Item::Index(n.parse().map_err(|_| format!("invalid item index '{}' in {}", n, value))?),
+                    None => return Err(format!("missing ']' in {}", value)),
+                };
+                (parse_tag(tag)?, item)
+            }
+            None => (parse_tag(step)?, Item::Index(0)),
+        };
+        sequences.push((tag, item));
+    }
+    let tag = parse_tag(last)?;
+    let top = sequences.first().map(|(tag, _)| *tag).unwrap_or(tag);
+    if top.group() == 0x0002 {
+        return Err(format!("file meta information cannot be edited: {}", value));
+    }
+    Ok(TagPath { sequences, tag })
+}
+
+/// 解析 PATH=VALUE (用作 clap value_parser)
+pub fn parse_assignment(value: &str) -> Result<(TagPath, String), String> {
+    let (path, text) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected PATH=VALUE: {}", value))?;
+    Ok((parse_tag_path(path)?, text.to_string()))
+}
+
+/// 路径中的序列或条目不存在时的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Missing {
+    Error,
+    Skip,
+    Create,
+}
+
+/// 对路径选中的每个条目 (没有序列时为数据集本身) 执行 f
+fn for_each_item(
+    obj: &mut InMemDicomObject,
+    sequences: &[(Tag, Item)],
+    missing: Missing,
+    f: &mut dyn FnMut(&mut InMemDicomObject) -> Result<(), String>,
+) -> Result<(), String> {
+    let Some(((tag, item), rest)) = sequences.split_first() else {
+        return f(obj);
+    };
+    let mut items = match obj.take_element(*tag) {
+        Ok(element) => match element.into_value() {
+            Value::Sequence(seq) => seq.into_items().into_vec(),
+            _ => return Err(format!("{} is not a sequence", tag)),
+        },
+        Err(_) if missing == Missing::Create => Vec::new(),
+        Err(_) if missing == Missing::Skip => return Ok(()),
+        Err(_) => return Err(format!("sequence {} not found", tag)),
+    };
+    let selected = match *item {
+        Item::All => 0..items.len(),
+        Item::Index(n) if n < items.len() => n..n + 1,
+        Item::Index(n) if n == items.len() && missing == Missing::Create => {
+            items.push(InMemDicomObject::new_empty());
+            n..n + 1
+        }
+        Item::Index(_) if missing == Missing::Skip => 0..0,
+        Item::Index(n) => return Err(format!("item {} of {} not found ({} items)", n, tag, items.len())),
+    };
+    let result = items[selected]
+        .iter_mut()
+        .try_for_each(|item| for_each_item(item, rest, missing, f));
+    obj.put(DataElement::new(*tag, VR::SQ, DataSetSequence::from(items)));
+    result
+}
+
+/// 按 VR 设置值: 已有属性沿用其 VR, 否则取字典中的 VR
+fn set_value(obj: &mut InMemDicomObject, tag: Tag, text: &str) -> Result<(), String> {
+    let vr = match obj.element_opt(tag) {
+        Ok(Some(element)) => element.vr(),
+        _ => StandardDataDictionary
+            .by_tag(tag)
+            .map(|entry| entry.vr().relaxed())
+            .ok_or_else(|| format!("{}: unknown VR, the attribute must already exist", tag))?,
+    };
+    if vr == VR::SQ || is_binary(vr) {
+        return Err(format!("{}: cannot set a value of VR {}", tag, vr));
+    }
+    let value = primitive_from_text(vr, text).map_err(|e| format!("{}: {}", tag, e))?;
+    obj.put(DataElement::new(tag, vr, value));
+    Ok(())
+}
+
+/// 一组编辑操作, 按 copy → delete → insert → set 的顺序执行
+#[derive(Debug, Default)]
+pub struct Editor {
+    /// 从该文件复制 copies 中的属性
+    pub source: Option<InMemDicomObject>,
+    pub copies: Vec<TagPath>,
+    pub deletes: Vec<TagPath>,
+    /// 路径中缺少的序列与条目 (下标等于条目数时) 会被创建
+    pub inserts: Vec<(TagPath, String)>,
+    /// 路径中的序列与条目必须存在, 属性不存在时创建
+    pub sets: Vec<(TagPath, String)>,
+}
+
+impl Editor {
+    pub fn is_empty(&self) -> bool {
+        self.copies.is_empty() && self.deletes.is_empty() && self.inserts.is_empty() && self.sets.is_empty()
+    }
+
+    pub fn apply(&self, obj: &mut InMemDicomObject) -> Result<(), String> {
+        for path in &self.copies {
+            let element = self.source_element(path)?;
+            for_each_item(obj, &path.sequences, Missing::Create, &mut |item| {
+                item.put(element.clone());
+                Ok(())
+            })?;
+        }
+        for path in &self.deletes {
+            // 不存在的属性不算错误, 便于对整个目录执行
+            for_each_item(obj, &path.sequences, Missing::Skip, &mut |item| {
+                item.remove_element(path.tag);
+                Ok(())
+            })?;
+        }
+        for (path, text) in &self.inserts {
+            for_each_item(obj, &path.sequences, Missing::Create, &mut |item| set_value(item, path.tag, text))?;
+        }
+        for (path, text) in &self.sets {
+            for_each_item(obj, &path.sequences, Missing::Error, &mut |item| set_value(item, path.tag, text))?;
+        }
+        Ok(())
+    }
+
+    /// 复制源文件中路径指向的属性; 路径中不能使用 [*]
+    fn source_element(&self, path: &TagPath) -> Result<DataElement<InMemDicomObject>, String> {
+        let mut obj = self.source.as_ref().ok_or("--copy requires --copy-from")?;
+        for (tag, item) in &path.sequences {
+            let Item::Index(n) = item else {
+                return Err(format!("[*] is not allowed in a copied path: {}", tag));
+            };
+            obj = obj
+                .element_opt(*tag)
+                .ok()
+                .flatten()
+                .and_then(|e| e.items())
+                .and_then(|items| items.get(*n))
+                .ok_or_else(|| format!("item {} of {} not found in --copy-from", n, tag))?;
+        }
+        obj.element_opt(path.tag)
+            .ok()
+            .flatten()
+            .cloned()
+            .ok_or_else(|| format!("{} not found in --copy-from", path.tag))
+    }
+
+    /// 编辑单个文件: 保持原传输语法, 写入同目录的临时文件后替换原文件
+    pub fn edit_file(&self, file: &Path) -> Result<(), DcmToolsError> {
+        let mut obj: DefaultDicomObject = dicom_object::open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+        self.apply(&mut obj).map_err(|e| DcmToolsError::parse(file, e))?;
+        // SOP Class/Instance UID 被修改时同步文件元信息
+        let uid = |obj: &DefaultDicomObject, tag| {
+            obj.element_opt(tag)
+                .ok()
+                .flatten()
+                .and_then(|e| e.to_str().ok())
+                .map(|s| s.trim_end_matches('\0').to_string())
+        };
+        let sop_class = uid(&obj, tags::SOP_CLASS_UID);
+        let sop_instance = uid(&obj, tags::SOP_INSTANCE_UID);
+        obj.update_meta(|meta| {
+            if let Some(uid) = sop_class {
+                meta.media_storage_sop_class_uid = uid;
+            }
+            if let Some(uid) = sop_instance {
+                meta.media_storage_sop_instance_uid = uid;
+            }
+        });
+
+        let dir = file.parent().unwrap_or(Path::new("."));
+        let mut tmp = tempfile::NamedTempFile::new_in(dir).map_err(|e| DcmToolsError::write(file, e))?;
+        obj.write_all(tmp.as_file_mut()).map_err(|e| DcmToolsError::write(file, e))?;
+        tmp.as_file_mut().flush().map_err(|e| DcmToolsError::write(file, e))?;
+        tmp.as_file().sync_all().map_err(|e| DcmToolsError::write(file, e))?;
+        // 临时文件的权限是 0600, 沿用原文件的权限
+        let permissions = std::fs::metadata(file).map_err(|e| DcmToolsError::write(file, e))?.permissions();
+        std::fs::set_permissions(tmp.path(), permissions).map_err(|e| DcmToolsError::write(file, e))?;
+        tmp.persist(file).map_err(|e| DcmToolsError::write(file, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tag_path() {
+        let path = parse_tag_path("PatientID").unwrap();
+        assert_eq!(path, TagPath { sequences: vec![], tag: tags::PATIENT_ID });
+        let path = parse_tag_path("(0040,0275)[1].0040,1001").unwrap();
+        assert_eq!(path.sequences, [(tags::REQUEST_ATTRIBUTES_SEQUENCE, Item::Index(1))]);
+        assert_eq!(path.tag, tags::REQUESTED_PROCEDURE_ID);
+        let path = parse_tag_path("ReferencedImageSequence[*].00081155").unwrap();
+        assert_eq!(path.sequences, [(tags::REFERENCED_IMAGE_SEQUENCE, Item::All)]);
+        assert!(parse_tag_path("0002,0010").is_err());
+        assert!(parse_tag_path("0040,0275[0]").is_err());
+        assert!(parse_tag_path("NoSuchKeyword").is_err());
+        assert_eq!(parse_assignment("0010,0010=A^B=C").unwrap().1, "A^B=C");
+    }
+
+    #[test]
+    fn test_edit_file_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.dcm");
+        std::fs::copy("./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm", &file).unwrap();
+        let original = dicom_object::open_file(&file).unwrap();
+        let source = dir.path().join("source.dcm");
+        std::fs::copy(&file, &source).unwrap();
+        let rename = Editor {
+            sets: vec![parse_assignment("PatientName=SOURCE^NAME").unwrap()],
+            ..Default::default()
+        };
+        rename.edit_file(&source).unwrap();
+
+        let editor = Editor {
+            source: Some(dicom_object::open_file(&source).unwrap().into_inner()),
+            copies: vec![parse_tag_path("PatientName").unwrap()],
+            deletes: vec![parse_tag_path("StudyDescription").unwrap(), parse_tag_path("0008,1032[*].0008,0100").unwrap()],
+            inserts: vec![
+                parse_assignment("RequestAttributesSequence[0].RequestedProcedureID=RP1").unwrap(),
+                parse_assignment("RequestAttributesSequence[1].RequestedProcedureID=RP2").unwrap(),
+            ],
+            sets: vec![
+                parse_assignment("0010,0020=NEW-ID").unwrap(),
+                parse_assignment("0028,0010=512").unwrap(),
+                parse_assignment("SOPInstanceUID=1.2.3.4").unwrap(),
+            ],
+        };
+        editor.edit_file(&file).unwrap();
+
+        let obj = dicom_object::open_file(&file).unwrap();
+        let text = |tag| obj.element(tag).unwrap().to_str().unwrap().trim_end_matches(['\0', ' ']).to_string();
+        assert_eq!(text(tags::PATIENT_ID), "NEW-ID");
+        assert_eq!(text(tags::PATIENT_NAME), "SOURCE^NAME");
+        assert!(obj.element_opt(tags::STUDY_DESCRIPTION).unwrap().is_none());
+        assert_eq!(obj.element(tags::ROWS).unwrap().vr(), VR::US);
+        assert_eq!(obj.element(tags::ROWS).unwrap().to_int::<u16>().unwrap(), 512);
+        let items = obj.element(tags::REQUEST_ATTRIBUTES_SEQUENCE).unwrap().items().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].element(tags::REQUESTED_PROCEDURE_ID).unwrap().to_str().unwrap().trim(), "RP2");
+        assert_eq!(obj.meta().media_storage_sop_instance_uid().trim_end_matches('\0'), "1.2.3.4");
+        assert_eq!(obj.meta().transfer_syntax(), original.meta().transfer_syntax());
+
+        // 序列不存在时 --set 失败, 原文件保持不变
+        let failing = Editor {
+            sets: vec![parse_assignment("RequestAttributesSequence[5].RequestedProcedureID=X").unwrap()],
+            ..Default::default()
+        };
+        assert!(failing.edit_file(&file).is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert_eq!(dicom_object::open_file(&file).unwrap().meta().media_storage_sop_instance_uid().trim_end_matches('\0'), "1.2.3.4");
+        let unknown = Editor {
+            inserts: vec![parse_assignment("0009,1001=X").unwrap()],
+            ..Default::default()
+        };
+        assert!(unknown.edit_file(&file).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use dicom_object::ReadError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// dcmobj 中所有操作的错误类型, 每个错误都带有出错的文件路径
+#[derive(Debug)]
+pub enum DcmToolsError {
+    /// 文件或目录不存在
+    NotFound { path: PathBuf },
+    /// 不是 DICOM 文件 (缺少 DICM 前缀或文件元信息)
+    NotDicom { path: PathBuf, source: BoxError },
+    /// DICOM 数据集解析失败, 或缺少必需的属性
+    Parse { path: PathBuf, source: BoxError },
+    /// 读取文件或目录失败
+    Read { path: PathBuf, source: std::io::Error },
+    /// 传输语法转换失败
+    Transcode { path: PathBuf, source: BoxError },
+    /// 写文件或创建目录失败
+    Write { path: PathBuf, source: BoxError },
+    /// 发送过程中关联或连接失败
+    Network { path: PathBuf, source: BoxError },
+    /// 对端以失败状态拒绝了该实例
+    Rejected { path: PathBuf, status: u16 },
+    /// 索引数据库 (SQLite) 打开或读写失败, path 为数据库文件
+    Database { path: PathBuf, source: BoxError },
+    /// 实例不符合 IOD 要求 (validate 发现错误)
+    Invalid { path: PathBuf, errors: usize },
+    /// 切片几何或像素不满足组装体数据的要求
+    Geometry { path: PathBuf, reason: String },
+    /// 多帧拆分或合并不支持该实例 (SOP Class、图像尺寸等)
+    Convert { path: PathBuf, reason: String },
+}
+
+impl DcmToolsError {
+    pub fn not_found(path: impl Into<PathBuf>) -> Self {
+        DcmToolsError::NotFound { path: path.into() }
+    }
+
+    pub fn parse(path: impl Into<PathBuf>, source: impl Into<BoxError>) -> Self {
+        DcmToolsError::Parse {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn transcode(path: impl Into<PathBuf>, source: impl Into<BoxError>) -> Self {
+        DcmToolsError::Transcode {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn write(path: impl Into<PathBuf>, source: impl Into<BoxError>) -> Self {
+        DcmToolsError::Write {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn network(path: impl Into<PathBuf>, source: impl Into<BoxError>) -> Self {
+        DcmToolsError::Network {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn database(path: impl Into<PathBuf>, source: impl Into<BoxError>) -> Self {
+        DcmToolsError::Database {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn rejected(path: impl Into<PathBuf>, status: u16) -> Self {
+        DcmToolsError::Rejected {
+            path: path.into(),
+            status,
+        }
+    }
+
+    pub fn invalid(path: impl Into<PathBuf>, errors: usize) -> Self {
+        DcmToolsError::Invalid {
+            path: path.into(),
+            errors,
+        }
+    }
+
+    pub fn geometry(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        DcmToolsError::Geometry {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn convert(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        DcmToolsError::Convert {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// 读取时的 io 错误, NotFound 单独归类
+    pub fn read(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        let path = path.into();
+        if source.kind() == std::io::ErrorKind::NotFound {
+            return DcmToolsError::NotFound { path };
+        }
+        DcmToolsError::Read { path, source }
+    }
+
+    /// 将 open_file / from_reader 的错误按原因归类
+    pub fn open(path: impl Into<PathBuf>, source: ReadError) -> Self {
+        let path = path.into();
+        match source {
+            ReadError::OpenFile { source, .. } | ReadError::ReadFile { source, .. } => {
+                DcmToolsError::read(path, source)
+            }
+            ReadError::ReadPreambleBytes { .. } | ReadError::ParseMetaDataSet { .. } => {
+                DcmToolsError::NotDicom {
+                    path,
+                    source: source.into(),
+                }
+            }
+            _ => DcmToolsError::parse(path, source),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            DcmToolsError::NotFound { path }
+            | DcmToolsError::NotDicom { path, .. }
+            | DcmToolsError::Parse { path, .. }
+            | DcmToolsError::Read { path, .. }
+            | DcmToolsError::Transcode { path, .. }
+            | DcmToolsError::Write { path, .. }
+            | DcmToolsError::Network { path, .. }
+            | DcmToolsError::Rejected { path, .. }
+            | DcmToolsError::Database { path, .. }
+            | DcmToolsError::Invalid { path, .. }
+            | DcmToolsError::Geometry { path, .. }
+            | DcmToolsError::Convert { path, .. } => path,
+        }
+    }
+
+    /// 是否值得重试: io 类错误和对端资源不足 (0xA7xx) 可能是暂时的, 文件本身的问题重试无意义
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DcmToolsError::Read { .. } | DcmToolsError::Write { .. } | DcmToolsError::Network { .. } => true,
+            DcmToolsError::Rejected { status, .. } => status & 0xFF00 == 0xA700,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DcmToolsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DcmToolsError::NotFound { path } => {
+                write!(f, "File or Directory does not exist: {:?}", path)
+            }
+            DcmToolsError::NotDicom { path, source } => {
+                write!(f, "Not a DICOM file: {:?}: {}", path, source)
+            }
+            DcmToolsError::Parse { path, source } => {
+                write!(f, "Error parsing DICOM file {:?}: {}", path, source)
+            }
+            DcmToolsError::Read { path, source } => {
+                write!(f, "Error reading {:?}: {}", path, source)
+            }
+            DcmToolsError::Transcode { path, source } => {
+                write!(f, "Error during transcoding {:?}: {}", path, source)
+            }
+            DcmToolsError::Write { path, source } => {
+                write!(f, "Error writing {:?}: {}", path, source)
+            }
+            DcmToolsError::Network { path, source } => {
+                write!(f, "Error sending {:?}: {}", path, source)
+            }
+            DcmToolsError::Rejected { path, status } => {
+                write!(f, "Rejected by peer {:?}: status 0x{:04X}", path, status)
+            }
+            DcmToolsError::Database { path, source } => {
+                write!(f, "Error accessing index database {:?}: {}", path, source)
+            }
+            DcmToolsError::Invalid { path, errors } => {
+                write!(f, "Invalid DICOM instance {:?}: {} error(s)", path, errors)
+            }
+            DcmToolsError::Geometry { path, reason } => {
+                write!(f, "Cannot assemble volume from {:?}: {}", path, reason)
+            }
+            DcmToolsError::Convert { path, reason } => {
+                write!(f, "Cannot convert {:?}: {}", path, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DcmToolsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DcmToolsError::NotFound { .. }
+            | DcmToolsError::Rejected { .. }
+            | DcmToolsError::Invalid { .. }
+            | DcmToolsError::Geometry { .. }
+            | DcmToolsError::Convert { .. } => None,
+            DcmToolsError::Read { source, .. } => Some(source),
+            DcmToolsError::NotDicom { source, .. }
+            | DcmToolsError::Parse { source, .. }
+            | DcmToolsError::Transcode { source, .. }
+            | DcmToolsError::Write { source, .. }
+            | DcmToolsError::Network { source, .. }
+            | DcmToolsError::Database { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom_object::open_file;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn open_errors_are_classified() {
+        let dir = tempdir().unwrap();
+
+        let missing = dir.path().join("missing.dcm");
+        let err = DcmToolsError::open(&missing, open_file(&missing).unwrap_err());
+        assert!(matches!(err, DcmToolsError::NotFound { .. }));
+        assert_eq!(err.path(), missing.as_path());
+
+        let text = dir.path().join("readme.txt");
+        let mut file = std::fs::File::create(&text).unwrap();
+        file.write_all(&[b'x'; 256]).unwrap();
+        let err = DcmToolsError::open(&text, open_file(&text).unwrap_err());
+        assert!(matches!(err, DcmToolsError::NotDicom { .. }));
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn io_errors_are_retryable() {
+        let err = DcmToolsError::write(
+            "/tmp/out.dcm",
+            std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"),
+        );
+        assert!(err.is_retryable());
+        assert!(err.to_string().contains("/tmp/out.dcm"));
+        // 对端资源不足可以重试, 数据集不符合 SOP Class 则不行
+        assert!(DcmToolsError::rejected("a.dcm", 0xA700).is_retryable());
+        assert!(!DcmToolsError::rejected("a.dcm", 0xA900).is_retryable());
+    }
+}
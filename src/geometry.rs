@@ -0,0 +1,556 @@
+use crate::error::DcmToolsError;
+use crate::render::{decode, stored_values};
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::pixeldata::PhotometricInterpretation;
+use dicom_object::{DefaultDicomObject, OpenFileOptions};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub type Vec3 = [f64; 3];
+/// 体素索引 (列, 行, 切片) 到病人坐标 (LPS, mm) 的 4x4 仿射矩阵
+pub type Affine = [[f64; 4]; 4];
+
+/// 方向余弦的容差
+const ORIENTATION_TOLERANCE: f64 = 1e-4;
+/// 法向投影相差小于该值 (mm) 的两张切片视为同一位置
+const DUPLICATE_TOLERANCE: f64 = 0.01;
+/// 层间距超过中位数的该倍数视为缺层
+const GAP_FACTOR: f64 = 1.5;
+/// 层间距相对中位数的偏差超过该比例视为不均匀
+const SPACING_TOLERANCE: f64 = 0.01;
+/// 切片堆叠方向偏离法向超过该角度 (度) 视为机架倾斜
+const TILT_TOLERANCE: f64 = 0.1;
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vec3, s: f64) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn norm(a: Vec3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: Vec3) -> Option<Vec3> {
+    let n = norm(a);
+    (n > f64::EPSILON).then(|| scale(a, 1.0 / n))
+}
+
+/// Image Orientation (Patient) 的行、列方向余弦 (归一化后)
+fn direction_cosines(orientation: &[f64]) -> Option<(Vec3, Vec3)> {
+    let [rx, ry, rz, cx, cy, cz] = *orientation else {
+        return None;
+    };
+    Some((normalize([rx, ry, rz])?, normalize([cx, cy, cz])?))
+}
+
+/// 切片法向: 行方向 × 列方向
+pub fn slice_normal(orientation: &[f64]) -> Option<Vec3> {
+    let (row, column) = direction_cosines(orientation)?;
+    normalize(cross(row, column))
+}
+
+/// 两组方向余弦是否相同
+pub fn same_orientation(a: &[f64], b: &[f64]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() <= ORIENTATION_TOLERANCE)
+}
+
+fn get_floats(tag: Tag, obj: &DefaultDicomObject) -> Option<Vec<f64>> {
+    obj.element_opt(tag).ok().flatten()?.to_multi_float64().ok()
+}
+
+/// 单张切片的几何信息
+#[derive(Debug, Clone, Serialize)]
+pub struct Slice {
+    pub path: PathBuf,
+    pub sop_uid: String,
+    pub instance_number: Option<i32>,
+    pub position: Vec3,
+    pub orientation: [f64; 6],
+    pub rows: u16,
+    pub columns: u16,
+    /// Pixel Spacing: 行间距 (沿列方向), 列间距 (沿行方向)
+    pub pixel_spacing: [f64; 2],
+    pub thickness: Option<f64>,
+}
+
+impl Slice {
+    /// 读取切片几何; 缺少位置、方向、间距或尺寸时返回原因
+    pub fn from_object(path: &Path, obj: &DefaultDicomObject) -> Result<Slice, String> {
+        let floats = |tag: Tag, name: &str, len: usize| {
+            get_floats(tag, obj)
+                .filter(|values| values.len() == len)
+                .ok_or_else(|| format!("missing or invalid {}", name))
+        };
+        let position = floats(tags::IMAGE_POSITION_PATIENT, "Image Position (Patient)", 3)?;
+        let orientation = floats(tags::IMAGE_ORIENTATION_PATIENT, "Image Orientation (Patient)", 6)?;
+        let pixel_spacing = floats(tags::PIXEL_SPACING, "Pixel Spacing", 2)?;
+        if slice_normal(&orientation).is_none() {
+            return Err("degenerate Image Orientation (Patient)".to_string());
+        }
+        let dimension = |tag: Tag, name: &str| {
+            obj.element_opt(tag)
+                .ok()
+                .flatten()
+                .and_then(|e| e.to_int::<u16>().ok())
+                .filter(|&v| v > 0)
+                .ok_or_else(|| format!("missing {}", name))
+        };
+        Ok(Slice {
+            path: path.to_path_buf(),
+            sop_uid: obj
+                .element_opt(tags::SOP_INSTANCE_UID)
+                .ok()
+                .flatten()
+                .and_then(|e| e.to_str().ok())
+                .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+                .unwrap_or_default(),
+            instance_number: obj
+                .element_opt(tags::INSTANCE_NUMBER)
+                .ok()
+                .flatten()
+                .and_then(|e| e.to_int::<i32>().ok()),
+            position: [position[0], position[1], position[2]],
+            orientation: [
+                orientation[0],
+                orientation[1],
+                orientation[2],
+                orientation[3],
+                orientation[4],
+                orientation[5],
+            ],
+            rows: dimension(tags::ROWS, "Rows")?,
+            columns: dimension(tags::COLUMNS, "Columns")?,
+            pixel_spacing: [pixel_spacing[0], pixel_spacing[1]],
+            thickness: get_floats(tags::SLICE_THICKNESS, obj).and_then(|v| v.first().copied()),
+        })
+    }
+}
+
+/// 组装体数据前发现的问题
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GeometryIssue {
+    /// 缺少几何属性, 无法参与排序
+    MissingGeometry { path: PathBuf, reason: String },
+    /// 方向与第一张切片不同
+    MixedOrientation { sop_uid: String },
+    /// 行列数或像素间距与第一张切片不同
+    MixedDimensions { sop_uid: String },
+    /// 两张切片位于同一位置
+    Duplicate { sop_uids: [String; 2], location: f64 },
+    /// 相邻切片的间距明显大于中位数, 中间缺层
+    Gap { after: String, before: String, distance: f64, expected: f64 },
+    /// 层间距不均匀, 但没有缺层
+    IrregularSpacing { min: f64, max: f64 },
+    /// 切片堆叠方向不垂直于切片 (机架倾斜), 仿射矩阵带剪切
+    Tilt { degrees: f64 },
+}
+
+impl GeometryIssue {
+    /// 是否无法组装为规则的体数据; 不均匀间距和倾斜只是警告
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, GeometryIssue::IrregularSpacing { .. } | GeometryIssue::Tilt { .. })
+    }
+}
+
+/// 一个 series 的切片几何: 按法向投影排序后的切片、层间距和仿射矩阵
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesGeometry {
+    pub series_uid: String,
+    pub slices: Vec<Slice>,
+    pub normal: Vec3,
+    /// 相邻切片间距的中位数
+    pub spacing: Option<f64>,
+    /// (列, 行, 切片)
+    pub dimensions: [usize; 3],
+    pub affine: Affine,
+    pub issues: Vec<GeometryIssue>,
+}
+
+impl SeriesGeometry {
+    /// 按第一张切片 (Instance Number 最小) 的法向排序并检查几何
+    pub fn new(series_uid: &str, mut slices: Vec<Slice>) -> SeriesGeometry {
+        slices.sort_by_key(|slice| slice.instance_number.unwrap_or(i32::MAX));
+        let mut issues = Vec::new();
+        let Some(first) = slices.first().cloned() else {
+            return SeriesGeometry {
+                series_uid: series_uid.to_string(),
+                slices,
+                normal: [0.0, 0.0, 1.0],
+                spacing: None,
+                dimensions: [0, 0, 0],
+                affine: identity(),
+                issues,
+            };
+        };
+        let normal = slice_normal(&first.orientation).unwrap_or([0.0, 0.0, 1.0]);
+        for slice in &slices[1..] {
+            if !same_orientation(&slice.orientation, &first.orientation) {
+                issues.push(GeometryIssue::MixedOrientation { sop_uid: slice.sop_uid.clone() });
+            }
+            if (slice.rows, slice.columns) != (first.rows, first.columns)
+                || !same_orientation(&slice.pixel_spacing, &first.pixel_spacing)
+            {
+                issues.push(GeometryIssue::MixedDimensions { sop_uid: slice.sop_uid.clone() });
+            }
+        }
+
+        // 排序: 法向投影, 相同时按 Instance Number (sort_by 是稳定的)
+        slices.sort_by(|a, b| dot(a.position, normal).total_cmp(&dot(b.position, normal)));
+        let locations: Vec<f64> = slices.iter().map(|slice| dot(slice.position, normal)).collect();
+        let mut distances = Vec::new();
+        for (i, pair) in locations.windows(2).enumerate() {
+            let distance = pair[1] - pair[0];
+            if distance < DUPLICATE_TOLERANCE {
+                issues.push(GeometryIssue::Duplicate {
+                    sop_uids: [slices[i].sop_uid.clone(), slices[i + 1].sop_uid.clone()],
+                    location: pair[0],
+                });
+            } else {
+                distances.push((i, distance));
+            }
+        }
+        let spacing = median(distances.iter().map(|(_, d)| *d).collect());
+        if let Some(expected) = spacing {
+            let mut irregular = false;
+            for &(i, distance) in &distances {
+                if distance > expected * GAP_FACTOR {
+                    issues.push(GeometryIssue::Gap {
+                        after: slices[i].sop_uid.clone(),
+                        before: slices[i + 1].sop_uid.clone(),
+                        distance,
+                        expected,
+                    });
+                } else if (distance - expected).abs() > expected * SPACING_TOLERANCE {
+                    irregular = true;
+                }
+            }
+            if irregular {
+                let (min, max) = distances
+                    .iter()
+                    .fold((f64::MAX, f64::MIN), |(min, max), (_, d)| (min.min(*d), max.max(*d)));
+                issues.push(GeometryIssue::IrregularSpacing { min, max });
+            }
+        }
+
+        // 堆叠方向: 首尾切片位置之差; 只有一张时沿法向, 步长取层厚
+        let last = slices.last().unwrap_or(&first);
+        let count = slices.len();
+        let step = if count > 1 && norm(sub(last.position, slices[0].position)) > DUPLICATE_TOLERANCE {
+            scale(sub(last.position, slices[0].position), 1.0 / (count - 1) as f64)
+        } else {
+            scale(normal, spacing.or(first.thickness).unwrap_or(1.0))
+        };
+        let cos = (dot(step, normal).abs() / norm(step)).min(1.0);
+        let degrees = cos.acos().to_degrees();
+        if degrees > TILT_TOLERANCE {
+            issues.push(GeometryIssue::Tilt { degrees });
+        }
+
+        let (row, column) = direction_cosines(&first.orientation).unwrap_or(([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+        let i = scale(row, first.pixel_spacing[1]);
+        let j = scale(column, first.pixel_spacing[0]);
+        let origin = slices[0].position;
+        let affine = [
+            [i[0], j[0], step[0], origin[0]],
+            [i[1], j[1], step[1], origin[1]],
+            [i[2], j[2], step[2], origin[2]],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        SeriesGeometry {
+            series_uid: series_uid.to_string(),
+            dimensions: [first.columns as usize, first.rows as usize, count],
+            slices,
+            normal,
+            spacing,
+            affine,
+            issues,
+        }
+    }
+
+    pub fn is_volume(&self) -> bool {
+        !self.slices.is_empty() && !self.issues.iter().any(GeometryIssue::is_fatal)
+    }
+
+    /// 报错时定位该 series 的文件: 第一张切片, 没有切片时取第一个缺少几何的文件
+    pub fn path(&self) -> PathBuf {
+        let missing = self.issues.iter().find_map(|issue| match issue {
+            GeometryIssue::MissingGeometry { path, .. } => Some(path.clone()),
+            _ => None,
+        });
+        self.slices.first().map(|slice| slice.path.clone()).or(missing).unwrap_or_default()
+    }
+}
+
+fn identity() -> Affine {
+    [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] })
+}
+
+/// 读取文件头 (不含像素), 按 Series Instance UID 分组计算几何
+pub fn load_series(files: &[PathBuf]) -> Vec<SeriesGeometry> {
+    let headers: Vec<(String, Result<Slice, GeometryIssue>)> = files
+        .par_iter()
+        .filter_map(|file| {
+            match OpenFileOptions::new().read_until(tags::PIXEL_DATA).open_file(file) {
+                Ok(obj) => {
+                    let series_uid = obj
+                        .element_opt(tags::SERIES_INSTANCE_UID)
+                        .ok()
+                        .flatten()
+                        .and_then(|e| e.to_str().ok())
+                        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+                        .unwrap_or_default();
+                    let slice = Slice::from_object(file, &obj).map_err(|reason| GeometryIssue::MissingGeometry {
+                        path: file.clone(),
+                        reason,
+                    });
+                    Some((series_uid, slice))
+                }
+                Err(e) => {
+                    eprintln!("Skipping: {}", DcmToolsError::open(file, e));
+                    None
+                }
+            }
+        })
+        .collect();
+    let mut series: BTreeMap<String, (Vec<Slice>, Vec<GeometryIssue>)> = BTreeMap::new();
+    for (series_uid, slice) in headers {
+        let (slices, issues) = series.entry(series_uid).or_default();
+        match slice {
+            Ok(slice) => slices.push(slice),
+            Err(issue) => issues.push(issue),
+        }
+    }
+    series
+        .into_iter()
+        .map(|(series_uid, (slices, missing))| {
+            let mut geometry = SeriesGeometry::new(&series_uid, slices);
+            geometry.issues.splice(0..0, missing);
+            geometry
+        })
+        .collect()
+}
+
+/// 像素值的 Modality Rescale: 输出值 = stored * slope + intercept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rescale {
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+/// 由一个 series 组装的体数据
+#[derive(Debug, Clone)]
+pub struct Volume {
+    /// (列, 行, 切片)
+    pub dimensions: [usize; 3],
+    pub affine: Affine,
+    /// 原始存储值, 列变化最快, 其次是行, 最后是切片
+    pub data: Vec<i32>,
+    /// 每张切片的 Rescale
+    pub rescale: Vec<Rescale>,
+}
+
+impl Volume {
+    /// 按排序后的切片读取像素; 几何有致命问题或图像不是单帧单色时失败
+    pub fn assemble(geometry: &SeriesGeometry) -> Result<Volume, DcmToolsError> {
+        let Some(first) = geometry.slices.first() else {
+            return Err(DcmToolsError::geometry(geometry.path(), "no slices"));
+        };
+        if let Some(issue) = geometry.issues.iter().find(|issue| issue.is_fatal()) {
+            return Err(DcmToolsError::geometry(&first.path, format!("{:?}", issue)));
+        }
+        let slices: Vec<(Vec<i32>, Rescale)> = geometry
+            .slices
+            .par_iter()
+            .map(|slice| read_slice(&slice.path, geometry.dimensions[0] * geometry.dimensions[1]))
+            .collect::<Result<_, _>>()?;
+        let mut data = Vec::with_capacity(geometry.dimensions.iter().product());
+        let mut rescale = Vec::with_capacity(slices.len());
+        for (values, slice_rescale) in slices {
+            data.extend(values);
+            rescale.push(slice_rescale);
+        }
+        Ok(Volume {
+            dimensions: geometry.dimensions,
+            affine: geometry.affine,
+            data,
+            rescale,
+        })
+    }
+
+    /// 所有切片共用的 Rescale; 各切片不同时为 None
+    pub fn common_rescale(&self) -> Option<Rescale> {
+        let first = *self.rescale.first()?;
+        self.rescale.iter().all(|r| *r == first).then_some(first)
+    }
+
+    /// 经 Rescale 后的体素值 (如 CT 的 HU)
+    pub fn values(&self) -> Vec<f32> {
+        let plane = self.dimensions[0] * self.dimensions[1];
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                let Rescale { slope, intercept } = self.rescale[i / plane];
+                (f64::from(v) * slope + intercept) as f32
+            })
+            .collect()
+    }
+}
+
+fn read_slice(path: &Path, plane: usize) -> Result<(Vec<i32>, Rescale), DcmToolsError> {
+    let obj = dicom_object::open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+    let decoded = decode(path, &obj)?;
+    match decoded.photometric_interpretation() {
+        PhotometricInterpretation::Monochrome1 | PhotometricInterpretation::Monochrome2 => {}
+        other => {
+            return Err(DcmToolsError::geometry(path, format!("unsupported Photometric Interpretation {}", other)));
+        }
+    }
+    if decoded.number_of_frames() != 1 {
+        return Err(DcmToolsError::geometry(path, "multi-frame images are not supported"));
+    }
+    let values = stored_values(&decoded, 0).map_err(|e| DcmToolsError::transcode(path, e))?;
+    if values.len() != plane {
+        return Err(DcmToolsError::geometry(
+            path,
+            format!("expected {} pixels, found {}", plane, values.len()),
+        ));
+    }
+    let rescale = decoded
+        .rescale()
+        .ok()
+        .and_then(|r| r.first().copied())
+        .map(|r| Rescale { slope: r.slope, intercept: r.intercept })
+        .unwrap_or(Rescale { slope: 1.0, intercept: 0.0 });
+    Ok((values.into_iter().map(|v| v as i32).collect(), rescale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::{DataElement, VR, dicom_value};
+
+    const TEST_FILE: &str = "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm";
+
+    fn slice(sop_uid: &str, instance_number: i32, position: Vec3) -> Slice {
+        Slice {
+            path: PathBuf::from(format!("{}.dcm", sop_uid)),
+            sop_uid: sop_uid.to_string(),
+            instance_number: Some(instance_number),
+            position,
+            orientation: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            rows: 4,
+            columns: 4,
+            pixel_spacing: [0.5, 0.5],
+            thickness: Some(2.0),
+        }
+    }
+
+    #[test]
+    fn test_sort_by_position() {
+        // Instance Number 与位置顺序相反
+        let slices = (0..4).map(|i| slice(&format!("s{}", i), i, [0.0, 0.0, -2.5 * i as f64])).collect();
+        let geometry = SeriesGeometry::new("1.2.3", slices);
+        let order: Vec<&str> = geometry.slices.iter().map(|s| s.sop_uid.as_str()).collect();
+        assert_eq!(order, ["s3", "s2", "s1", "s0"]);
+        assert_eq!(geometry.spacing, Some(2.5));
+        assert!(geometry.issues.is_empty());
+        assert!(geometry.is_volume());
+        assert_eq!(geometry.dimensions, [4, 4, 4]);
+        assert_eq!(geometry.affine[0], [0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(geometry.affine[2], [0.0, 0.0, 2.5, -7.5]);
+    }
+
+    #[test]
+    fn test_geometry_issues() {
+        let positions = [0.0, 2.0, 4.0, 4.0, 10.0, 12.0];
+        let slices = positions.iter().enumerate().map(|(i, &z)| slice(&format!("s{}", i), i as i32, [0.0, 0.0, z])).collect();
+        let geometry = SeriesGeometry::new("1.2.3", slices);
+        assert_eq!(geometry.spacing, Some(2.0));
+        assert_eq!(
+            geometry.issues,
+            vec![
+                GeometryIssue::Duplicate { sop_uids: ["s2".into(), "s3".into()], location: 4.0 },
+                GeometryIssue::Gap { after: "s3".into(), before: "s4".into(), distance: 6.0, expected: 2.0 },
+            ]
+        );
+        assert!(!geometry.is_volume());
+
+        // 机架倾斜: 每层在 y 方向偏移
+        let slices = (0..3).map(|i| slice(&format!("t{}", i), i, [0.0, i as f64, 2.0 * i as f64])).collect();
+        let geometry = SeriesGeometry::new("1.2.3", slices);
+        let [GeometryIssue::Tilt { degrees }] = geometry.issues[..] else {
+            panic!("expected tilt: {:?}", geometry.issues);
+        };
+        assert!((degrees - 0.5f64.atan().to_degrees()).abs() < 1e-9);
+        assert!(geometry.is_volume());
+        assert_eq!(geometry.affine[1][2], 1.0);
+
+        // 方向不同
+        let mut slices: Vec<Slice> = (0..3).map(|i| slice(&format!("o{}", i), i, [0.0, 0.0, i as f64])).collect();
+        slices[2].orientation = [1.0, 0.0, 0.0, 0.0, 0.0, -1.0];
+        let geometry = SeriesGeometry::new("1.2.3", slices);
+        assert!(geometry.issues.contains(&GeometryIssue::MixedOrientation { sop_uid: "o2".into() }));
+        assert!(!geometry.is_volume());
+    }
+
+    #[test]
+    fn test_assemble_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let obj = dicom_object::open_file(TEST_FILE).unwrap();
+        let mut files = Vec::new();
+        for i in 0..3 {
+            let mut slice = obj.clone();
+            let z = format!("-150.200\\-160.000\\{}", -149.0 + 1.25 * i as f64);
+            slice.put(DataElement::new(tags::IMAGE_POSITION_PATIENT, VR::DS, dicom_value!(Str, z)));
+            slice.put(DataElement::new(tags::INSTANCE_NUMBER, VR::IS, dicom_value!(Str, (10 - i).to_string())));
+            let sop_uid = format!("1.2.3.{}", 10 + i);
+            slice.put(DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, dicom_value!(Str, sop_uid.clone())));
+            slice.update_meta(|meta| meta.media_storage_sop_instance_uid = sop_uid);
+            let file = dir.path().join(format!("{}.dcm", i));
+            slice.write_to_file(&file).unwrap();
+            files.push(file);
+        }
+        let geometry = load_series(&files);
+        assert_eq!(geometry.len(), 1);
+        let geometry = &geometry[0];
+        assert!(geometry.is_volume(), "{:?}", geometry.issues);
+        assert_eq!(geometry.slices[0].sop_uid, "1.2.3.10");
+        assert_eq!(geometry.path(), files[0]);
+        assert_eq!(geometry.spacing, Some(1.25));
+        assert_eq!(geometry.affine[0][0], 0.625);
+        assert_eq!(geometry.affine[2][3], -149.0);
+
+        let volume = Volume::assemble(geometry).unwrap();
+        assert_eq!(volume.dimensions, [512, 512, 3]);
+        assert_eq!(volume.data.len(), 512 * 512 * 3);
+        assert_eq!(volume.common_rescale(), Some(Rescale { slope: 1.0, intercept: -1024.0 }));
+        let values = volume.values();
+        assert_eq!(values[0], volume.data[0] as f32 - 1024.0);
+        assert_eq!(&volume.data[..512 * 512], &volume.data[512 * 512 * 2..]);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::dcmobj::{BatchSummary, json_key_tag, walk_directory};
+use crate::error::DcmToolsError;
+use crate::image_info::ImageInfo;
+use crate::patient_info::PatientInfo;
+use crate::series_info::SeriesInfo;
+use crate::study_info::StudyInfo;
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::DefaultDicomObject;
+use dicom_object::OpenFileOptions;
+use rayon::prelude::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// 一个实例在索引中的记录: 文件路径 + 各级提取的属性
+#[derive(Debug, Clone)]
+pub struct IndexRecord {
+    pub path: PathBuf,
+    pub sop_class_uid: String,
+    pub transfer_syntax: String,
+    pub patient: PatientInfo,
+    pub study: StudyInfo,
+    pub series: SeriesInfo,
+    pub image: ImageInfo,
+    /// 上面各级属性按 tag 展开, 用于查询匹配
+    attributes: HashMap<Tag, String>,
+}
+
+/// 将 DicomTagFields 的 JSON ("xGGGGEEEE" -> 值) 展开到 tag -> 字符串
+fn collect_attributes(value: Value, attributes: &mut HashMap<Tag, String>) {
+    if let Value::Object(map) = value {
+        for (key, value) in map {
+            let Some(tag) = json_key_tag(&key) else { continue };
+            let text = match value {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            attributes.insert(tag, text);
+        }
+    }
+}
+
+impl IndexRecord {
+    pub fn new(path: &Path, obj: &DefaultDicomObject) -> Self {
+        let trim = |s: &str| s.trim_end_matches(['\0', ' ']).to_string();
+        Self::from_parts(
+            path,
+            trim(obj.meta().media_storage_sop_class_uid()),
+            trim(obj.meta().transfer_syntax()),
+            PatientInfo::new(obj),
+            StudyInfo::new(obj),
+            SeriesInfo::new(obj),
+            ImageInfo::new(obj),
+        )
+    }
+
+    /// 由已提取的各级信息组装记录 (如从索引数据库读出)
+    pub fn from_parts(
+        path: &Path,
+        sop_class_uid: String,
+        transfer_syntax: String,
+        patient: PatientInfo,
+        study: StudyInfo,
+        series: SeriesInfo,
+        image: ImageInfo,
+    ) -> Self {
+        let mut attributes = HashMap::new();
+        for value in [
+            serde_json::to_value(&patient),
+            serde_json::to_value(&study),
+            serde_json::to_value(&series),
+            serde_json::to_value(&image),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            collect_attributes(value, &mut attributes);
+        }
+        attributes.insert(tags::SOP_CLASS_UID, sop_class_uid.clone());
+        Self {
+            path: path.to_path_buf(),
+            sop_class_uid,
+            transfer_syntax,
+            patient,
+            study,
+            series,
+            image,
+            attributes,
+        }
+    }
+
+    /// 读取文件 (不含像素数据) 生成记录
+    pub fn open(path: &Path) -> Result<Self, DcmToolsError> {
+        let obj = OpenFileOptions::new()
+            .read_until(tags::PIXEL_DATA)
+            .open_file(path)
+            .map_err(|e| DcmToolsError::open(path, e))?;
+        Ok(Self::new(path, &obj))
+    }
+
+    /// 文件被复制或移动后的记录
+    pub fn with_path(&self, path: PathBuf) -> Self {
+        Self {
+            path,
+            ..self.clone()
+        }
+    }
+
+    /// 按 tag 取属性值, 索引中没有的属性返回 None
+    pub fn get(&self, tag: Tag) -> Option<&str> {
+        self.attributes.get(&tag).map(String::as_str)
+    }
+}
+
+/// 已存储文件的元数据索引, 以 SOP Instance UID 为键
+#[derive(Debug, Default)]
+pub struct MetadataIndex {
+    records: RwLock<BTreeMap<String, IndexRecord>>,
+}
+
+impl MetadataIndex {
+    /// 扫描目录建立索引, 无法读取的文件记入 BatchSummary
+    pub fn scan(dir: &Path) -> Result<(Self, BatchSummary), DcmToolsError> {
+        let files = walk_directory(dir).map_err(|e| DcmToolsError::read(dir, e))?;
+        let records: Vec<Result<IndexRecord, DcmToolsError>> =
+            files.par_iter().map(|file| IndexRecord::open(file)).collect();
+        let index = MetadataIndex::default();
+        let mut results = Vec::with_capacity(records.len());
+        for record in records {
+            results.push(record.map(|record| index.insert(record)));
+        }
+        Ok((index, BatchSummary::from_results(results)))
+    }
+
+    /// 由已有记录 (如索引数据库) 建立
+    pub fn from_records(records: impl IntoIterator<Item = IndexRecord>) -> Self {
+        let index = MetadataIndex::default();
+        for record in records {
+            index.insert(record);
+        }
+        index
+    }
+
+    /// 加入或替换一条记录
+    pub fn insert(&self, record: IndexRecord) {
+        let mut records = self.records.write().unwrap_or_else(|e| e.into_inner());
+        records.insert(record.image.sop_uid.clone(), record);
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// 满足条件的记录 (克隆), 按 SOP Instance UID 排序
+    pub fn filter(&self, predicate: impl Fn(&IndexRecord) -> bool) -> Vec<IndexRecord> {
+        self.records
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter(|record| predicate(record))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_test_data() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::copy(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+            dir.path().join("a.dcm"),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("readme.txt"), "not dicom").unwrap();
+        let (index, summary) = MetadataIndex::scan(dir.path()).unwrap();
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(summary.failed(), 1);
+        assert_eq!(index.len(), 1);
+        let records = index.filter(|_| true);
+        let record = &records[0];
+        assert_eq!(record.get(tags::PATIENT_ID), Some(record.patient.id.as_str()));
+        assert_eq!(record.get(tags::SERIES_INSTANCE_UID), Some(record.series.series_uid.as_str()));
+        assert_eq!(record.get(tags::SOP_CLASS_UID), Some(record.sop_class_uid.as_str()));
+        // 数字字段也以字符串保存
+        assert_eq!(record.get(tags::ROWS), Some(record.image.rows.to_string().as_str()));
+    }
+}
@@ -0,0 +1,393 @@
+use crate::dcmobj::{BatchSummary, ManifestEntry, walk_directory};
+use crate::error::DcmToolsError;
+use crate::index::IndexRecord;
+use dicom::dictionary_std::tags;
+use dicom_object::OpenFileOptions;
+use rayon::prelude::*;
+use rusqlite::{Connection, params};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS patients (
+    patient_id TEXT PRIMARY KEY,
+    info TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS studies (
+    study_uid TEXT PRIMARY KEY,
+    patient_id TEXT NOT NULL,
+    info TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS series (
+    series_uid TEXT PRIMARY KEY,
+    study_uid TEXT NOT NULL,
+    info TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS instances (
+    path TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    mtime INTEGER NOT NULL,
+    sop_uid TEXT,
+    series_uid TEXT,
+    sop_class_uid TEXT,
+    transfer_syntax TEXT,
+    info TEXT,
+    manifest TEXT,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS instances_series ON instances (series_uid);
+";
+
+/// 文件大小与修改时间 (纳秒), 两者都未变化的文件不再重新解析
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: i64,
+    mtime: i64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> std::io::Result<Self> {
+        let meta = std::fs::metadata(path)?;
+        let mtime = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or(0);
+        Ok(Self {
+            size: meta.len() as i64,
+            mtime,
+        })
+    }
+}
+
+/// 一次增量更新的统计
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndexStats {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// 解析一个新增或修改的文件得到的结果
+struct Parsed {
+    path: PathBuf,
+    stamp: FileStamp,
+    is_new: bool,
+    result: Result<(IndexRecord, ManifestEntry), DcmToolsError>,
+}
+
+/// 持久化的元数据索引 (SQLite): patient / study / series / instance 四张表,
+/// instance 记录文件路径、大小与修改时间
+pub struct IndexDb {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl IndexDb {
+    /// 打开或创建数据库
+    pub fn open(path: &Path) -> Result<Self, DcmToolsError> {
+        let conn = Connection::open(path).map_err(|e| DcmToolsError::database(path, e))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| DcmToolsError::database(path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            conn,
+        })
+    }
+
+    fn error(&self, e: impl Into<crate::error::BoxError>) -> DcmToolsError {
+        DcmToolsError::database(&self.path, e)
+    }
+
+    /// 数据库中位于 dir 下的文件及其 stamp
+    fn stamps(&self, dir: &Path) -> Result<HashMap<PathBuf, FileStamp>, DcmToolsError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, size, mtime FROM instances")
+            .map_err(|e| self.error(e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let path: String = row.get(0)?;
+                Ok((PathBuf::from(path), FileStamp { size: row.get(1)?, mtime: row.get(2)? }))
+            })
+            .map_err(|e| self.error(e))?;
+        let mut stamps = HashMap::new();
+        for row in rows {
+            let (path, stamp) = row.map_err(|e| self.error(e))?;
+            if path.starts_with(dir) {
+                stamps.insert(path, stamp);
+            }
+        }
+        Ok(stamps)
+    }
+
+    /// 扫描目录并增量更新: 只解析新增或大小/修改时间变化的文件, 删除已不存在的文件;
+    /// 本次解析失败的文件记入 BatchSummary, 未变化前不再重试
+    pub fn update(&mut self, dir: &Path) -> Result<(IndexStats, BatchSummary), DcmToolsError> {
+        let dir = dir.canonicalize().map_err(|e| DcmToolsError::read(dir, e))?;
+        let files = walk_directory(&dir).map_err(|e| DcmToolsError::read(&dir, e))?;
+        let known = self.stamps(&dir)?;
+        let mut stats = IndexStats::default();
+        let mut results = Vec::new();
+        let mut changed = Vec::new();
+        for file in &files {
+            let stamp = match FileStamp::of(file) {
+                Ok(stamp) => stamp,
+                Err(e) => {
+                    results.push(Err(DcmToolsError::read(file, e)));
+                    continue;
+                }
+            };
+            match known.get(file) {
+                Some(old) if *old == stamp => stats.unchanged += 1,
+                old => changed.push((file, stamp, old.is_none())),
+            }
+        }
+        let parsed: Vec<Parsed> = changed
+            .into_par_iter()
+            .map(|(file, stamp, is_new)| Parsed {
+                path: file.clone(),
+                stamp,
+                is_new,
+                result: OpenFileOptions::new()
+                    .read_until(tags::PIXEL_DATA)
+                    .open_file(file)
+                    .map(|obj| (IndexRecord::new(file, &obj), ManifestEntry::new(file, &obj)))
+                    .map_err(|e| DcmToolsError::open(file, e)),
+            })
+            .collect();
+
+        let path = self.path.clone();
+        let db_error = |e: rusqlite::Error| DcmToolsError::database(&path, e);
+        let tx = self.conn.transaction().map_err(db_error)?;
+        for item in parsed {
+            if item.is_new {
+                stats.added += 1;
+            } else {
+                stats.updated += 1;
+            }
+            match item.result {
+                Ok((record, entry)) => {
+                    write_record(&tx, &item.path, item.stamp, &record, &entry).map_err(db_error)?;
+                    results.push(Ok(()));
+                }
+                Err(e) => {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO instances (path, size, mtime, error) VALUES (?1, ?2, ?3, ?4)",
+                        params![item.path.to_string_lossy(), item.stamp.size, item.stamp.mtime, e.to_string()],
+                    )
+                    .map_err(db_error)?;
+                    results.push(Err(e));
+                }
+            }
+        }
+        let present: HashSet<&PathBuf> = files.iter().collect();
+        for path in known.keys().filter(|path| !present.contains(path)) {
+            tx.execute("DELETE FROM instances WHERE path = ?1", params![path.to_string_lossy()])
+                .map_err(db_error)?;
+            stats.removed += 1;
+        }
+        // 删除已没有实例的 series / study / patient
+        tx.execute_batch(
+            "DELETE FROM series WHERE series_uid NOT IN (SELECT series_uid FROM instances WHERE series_uid IS NOT NULL);
+             DELETE FROM studies WHERE study_uid NOT IN (SELECT study_uid FROM series);
+             DELETE FROM patients WHERE patient_id NOT IN (SELECT patient_id FROM studies);",
+        )
+        .map_err(db_error)?;
+        tx.commit().map_err(db_error)?;
+        Ok((stats, BatchSummary::from_results(results)))
+    }
+
+    /// dir 下 (None 为全部) 可读实例的记录
+    pub fn records(&self, dir: Option<&Path>) -> Result<Vec<IndexRecord>, DcmToolsError> {
+        let dir = canonical_dir(dir)?;
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT i.path, i.sop_class_uid, i.transfer_syntax, p.info, st.info, se.info, i.info
+                 FROM instances i
+                 JOIN series se ON se.series_uid = i.series_uid
+                 JOIN studies st ON st.study_uid = se.study_uid
+                 JOIN patients p ON p.patient_id = st.patient_id
+                 WHERE i.error IS NULL
+                 ORDER BY i.path",
+            )
+            .map_err(|e| self.error(e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let texts: Vec<String> = (0..7).map(|i| row.get(i)).collect::<Result<_, _>>()?;
+                Ok(texts)
+            })
+            .map_err(|e| self.error(e))?;
+        let mut records = Vec::new();
+        for row in rows {
+            let row = row.map_err(|e| self.error(e))?;
+            let path = PathBuf::from(&row[0]);
+            if dir.as_ref().is_some_and(|dir| !path.starts_with(dir)) {
+                continue;
+            }
+            records.push(IndexRecord::from_parts(
+                &path,
+                row[1].clone(),
+                row[2].clone(),
+                serde_json::from_str(&row[3]).map_err(|e| self.error(e))?,
+                serde_json::from_str(&row[4]).map_err(|e| self.error(e))?,
+                serde_json::from_str(&row[5]).map_err(|e| self.error(e))?,
+                serde_json::from_str(&row[6]).map_err(|e| self.error(e))?,
+            ));
+        }
+        Ok(records)
+    }
+
+    /// dir 下 (None 为全部) 实例的清单条目, 用于生成 JSON 清单
+    pub fn manifest_entries(&self, dir: Option<&Path>) -> Result<Vec<ManifestEntry>, DcmToolsError> {
+        let dir = canonical_dir(dir)?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, series_uid, manifest FROM instances WHERE error IS NULL ORDER BY path")
+            .map_err(|e| self.error(e))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+            .map_err(|e| self.error(e))?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (path, series_uid, manifest) = row.map_err(|e| self.error(e))?;
+            if dir.as_ref().is_some_and(|dir| !Path::new(&path).starts_with(dir)) {
+                continue;
+            }
+            let mut manifest: Value = serde_json::from_str(&manifest).map_err(|e| self.error(e))?;
+            entries.push(ManifestEntry {
+                path: PathBuf::from(path),
+                series_uid,
+                series_json: manifest["series"].take(),
+                sop_json: manifest["sop"].take(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+fn canonical_dir(dir: Option<&Path>) -> Result<Option<PathBuf>, DcmToolsError> {
+    dir.map(|dir| dir.canonicalize().map_err(|e| DcmToolsError::read(dir, e)))
+        .transpose()
+}
+
+/// 写入一个实例及其所属的 series / study / patient
+fn write_record(
+    tx: &rusqlite::Transaction,
+    path: &Path,
+    stamp: FileStamp,
+    record: &IndexRecord,
+    entry: &ManifestEntry,
+) -> rusqlite::Result<()> {
+    let to_json = |value: Result<String, serde_json::Error>| {
+        value.map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    };
+    tx.execute(
+        "INSERT OR REPLACE INTO patients (patient_id, info) VALUES (?1, ?2)",
+        params![record.patient.id, to_json(serde_json::to_string(&record.patient))?],
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO studies (study_uid, patient_id, info) VALUES (?1, ?2, ?3)",
+        params![record.study.study_uid, record.patient.id, to_json(serde_json::to_string(&record.study))?],
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO series (series_uid, study_uid, info) VALUES (?1, ?2, ?3)",
+        params![record.series.series_uid, record.study.study_uid, to_json(serde_json::to_string(&record.series))?],
+    )?;
+    let manifest = json!({ "series": entry.series_json, "sop": entry.sop_json });
+    tx.execute(
+        "INSERT OR REPLACE INTO instances
+         (path, size, mtime, sop_uid, series_uid, sop_class_uid, transfer_syntax, info, manifest, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, NULL)",
+        params![
+            path.to_string_lossy(),
+            stamp.size,
+            stamp.mtime,
+            record.image.sop_uid,
+            record.series.series_uid,
+            record.sop_class_uid,
+            record.transfer_syntax,
+            to_json(serde_json::to_string(&record.image))?,
+            manifest.to_string(),
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcmobj::{ManifestOptions, build_manifest, generate_json_file};
+    use std::time::{Duration, SystemTime};
+
+    /// 某个文件在索引中记录的解析错误
+    fn file_error(db: &IndexDb, path: &Path) -> Option<String> {
+        db.conn
+            .query_row("SELECT error FROM instances WHERE path = ?1", params![path.to_string_lossy()], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    const TEST_FILE: &str = "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm";
+
+    #[test]
+    fn test_incremental_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path().join("data");
+        std::fs::create_dir(&data).unwrap();
+        std::fs::copy(TEST_FILE, data.join("a.dcm")).unwrap();
+        std::fs::write(data.join("readme.txt"), "not dicom").unwrap();
+        let mut db = IndexDb::open(&dir.path().join("index.sqlite")).unwrap();
+
+        let (stats, summary) = db.update(&data).unwrap();
+        assert_eq!(stats, IndexStats { added: 2, ..IndexStats::default() });
+        assert_eq!((summary.succeeded, summary.failed()), (1, 1));
+        let readme = data.canonicalize().unwrap().join("readme.txt");
+        assert!(file_error(&db, &readme).is_some());
+
+        // 未变化的文件 (包括无法解析的) 不再重新解析
+        let (stats, summary) = db.update(&data).unwrap();
+        assert_eq!(stats, IndexStats { unchanged: 2, ..IndexStats::default() });
+        assert_eq!((summary.succeeded, summary.failed()), (0, 0));
+
+        // 修改时间变化后重新解析, 删除的文件从索引中移除
+        let file = std::fs::File::options().write(true).open(data.join("a.dcm")).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        std::fs::remove_file(data.join("readme.txt")).unwrap();
+        let (stats, _) = db.update(&data).unwrap();
+        assert_eq!(stats, IndexStats { updated: 1, removed: 1, ..IndexStats::default() });
+
+        let records = db.records(Some(&data)).unwrap();
+        assert_eq!(records.len(), 1);
+        let original = IndexRecord::open(std::path::Path::new(TEST_FILE)).unwrap();
+        assert_eq!(records[0].image.sop_uid, original.image.sop_uid);
+        assert_eq!(records[0].get(tags::PATIENT_ID), original.get(tags::PATIENT_ID));
+        assert_eq!(records[0].get(tags::ROWS), original.get(tags::ROWS));
+        assert!(db.records(Some(dir.path())).unwrap().len() == 1);
+
+        // 由索引生成的清单与直接扫描目录的结果一致
+        let options = ManifestOptions {
+            output: None,
+            hiscode: "H".to_string(),
+            token: "T".to_string(),
+            ttl: Duration::from_secs(60),
+            thumbnails: None,
+            follow_dicomdir: false,
+        };
+        let from_db = build_manifest(db.manifest_entries(Some(&data)).unwrap(), &options).unwrap();
+        let scanned = generate_json_file(&data, &options).unwrap();
+        assert_eq!(from_db["seriesData"], scanned["seriesData"]);
+
+        std::fs::remove_file(data.join("a.dcm")).unwrap();
+        let (stats, _) = db.update(&data).unwrap();
+        assert_eq!(stats.removed, 1);
+        assert!(db.records(None).unwrap().is_empty());
+        let series: i64 = db.conn.query_row("SELECT COUNT(*) FROM series", [], |row| row.get(0)).unwrap();
+        assert_eq!(series, 0);
+    }
+}
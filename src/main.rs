@@ -1,100 +1,77 @@
+mod anonymize;
+mod association;
+mod commands;
 mod dcm_meta;
 mod dcmobj;
 mod dicom_info;
+mod dicom_json;
+mod dicomdir;
+mod dimse;
+mod edit;
+mod error;
+mod geometry;
 mod image_info;
+mod index;
+mod index_db;
+mod multiframe;
+mod nifti;
 mod patient_info;
+mod query;
+mod render;
+mod scp;
+mod scu;
 mod series_info;
+mod storage;
 mod study_info;
- 
+mod thumbnail;
+mod tls;
+mod transcode;
+mod uid_map;
+mod validate;
 
-use crate::dcmobj::{file_exists, generate_json_file};
+use crate::commands::Commands;
 use clap::Parser;
-use std::path::PathBuf;
+use std::process::ExitCode;
 use std::time::Instant;
-use dicom_codegen::log_execution;
-use crate::dcm_meta::DcmEntityBaseMeta;
-#[log_execution]
-fn add_numbers(a: i32, b: i32) {
-    let result = a + b;
-    println!("The result of {} + {} is {}", a, b, result);
-}
 
 #[derive(Debug, Parser)]
-#[command(version)]
+#[command(version, about = "DICOM 文件处理工具")]
 struct Application {
     /// Verbose mode
-    #[arg(short = 'v', long = "verbose")]
+    #[arg(short = 'v', long = "verbose", global = true)]
     verbose: bool,
-    #[arg(short = 'i', long = "dicom-file", required = true)]
-    input_file: PathBuf,
-    #[arg(short = 'o', long = "json-file", required = true)]
-    output_file: PathBuf,
+    #[command(subcommand)]
+    command: Commands,
 }
-fn main() {
-    println!("Hello, world!");
-    let app = Application::parse();
-    println!("{:?}", app);
-
-    if !file_exists(&app.input_file) {
-        println!("{}: File  not exists", app.input_file.to_str().unwrap());
-        return;
-    }
-    println!("{}: File  exists", app.input_file.to_str().unwrap());
-    println!("{}: target file ", app.output_file.to_str().unwrap());
-    // let start = Instant::now();
-    // change_transfer_syntax_iter(
-    //     &app.input_file,
-    //     &app.output_file,
-    //     gdcm_conv::TransferSyntax::JPEG2000Lossless,
-    //     None,
-    // )
-    // .unwrap();
-    // let duration = start.elapsed();
-    // println!("耗时: {} 秒", duration.as_secs_f64());
-    // println!("耗时: {} 毫秒", duration.as_millis());
-
-    let start2 = Instant::now();
-    let di_com = PathBuf::from("/home/dhz/jpdata/CDSS/89269-1.2.156.112605.0.1685486876.2025061710152134339.2.1.1");
-    generate_json_file(&di_com).unwrap();
-    let duration2 = start2.elapsed();
-    println!("JSON耗时: {} 秒", duration2.as_secs_f64());
-    println!("JSON耗时: {} 毫秒", duration2.as_millis());
-    add_numbers(1, 2);
-
 
+fn main() -> ExitCode {
+    let app = Application::parse();
+    if app.verbose {
+        println!("{:?}", app);
+    }
 
+    let start = Instant::now();
+    let result = app.command.run();
+    let duration = start.elapsed();
+    match &result {
+        Ok(summary) => {
+            eprintln!(
+                "[{}] succeeded: {}, failed: {}, 耗时: {} 毫秒",
+                app.command.name(),
+                summary.succeeded,
+                summary.failed(),
+                duration.as_millis()
+            );
+            // io 类错误可以重试, 单独列出便于脚本重新处理
+            for e in summary.errors.iter().filter(|e| e.is_retryable()) {
+                eprintln!("[{}] retryable: {}", app.command.name(), e.path().display());
+            }
+        }
+        Err(e) => eprintln!("[{}] error: {}", app.command.name(), e),
+    }
+    commands::exit_code(&result)
 }
 
-// fn convert_ts(p0: &PathBuf, output_path: String) -> Result<(), Box<dyn std::error::Error>> {
-//     // 步骤 1: 读取 DICOM 文件
-//     let obj = OpenFileOptions::new()
-//         .read_until(tags::PIXEL_DATA)
-//         .open_file(p0)
-//         .unwrap();
-//     // clone 一份对象用于构建新文件
-//     let meta = obj.meta().clone();
-//
-//     let sop_class_uid = meta.media_storage_sop_class_uid;
-//     let sop_inst_uid = meta.media_storage_sop_instance_uid;
-//
-//     // 步骤 3: 创建一个新的 DICOM 文件对象并设置传输语法
-//     let file_obj = FileMetaTableBuilder::new()
-//         .media_storage_sop_class_uid(sop_class_uid)
-//         .media_storage_sop_instance_uid(sop_inst_uid)
-//         .transfer_syntax(dicom_transfer_syntax_registry::entries::EXPLICIT_VR_LITTLE_ENDIAN.uid())
-//         .implementation_class_uid(IMPLEMENTATION_CLASS_UID)
-//         .implementation_version_name(IMPLEMENTATION_VERSION_NAME)
-//         .build()
-//         .unwrap();
-//
-//     // 注意 with_meta 返回新对象（有的版本直接返回 Result）
-//     let file_obj = DefaultDicomObject::new_empty_with_meta(file_obj);
-//     file_obj.write_to_file(output_path)?;
-//     println!("传输语法已修改并保存到  ");
-//     Ok(())
-// }
-//
-
 #[cfg(test)]
 mod tests {
     use crate::Application;
@@ -105,9 +82,181 @@ mod tests {
         Application::command().debug_assert();
     }
 
+    #[test]
+    fn parse_subcommands() {
+        let app = Application::try_parse_from(["dcm-tools", "json", "-i", "./test_data"]).unwrap();
+        assert!(matches!(app.command, Commands::Json(_)));
+        assert_eq!(app.command.name(), "json");
+
+        let app = Application::try_parse_from([
+            "dcm-tools", "-v", "transcode", "-i", "./in", "-o", "./out",
+        ])
+        .unwrap();
+        assert!(app.verbose);
+        match app.command {
+            Commands::Transcode(args) => {
+                assert_eq!(args.input, PathBuf::from("./in"));
+                assert_eq!(args.output, PathBuf::from("./out"));
+            }
+            _ => panic!("expected transcode"),
+        }
+
+        // 缺少子命令或必填参数时报错
+        assert!(Application::try_parse_from(["dcm-tools"]).is_err());
+        assert!(Application::try_parse_from(["dcm-tools", "dump"]).is_err());
+    }
+
+    #[test]
+    fn parse_transcode_pipeline() {
+        let app = Application::try_parse_from([
+            "dcm-tools", "transcode", "-i", "a.dcm", "-o", "b.dcm", "-t", "1.2.840.10008.1.2.1",
+            "-p", "YBR_FULL_422", "--then", "j2k", "--quality", "50",
+        ])
+        .unwrap();
+        match app.command {
+            Commands::Transcode(args) => {
+                let options = args.options().unwrap();
+                assert_eq!(options.output_uid(), Some("1.2.840.10008.1.2.4.91"));
+                assert!(matches!(
+                    options.second_transfer_syntax,
+                    gdcm_conv::TransferSyntax::JPEG2000(50, 0, 0, false)
+                ));
+            }
+            _ => panic!("expected transcode"),
+        }
+        assert!(
+            Application::try_parse_from(["dcm-tools", "transcode", "-i", "a", "-o", "b", "-t", "xyz"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_tls() {
+        // TLS 证书参数需要 --tls, 证书与私钥必须同时指定
+        assert!(Application::try_parse_from(["dcm-tools", "echo", "--tls-ca", "ca.pem"]).is_err());
+        assert!(Application::try_parse_from(["dcm-tools", "echo-scp", "--tls", "--tls-cert", "a.pem"]).is_err());
+    }
+
+    #[test]
+    fn parse_dicomdir() {
+        // DICOMDIR 重命名模式需要输出目录
+        assert!(Application::try_parse_from(["dcm-tools", "dicomdir", "-i", "a", "--rename"]).is_err());
+        assert!(Application::try_parse_from(["dcm-tools", "dicomdir", "-i", "a", "--file-set-id", "lower"]).is_err());
+    }
+
+    #[test]
+    fn parse_anonymize() {
+        assert!(
+            Application::try_parse_from([
+                "dcm-tools", "anonymize", "-i", "a", "-o", "b",
+                "--option", "retain-longitudinal-modified-dates", "--date-shift", "-30",
+            ])
+            .is_ok()
+        );
+        assert!(Application::try_parse_from(["dcm-tools", "anonymize", "-i", "a", "-o", "b", "--option", "keep-all"]).is_err());
+    }
+
+    #[test]
+    fn parse_remap_uids() {
+        assert!(Application::try_parse_from(["dcm-tools", "remap-uids", "-i", "a", "-o", "b", "--reverse"]).is_err());
+    }
+
+    #[test]
+    fn parse_edit() {
+        assert!(
+            Application::try_parse_from(["dcm-tools", "edit", "-i", "a", "--set", "0010,0020=X", "--delete", "PatientName"])
+                .is_ok()
+        );
+        assert!(Application::try_parse_from(["dcm-tools", "edit", "-i", "a", "--set", "0010,0020"]).is_err());
+        assert!(Application::try_parse_from(["dcm-tools", "edit", "-i", "a", "--copy-from", "b.dcm"]).is_err());
+    }
+
+    #[test]
+    fn parse_validate() {
+        assert!(Application::try_parse_from(["dcm-tools", "validate", "-i", "a", "--report", "r.json"]).is_ok());
+    }
+
+    #[test]
+    fn parse_render() {
+        assert!(
+            Application::try_parse_from(["dcm-tools", "render", "-i", "a", "-o", "b", "--window=-600,1500", "--bits", "16"])
+                .is_ok()
+        );
+        // --window 与 --preset 互斥, 只支持 8/16 位输出
+        assert!(Application::try_parse_from(["dcm-tools", "render", "-i", "a", "-o", "b", "--window", "40,400", "--preset", "lung"]).is_err());
+        assert!(Application::try_parse_from(["dcm-tools", "render", "-i", "a", "-o", "b", "--bits", "12"]).is_err());
+    }
+
+    #[test]
+    fn parse_geometry() {
+        assert!(Application::try_parse_from(["dcm-tools", "geometry", "-i", "a", "--volume", "v"]).is_ok());
+    }
+
+    #[test]
+    fn parse_to_nifti() {
+        assert!(Application::try_parse_from(["dcm-tools", "to-nifti", "-i", "a", "-o", "b.nii.gz"]).is_ok());
+    }
+
+    #[test]
+    fn parse_split_merge_frames() {
+        assert!(Application::try_parse_from(["dcm-tools", "split-frames", "-i", "a", "-o", "b"]).is_ok());
+        assert!(Application::try_parse_from(["dcm-tools", "merge-frames", "-i", "a", "-o", "b.dcm"]).is_ok());
+    }
+
+    #[test]
+    fn parse_dicom_json() {
+        // --bulk-threshold 与 --bulk-dir 必须同时指定
+        assert!(
+            Application::try_parse_from(["dcm-tools", "dicom-json", "-i", "a", "--bulk-threshold", "1024"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_follow_dicomdir() {
+        let app = Application::try_parse_from(["dcm-tools", "send", "-i", "./cd", "--follow-dicomdir"]).unwrap();
+        match app.command {
+            Commands::Send(args) => assert!(args.follow_dicomdir),
+            _ => panic!("expected send"),
+        }
+        // 默认列出目录中的全部文件
+        let app = Application::try_parse_from(["dcm-tools", "anonymize", "-i", "a", "-o", "b"]).unwrap();
+        match app.command {
+            Commands::Anonymize(args) => assert!(!args.follow_dicomdir),
+            _ => panic!("expected anonymize"),
+        }
+        // --db 由索引扫描全部文件, 不能同时指定
+        assert!(
+            Application::try_parse_from(["dcm-tools", "json", "-i", "a", "--db", "i.db", "--follow-dicomdir"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn exit_code_for_results() {
+        use crate::commands::exit_code;
+        use crate::dcmobj::BatchSummary;
+        use crate::error::DcmToolsError;
+        let ok = BatchSummary::ok(3);
+        let partial = BatchSummary::from_results([
+            Ok(()),
+            Ok(()),
+            Err(DcmToolsError::not_found("a.dcm")),
+        ]);
+        let none = BatchSummary::from_results([
+            Err(DcmToolsError::not_found("a.dcm")),
+            Err(DcmToolsError::not_found("b.dcm")),
+        ]);
+        assert_eq!(exit_code(&Ok(ok)), ExitCode::from(commands::EXIT_OK));
+        assert_eq!(exit_code(&Ok(partial)), ExitCode::from(commands::EXIT_PARTIAL));
+        assert_eq!(exit_code(&Ok(none)), ExitCode::from(commands::EXIT_FAILURE));
+    }
+
     use super::*;
+    use crate::dcmobj::file_exists;
     use crate::dcm_meta::{DcmMapMeta, DcmMeta};
     use crate::dcmobj::convert_ts_with_gdcm;
+    use crate::transcode::TranscodeOptions;
     use crate::dicom_info::DicomInfo;
     use crate::image_info::ImageInfo;
     use crate::patient_info::PatientInfo;
@@ -151,6 +300,152 @@ mod tests {
         assert!(file_exists(&dir.path().to_path_buf()));
     }
 
+    #[test]
+    fn test_parse_ttl() {
+        use crate::commands::parse_ttl;
+        use std::time::Duration;
+        assert_eq!(parse_ttl("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_ttl("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_ttl("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert_eq!(parse_ttl("7d").unwrap(), Duration::from_secs(7 * 86400));
+        assert!(parse_ttl("1w").is_err());
+        assert!(parse_ttl("h").is_err());
+    }
+
+    #[test]
+    fn test_generate_json_file_returns_manifest() {
+        use crate::dcmobj::{ManifestOptions, generate_json_file};
+        use chrono::{Local, TimeZone};
+        use std::time::Duration;
+
+        let options = ManifestOptions {
+            output: None,
+            hiscode: "89269".to_string(),
+            token: "t0ken".to_string(),
+            ttl: Duration::from_secs(3600),
+            thumbnails: None,
+            follow_dicomdir: false,
+        };
+        let now = Local.with_ymd_and_hms(2025, 6, 20, 12, 5, 16).unwrap();
+        assert_eq!(options.expires_at(now), "2025-06-20T13-05-16");
+
+        let input = tempdir().unwrap();
+        std::fs::copy(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+            input.path().join("1.dcm"),
+        )
+        .unwrap();
+        // 非 DICOM 文件应被跳过, 不影响清单
+        std::fs::write(input.path().join("readme.txt"), "not dicom").unwrap();
+        let input = input.path().to_path_buf();
+        let manifest = generate_json_file(&input, &options).unwrap();
+        assert_eq!(manifest["hiscode"], "89269");
+        assert_eq!(manifest["token"], "t0ken");
+        assert!(manifest["expires"].as_str().is_some_and(|s| !s.is_empty()));
+        let series = manifest["seriesData"].as_array().unwrap();
+        assert_eq!(series.len(), 1);
+        assert!(!series[0]["sopData"].as_array().unwrap().is_empty());
+
+        // 指定输出文件时写入磁盘
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("manifest.json");
+        let options = ManifestOptions {
+            output: Some(output.clone()),
+            ..options
+        };
+        let manifest = generate_json_file(&input, &options).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(written, manifest);
+    }
+
+    #[test]
+    fn test_generate_json_file_thumbnails() {
+        use crate::dcmobj::{ManifestOptions, generate_json_file};
+        use crate::thumbnail::{DEFAULT_THUMBNAIL_SIZE, ThumbnailFormat, ThumbnailOptions, ThumbnailTarget};
+        use std::time::Duration;
+
+        let input = tempdir().unwrap();
+        std::fs::copy(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+            input.path().join("1.dcm"),
+        )
+        .unwrap();
+        let input = input.path().to_path_buf();
+        let options = ManifestOptions {
+            output: None,
+            hiscode: "89269".to_string(),
+            token: "t0ken".to_string(),
+            ttl: Duration::from_secs(3600),
+            thumbnails: None,
+            follow_dicomdir: false,
+        };
+        let manifest = generate_json_file(&input, &options).unwrap();
+        assert!(manifest["seriesData"][0].get("thumbnail").is_none());
+
+        // 缩略图: 内嵌为 data URI, 或写入目录并记录路径
+        let options = ManifestOptions {
+            thumbnails: Some(ThumbnailOptions {
+                target: ThumbnailTarget::Inline,
+                format: ThumbnailFormat::Jpeg,
+                size: DEFAULT_THUMBNAIL_SIZE,
+            }),
+            ..options
+        };
+        let manifest = generate_json_file(&input, &options).unwrap();
+        let thumbnail = manifest["seriesData"][0]["thumbnail"].as_str().unwrap();
+        assert!(thumbnail.starts_with("data:image/jpeg;base64,"));
+
+        let dir = tempdir().unwrap();
+        let thumbnails = dir.path().join("thumbnails");
+        let options = ManifestOptions {
+            thumbnails: Some(ThumbnailOptions {
+                target: ThumbnailTarget::Directory(thumbnails.clone()),
+                format: ThumbnailFormat::Png,
+                size: 64,
+            }),
+            ..options
+        };
+        let manifest = generate_json_file(&input, &options).unwrap();
+        let thumbnail = PathBuf::from(manifest["seriesData"][0]["thumbnail"].as_str().unwrap());
+        assert_eq!(thumbnail.parent(), Some(thumbnails.as_path()));
+        let decoder = png::Decoder::new(std::io::BufReader::new(std::fs::File::open(&thumbnail).unwrap()));
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), (64, 64));
+    }
+
+    #[test]
+    fn test_json_args_merge_config() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.json");
+        std::fs::write(&config, r#"{"hiscode":"A1","token":"cfg","ttl":"2h"}"#).unwrap();
+        let app = Application::try_parse_from([
+            "dcm-tools",
+            "json",
+            "-i",
+            "./test_data",
+            "-c",
+            config.to_str().unwrap(),
+            "--token",
+            "cli",
+        ])
+        .unwrap();
+        let Commands::Json(args) = app.command else {
+            panic!("expected json");
+        };
+        let options = args.options().unwrap();
+        assert_eq!(options.hiscode, "A1");
+        assert_eq!(options.token, "cli");
+        assert_eq!(options.ttl.as_secs(), 7200);
+        assert!(options.output.is_none());
+
+        let app = Application::try_parse_from(["dcm-tools", "json", "-i", "./test_data"]).unwrap();
+        let Commands::Json(args) = app.command else {
+            panic!("expected json");
+        };
+        assert!(args.options().is_err());
+    }
+
     #[test]
     fn test_file_read() {
         let input_path =
@@ -193,6 +488,6 @@ mod tests {
         let json = serde_json::to_string(&dicom_info).unwrap();
         std::fs::write(json_path, json).unwrap();
 
-        convert_ts_with_gdcm(&input_path, &output_path).unwrap();
+        convert_ts_with_gdcm(&input_path, &output_path, &TranscodeOptions::default()).unwrap();
     }
 }
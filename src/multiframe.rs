@@ -0,0 +1,664 @@
+use crate::association::{IMPLEMENTATION_CLASS_UID, IMPLEMENTATION_VERSION_NAME};
+use crate::error::DcmToolsError;
+use crate::geometry::{SeriesGeometry, Slice};
+use crate::render::decode;
+use dicom::core::header::Header;
+use dicom::core::value::{DataSetSequence, PrimitiveValue};
+use dicom::core::{DataElement, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::pixeldata::{DecodedPixelData, PlanarConfiguration};
+use dicom_object::mem::InMemElement;
+use dicom_object::meta::FileMetaTableBuilder;
+use dicom_object::{DefaultDicomObject, InMemDicomObject, OpenFileOptions};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// 拆分时多帧 SOP Class 对应的单帧 SOP Class; 不在表中的保持原 SOP Class
+const SPLIT_SOP_CLASSES: &[(&str, &str)] = &[
+    (uids::ENHANCED_CT_IMAGE_STORAGE, uids::CT_IMAGE_STORAGE),
+    (uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE, uids::CT_IMAGE_STORAGE),
+    (uids::ENHANCED_MR_IMAGE_STORAGE, uids::MR_IMAGE_STORAGE),
+    (uids::LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE, uids::MR_IMAGE_STORAGE),
+    (uids::ENHANCED_PET_IMAGE_STORAGE, uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE),
+    (uids::LEGACY_CONVERTED_ENHANCED_PET_IMAGE_STORAGE, uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE),
+    (uids::ULTRASOUND_MULTI_FRAME_IMAGE_STORAGE, uids::ULTRASOUND_IMAGE_STORAGE),
+    (uids::MULTI_FRAME_GRAYSCALE_BYTE_SECONDARY_CAPTURE_IMAGE_STORAGE, uids::SECONDARY_CAPTURE_IMAGE_STORAGE),
+    (uids::MULTI_FRAME_GRAYSCALE_WORD_SECONDARY_CAPTURE_IMAGE_STORAGE, uids::SECONDARY_CAPTURE_IMAGE_STORAGE),
+    (uids::MULTI_FRAME_TRUE_COLOR_SECONDARY_CAPTURE_IMAGE_STORAGE, uids::SECONDARY_CAPTURE_IMAGE_STORAGE),
+];
+
+/// 合并时单帧 SOP Class 对应的 Legacy Converted Enhanced SOP Class
+const MERGE_SOP_CLASSES: &[(&str, &str)] = &[
+    (uids::CT_IMAGE_STORAGE, uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE),
+    (uids::MR_IMAGE_STORAGE, uids::LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE),
+    (uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE, uids::LEGACY_CONVERTED_ENHANCED_PET_IMAGE_STORAGE),
+];
+
+/// 合并时放入功能组宏的属性: (宏序列, 属性)
+const FUNCTIONAL_GROUP_MACROS: &[(Tag, &[Tag])] = &[
+    (tags::PIXEL_MEASURES_SEQUENCE, &[tags::PIXEL_SPACING, tags::SLICE_THICKNESS]),
+    (tags::PLANE_POSITION_SEQUENCE, &[tags::IMAGE_POSITION_PATIENT]),
+    (tags::PLANE_ORIENTATION_SEQUENCE, &[tags::IMAGE_ORIENTATION_PATIENT]),
+    (
+        tags::FRAME_VOILUT_SEQUENCE,
+        &[tags::WINDOW_CENTER, tags::WINDOW_WIDTH, tags::WINDOW_CENTER_WIDTH_EXPLANATION],
+    ),
+    (
+        tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+        &[tags::RESCALE_INTERCEPT, tags::RESCALE_SLOPE, tags::RESCALE_TYPE],
+    ),
+];
+
+/// 只在多帧对象中有意义的顶层属性, 拆分时去掉
+const MULTI_FRAME_ONLY: &[Tag] = &[
+    tags::NUMBER_OF_FRAMES,
+    tags::FRAME_INCREMENT_POINTER,
+    tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+    tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+    tags::DIMENSION_ORGANIZATION_SEQUENCE,
+    tags::DIMENSION_INDEX_SEQUENCE,
+    tags::DIMENSION_ORGANIZATION_TYPE,
+    tags::PIXEL_DATA,
+];
+
+/// 按帧区分的属性, 合并时不比较, 单独处理
+const FRAME_IDENTITY: &[Tag] = &[tags::SOP_INSTANCE_UID, tags::PIXEL_DATA];
+
+fn string(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.element_opt(tag)
+        .ok()
+        .flatten()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn put_str(obj: &mut InMemDicomObject, tag: Tag, vr: VR, value: &str) {
+    obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+}
+
+fn sequence(tag: Tag, items: Vec<InMemDicomObject>) -> DataElement<InMemDicomObject> {
+    DataElement::new(tag, VR::SQ, DataSetSequence::from(items))
+}
+
+/// 按 VR 和值比较, 序列逐项递归 (dicom-core 中两个未定义长度互不相等, 不能直接用 ==)
+fn same_element(a: &InMemElement, b: &InMemElement) -> bool {
+    a.vr() == b.vr()
+        && match (a.items(), b.items()) {
+            (Some(a), Some(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_dataset(a, b)),
+            _ => a.value() == b.value(),
+        }
+}
+
+fn same_dataset(a: &InMemDicomObject, b: &InMemDicomObject) -> bool {
+    a.iter().count() == b.iter().count()
+        && a.iter().all(|e| b.element_opt(e.tag()).ok().flatten().is_some_and(|other| same_element(e, other)))
+}
+
+fn sop_class_for(table: &[(&str, &'static str)], sop_class: &str) -> Option<&'static str> {
+    table.iter().find(|(from, _)| *from == sop_class).map(|(_, to)| *to)
+}
+
+/// 一帧的本地 (Explicit VR Little Endian) 像素字节
+fn native_frame(decoded: &DecodedPixelData, frame: u32) -> Result<Vec<u8>, String> {
+    let data = decoded.frame_data(frame).map_err(|e| e.to_string())?;
+    match decoded.bits_allocated() {
+        8 => Ok(data.to_vec()),
+        16 => Ok(decoded
+            .frame_data_ow(frame)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .flat_map(u16::to_le_bytes)
+            .collect()),
+        32 => Ok(data
+            .chunks_exact(4)
+            .flat_map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]).to_le_bytes())
+            .collect()),
+        bits => Err(format!("unsupported Bits Allocated {}", bits)),
+    }
+}
+
+/// 写入本地像素数据, 并按解码结果更新 Photometric Interpretation / Planar Configuration
+fn put_pixels(obj: &mut InMemDicomObject, decoded: &DecodedPixelData, mut bytes: Vec<u8>) {
+    if !bytes.len().is_multiple_of(2) {
+        bytes.push(0);
+    }
+    let vr = if decoded.bits_allocated() == 8 { VR::OB } else { VR::OW };
+    obj.put(DataElement::new(tags::PIXEL_DATA, vr, PrimitiveValue::from(bytes)));
+    put_str(obj, tags::PHOTOMETRIC_INTERPRETATION, VR::CS, decoded.photometric_interpretation().as_ref());
+    if decoded.samples_per_pixel() > 1 {
+        let planar = u16::from(decoded.planar_configuration() != PlanarConfiguration::Standard);
+        obj.put(DataElement::new(tags::PLANAR_CONFIGURATION, VR::US, PrimitiveValue::from(planar)));
+    }
+}
+
+fn into_file_object(obj: InMemDicomObject) -> Result<DefaultDicomObject, String> {
+    obj.with_meta(
+        FileMetaTableBuilder::new()
+            .transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+            .implementation_class_uid(IMPLEMENTATION_CLASS_UID)
+            .implementation_version_name(IMPLEMENTATION_VERSION_NAME),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 把功能组 item 中各宏的属性提升到顶层; Frame Type 等按单帧 IOD 的对应属性写入
+fn flatten_functional_groups(target: &mut InMemDicomObject, group: &InMemDicomObject) {
+    for functional_group in group {
+        if functional_group.tag() == tags::CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE {
+            continue;
+        }
+        let Some(item) = functional_group.items().and_then(|items| items.first()) else {
+            continue;
+        };
+        for element in item {
+            match element.tag() {
+                tags::FRAME_TYPE => {
+                    target.put(DataElement::new(tags::IMAGE_TYPE, VR::CS, element.value().clone()));
+                }
+                tags::FRAME_ACQUISITION_DATE_TIME => {
+                    target.put(DataElement::new(tags::ACQUISITION_DATE_TIME, VR::DT, element.value().clone()));
+                }
+                tags::FRAME_ACQUISITION_NUMBER => {
+                    if let Ok(number) = element.to_int::<i32>() {
+                        put_str(target, tags::ACQUISITION_NUMBER, VR::IS, &number.to_string());
+                    }
+                }
+                // 维度索引只在多帧对象中有意义
+                tags::DIMENSION_INDEX_VALUES
+                | tags::STACK_ID
+                | tags::IN_STACK_POSITION_NUMBER
+                | tags::TEMPORAL_POSITION_INDEX => {}
+                _ => {
+                    target.put(element.clone());
+                }
+            }
+        }
+    }
+}
+
+/// 将多帧实例拆分为单帧实例 (新 SOP Instance UID, 同一 series); 单帧实例返回空列表
+pub fn split(path: &Path, obj: &DefaultDicomObject) -> Result<Vec<DefaultDicomObject>, DcmToolsError> {
+    let decoded = decode(path, obj)?;
+    let frames = decoded.number_of_frames();
+    if frames < 2 {
+        return Ok(Vec::new());
+    }
+    let dataset: &InMemDicomObject = obj;
+    let sop_class = string(dataset, tags::SOP_CLASS_UID).unwrap_or_default();
+    let sop_uid = string(dataset, tags::SOP_INSTANCE_UID).unwrap_or_default();
+    let shared = dataset
+        .element_opt(tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE)
+        .ok()
+        .flatten()
+        .and_then(|e| e.items())
+        .and_then(|items| items.first());
+    let per_frame = dataset
+        .element_opt(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE)
+        .ok()
+        .flatten()
+        .and_then(|e| e.items())
+        .unwrap_or_default();
+
+    let mut base = dataset.clone();
+    for tag in MULTI_FRAME_ONLY {
+        base.remove_element(*tag);
+    }
+    put_str(&mut base, tags::SOP_CLASS_UID, VR::UI, sop_class_for(SPLIT_SOP_CLASSES, &sop_class).unwrap_or(&sop_class));
+    (0..frames)
+        .map(|frame| {
+            let mut single = base.clone();
+            if let Some(shared) = shared {
+                flatten_functional_groups(&mut single, shared);
+            }
+            if let Some(group) = per_frame.get(frame as usize) {
+                flatten_functional_groups(&mut single, group);
+            }
+            put_str(&mut single, tags::SOP_INSTANCE_UID, VR::UI, &dicom_gen_uid::gen_uid());
+            put_str(&mut single, tags::INSTANCE_NUMBER, VR::IS, &(frame + 1).to_string());
+            // 记录来源的多帧实例及帧号
+            let mut source = InMemDicomObject::new_empty();
+            put_str(&mut source, tags::REFERENCED_SOP_CLASS_UID, VR::UI, &sop_class);
+            put_str(&mut source, tags::REFERENCED_SOP_INSTANCE_UID, VR::UI, &sop_uid);
+            put_str(&mut source, tags::REFERENCED_FRAME_NUMBER, VR::IS, &(frame + 1).to_string());
+            single.put(sequence(tags::SOURCE_IMAGE_SEQUENCE, vec![source]));
+
+            let bytes = native_frame(&decoded, frame).map_err(|e| DcmToolsError::transcode(path, e))?;
+            put_pixels(&mut single, &decoded, bytes);
+            into_file_object(single).map_err(|e| DcmToolsError::transcode(path, e))
+        })
+        .collect()
+}
+
+/// 按几何 (Image Position 在法向上的投影) 排序, 缺少几何或方向不一致时按 Instance Number
+fn frame_order(objects: &[(PathBuf, DefaultDicomObject)]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..objects.len()).collect();
+    order.sort_by_key(|&i| {
+        objects[i]
+            .1
+            .element_opt(tags::INSTANCE_NUMBER)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_int::<i32>().ok())
+            .unwrap_or(i32::MAX)
+    });
+    let slices: Result<Vec<Slice>, String> =
+        objects.iter().map(|(path, obj)| Slice::from_object(path, obj)).collect();
+    let Ok(slices) = slices else {
+        return order;
+    };
+    let geometry = SeriesGeometry::new("", slices);
+    if geometry.issues.iter().any(|issue| matches!(issue, crate::geometry::GeometryIssue::MixedOrientation { .. })) {
+        return order;
+    }
+    geometry
+        .slices
+        .iter()
+        .filter_map(|slice| objects.iter().position(|(path, _)| *path == slice.path))
+        .collect()
+}
+
+/// 宏中的属性是否各帧相同: 相同放入 Shared Functional Groups, 否则放入 Per-frame
+fn macro_item(dataset: &InMemDicomObject, attributes: &[Tag], modality: &str) -> Option<InMemDicomObject> {
+    let mut item = InMemDicomObject::new_empty();
+    for tag in attributes {
+        if let Ok(Some(element)) = dataset.element_opt(*tag) {
+            item.put(element.clone());
+        }
+    }
+    // Pixel Value Transformation 中 Rescale Type 为 1 类
+    if attributes.contains(&tags::RESCALE_INTERCEPT)
+        && item.element_opt(tags::RESCALE_INTERCEPT).ok().flatten().is_some()
+        && item.element_opt(tags::RESCALE_TYPE).ok().flatten().is_none()
+    {
+        put_str(&mut item, tags::RESCALE_TYPE, VR::LO, if modality == "CT" { "HU" } else { "US" });
+    }
+    let empty = item.iter().next().is_none();
+    (!empty).then_some(item)
+}
+
+/// 将同一 series 的单帧 CT/MR/PET 实例合并为 Legacy Converted Enhanced 实例
+pub fn merge(objects: &[(PathBuf, DefaultDicomObject)]) -> Result<DefaultDicomObject, DcmToolsError> {
+    let Some((first_path, first)) = objects.first() else {
+        return Err(DcmToolsError::convert("", "no instances to merge"));
+    };
+    let sop_class = string(first, tags::SOP_CLASS_UID).unwrap_or_default();
+    let Some(enhanced_class) = sop_class_for(MERGE_SOP_CLASSES, &sop_class) else {
+        return Err(DcmToolsError::convert(
+            first_path,
+            format!("SOP Class {} has no Legacy Converted Enhanced counterpart", sop_class),
+        ));
+    };
+    // 这些属性不同则像素无法拼接, 也不能放入 Unassigned Per-frame (Image Pixel 模块须在顶层)
+    let image_attributes = [
+        tags::SOP_CLASS_UID,
+        tags::SERIES_INSTANCE_UID,
+        tags::ROWS,
+        tags::COLUMNS,
+        tags::SAMPLES_PER_PIXEL,
+        tags::PHOTOMETRIC_INTERPRETATION,
+        tags::PLANAR_CONFIGURATION,
+        tags::BITS_ALLOCATED,
+        tags::BITS_STORED,
+        tags::HIGH_BIT,
+        tags::PIXEL_REPRESENTATION,
+    ];
+    for (path, obj) in objects {
+        for tag in image_attributes {
+            if string(obj, tag) != string(first, tag) {
+                return Err(DcmToolsError::convert(path, format!("{} differs from {:?}", tag, first_path)));
+            }
+        }
+    }
+
+    let order = frame_order(objects);
+    let frames: Vec<(Vec<u8>, DecodedPixelData<'static>)> = order
+        .par_iter()
+        .map(|&i| {
+            let (path, obj) = &objects[i];
+            let decoded = decode(path, obj)?;
+            if decoded.number_of_frames() != 1 {
+                return Err(DcmToolsError::convert(path, "already a multi-frame instance"));
+            }
+            let bytes = native_frame(&decoded, 0).map_err(|e| DcmToolsError::transcode(path, e))?;
+            Ok((bytes, decoded))
+        })
+        .collect::<Result<_, _>>()?;
+    let datasets: Vec<&InMemDicomObject> = order.iter().map(|&i| &*objects[i].1).collect();
+    let modality = string(first, tags::MODALITY).unwrap_or_default();
+
+    // 功能组宏: 各帧相同的放入 Shared, 否则逐帧放入 Per-frame
+    let mut shared = InMemDicomObject::new_empty();
+    let mut per_frame: Vec<InMemDicomObject> = datasets.iter().map(|_| InMemDicomObject::new_empty()).collect();
+    let macro_tags: BTreeSet<Tag> = FUNCTIONAL_GROUP_MACROS.iter().flat_map(|(_, attributes)| attributes.iter().copied()).collect();
+    for (macro_tag, attributes) in FUNCTIONAL_GROUP_MACROS {
+        let items: Vec<Option<InMemDicomObject>> =
+            datasets.iter().map(|dataset| macro_item(dataset, attributes, &modality)).collect();
+        let same = items.iter().all(|item| match (item, &items[0]) {
+            (Some(a), Some(b)) => same_dataset(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        });
+        if same {
+            if let Some(item) = items[0].clone() {
+                shared.put(sequence(*macro_tag, vec![item]));
+            }
+        } else {
+            for (group, item) in per_frame.iter_mut().zip(items) {
+                if let Some(item) = item {
+                    group.put(sequence(*macro_tag, vec![item]));
+                }
+            }
+        }
+    }
+
+    // 其余属性: 各帧相同的留在顶层, 不同的放入 Unassigned Per-frame Converted Attributes
+    let all_tags: BTreeSet<Tag> = datasets.iter().flat_map(|dataset| dataset.iter().map(|e| e.tag())).collect();
+    let varying: Vec<Tag> = all_tags
+        .into_iter()
+        .filter(|tag| !macro_tags.contains(tag) && !FRAME_IDENTITY.contains(tag))
+        .filter(|tag| {
+            let first = datasets[0].element_opt(*tag).ok().flatten();
+            datasets.iter().any(|dataset| match (dataset.element_opt(*tag).ok().flatten(), first) {
+                (Some(a), Some(b)) => !same_element(a, b),
+                (a, b) => a.is_some() || b.is_some(),
+            })
+        })
+        .collect();
+    for (group, dataset) in per_frame.iter_mut().zip(&datasets) {
+        let mut unassigned = InMemDicomObject::new_empty();
+        for tag in &varying {
+            if let Ok(Some(element)) = dataset.element_opt(*tag) {
+                unassigned.put(element.clone());
+            }
+        }
+        if unassigned.iter().next().is_some() {
+            group.put(sequence(tags::UNASSIGNED_PER_FRAME_CONVERTED_ATTRIBUTES_SEQUENCE, vec![unassigned]));
+        }
+        // Frame Content 与转换来源
+        let mut content = InMemDicomObject::new_empty();
+        if let Some(number) = string(dataset, tags::ACQUISITION_NUMBER).and_then(|n| n.trim().parse::<u16>().ok()) {
+            content.put(DataElement::new(tags::FRAME_ACQUISITION_NUMBER, VR::US, PrimitiveValue::from(number)));
+        }
+        let date_time = string(dataset, tags::ACQUISITION_DATE_TIME).or_else(|| {
+            Some(format!("{}{}", string(dataset, tags::ACQUISITION_DATE)?, string(dataset, tags::ACQUISITION_TIME)?))
+        });
+        if let Some(date_time) = date_time {
+            put_str(&mut content, tags::FRAME_ACQUISITION_DATE_TIME, VR::DT, &date_time);
+        }
+        group.put(sequence(tags::FRAME_CONTENT_SEQUENCE, vec![content]));
+        let mut source = InMemDicomObject::new_empty();
+        put_str(&mut source, tags::REFERENCED_SOP_CLASS_UID, VR::UI, &sop_class);
+        put_str(
+            &mut source,
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            &string(dataset, tags::SOP_INSTANCE_UID).unwrap_or_default(),
+        );
+        group.put(sequence(tags::CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE, vec![source]));
+    }
+
+    let mut merged = datasets[0].clone();
+    for tag in macro_tags.iter().chain(&varying) {
+        merged.remove_element(*tag);
+    }
+    put_str(&mut merged, tags::SOP_CLASS_UID, VR::UI, enhanced_class);
+    put_str(&mut merged, tags::SOP_INSTANCE_UID, VR::UI, &dicom_gen_uid::gen_uid());
+    put_str(&mut merged, tags::INSTANCE_NUMBER, VR::IS, "1");
+    put_str(&mut merged, tags::NUMBER_OF_FRAMES, VR::IS, &frames.len().to_string());
+    merged.put(sequence(tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE, vec![shared]));
+    merged.put(sequence(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE, per_frame));
+    let decoded = &frames[0].1;
+    let bytes: Vec<u8> = frames.iter().flat_map(|(bytes, _)| bytes.iter().copied()).collect();
+    put_pixels(&mut merged, decoded, bytes);
+    into_file_object(merged).map_err(|e| DcmToolsError::transcode(first_path, e))
+}
+
+/// 只读文件头, 按 Series Instance UID 分组
+pub fn group_by_series(files: &[PathBuf]) -> BTreeMap<String, Vec<PathBuf>> {
+    let mut series: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for file in files {
+        match OpenFileOptions::new().read_until(tags::PIXEL_DATA).open_file(file) {
+            Ok(obj) => series.entry(string(&obj, tags::SERIES_INSTANCE_UID).unwrap_or_default()).or_default().push(file.clone()),
+            Err(e) => eprintln!("Skipping: {}", DcmToolsError::open(file, e)),
+        }
+    }
+    series
+}
+
+/// 读取同一 series 的文件并合并
+pub fn merge_files(files: &[PathBuf]) -> Result<DefaultDicomObject, DcmToolsError> {
+    let objects = files
+        .par_iter()
+        .map(|file| {
+            let obj = dicom_object::open_file(file).map_err(|e| DcmToolsError::open(file, e))?;
+            Ok((file.clone(), obj))
+        })
+        .collect::<Result<Vec<_>, DcmToolsError>>()?;
+    merge(&objects)
+}
+
+/// 拆分文件, 单帧实例写为 output/{sop_uid}.dcm, 返回写出的文件
+pub fn split_file(path: &Path, output: &Path) -> Result<Vec<PathBuf>, DcmToolsError> {
+    let obj = dicom_object::open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+    let frames = split(path, &obj)?;
+    if frames.is_empty() {
+        eprintln!("Skipping single-frame instance: {:?}", path);
+        return Ok(Vec::new());
+    }
+    std::fs::create_dir_all(output).map_err(|e| DcmToolsError::write(output, e))?;
+    frames
+        .into_iter()
+        .map(|frame| {
+            let target = output.join(format!("{}.dcm", frame.meta().media_storage_sop_instance_uid().trim_end_matches('\0')));
+            frame.write_to_file(&target).map_err(|e| DcmToolsError::write(&target, e))?;
+            Ok(target)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::dicom_value;
+
+    const TEST_FILE: &str = "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm";
+
+    /// 由测试 CT 生成同一 series 的三张切片 (Instance Number 与位置顺序相反, 窗口各不相同)
+    fn ct_slices() -> Vec<(PathBuf, DefaultDicomObject)> {
+        let obj = dicom_object::open_file(TEST_FILE).unwrap();
+        (0..3)
+            .map(|i| {
+                let mut slice = obj.clone();
+                let z = (-149.0 + 1.25 * i as f64).to_string();
+                slice.put(DataElement::new(
+                    tags::IMAGE_POSITION_PATIENT,
+                    VR::DS,
+                    dicom_value!(Strs, ["-150.200".to_string(), "-160.000".to_string(), z]),
+                ));
+                slice.put(DataElement::new(tags::INSTANCE_NUMBER, VR::IS, dicom_value!(Str, (10 - i).to_string())));
+                slice.put(DataElement::new(tags::WINDOW_CENTER, VR::DS, dicom_value!(Str, (40 + i).to_string())));
+                slice.put(DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, dicom_value!(Str, format!("1.2.3.{}", 10 + i))));
+                (PathBuf::from(format!("{}.dcm", i)), slice)
+            })
+            .collect()
+    }
+
+    /// 手工构造的两帧 Enhanced CT: 位置、方向与 Rescale 逐帧不同, 像素间距共用
+    fn enhanced_ct() -> (DefaultDicomObject, Vec<u8>) {
+        let obj = dicom_object::open_file(TEST_FILE).unwrap();
+        let decoded = decode(Path::new(TEST_FILE), &obj).unwrap();
+        let frame = native_frame(&decoded, 0).unwrap();
+        let mut dataset: InMemDicomObject = (*obj).clone();
+        for tag in [
+            tags::IMAGE_POSITION_PATIENT,
+            tags::IMAGE_ORIENTATION_PATIENT,
+            tags::PIXEL_SPACING,
+            tags::SLICE_THICKNESS,
+            tags::RESCALE_INTERCEPT,
+            tags::RESCALE_SLOPE,
+            tags::RESCALE_TYPE,
+            tags::WINDOW_CENTER,
+            tags::WINDOW_WIDTH,
+        ] {
+            dataset.remove_element(tag);
+        }
+        put_str(&mut dataset, tags::SOP_CLASS_UID, VR::UI, uids::ENHANCED_CT_IMAGE_STORAGE);
+        put_str(&mut dataset, tags::SOP_INSTANCE_UID, VR::UI, "1.2.3.99");
+        put_str(&mut dataset, tags::NUMBER_OF_FRAMES, VR::IS, "2");
+
+        let item = |elements: Vec<DataElement<InMemDicomObject>>| InMemDicomObject::from_element_iter(elements);
+        let strs = |values: &[&str]| PrimitiveValue::Strs(values.iter().map(|v| v.to_string()).collect());
+        let measures = item(vec![
+            DataElement::new(tags::PIXEL_SPACING, VR::DS, strs(&["0.5", "0.5"])),
+            DataElement::new(tags::SLICE_THICKNESS, VR::DS, strs(&["2"])),
+        ]);
+        let shared = item(vec![sequence(tags::PIXEL_MEASURES_SEQUENCE, vec![measures])]);
+        let orientations = [["1", "0", "0", "0", "1", "0"], ["1", "0", "0", "0", "0", "-1"]];
+        let per_frame = (0..2)
+            .map(|i| {
+                let z = (10 * i).to_string();
+                let intercept = (-1024 + i).to_string();
+                item(vec![
+                    sequence(
+                        tags::PLANE_POSITION_SEQUENCE,
+                        vec![item(vec![DataElement::new(tags::IMAGE_POSITION_PATIENT, VR::DS, strs(&["-100", "-100", &z]))])],
+                    ),
+                    sequence(
+                        tags::PLANE_ORIENTATION_SEQUENCE,
+                        vec![item(vec![DataElement::new(tags::IMAGE_ORIENTATION_PATIENT, VR::DS, strs(&orientations[i as usize]))])],
+                    ),
+                    sequence(
+                        tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+                        vec![item(vec![
+                            DataElement::new(tags::RESCALE_INTERCEPT, VR::DS, strs(&[&intercept])),
+                            DataElement::new(tags::RESCALE_SLOPE, VR::DS, strs(&["1"])),
+                            DataElement::new(tags::RESCALE_TYPE, VR::LO, strs(&["HU"])),
+                        ])],
+                    ),
+                    sequence(
+                        tags::FRAME_CONTENT_SEQUENCE,
+                        vec![item(vec![DataElement::new(tags::DIMENSION_INDEX_VALUES, VR::UL, dicom_value!(U32, [1, i as u32 + 1]))])],
+                    ),
+                    sequence(
+                        tags::CT_IMAGE_FRAME_TYPE_SEQUENCE,
+                        vec![item(vec![DataElement::new(tags::FRAME_TYPE, VR::CS, strs(&["ORIGINAL", "PRIMARY", "AXIAL", "NONE"]))])],
+                    ),
+                ])
+            })
+            .collect();
+        dataset.put(sequence(tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE, vec![shared]));
+        dataset.put(sequence(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE, per_frame));
+        put_pixels(&mut dataset, &decoded, [frame.as_slice(), frame.as_slice()].concat());
+        (into_file_object(dataset).unwrap(), frame)
+    }
+
+    #[test]
+    fn test_split_enhanced_ct() {
+        let (enhanced, original) = enhanced_ct();
+        let frames = split(Path::new(TEST_FILE), &enhanced).unwrap();
+        assert_eq!(frames.len(), 2);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(string(frame, tags::SOP_CLASS_UID).unwrap(), uids::CT_IMAGE_STORAGE);
+            assert!(frame.element_opt(tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE).unwrap().is_none());
+            // 逐帧的 Plane Position/Orientation 与 Pixel Value Transformation 提升到顶层
+            let position = frame.element(tags::IMAGE_POSITION_PATIENT).unwrap().to_multi_float64().unwrap();
+            assert_eq!(position, [-100.0, -100.0, 10.0 * i as f64]);
+            let orientation = frame.element(tags::IMAGE_ORIENTATION_PATIENT).unwrap().to_multi_float64().unwrap();
+            assert_eq!(orientation[5], if i == 0 { 0.0 } else { -1.0 });
+            assert_eq!(string(frame, tags::RESCALE_INTERCEPT).unwrap(), (-1024 + i as i32).to_string());
+            assert_eq!(string(frame, tags::RESCALE_TYPE).unwrap(), "HU");
+            // 共用的 Pixel Measures 以及 Frame Type -> Image Type
+            assert_eq!(frame.element(tags::PIXEL_SPACING).unwrap().to_multi_float64().unwrap(), [0.5, 0.5]);
+            assert_eq!(frame.element(tags::IMAGE_TYPE).unwrap().to_multi_str().unwrap().len(), 4);
+            assert!(frame.element_opt(tags::DIMENSION_INDEX_VALUES).unwrap().is_none());
+            let source = &frame.element(tags::SOURCE_IMAGE_SEQUENCE).unwrap().items().unwrap()[0];
+            assert_eq!(string(source, tags::REFERENCED_SOP_CLASS_UID).unwrap(), uids::ENHANCED_CT_IMAGE_STORAGE);
+            assert_eq!(string(source, tags::REFERENCED_SOP_INSTANCE_UID).unwrap(), "1.2.3.99");
+            assert_eq!(string(source, tags::REFERENCED_FRAME_NUMBER).unwrap(), (i + 1).to_string());
+            let decoded = decode(Path::new(TEST_FILE), frame).unwrap();
+            assert_eq!(native_frame(&decoded, 0).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn test_merge_and_split() {
+        let slices = ct_slices();
+        let merged = merge(&slices).unwrap();
+        assert_eq!(string(&merged, tags::SOP_CLASS_UID).unwrap(), uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE);
+        assert_eq!(string(&merged, tags::NUMBER_OF_FRAMES).unwrap(), "3");
+        assert!(merged.element_opt(tags::IMAGE_POSITION_PATIENT).unwrap().is_none());
+        assert!(merged.element_opt(tags::INSTANCE_NUMBER).unwrap().is_some());
+
+        // 像素间距各帧相同放入 Shared, 位置与窗口不同放入 Per-frame
+        let shared = &merged.element(tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE).unwrap().items().unwrap()[0];
+        let measures = &shared.element(tags::PIXEL_MEASURES_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(measures.element(tags::PIXEL_SPACING).unwrap().to_multi_float64().unwrap(), [0.625, 0.625]);
+        let transformation = &shared.element(tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(string(transformation, tags::RESCALE_TYPE).unwrap(), "HU");
+        let per_frame = merged.element(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE).unwrap().items().unwrap();
+        assert_eq!(per_frame.len(), 3);
+        // 按位置排序: 第一帧是 z 最小的切片 (1.2.3.10)
+        let position = &per_frame[0].element(tags::PLANE_POSITION_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(position.element(tags::IMAGE_POSITION_PATIENT).unwrap().to_multi_float64().unwrap()[2], -149.0);
+        let source = &per_frame[0].element(tags::CONVERSION_SOURCE_ATTRIBUTES_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(string(source, tags::REFERENCED_SOP_INSTANCE_UID).unwrap(), "1.2.3.10");
+        // 各帧不同的 Instance Number 放入 Unassigned, 相同的序列 (未定义长度) 留在顶层
+        let unassigned =
+            &per_frame[0].element(tags::UNASSIGNED_PER_FRAME_CONVERTED_ATTRIBUTES_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(string(unassigned, tags::INSTANCE_NUMBER).unwrap(), "10");
+        assert!(unassigned.element_opt(tags::REFERENCED_IMAGE_SEQUENCE).unwrap().is_none());
+        assert!(merged.element_opt(tags::REFERENCED_IMAGE_SEQUENCE).unwrap().is_some());
+
+        let original = decode(Path::new(TEST_FILE), &slices[0].1).unwrap();
+        let original = native_frame(&original, 0).unwrap();
+        let decoded = decode(Path::new(TEST_FILE), &merged).unwrap();
+        assert_eq!(decoded.number_of_frames(), 3);
+
+        // 拆分回单帧: 功能组的值回到顶层, SOP UID 是新的
+        let frames = split(Path::new(TEST_FILE), &merged).unwrap();
+        assert_eq!(frames.len(), 3);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(string(frame, tags::SOP_CLASS_UID).unwrap(), uids::CT_IMAGE_STORAGE);
+            assert_eq!(string(frame, tags::INSTANCE_NUMBER).unwrap(), (i + 1).to_string());
+            assert!(frame.element_opt(tags::NUMBER_OF_FRAMES).unwrap().is_none());
+            assert!(frame.element_opt(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE).unwrap().is_none());
+            assert!(!string(frame, tags::SOP_INSTANCE_UID).unwrap().starts_with("1.2.3."));
+            let z = frame.element(tags::IMAGE_POSITION_PATIENT).unwrap().to_multi_float64().unwrap()[2];
+            assert_eq!(z, -149.0 + 1.25 * i as f64);
+            assert_eq!(string(frame, tags::WINDOW_CENTER).unwrap(), (40 + i).to_string());
+            assert_eq!(frame.element(tags::PIXEL_SPACING).unwrap().to_multi_float64().unwrap(), [0.625, 0.625]);
+            let decoded = decode(Path::new(TEST_FILE), frame).unwrap();
+            assert_eq!(native_frame(&decoded, 0).unwrap(), original);
+        }
+
+        // 单帧实例不拆分
+        assert!(split(Path::new(TEST_FILE), &slices[0].1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merge_rejects_unsupported() {
+        let mut slices = ct_slices();
+        slices[1].1.put(DataElement::new(tags::ROWS, VR::US, dicom_value!(U16, [256])));
+        assert!(matches!(merge(&slices), Err(DcmToolsError::Convert { .. })));
+        // Image Pixel 模块的属性不同不能放入 Unassigned Per-frame
+        for (tag, value) in [(tags::BITS_STORED, 16), (tags::HIGH_BIT, 11), (tags::PIXEL_REPRESENTATION, 0)] {
+            let mut slices = ct_slices();
+            let changed = if string(&slices[1].1, tag) == Some(value.to_string()) { value - 1 } else { value };
+            slices[1].1.put(DataElement::new(tag, VR::US, dicom_value!(U16, [changed])));
+            assert!(matches!(merge(&slices), Err(DcmToolsError::Convert { .. })), "{}", tag);
+        }
+        let mut slices = ct_slices();
+        slices[2].1.put(DataElement::new(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, dicom_value!(Str, "MONOCHROME1")));
+        assert!(matches!(merge(&slices), Err(DcmToolsError::Convert { .. })));
+        let mut slices = ct_slices();
+        for (_, slice) in &mut slices {
+            slice.put(DataElement::new(
+                tags::SOP_CLASS_UID,
+                VR::UI,
+                dicom_value!(Str, uids::SECONDARY_CAPTURE_IMAGE_STORAGE),
+            ));
+        }
+        assert!(matches!(merge(&slices), Err(DcmToolsError::Convert { .. })));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::dicom_info::DicomInfo;
+use crate::error::DcmToolsError;
+use crate::geometry::{Affine, SeriesGeometry, Vec3, Volume};
+use dicom::dictionary_std::tags;
+use dicom_object::OpenFileOptions;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// NIfTI-1 头的长度; 其后是 4 字节的扩展标记, 数据从 352 开始
+const HEADER_SIZE: usize = 348;
+const VOX_OFFSET: usize = 352;
+
+const DT_INT16: i16 = 4;
+const DT_INT32: i16 = 8;
+const DT_FLOAT32: i16 = 16;
+/// qform/sform_code: 扫描仪坐标
+const NIFTI_XFORM_SCANNER_ANAT: i16 = 1;
+const NIFTI_UNITS_MM: u8 = 2;
+
+/// 体素的存储方式
+enum Voxels {
+    /// 原始存储值, 头中记录共用的 Rescale
+    Int16(Vec<i16>, f64, f64),
+    Int32(Vec<i32>, f64, f64),
+    /// 各切片 Rescale 不同时直接存 Rescale 后的值
+    Float32(Vec<f32>),
+}
+
+impl Voxels {
+    fn new(volume: &Volume) -> Voxels {
+        let Some(rescale) = volume.common_rescale() else {
+            return Voxels::Float32(volume.values());
+        };
+        let fits_i16 = volume.data.iter().all(|&v| i16::try_from(v).is_ok());
+        if fits_i16 {
+            Voxels::Int16(volume.data.iter().map(|&v| v as i16).collect(), rescale.slope, rescale.intercept)
+        } else {
+            Voxels::Int32(volume.data.clone(), rescale.slope, rescale.intercept)
+        }
+    }
+
+    /// (datatype, bitpix, scl_slope, scl_inter)
+    fn header(&self) -> (i16, i16, f64, f64) {
+        match self {
+            Voxels::Int16(_, slope, intercept) => (DT_INT16, 16, *slope, *intercept),
+            Voxels::Int32(_, slope, intercept) => (DT_INT32, 32, *slope, *intercept),
+            Voxels::Float32(_) => (DT_FLOAT32, 32, 1.0, 0.0),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Voxels::Int16(data, ..) => data.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Voxels::Int32(data, ..) => data.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Voxels::Float32(data) => data.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+}
+
+/// DICOM 的 LPS 转为 NIfTI 的 RAS: x, y 取反
+fn lps_to_ras(affine: &Affine) -> Affine {
+    let mut ras = *affine;
+    for row in ras.iter_mut().take(2) {
+        for value in row.iter_mut() {
+            *value = -*value;
+        }
+    }
+    ras
+}
+
+fn column(affine: &Affine, j: usize) -> Vec3 {
+    [affine[0][j], affine[1][j], affine[2][j]]
+}
+
+fn length(v: Vec3) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+/// 正交旋转矩阵 (列为方向) 转为四元数 (b, c, d) 和 qfac, 同 nifti1_io 的 mat44_to_quatern
+fn quaternion(mut r: [[f64; 3]; 3]) -> ([f64; 3], f64) {
+    let det = r[0][0] * (r[1][1] * r[2][2] - r[2][1] * r[1][2]) - r[0][1] * (r[1][0] * r[2][2] - r[2][0] * r[1][2])
+        + r[0][2] * (r[1][0] * r[2][1] - r[2][0] * r[1][1]);
+    let qfac = if det < 0.0 { -1.0 } else { 1.0 };
+    if qfac < 0.0 {
+        for row in r.iter_mut() {
+            row[2] = -row[2];
+        }
+    }
+    let trace = r[0][0] + r[1][1] + r[2][2] + 1.0;
+    let (a, b, c, d) = if trace > 0.5 {
+        let a = 0.5 * trace.sqrt();
+        (a, 0.25 * (r[2][1] - r[1][2]) / a, 0.25 * (r[0][2] - r[2][0]) / a, 0.25 * (r[1][0] - r[0][1]) / a)
+    } else {
+        let xd = 1.0 + r[0][0] - (r[1][1] + r[2][2]);
+        let yd = 1.0 + r[1][1] - (r[0][0] + r[2][2]);
+        let zd = 1.0 + r[2][2] - (r[0][0] + r[1][1]);
+        if xd > 1.0 {
+            let b = 0.5 * xd.sqrt();
+            (0.25 * (r[2][1] - r[1][2]) / b, b, 0.25 * (r[0][1] + r[1][0]) / b, 0.25 * (r[0][2] + r[2][0]) / b)
+        } else if yd > 1.0 {
+            let c = 0.5 * yd.sqrt();
+            (0.25 * (r[0][2] - r[2][0]) / c, 0.25 * (r[0][1] + r[1][0]) / c, c, 0.25 * (r[1][2] + r[2][1]) / c)
+        } else {
+            let d = 0.5 * zd.sqrt();
+            (0.25 * (r[1][0] - r[0][1]) / d, 0.25 * (r[0][2] + r[2][0]) / d, 0.25 * (r[1][2] + r[2][1]) / d, d)
+        }
+    };
+    if a < 0.0 { ([-b, -c, -d], qfac) } else { ([b, c, d], qfac) }
+}
+
+/// 生成 352 字节的头 (含空扩展标记)
+fn header(volume: &Volume, normal: Vec3, voxels: &Voxels) -> Vec<u8> {
+    let mut h = vec![0u8; VOX_OFFSET];
+    let put_i16 = |h: &mut [u8], offset: usize, v: i16| h[offset..offset + 2].copy_from_slice(&v.to_le_bytes());
+    let put_f32 = |h: &mut [u8], offset: usize, v: f64| h[offset..offset + 4].copy_from_slice(&(v as f32).to_le_bytes());
+
+    h[0..4].copy_from_slice(&(HEADER_SIZE as i32).to_le_bytes());
+    h[38] = b'r';
+    let [nx, ny, nz] = volume.dimensions;
+    for (i, dim) in [3, nx, ny, nz, 1, 1, 1, 1].into_iter().enumerate() {
+        put_i16(&mut h, 40 + i * 2, dim as i16);
+    }
+    let (datatype, bitpix, slope, intercept) = voxels.header();
+    put_i16(&mut h, 70, datatype);
+    put_i16(&mut h, 72, bitpix);
+
+    // sform: 完整的仿射矩阵 (倾斜时带剪切)
+    let ras = lps_to_ras(&volume.affine);
+    // qform: 行、列方向与切片法向组成的旋转, 层间距取沿法向的分量
+    let step = column(&volume.affine, 2);
+    let along = step[0] * normal[0] + step[1] * normal[1] + step[2] * normal[2];
+    let k = if along < 0.0 { [-normal[0], -normal[1], -normal[2]] } else { normal };
+    let i = column(&volume.affine, 0);
+    let j = column(&volume.affine, 1);
+    let (dx, dy, dz) = (length(i), length(j), along.abs().max(f64::EPSILON));
+    let directions = [[i[0] / dx, j[0] / dy, k[0]], [i[1] / dx, j[1] / dy, k[1]], [i[2] / dx, j[2] / dy, k[2]]];
+    // LPS -> RAS
+    let rotation = [
+        directions[0].map(|v| -v),
+        directions[1].map(|v| -v),
+        directions[2],
+    ];
+    let ([b, c, d], qfac) = quaternion(rotation);
+
+    for (n, value) in [qfac, dx, dy, dz, 1.0, 1.0, 1.0, 1.0].into_iter().enumerate() {
+        put_f32(&mut h, 76 + n * 4, value);
+    }
+    put_f32(&mut h, 108, VOX_OFFSET as f64);
+    put_f32(&mut h, 112, slope);
+    put_f32(&mut h, 116, intercept);
+    h[123] = NIFTI_UNITS_MM;
+    let descrip = b"dcm-tools";
+    h[148..148 + descrip.len()].copy_from_slice(descrip);
+    put_i16(&mut h, 252, NIFTI_XFORM_SCANNER_ANAT);
+    put_i16(&mut h, 254, NIFTI_XFORM_SCANNER_ANAT);
+    for (n, value) in [b, c, d, ras[0][3], ras[1][3], ras[2][3]].into_iter().enumerate() {
+        put_f32(&mut h, 256 + n * 4, value);
+    }
+    for (row, values) in ras.iter().take(3).enumerate() {
+        for (n, value) in values.iter().enumerate() {
+            put_f32(&mut h, 280 + row * 16 + n * 4, *value);
+        }
+    }
+    h[344..348].copy_from_slice(b"n+1\0");
+    h
+}
+
+/// 输出文件对应的 JSON 附属文件: a.nii.gz -> a.json
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let name = output.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let stem = name.strip_suffix(".nii.gz").or(name.strip_suffix(".nii")).unwrap_or(&name);
+    output.with_file_name(format!("{}.json", stem))
+}
+
+/// 将体数据写为 .nii.gz
+pub fn write_nifti(volume: &Volume, normal: Vec3, output: &Path) -> Result<(), DcmToolsError> {
+    let voxels = Voxels::new(volume);
+    let file = File::create(output).map_err(|e| DcmToolsError::write(output, e))?;
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    encoder
+        .write_all(&header(volume, normal, &voxels))
+        .and_then(|_| encoder.write_all(&voxels.bytes()))
+        .and_then(|_| encoder.finish()?.flush())
+        .map_err(|e| DcmToolsError::write(output, e))
+}
+
+/// 组装 series 并写出 .nii.gz 和由第一张切片的 DicomInfo 生成的 JSON 附属文件
+pub fn export_series(geometry: &SeriesGeometry, output: &Path) -> Result<(), DcmToolsError> {
+    let volume = Volume::assemble(geometry)?;
+    if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| DcmToolsError::write(dir, e))?;
+    }
+    write_nifti(&volume, geometry.normal, output)?;
+
+    let first = &geometry.slices[0].path;
+    let obj = OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .open_file(first)
+        .map_err(|e| DcmToolsError::open(first, e))?;
+    let sidecar = sidecar_path(output);
+    let json = serde_json::to_string_pretty(&DicomInfo::new(&obj)).map_err(|e| DcmToolsError::write(&sidecar, e))?;
+    std::fs::write(&sidecar, json).map_err(|e| DcmToolsError::write(&sidecar, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Rescale;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn f32_at(h: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(h[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn i16_at(h: &[u8], offset: usize) -> i16 {
+        i16::from_le_bytes(h[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_quaternion() {
+        let identity = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_eq!(quaternion(identity), ([0.0, 0.0, 0.0], 1.0));
+        // LPS 的轴位图像: x, y 取反, 即绕 z 旋转 180 度
+        let axial = [[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_eq!(quaternion(axial), ([0.0, 0.0, 1.0], 1.0));
+        // 左手系时 qfac 为 -1
+        let flipped = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]];
+        assert_eq!(quaternion(flipped), ([0.0, 0.0, 0.0], -1.0));
+        assert_eq!(sidecar_path(Path::new("out/a.nii.gz")), PathBuf::from("out/a.json"));
+    }
+
+    #[test]
+    fn test_write_nifti() {
+        let dir = tempfile::tempdir().unwrap();
+        let volume = Volume {
+            dimensions: [2, 2, 2],
+            affine: [[0.5, 0.0, 0.0, -10.0], [0.0, 0.5, 0.0, -20.0], [0.0, 0.0, 2.0, 30.0], [0.0, 0.0, 0.0, 1.0]],
+            data: vec![0, 1, 2, 3, 4, 5, 6, 7],
+            rescale: vec![Rescale { slope: 1.0, intercept: -1024.0 }; 2],
+        };
+        let output = dir.path().join("v.nii.gz");
+        write_nifti(&volume, [0.0, 0.0, 1.0], &output).unwrap();
+
+        let mut bytes = Vec::new();
+        GzDecoder::new(File::open(&output).unwrap()).read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), VOX_OFFSET + 8 * 2);
+        assert_eq!(i32::from_le_bytes(bytes[0..4].try_into().unwrap()), 348);
+        assert_eq!(&bytes[344..348], b"n+1\0");
+        assert_eq!((i16_at(&bytes, 40), i16_at(&bytes, 42), i16_at(&bytes, 46)), (3, 2, 2));
+        assert_eq!((i16_at(&bytes, 70), i16_at(&bytes, 72)), (DT_INT16, 16));
+        assert_eq!((f32_at(&bytes, 112), f32_at(&bytes, 116)), (1.0, -1024.0));
+        assert_eq!((f32_at(&bytes, 80), f32_at(&bytes, 84), f32_at(&bytes, 88)), (0.5, 0.5, 2.0));
+        // sform 为 RAS: x, y 行取反
+        assert_eq!([f32_at(&bytes, 280), f32_at(&bytes, 292)], [-0.5, 10.0]);
+        assert_eq!([f32_at(&bytes, 300), f32_at(&bytes, 308)], [-0.5, 20.0]);
+        assert_eq!([f32_at(&bytes, 320), f32_at(&bytes, 324)], [2.0, 30.0]);
+        // qform: 绕 z 轴 180 度, 偏移与 sform 一致
+        assert_eq!([f32_at(&bytes, 256), f32_at(&bytes, 260), f32_at(&bytes, 264)], [0.0, 0.0, 1.0]);
+        assert_eq!([f32_at(&bytes, 268), f32_at(&bytes, 272), f32_at(&bytes, 276)], [10.0, 20.0, 30.0]);
+        assert_eq!(i16_at(&bytes, VOX_OFFSET + 2 * 7), 7);
+
+        // 各切片 Rescale 不同时存 float32
+        let volume = Volume {
+            rescale: vec![Rescale { slope: 1.0, intercept: 0.0 }, Rescale { slope: 2.0, intercept: 0.0 }],
+            ..volume
+        };
+        write_nifti(&volume, [0.0, 0.0, 1.0], &output).unwrap();
+        let mut bytes = Vec::new();
+        GzDecoder::new(File::open(&output).unwrap()).read_to_end(&mut bytes).unwrap();
+        assert_eq!(i16_at(&bytes, 70), DT_FLOAT32);
+        assert_eq!(f32_at(&bytes, VOX_OFFSET + 4 * 7), 14.0);
+    }
+
+    #[test]
+    fn test_export_series() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [PathBuf::from("./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm")];
+        let geometry = crate::geometry::load_series(&files);
+        let output = dir.path().join("ct").join("ct.nii.gz");
+        export_series(&geometry[0], &output).unwrap();
+
+        let mut bytes = Vec::new();
+        GzDecoder::new(File::open(&output).unwrap()).read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), VOX_OFFSET + 512 * 512 * 2);
+        assert_eq!(f32_at(&bytes, 116), -1024.0);
+        let sidecar: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join("ct").join("ct.json")).unwrap()).unwrap();
+        assert_eq!(sidecar["x0020000E"], geometry[0].series_uid);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::dicom_json::element_from_tag_keyed;
+use crate::index::{IndexRecord, MetadataIndex};
+use crate::dimse::{self, status};
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags};
+use dicom::core::dictionary::DataDictionary;
+use dicom_object::InMemDicomObject;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Patient Root Query/Retrieve Information Model - FIND
+pub const PATIENT_ROOT_FIND: &str = "1.2.840.10008.5.1.4.1.2.1.1";
+/// Study Root Query/Retrieve Information Model - FIND
+pub const STUDY_ROOT_FIND: &str = "1.2.840.10008.5.1.4.1.2.2.1";
+/// Patient Root Query/Retrieve Information Model - MOVE / GET
+pub const PATIENT_ROOT_MOVE: &str = "1.2.840.10008.5.1.4.1.2.1.2";
+pub const PATIENT_ROOT_GET: &str = "1.2.840.10008.5.1.4.1.2.1.3";
+/// Study Root Query/Retrieve Information Model - MOVE / GET
+pub const STUDY_ROOT_MOVE: &str = "1.2.840.10008.5.1.4.1.2.2.2";
+pub const STUDY_ROOT_GET: &str = "1.2.840.10008.5.1.4.1.2.2.3";
+
+/// C-FIND 响应的 Specific Character Set
+const UTF8_CHARACTER_SET: &str = "ISO_IR 192";
+
+/// Query/Retrieve Level (0008,0052)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QueryLevel {
+    Patient,
+    Study,
+    Series,
+    Image,
+}
+
+impl QueryLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "PATIENT" => Some(QueryLevel::Patient),
+            "STUDY" => Some(QueryLevel::Study),
+            "SERIES" => Some(QueryLevel::Series),
+            "IMAGE" => Some(QueryLevel::Image),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueryLevel::Patient => "PATIENT",
+            QueryLevel::Study => "STUDY",
+            QueryLevel::Series => "SERIES",
+            QueryLevel::Image => "IMAGE",
+        }
+    }
+
+    /// 该级别的唯一键
+    pub fn unique_key(self) -> Tag {
+        match self {
+            QueryLevel::Patient => tags::PATIENT_ID,
+            QueryLevel::Study => tags::STUDY_INSTANCE_UID,
+            QueryLevel::Series => tags::SERIES_INSTANCE_UID,
+            QueryLevel::Image => tags::SOP_INSTANCE_UID,
+        }
+    }
+}
+
+/// 查询失败, 附带返回给 SCU 的状态码
+#[derive(Debug)]
+pub struct QueryError {
+    pub status: u16,
+    pub reason: String,
+}
+
+fn query_error(status: u16, reason: impl Into<String>) -> QueryError {
+    QueryError {
+        status,
+        reason: reason.into(),
+    }
+}
+
+fn dictionary_vr(tag: Tag) -> VR {
+    StandardDataDictionary
+        .by_tag(tag)
+        .map(|entry| entry.vr.relaxed())
+        .unwrap_or(VR::UN)
+}
+
+/// 通配符匹配: * 匹配任意个字符, ? 匹配一个字符
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let v: Vec<char> = value.chars().collect();
+    let (mut pi, mut vi) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while vi < v.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == v[vi]) {
+            pi += 1;
+            vi += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, vi));
+            pi += 1;
+        } else if let Some((sp, sv)) = star {
+            pi = sp + 1;
+            vi = sv + 1;
+            star = Some((sp, sv + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+/// 日期/时间范围匹配: "a-b", "a-", "-b"; 按字符串比较, 值按范围端点的长度截取
+fn range_match(range: &str, value: &str) -> bool {
+    let Some((low, high)) = range.split_once('-') else {
+        return value == range;
+    };
+    if value.is_empty() {
+        return false;
+    }
+    let cut = |bound: &str| value.get(..bound.len().min(value.len())).unwrap_or(value).to_string();
+    (low.is_empty() || cut(low).as_str() >= low) && (high.is_empty() || cut(high).as_str() <= high)
+}
+
+/// 单个键的匹配规则 (PS3.4 C.2.2.2): 空值通配, UID 列表, 通配符, 日期时间范围, 单值; 区分大小写
+pub fn matches(vr: VR, key: &str, value: &str) -> bool {
+    let key = key.trim_end_matches(['\0', ' ']);
+    if key.is_empty() || key == "*" {
+        return true;
+    }
+    let value = value.trim();
+    match vr {
+        VR::UI => key.split('\\').any(|uid| uid.trim() == value),
+        VR::DA | VR::TM | VR::DT => (key.contains('-') && range_match(key, value)) || key == value,
+        _ if key.contains(['*', '?']) => wildcard_match(key, value),
+        // 多值属性 (如 ImageType, ModalitiesInStudy) 任一值相同即匹配
+        _ => value == key || value.split('\\').any(|v| v == key),
+    }
+}
+
+/// 请求标识中的匹配键: tag, VR, 键值; 不参与匹配的元素 (QueryRetrieveLevel 等) 已排除
+fn matching_keys(identifier: &InMemDicomObject) -> Vec<(Tag, VR, String)> {
+    identifier
+        .iter()
+        .filter(|e| {
+            let tag = e.header().tag;
+            tag != tags::QUERY_RETRIEVE_LEVEL && tag != tags::SPECIFIC_CHARACTER_SET && e.header().vr != VR::SQ
+        })
+        .map(|e| {
+            let value = e.to_str().map(|s| s.to_string()).unwrap_or_default();
+            (e.header().tag, e.header().vr, value)
+        })
+        .collect()
+}
+
+/// 根据统计得到的属性值 (Number of ... Related ..., ModalitiesInStudy)
+fn computed_value(tag: Tag, group: &[IndexRecord]) -> Option<String> {
+    let count = |key: fn(&IndexRecord) -> &str| group.iter().map(key).collect::<BTreeSet<_>>().len();
+    let value = match tag {
+        tags::NUMBER_OF_PATIENT_RELATED_STUDIES => count(|r| &r.study.study_uid).to_string(),
+        tags::NUMBER_OF_PATIENT_RELATED_SERIES | tags::NUMBER_OF_STUDY_RELATED_SERIES => {
+            count(|r| &r.series.series_uid).to_string()
+        }
+        tags::NUMBER_OF_PATIENT_RELATED_INSTANCES
+        | tags::NUMBER_OF_STUDY_RELATED_INSTANCES
+        | tags::NUMBER_OF_SERIES_RELATED_INSTANCES => group.len().to_string(),
+        tags::MODALITIES_IN_STUDY => group
+            .iter()
+            .map(|r| r.series.modality.as_str())
+            .filter(|m| !m.is_empty())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+            .join("\\"),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// 请求标识中的 Query/Retrieve Level, Study Root 模型没有 PATIENT 级别
+fn query_level(model: &str, identifier: &InMemDicomObject) -> Result<QueryLevel, QueryError> {
+    let level = dimse::get_str(identifier, tags::QUERY_RETRIEVE_LEVEL)
+        .as_deref()
+        .and_then(QueryLevel::parse)
+        .ok_or_else(|| query_error(status::DATA_SET_DOES_NOT_MATCH, "missing or invalid Query/Retrieve Level"))?;
+    let study_root = [STUDY_ROOT_FIND, STUDY_ROOT_MOVE, STUDY_ROOT_GET].contains(&model);
+    if study_root && level == QueryLevel::Patient {
+        return Err(query_error(
+            status::DATA_SET_DOES_NOT_MATCH,
+            "PATIENT level is not part of the Study Root model",
+        ));
+    }
+    Ok(level)
+}
+
+/// C-MOVE / C-GET: 按请求级别的唯一键 (及上级唯一键) 选出要发送的实例
+pub fn retrieve(
+    index: &MetadataIndex,
+    model: &str,
+    identifier: &InMemDicomObject,
+) -> Result<Vec<IndexRecord>, QueryError> {
+    let level = query_level(model, identifier)?;
+    let keys: Vec<_> = matching_keys(identifier)
+        .into_iter()
+        .filter(|(_, _, key)| !key.is_empty())
+        .collect();
+    if !keys.iter().any(|(tag, _, _)| *tag == level.unique_key()) {
+        return Err(query_error(
+            status::DATA_SET_DOES_NOT_MATCH,
+            format!("missing unique key for {} level", level.as_str()),
+        ));
+    }
+    Ok(index.filter(|record| {
+        keys.iter()
+            .all(|(tag, vr, key)| record.get(*tag).is_some_and(|value| matches(*vr, key, value)))
+    }))
+}
+
+/// C-FIND: 按信息模型与级别在索引中查找, 每个匹配的实体返回一个响应标识
+pub fn find(
+    index: &MetadataIndex,
+    model: &str,
+    identifier: &InMemDicomObject,
+    retrieve_ae: &str,
+) -> Result<Vec<InMemDicomObject>, QueryError> {
+    let level = query_level(model, identifier)?;
+    let keys = matching_keys(identifier);
+    // ModalitiesInStudy 与计数属性在分组后匹配
+    let (group_keys, record_keys): (Vec<_>, Vec<_>) = keys
+        .iter()
+        .partition(|(tag, _, _)| computed_value(*tag, &[]).is_some());
+    let record_matches = |record: &IndexRecord| {
+        record_keys.iter().all(|(tag, vr, key)| match record.get(*tag) {
+            Some(value) => matches(*vr, key, value),
+            // 索引不支持的可选键不参与匹配, 响应中返回空值
+            None => true,
+        })
+    };
+
+    // 按级别的唯一键分组; 计数与 ModalitiesInStudy 需要整个实体下的全部记录
+    let unique_key = level.unique_key();
+    let mut groups: BTreeMap<String, Vec<IndexRecord>> = BTreeMap::new();
+    for record in index.filter(|_| true) {
+        let key = record.get(unique_key).unwrap_or_default().to_string();
+        groups.entry(key).or_default().push(record);
+    }
+    let mut responses = Vec::new();
+    for (key, group) in groups {
+        let Some(first) = group.iter().find(|r| record_matches(r)) else {
+            continue;
+        };
+        if !group_keys
+            .iter()
+            .all(|(tag, vr, k)| matches(*vr, k, &computed_value(*tag, &group).unwrap_or_default()))
+        {
+            continue;
+        }
+        // 索引中的值是 Unicode 字符串, 响应统一按 UTF-8 编码
+        let mut response = InMemDicomObject::new_empty();
+        response.put(DataElement::new(
+            tags::SPECIFIC_CHARACTER_SET,
+            VR::CS,
+            PrimitiveValue::from(UTF8_CHARACTER_SET),
+        ));
+        for (tag, _, _) in &keys {
+            let value = computed_value(*tag, &group)
+                .or_else(|| first.get(*tag).map(str::to_string))
+                .unwrap_or_default();
+            let element = element_from_tag_keyed(*tag, &json!(value)).unwrap_or_else(|_| {
+                DataElement::new(*tag, dictionary_vr(*tag), PrimitiveValue::Empty)
+            });
+            response.put(element);
+        }
+        response.put(DataElement::new(
+            tags::QUERY_RETRIEVE_LEVEL,
+            VR::CS,
+            PrimitiveValue::from(level.as_str()),
+        ));
+        response.put(DataElement::new(
+            unique_key,
+            dictionary_vr(unique_key),
+            PrimitiveValue::from(key.as_str()),
+        ));
+        if identifier.get(tags::RETRIEVE_AE_TITLE).is_some() {
+            response.put(DataElement::new(
+                tags::RETRIEVE_AE_TITLE,
+                VR::AE,
+                PrimitiveValue::from(retrieve_ae),
+            ));
+        }
+        responses.push(response);
+    }
+    Ok(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_rules() {
+        assert!(matches(VR::LO, "", "anything"));
+        assert!(matches(VR::PN, "ZHANG*", "ZHANG^SAN"));
+        assert!(!matches(VR::PN, "zhang*", "ZHANG^SAN"));
+        assert!(!matches(VR::PN, "zhang^san", "ZHANG^SAN"));
+        assert!(matches(VR::PN, "?HANG^SAN", "ZHANG^SAN"));
+        assert!(!matches(VR::PN, "LI*", "ZHANG^SAN"));
+        assert!(matches(VR::UI, "1.2.3\\1.2.4", "1.2.4"));
+        assert!(!matches(VR::UI, "1.2.3\\1.2.4", "1.2.5"));
+        assert!(matches(VR::DA, "20240101-20241231", "20240615"));
+        assert!(matches(VR::DA, "-20240101", "20231231"));
+        assert!(matches(VR::DA, "20240101-", "20240101"));
+        assert!(!matches(VR::DA, "20240101-", "20231231"));
+        assert!(!matches(VR::DA, "20240101-", ""));
+        assert!(matches(VR::TM, "0800-1200", "093015.123"));
+        assert!(matches(VR::CS, "CT", "CT\\PT"));
+        assert!(!matches(VR::CS, "MR", "CT"));
+    }
+
+    fn test_index() -> MetadataIndex {
+        let obj = dicom_object::open_file(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+        )
+        .unwrap();
+        let index = MetadataIndex::default();
+        // 同一病人的两个检查, 第二个检查有两个 MR 序列
+        for (study, series, sop, modality, date) in [
+            ("1.1", "1.1.1", "1.1.1.1", "CT", "20240105"),
+            ("1.2", "1.2.1", "1.2.1.1", "MR", "20240301"),
+            ("1.2", "1.2.1", "1.2.1.2", "MR", "20240301"),
+            ("1.2", "1.2.2", "1.2.2.1", "MR", "20240301"),
+        ] {
+            let mut obj = obj.clone();
+            for (tag, vr, value) in [
+                (tags::STUDY_INSTANCE_UID, VR::UI, study),
+                (tags::SERIES_INSTANCE_UID, VR::UI, series),
+                (tags::SOP_INSTANCE_UID, VR::UI, sop),
+                (tags::MODALITY, VR::CS, modality),
+                (tags::STUDY_DATE, VR::DA, date),
+                (tags::PATIENT_ID, VR::LO, "P1"),
+                (tags::PATIENT_NAME, VR::PN, "ZHANG^SAN"),
+            ] {
+                obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+            }
+            index.insert(IndexRecord::new(std::path::Path::new(sop), &obj));
+        }
+        index
+    }
+
+    fn identifier(level: &str, keys: &[(Tag, VR, &str)]) -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(tags::QUERY_RETRIEVE_LEVEL, VR::CS, PrimitiveValue::from(level)));
+        for (tag, vr, value) in keys {
+            obj.put(DataElement::new(*tag, *vr, PrimitiveValue::from(*value)));
+        }
+        obj
+    }
+
+    fn values(responses: &[InMemDicomObject], tag: Tag) -> Vec<String> {
+        responses.iter().map(|r| dimse::get_str(r, tag).unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn test_find_levels() {
+        let index = test_index();
+        let rq = identifier(
+            "PATIENT",
+            &[
+                (tags::PATIENT_NAME, VR::PN, "ZHANG*"),
+                (tags::NUMBER_OF_PATIENT_RELATED_STUDIES, VR::IS, ""),
+                (tags::NUMBER_OF_PATIENT_RELATED_INSTANCES, VR::IS, ""),
+            ],
+        );
+        let found = find(&index, PATIENT_ROOT_FIND, &rq, "QR").unwrap();
+        assert_eq!(values(&found, tags::PATIENT_ID), ["P1"]);
+        assert_eq!(values(&found, tags::NUMBER_OF_PATIENT_RELATED_STUDIES), ["2"]);
+        assert_eq!(values(&found, tags::NUMBER_OF_PATIENT_RELATED_INSTANCES), ["4"]);
+        // Study Root 没有 PATIENT 级别
+        let err = find(&index, STUDY_ROOT_FIND, &rq, "QR").unwrap_err();
+        assert_eq!(err.status, status::DATA_SET_DOES_NOT_MATCH);
+
+        let rq = identifier(
+            "STUDY",
+            &[
+                (tags::STUDY_DATE, VR::DA, "20240201-"),
+                (tags::MODALITIES_IN_STUDY, VR::CS, ""),
+                (tags::NUMBER_OF_STUDY_RELATED_SERIES, VR::IS, ""),
+                (tags::RETRIEVE_AE_TITLE, VR::AE, ""),
+            ],
+        );
+        let found = find(&index, STUDY_ROOT_FIND, &rq, "QR").unwrap();
+        assert_eq!(values(&found, tags::STUDY_INSTANCE_UID), ["1.2"]);
+        assert_eq!(values(&found, tags::MODALITIES_IN_STUDY), ["MR"]);
+        assert_eq!(values(&found, tags::NUMBER_OF_STUDY_RELATED_SERIES), ["2"]);
+        assert_eq!(values(&found, tags::RETRIEVE_AE_TITLE), ["QR"]);
+
+        let rq = identifier(
+            "SERIES",
+            &[
+                (tags::STUDY_INSTANCE_UID, VR::UI, "1.2"),
+                (tags::NUMBER_OF_SERIES_RELATED_INSTANCES, VR::IS, ""),
+            ],
+        );
+        let found = find(&index, STUDY_ROOT_FIND, &rq, "QR").unwrap();
+        assert_eq!(values(&found, tags::SERIES_INSTANCE_UID), ["1.2.1", "1.2.2"]);
+        assert_eq!(values(&found, tags::NUMBER_OF_SERIES_RELATED_INSTANCES), ["2", "1"]);
+
+        // UID 列表
+        let rq = identifier("IMAGE", &[(tags::SOP_INSTANCE_UID, VR::UI, "1.1.1.1\\1.2.2.1")]);
+        let found = find(&index, PATIENT_ROOT_FIND, &rq, "QR").unwrap();
+        assert_eq!(values(&found, tags::SOP_INSTANCE_UID), ["1.1.1.1", "1.2.2.1"]);
+    }
+
+    #[test]
+    fn test_find_non_ascii_patient_name() {
+        use dicom_transfer_syntax_registry::entries::EXPLICIT_VR_LITTLE_ENDIAN;
+
+        let mut obj = dicom_object::open_file(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+        )
+        .unwrap();
+        obj.put(DataElement::new(tags::PATIENT_NAME, VR::PN, PrimitiveValue::from("张^三")));
+        let index = MetadataIndex::default();
+        index.insert(IndexRecord::new(std::path::Path::new("a.dcm"), &obj));
+
+        let rq = identifier("PATIENT", &[(tags::PATIENT_NAME, VR::PN, "张*")]);
+        let found = find(&index, PATIENT_ROOT_FIND, &rq, "QR").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(dimse::get_str(&found[0], tags::SPECIFIC_CHARACTER_SET).as_deref(), Some(UTF8_CHARACTER_SET));
+        // 响应能按其 Specific Character Set 编码并原样解码
+        let ts = EXPLICIT_VR_LITTLE_ENDIAN.erased();
+        let mut data = Vec::new();
+        found[0].write_dataset_with_ts(&mut data, &ts).unwrap();
+        let decoded = InMemDicomObject::read_dataset_with_ts(data.as_slice(), &ts).unwrap();
+        assert_eq!(dimse::get_str(&decoded, tags::PATIENT_NAME).as_deref(), Some("张^三"));
+    }
+
+    #[test]
+    fn test_retrieve_by_unique_keys() {
+        let index = test_index();
+        let sops = |records: Vec<IndexRecord>| records.into_iter().map(|r| r.image.sop_uid).collect::<Vec<_>>();
+        let rq = identifier("STUDY", &[(tags::STUDY_INSTANCE_UID, VR::UI, "1.2")]);
+        assert_eq!(sops(retrieve(&index, STUDY_ROOT_MOVE, &rq).unwrap()), ["1.2.1.1", "1.2.1.2", "1.2.2.1"]);
+        let rq = identifier(
+            "SERIES",
+            &[
+                (tags::STUDY_INSTANCE_UID, VR::UI, "1.2"),
+                (tags::SERIES_INSTANCE_UID, VR::UI, "1.2.2\\1.1.1"),
+            ],
+        );
+        assert_eq!(sops(retrieve(&index, STUDY_ROOT_GET, &rq).unwrap()), ["1.2.2.1"]);
+        let rq = identifier("PATIENT", &[(tags::PATIENT_ID, VR::LO, "P1")]);
+        assert_eq!(retrieve(&index, PATIENT_ROOT_GET, &rq).unwrap().len(), 4);
+        assert!(retrieve(&index, STUDY_ROOT_GET, &rq).is_err());
+        // 缺少该级别的唯一键
+        let rq = identifier("SERIES", &[(tags::STUDY_INSTANCE_UID, VR::UI, "1.2")]);
+        assert_eq!(retrieve(&index, STUDY_ROOT_MOVE, &rq).unwrap_err().status, status::DATA_SET_DOES_NOT_MATCH);
+    }
+}
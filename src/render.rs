@@ -0,0 +1,577 @@
+use crate::error::DcmToolsError;
+use crate::image_info::ImageInfo;
+use crate::scu::{decompress, is_native};
+use clap::ValueEnum;
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::pixeldata::{DecodedPixelData, PhotometricInterpretation, PixelDecoder, PixelRepresentation, PlanarConfiguration};
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// 窗宽窗位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub center: f64,
+    pub width: f64,
+}
+
+/// 解析 "C,W" 形式的窗位与窗宽 (用作 clap value_parser)
+pub fn parse_window(value: &str) -> Result<Window, String> {
+    let (center, width) = value
+        .split_once(',')
+        .ok_or_else(|| format!("invalid window: {} (expected CENTER,WIDTH)", value))?;
+    let parse = |s: &str| s.trim().parse::<f64>().map_err(|e| format!("invalid window {}: {}", value, e));
+    let window = Window {
+        center: parse(center)?,
+        width: parse(width)?,
+    };
+    if window.width.is_nan() || window.width < 1.0 {
+        return Err(format!("invalid window {}: width must be >= 1", value));
+    }
+    Ok(window)
+}
+
+/// 常用 CT 窗 (HU)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    Brain,
+    Subdural,
+    Stroke,
+    Lung,
+    Mediastinum,
+    Abdomen,
+    Liver,
+    Bone,
+}
+
+impl Preset {
+    pub fn window(self) -> Window {
+        let (center, width) = match self {
+            Preset::Brain => (40.0, 80.0),
+            Preset::Subdural => (75.0, 215.0),
+            Preset::Stroke => (40.0, 40.0),
+            Preset::Lung => (-600.0, 1500.0),
+            Preset::Mediastinum => (50.0, 350.0),
+            Preset::Abdomen => (50.0, 400.0),
+            Preset::Liver => (30.0, 150.0),
+            Preset::Bone => (400.0, 1800.0),
+        };
+        Window { center, width }
+    }
+}
+
+/// VOI LUT Function (0028,1056)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VoiFunction {
+    Linear,
+    LinearExact,
+    Sigmoid,
+}
+
+impl VoiFunction {
+    /// 按 PS3.3 C.11.2.1.2 把值映射到 [0, 1]
+    fn apply(self, window: Window, x: f64) -> f64 {
+        let Window { center, width } = window;
+        match self {
+            VoiFunction::Linear => {
+                if x <= center - 0.5 - (width - 1.0) / 2.0 {
+                    0.0
+                } else if x > center - 0.5 + (width - 1.0) / 2.0 {
+                    1.0
+                } else {
+                    (x - (center - 0.5)) / (width - 1.0) + 0.5
+                }
+            }
+            VoiFunction::LinearExact => ((x - center) / width + 0.5).clamp(0.0, 1.0),
+            VoiFunction::Sigmoid => 1.0 / (1.0 + (-4.0 * (x - center) / width).exp()),
+        }
+    }
+}
+
+/// LUT Descriptor + LUT Data, 用于 Modality LUT, VOI LUT 和调色板
+#[derive(Debug, Clone)]
+struct Lut {
+    first: i64,
+    bits: u32,
+    data: Vec<u32>,
+}
+
+impl Lut {
+    fn read(obj: &InMemDicomObject, descriptor: Tag, data: Tag, signed: bool) -> Option<Lut> {
+        let descriptor: Vec<i64> = obj.element_opt(descriptor).ok().flatten()?.to_multi_int().ok()?;
+        let [entries, first, bits] = descriptor[..] else {
+            return None;
+        };
+        let entries = if entries == 0 { 65536 } else { entries as usize };
+        // 像素值有符号时, 以 US 存储的第一个映射值需按 SS 解释
+        let first = if signed && first > 32767 { first - 65536 } else { first };
+        let mut data: Vec<u32> = obj.element_opt(data).ok().flatten()?.to_multi_int().ok()?;
+        if data.len() < entries {
+            return None;
+        }
+        data.truncate(entries);
+        Some(Lut {
+            first,
+            bits: bits.clamp(1, 16) as u32,
+            data,
+        })
+    }
+
+    /// 序列第一个条目中的 LUT
+    fn from_sequence(obj: &InMemDicomObject, sequence: Tag, signed: bool) -> Option<Lut> {
+        let item = obj.element_opt(sequence).ok().flatten()?.items()?.first()?;
+        Lut::read(item, tags::LUT_DESCRIPTOR, tags::LUT_DATA, signed)
+    }
+
+    fn get(&self, x: f64) -> f64 {
+        let index = (x.round() as i64 - self.first).clamp(0, self.data.len() as i64 - 1);
+        f64::from(self.data[index as usize])
+    }
+
+    /// 映射到 [0, 1]
+    fn normalized(&self, x: f64) -> f64 {
+        self.get(x) / ((1u64 << self.bits) - 1) as f64
+    }
+}
+
+/// VOI 变换的来源, 优先级: 命令行窗口 > VOI LUT Sequence > Window Center/Width > 最小最大值
+#[derive(Debug, Clone)]
+enum Voi {
+    Window(Window, VoiFunction),
+    Lut(Lut),
+    MinMax,
+}
+
+/// 渲染好的一帧, 样本按行优先排列, 彩色为 RGB 交错
+#[derive(Debug, Clone)]
+pub struct RenderedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u8,
+    pub bits: u8,
+    pub samples: Vec<u16>,
+}
+
+impl RenderedFrame {
+    /// 8 位输出时每个样本一个字节, 16 位为 PNG 要求的大端
+    fn bytes(&self) -> Vec<u8> {
+        if self.bits == 16 {
+            self.samples.iter().flat_map(|s| s.to_be_bytes()).collect()
+        } else {
+            self.samples.iter().map(|&s| s as u8).collect()
+        }
+    }
+
+    pub fn encode_png(&self, writer: impl Write) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(if self.channels == 3 { png::ColorType::Rgb } else { png::ColorType::Grayscale });
+        encoder.set_depth(if self.bits == 16 { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.bytes())?;
+        writer.finish()
+    }
+
+    /// JPEG 只支持 8 位输出
+    pub fn encode_jpeg(&self, quality: u8) -> Result<Vec<u8>, jpeg_encoder::EncodingError> {
+        let mut buffer = Vec::new();
+        let color = if self.channels == 3 { jpeg_encoder::ColorType::Rgb } else { jpeg_encoder::ColorType::Luma };
+        let data: Vec<u8> = if self.bits == 16 {
+            self.samples.iter().map(|&s| (s >> 8) as u8).collect()
+        } else {
+            self.bytes()
+        };
+        jpeg_encoder::Encoder::new(&mut buffer, quality).encode(&data, self.width as u16, self.height as u16, color)?;
+        Ok(buffer)
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<(), DcmToolsError> {
+        let file = File::create(path).map_err(|e| DcmToolsError::write(path, e))?;
+        self.encode_png(BufWriter::new(file)).map_err(|e| DcmToolsError::write(path, e))
+    }
+}
+
+fn text(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.element_opt(tag)
+        .ok()
+        .flatten()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 多值属性的第一个值, 如 "40\\400" 中的 40
+fn first_value(value: &str) -> Option<f64> {
+    value.split('\\').next()?.trim().parse().ok()
+}
+
+/// 解码像素数据; 本地解码器不支持的压缩传输语法先经 gdcm 解压
+pub fn decode(path: &Path, obj: &DefaultDicomObject) -> Result<DecodedPixelData<'static>, DcmToolsError> {
+    match obj.decode_pixel_data() {
+        Ok(decoded) => Ok(decoded.to_owned()),
+        Err(e) if is_native(obj.meta().transfer_syntax()) => Err(DcmToolsError::transcode(path, e)),
+        Err(_) => {
+            let native = decompress(path, obj)?;
+            let decoded = native.decode_pixel_data().map_err(|e| DcmToolsError::transcode(path, e))?;
+            Ok(decoded.to_owned())
+        }
+    }
+}
+
+/// 一帧的原始样本值: 按 Bits Stored 截取, 有符号时做符号扩展
+pub(crate) fn stored_values(decoded: &DecodedPixelData, frame: u32) -> Result<Vec<f64>, String> {
+    let data = decoded.frame_data(frame).map_err(|e| e.to_string())?;
+    let raw: Vec<u32> = match decoded.bits_allocated() {
+        8 => data.iter().map(|&b| u32::from(b)).collect(),
+        16 => decoded.frame_data_ow(frame).map_err(|e| e.to_string())?.into_iter().map(u32::from).collect(),
+        32 => data.chunks_exact(4).map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect(),
+        bits => return Err(format!("unsupported Bits Allocated {}", bits)),
+    };
+    let bits_stored = u32::from(decoded.bits_stored()).clamp(1, 32);
+    let mask = if bits_stored == 32 { u32::MAX } else { (1 << bits_stored) - 1 };
+    let signed = decoded.pixel_representation() == PixelRepresentation::Signed;
+    Ok(raw
+        .into_iter()
+        .map(|v| {
+            let v = v & mask;
+            if signed && v >> (bits_stored - 1) & 1 == 1 {
+                f64::from(v) - (1u64 << bits_stored) as f64
+            } else {
+                f64::from(v)
+            }
+        })
+        .collect())
+}
+
+/// 渲染参数: 窗口覆盖和输出位深
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    pub window: Option<Window>,
+    pub bits: u8,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self { window: None, bits: 8 }
+    }
+}
+
+impl Renderer {
+    fn max_output(&self) -> f64 {
+        if self.bits == 16 { 65535.0 } else { 255.0 }
+    }
+
+    fn quantize(&self, value: f64) -> u16 {
+        (value.clamp(0.0, 1.0) * self.max_output()).round() as u16
+    }
+
+    /// 渲染一帧 (frame 从 0 开始)
+    pub fn render_frame(
+        &self,
+        obj: &DefaultDicomObject,
+        decoded: &DecodedPixelData,
+        frame: u32,
+    ) -> Result<RenderedFrame, String> {
+        let values = stored_values(decoded, frame)?;
+        let (samples, channels) = match decoded.photometric_interpretation() {
+            PhotometricInterpretation::Monochrome1 | PhotometricInterpretation::Monochrome2 => {
+                (self.render_monochrome(obj, decoded, frame, values)?, 1)
+            }
+            PhotometricInterpretation::PaletteColor => (self.render_palette(obj, decoded, &values)?, 3),
+            PhotometricInterpretation::Rgb
+            | PhotometricInterpretation::YbrFull
+            | PhotometricInterpretation::YbrFull422
+                if decoded.samples_per_pixel() == 3 =>
+            {
+                (self.render_color(decoded, values), 3)
+            }
+            other => return Err(format!("unsupported Photometric Interpretation {}", other)),
+        };
+        Ok(RenderedFrame {
+            width: decoded.columns(),
+            height: decoded.rows(),
+            channels,
+            bits: self.bits,
+            samples,
+        })
+    }
+
+    /// Modality LUT (LUT 或 Rescale) -> VOI -> MONOCHROME1 反转
+    fn render_monochrome(
+        &self,
+        obj: &DefaultDicomObject,
+        decoded: &DecodedPixelData,
+        frame: u32,
+        values: Vec<f64>,
+    ) -> Result<Vec<u16>, String> {
+        let info = ImageInfo::new(obj);
+        let signed = decoded.pixel_representation() == PixelRepresentation::Signed;
+        let values: Vec<f64> = match Lut::from_sequence(obj, tags::MODALITY_LUT_SEQUENCE, signed) {
+            Some(lut) => values.into_iter().map(|v| lut.get(v)).collect(),
+            None => {
+                let rescale = decoded.rescale().map_err(|e| e.to_string())?;
+                let rescale = rescale.get(frame as usize).or(rescale.first());
+                let (slope, intercept) = rescale.map(|r| (r.slope, r.intercept)).unwrap_or((1.0, 0.0));
+                values.into_iter().map(|v| v * slope + intercept).collect()
+            }
+        };
+        let function = match text(obj, tags::VOILUT_FUNCTION).as_deref() {
+            Some("LINEAR_EXACT") => VoiFunction::LinearExact,
+            Some("SIGMOID") => VoiFunction::Sigmoid,
+            _ => VoiFunction::Linear,
+        };
+        let window = (first_value(&info.window_center), first_value(&info.window_width));
+        let voi = if let Some(window) = self.window {
+            Voi::Window(window, VoiFunction::Linear)
+        } else if let Some(lut) = Lut::from_sequence(obj, tags::VOILUT_SEQUENCE, false) {
+            Voi::Lut(lut)
+        } else if let (Some(center), Some(width)) = window
+            && width >= 1.0
+        {
+            Voi::Window(Window { center, width }, function)
+        } else {
+            Voi::MinMax
+        };
+        let (min, max) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)));
+        let invert = info.photometric_interpretation.trim() == "MONOCHROME1";
+        Ok(values
+            .into_iter()
+            .map(|v| {
+                let v = match &voi {
+                    Voi::Window(window, function) => function.apply(*window, v),
+                    Voi::Lut(lut) => lut.normalized(v),
+                    Voi::MinMax if max > min => (v - min) / (max - min),
+                    Voi::MinMax => 0.0,
+                };
+                self.quantize(if invert { 1.0 - v } else { v })
+            })
+            .collect())
+    }
+
+    fn render_palette(
+        &self,
+        obj: &DefaultDicomObject,
+        decoded: &DecodedPixelData,
+        values: &[f64],
+    ) -> Result<Vec<u16>, String> {
+        let signed = decoded.pixel_representation() == PixelRepresentation::Signed;
+        let lut = |descriptor, data| {
+            Lut::read(obj, descriptor, data, signed).ok_or_else(|| "missing or segmented palette color lookup table".to_string())
+        };
+        let palette = [
+            lut(tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR, tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DATA)?,
+            lut(tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR, tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DATA)?,
+            lut(tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR, tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DATA)?,
+        ];
+        Ok(values
+            .iter()
+            .flat_map(|&v| palette.iter().map(move |lut| lut.normalized(v)))
+            .map(|v| self.quantize(v))
+            .collect())
+    }
+
+    /// RGB、YBR_FULL 与解码为完整样本的 YBR_FULL_422; 不做 VOI 变换
+    fn render_color(&self, decoded: &DecodedPixelData, values: Vec<f64>) -> Vec<u16> {
+        let max = ((1u64 << decoded.bits_stored().clamp(1, 32)) - 1) as f64;
+        let pixels = values.len() / 3;
+        let sample = |i: usize, channel: usize| match decoded.planar_configuration() {
+            PlanarConfiguration::Standard => values[i * 3 + channel] / max,
+            PlanarConfiguration::PixelFirst => values[channel * pixels + i] / max,
+        };
+        let ybr = matches!(
+            decoded.photometric_interpretation(),
+            PhotometricInterpretation::YbrFull | PhotometricInterpretation::YbrFull422
+        );
+        (0..pixels)
+            .flat_map(|i| {
+                let (a, b, c) = (sample(i, 0), sample(i, 1), sample(i, 2));
+                if ybr {
+                    // PS3.3 C.7.6.3.1.2 YBR_FULL -> RGB
+                    let (cb, cr) = (b - 0.5, c - 0.5);
+                    [a + 1.402 * cr, a - 0.344136 * cb - 0.714136 * cr, a + 1.772 * cb]
+                } else {
+                    [a, b, c]
+                }
+            })
+            .map(|v| self.quantize(v))
+            .collect()
+    }
+
+    /// 渲染文件中的帧 (frame 从 1 开始, None 为全部帧) 并写入 PNG;
+    /// output 以 .png 结尾且只有一帧时直接写入该文件, 否则写入目录,
+    /// 以 SOP Instance UID 命名, 多帧时追加 4 位帧号
+    pub fn render_file(&self, path: &Path, output: &Path, frame: Option<u32>) -> Result<Vec<PathBuf>, DcmToolsError> {
+        let obj = dicom_object::open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+        let decoded = decode(path, &obj)?;
+        let frames = decoded.number_of_frames().max(1);
+        let selected: Vec<u32> = match frame {
+            Some(frame) if frame == 0 || frame > frames => {
+                return Err(DcmToolsError::parse(path, format!("frame {} out of range 1..={}", frame, frames)));
+            }
+            Some(frame) => vec![frame],
+            None => (1..=frames).collect(),
+        };
+        let single_file = selected.len() == 1
+            && output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        let directory = if single_file { output.parent().unwrap_or(Path::new("")) } else { output };
+        if !directory.as_os_str().is_empty() {
+            std::fs::create_dir_all(directory).map_err(|e| DcmToolsError::write(directory, e))?;
+        }
+        let sop_uid = obj.meta().media_storage_sop_instance_uid().trim_end_matches('\0').to_string();
+        let mut written = Vec::with_capacity(selected.len());
+        for frame in selected {
+            let rendered = self
+                .render_frame(&obj, &decoded, frame - 1)
+                .map_err(|e| DcmToolsError::transcode(path, e))?;
+            let target = if single_file {
+                output.to_path_buf()
+            } else if frames > 1 {
+                output.join(format!("{}_{:04}.png", sop_uid, frame))
+            } else {
+                output.join(format!("{}.png", sop_uid))
+            };
+            rendered.write_png(&target)?;
+            written.push(target);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::{DataElement, PrimitiveValue, VR, dicom_value};
+    use std::io::BufReader;
+
+    const TEST_FILE: &str = "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm";
+
+    fn read_png(path: &Path) -> (png::OutputInfo, Vec<u8>) {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        buffer.truncate(info.buffer_size());
+        (info, buffer)
+    }
+
+    #[test]
+    fn test_window_functions() {
+        let window = Window { center: 40.0, width: 400.0 };
+        assert_eq!(VoiFunction::Linear.apply(window, -200.0), 0.0);
+        assert_eq!(VoiFunction::Linear.apply(window, 300.0), 1.0);
+        assert!((VoiFunction::Linear.apply(window, 40.0) - 0.5).abs() < 0.01);
+        assert_eq!(VoiFunction::LinearExact.apply(window, 40.0), 0.5);
+        assert_eq!(VoiFunction::Sigmoid.apply(window, 40.0), 0.5);
+        assert_eq!(parse_window("-600,1500").unwrap(), Preset::Lung.window());
+        assert!(parse_window("40").is_err());
+        assert!(parse_window("40,0").is_err());
+    }
+
+    #[test]
+    fn test_render_ct() {
+        let dir = tempfile::tempdir().unwrap();
+        let obj = dicom_object::open_file(TEST_FILE).unwrap();
+        let decoded = decode(Path::new(TEST_FILE), &obj).unwrap();
+
+        // 文件中的窗口 (-600/1300): 空气为黑色
+        let rendered = Renderer::default().render_frame(&obj, &decoded, 0).unwrap();
+        assert_eq!((rendered.width, rendered.height, rendered.channels), (512, 512, 1));
+        assert_eq!(rendered.samples[0], 0);
+        let center = rendered.samples[256 * 512 + 256];
+
+        let output = dir.path().join("ct.png");
+        let written = Renderer { window: Some(Preset::Bone.window()), bits: 16 }
+            .render_file(Path::new(TEST_FILE), &output, None)
+            .unwrap();
+        assert_eq!(written, vec![output.clone()]);
+        let (info, data) = read_png(&output);
+        assert_eq!((info.width, info.height), (512, 512));
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+        assert_eq!(data.len(), 512 * 512 * 2);
+
+        // MONOCHROME1 时灰度反转
+        let mut inverted = obj.clone();
+        inverted.put(DataElement::new(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, dicom_value!(Str, "MONOCHROME1")));
+        let decoded = decode(Path::new(TEST_FILE), &inverted).unwrap();
+        let rendered = Renderer::default().render_frame(&inverted, &decoded, 0).unwrap();
+        assert_eq!(rendered.samples[0], 255);
+        assert_eq!(rendered.samples[256 * 512 + 256], 255 - center);
+    }
+
+    /// JPEG Baseline (4:2:2 采样) 的 YBR_FULL_422 彩色图像: 左半红色, 右半蓝色
+    fn jpeg_ybr_full_422() -> DefaultDicomObject {
+        use dicom::core::value::PixelFragmentSequence;
+        use dicom_object::meta::FileMetaTableBuilder;
+
+        let (width, height) = (32_u16, 16_u16);
+        let rgb: Vec<u8> = (0..height)
+            .flat_map(|_| (0..width).flat_map(|x| if x < width / 2 { [255, 0, 0] } else { [0, 0, 255] }))
+            .collect();
+        let mut jpeg = Vec::new();
+        let mut encoder = jpeg_encoder::Encoder::new(&mut jpeg, 95);
+        encoder.set_sampling_factor(jpeg_encoder::SamplingFactor::R_4_2_2);
+        encoder.encode(&rgb, width, height, jpeg_encoder::ColorType::Rgb).unwrap();
+        if jpeg.len() % 2 == 1 {
+            jpeg.push(0);
+        }
+        InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, dicom_value!(Str, "1.2.840.10008.5.1.4.1.1.7")),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, dicom_value!(Str, "1.2.826.0.1.3680043.2.1125.422")),
+            DataElement::new(tags::SAMPLES_PER_PIXEL, VR::US, dicom_value!(U16, [3])),
+            DataElement::new(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, dicom_value!(Str, "YBR_FULL_422")),
+            DataElement::new(tags::PLANAR_CONFIGURATION, VR::US, dicom_value!(U16, [0])),
+            DataElement::new(tags::ROWS, VR::US, dicom_value!(U16, [height])),
+            DataElement::new(tags::COLUMNS, VR::US, dicom_value!(U16, [width])),
+            DataElement::new(tags::BITS_ALLOCATED, VR::US, dicom_value!(U16, [8])),
+            DataElement::new(tags::BITS_STORED, VR::US, dicom_value!(U16, [8])),
+            DataElement::new(tags::HIGH_BIT, VR::US, dicom_value!(U16, [7])),
+            DataElement::new(tags::PIXEL_REPRESENTATION, VR::US, dicom_value!(U16, [0])),
+            DataElement::new(tags::PIXEL_DATA, VR::OB, PixelFragmentSequence::new(Vec::<u32>::new(), vec![jpeg])),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax("1.2.840.10008.1.2.4.50"))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_jpeg_ybr_full_422() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("us.dcm");
+        jpeg_ybr_full_422().write_to_file(&path).unwrap();
+        let obj = dicom_object::open_file(&path).unwrap();
+        let decoded = decode(&path, &obj).unwrap();
+
+        let rendered = Renderer::default().render_frame(&obj, &decoded, 0).unwrap();
+        assert_eq!((rendered.width, rendered.height, rendered.channels), (32, 16, 3));
+        let pixel = |x: usize, y: usize| {
+            let i = (y * 32 + x) * 3;
+            [rendered.samples[i], rendered.samples[i + 1], rendered.samples[i + 2]]
+        };
+        let close = |a: [u16; 3], b: [u16; 3]| a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 24);
+        assert!(close(pixel(4, 8), [255, 0, 0]), "{:?}", pixel(4, 8));
+        assert!(close(pixel(28, 8), [0, 0, 255]), "{:?}", pixel(28, 8));
+
+        let output = dir.path().join("us.png");
+        Renderer::default().render_file(&path, &output, Some(1)).unwrap();
+        let (info, data) = read_png(&output);
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(data.len(), 32 * 16 * 3);
+
+        // 解压后仍标记为 YBR_FULL_422 且每像素 3 个样本 (如 gdcm 的输出): 纯红色为 (76, 85, 255)
+        let mut native = obj.clone();
+        native.put(DataElement::new(tags::PIXEL_DATA, VR::OB, PrimitiveValue::from([76_u8, 85, 255].repeat(32 * 16))));
+        native.update_meta(|meta| meta.transfer_syntax = "1.2.840.10008.1.2.1\0".to_string());
+        let decoded = native.decode_pixel_data().unwrap();
+        assert_eq!(decoded.photometric_interpretation(), &PhotometricInterpretation::YbrFull422);
+        let rendered = Renderer::default().render_frame(&native, &decoded, 0).unwrap();
+        assert!(close([rendered.samples[0], rendered.samples[1], rendered.samples[2]], [255, 0, 0]));
+
+        // 未解压的 4:2:2 数据 (每像素 2 个样本) 报错而不是输出错位的图像
+        native.put(DataElement::new(tags::PIXEL_DATA, VR::OB, PrimitiveValue::from([76_u8, 76, 85, 255].repeat(16 * 16))));
+        let decoded = native.decode_pixel_data().unwrap();
+        assert!(Renderer::default().render_frame(&native, &decoded, 0).is_err());
+    }
+}
@@ -0,0 +1,1106 @@
+use crate::association::{
+    AcceptOptions, Association, DEFAULT_TRANSFER_SYNTAXES, Incoming, Message, NetError, PollRead,
+    VERIFICATION_SOP_CLASS, parse_ae_title,
+};
+use crate::commands::parse_ttl;
+use crate::dimse::{self, C_ECHO_RQ, C_FIND_RQ, C_GET_RQ, C_MOVE_RQ, C_STORE_RQ, SubOperations, status};
+use crate::error::DcmToolsError;
+use crate::index::{IndexRecord, MetadataIndex};
+use crate::query::{
+    PATIENT_ROOT_FIND, PATIENT_ROOT_GET, PATIENT_ROOT_MOVE, QueryError, STUDY_ROOT_FIND, STUDY_ROOT_GET,
+    STUDY_ROOT_MOVE, find, retrieve,
+};
+use crate::scu::{ScuArgs, SendItem, plan_associations, store_one};
+use crate::storage::{STORAGE_SOP_CLASS_ROOT, storage_transfer_syntaxes, store_instance};
+use crate::tls::{NetStream, TlsArgs};
+use clap::Args;
+use rustls::ServerConfig;
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::tags;
+use dicom_encoding::TransferSyntaxIndex;
+use dicom_encoding::transfer_syntax::TransferSyntax;
+use dicom_object::InMemDicomObject;
+use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+use dicom_ul::pdu::DEFAULT_MAX_PDU;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// SCP 的监听参数
+#[derive(Debug, Clone, Args)]
+pub struct ScpArgs {
+    /// 监听地址
+    #[arg(long = "bind", default_value = "0.0.0.0")]
+    pub bind: String,
+    /// 监听端口
+    #[arg(short = 'p', long = "port", default_value_t = 11112)]
+    pub port: u16,
+    /// 本端 AE Title
+    #[arg(long = "ae-title", default_value = "DCMTOOLS", value_parser = parse_ae_title)]
+    pub ae_title: String,
+    /// 拒绝 Called AE Title 与 --ae-title 不一致的关联
+    #[arg(long = "strict-ae")]
+    pub strict_ae: bool,
+    /// 允许的 Calling AE Title, 可多次指定, 不指定时不限制
+    #[arg(long = "allow-calling", value_parser = parse_ae_title)]
+    pub allowed_calling: Vec<String>,
+    /// 关联空闲超时, 超时后关闭连接
+    #[arg(long = "timeout", default_value = "60s", value_parser = parse_ttl)]
+    pub timeout: Duration,
+    /// 本端可接收的最大 PDU
+    #[arg(long = "max-pdu", default_value_t = DEFAULT_MAX_PDU)]
+    pub max_pdu: u32,
+    #[command(flatten)]
+    pub tls: TlsArgs,
+}
+
+impl ScpArgs {
+    pub fn listen(&self) -> std::io::Result<TcpListener> {
+        TcpListener::bind((self.bind.as_str(), self.port))
+    }
+
+    pub fn accept_options(&self) -> AcceptOptions {
+        AcceptOptions {
+            ae_title: self.ae_title.clone(),
+            strict_called_ae: self.strict_ae,
+            allowed_calling_aes: self.allowed_calling.clone(),
+            max_pdu: self.max_pdu,
+            abstract_syntaxes: vec![VERIFICATION_SOP_CLASS.to_string()],
+            abstract_syntax_prefixes: Vec::new(),
+            transfer_syntaxes: DEFAULT_TRANSFER_SYNTAXES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// C-MOVE 目的地, 命令行格式 AE=host:port, 用 TLS 连接时为 AE=tls://host:port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveDestination {
+    pub ae_title: String,
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+pub fn parse_move_destination(s: &str) -> Result<MoveDestination, String> {
+    let (ae_title, address) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected AE=host:port, got '{}'", s))?;
+    let (tls, address) = match address.strip_prefix("tls://") {
+        Some(address) => (true, address),
+        None => (false, address),
+    };
+    let (host, port) = address
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected host:port, got '{}'", address))?;
+    let port = port.parse().map_err(|e| format!("invalid port '{}': {}", port, e))?;
+    if host.is_empty() {
+        return Err(format!("missing host in '{}'", s));
+    }
+    Ok(MoveDestination {
+        ae_title: parse_ae_title(ae_title)?,
+        host: host.to_string(),
+        port,
+        tls,
+    })
+}
+
+/// SCP 提供的服务
+pub struct ScpService {
+    pub args: ScpArgs,
+    /// 设置后接受 C-STORE, 写入 storage/patient/study/series/sop.dcm
+    pub storage: Option<PathBuf>,
+    /// 设置后接受 C-FIND/C-MOVE/C-GET, 新存储的实例同时加入索引
+    pub index: Option<Arc<MetadataIndex>>,
+    /// C-MOVE 允许的目的地
+    pub move_destinations: Vec<MoveDestination>,
+}
+
+impl ScpService {
+    pub fn new(args: ScpArgs) -> Self {
+        Self {
+            args,
+            storage: None,
+            index: None,
+            move_destinations: Vec::new(),
+        }
+    }
+
+    pub fn with_move_destinations(mut self, destinations: Vec<MoveDestination>) -> Self {
+        self.move_destinations = destinations;
+        self
+    }
+
+    pub fn with_storage(mut self, dest: PathBuf) -> Self {
+        self.storage = Some(dest);
+        self
+    }
+
+    pub fn with_index(mut self, index: Arc<MetadataIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// 在 ScpArgs 的基础上加入已启用服务的 SOP Class 与传输语法
+    pub fn accept_options(&self) -> AcceptOptions {
+        let mut options = self.args.accept_options();
+        if self.storage.is_some() {
+            options
+                .abstract_syntax_prefixes
+                .push(STORAGE_SOP_CLASS_ROOT.to_string());
+            for ts in storage_transfer_syntaxes() {
+                if !options.transfer_syntaxes.contains(&ts) {
+                    options.transfer_syntaxes.push(ts);
+                }
+            }
+        }
+        if self.index.is_some() {
+            options.abstract_syntaxes.extend(
+                [
+                    PATIENT_ROOT_FIND,
+                    STUDY_ROOT_FIND,
+                    PATIENT_ROOT_MOVE,
+                    STUDY_ROOT_MOVE,
+                    PATIENT_ROOT_GET,
+                    STUDY_ROOT_GET,
+                ]
+                .map(str::to_string),
+            );
+        }
+        options
+    }
+
+    /// 接受连接, 每个关联一个线程
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> Result<(), NetError> {
+        // 证书在启动时加载, 配置错误直接退出而不是每个连接报一次
+        let tls = match self.args.tls.enabled {
+            true => Some(self.args.tls.server_config()?),
+            false => None,
+        };
+        if self.move_destinations.iter().any(|d| d.tls) {
+            if !self.args.tls.enabled {
+                return Err(NetError::Tls("TLS move destinations require --tls".to_string()));
+            }
+            self.args.tls.client_config()?;
+        }
+        eprintln!(
+            "[{}] listening on {}{}",
+            self.args.ae_title,
+            listener.local_addr()?,
+            if tls.is_some() { " (TLS)" } else { "" }
+        );
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("accept failed: {}", e);
+                    continue;
+                }
+            };
+            let service = Arc::clone(&self);
+            let tls = tls.clone();
+            std::thread::spawn(move || {
+                let peer = stream
+                    .peer_addr()
+                    .map(|a| a.to_string())
+                    .unwrap_or_default();
+                if let Err(e) = service.handle_connection(stream, tls) {
+                    eprintln!("[{}] association from {} failed: {}", service.args.ae_title, peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn handle_connection(&self, stream: TcpStream, tls: Option<Arc<ServerConfig>>) -> Result<(), NetError> {
+        stream.set_read_timeout(Some(self.args.timeout))?;
+        stream.set_write_timeout(Some(self.args.timeout))?;
+        let stream = match tls {
+            Some(config) => NetStream::server(stream, config)?,
+            None => NetStream::Plain(stream),
+        };
+        let assoc = Association::accept(stream, &self.accept_options())?;
+        self.handle_association(assoc)
+    }
+
+    /// 处理一个已建立的关联, 直到对端释放
+    pub fn handle_association<S: Read + Write + PollRead>(&self, mut assoc: Association<S>) -> Result<(), NetError> {
+        loop {
+            match assoc.receive_message()? {
+                Incoming::Release => return assoc.confirm_release(),
+                Incoming::Message(msg) => self.dispatch(&mut assoc, msg)?,
+            }
+        }
+    }
+
+    fn dispatch<S: Read + Write + PollRead>(&self, assoc: &mut Association<S>, msg: Message) -> Result<(), NetError> {
+        let pc_id = msg.presentation_context_id;
+        match dimse::command_field(&msg.command) {
+            Some(C_ECHO_RQ) => {
+                assoc.send_message(pc_id, &dimse::response(&msg.command, status::SUCCESS, false), None)
+            }
+            Some(C_STORE_RQ) => {
+                let status = self.store(assoc, &msg);
+                assoc.send_message(pc_id, &dimse::response(&msg.command, status, false), None)
+            }
+            Some(C_FIND_RQ) if self.index.is_some() => self.find(assoc, &msg),
+            Some(C_GET_RQ | C_MOVE_RQ) if self.index.is_some() => self.retrieve(assoc, &msg),
+            _ => assoc.send_message(
+                pc_id,
+                &dimse::response(&msg.command, status::UNRECOGNIZED_OPERATION, false),
+                None,
+            ),
+        }
+    }
+
+    /// 处理 C-STORE-RQ, 返回响应状态
+    fn store<S: Read + Write>(&self, assoc: &Association<S>, msg: &Message) -> u16 {
+        let (Some(dest), Some(pc)) = (
+            &self.storage,
+            assoc
+                .contexts
+                .iter()
+                .find(|pc| pc.id == msg.presentation_context_id),
+        ) else {
+            return status::SOP_CLASS_NOT_SUPPORTED;
+        };
+        let sop_class = dimse::get_str(&msg.command, tags::AFFECTED_SOP_CLASS_UID).unwrap_or_default();
+        if sop_class != pc.abstract_syntax {
+            eprintln!(
+                "[{}] C-STORE from {}: SOP class {} sent on context for {}",
+                self.args.ae_title, assoc.calling_ae, sop_class, pc.abstract_syntax
+            );
+            return status::SOP_CLASS_NOT_SUPPORTED;
+        }
+        let data = msg.data.as_deref().unwrap_or_default();
+        match store_instance(dest, &msg.command, &pc.transfer_syntax, data, &assoc.calling_ae) {
+            Ok(path) => {
+                println!("[{}] stored {} from {}", self.args.ae_title, path.display(), assoc.calling_ae);
+                if let Some(index) = &self.index {
+                    match IndexRecord::open(&path) {
+                        Ok(record) => index.insert(record),
+                        Err(e) => eprintln!("[{}] cannot index {}", self.args.ae_title, e),
+                    }
+                }
+                status::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("[{}] C-STORE from {} failed: {}", self.args.ae_title, assoc.calling_ae, e);
+                e.status
+            }
+        }
+    }
+
+    /// 处理 C-FIND-RQ: 每个匹配返回一个 Pending 响应, 最后返回 Success
+    fn find<S: Read + Write + PollRead>(&self, assoc: &mut Association<S>, msg: &Message) -> Result<(), NetError> {
+        let pc_id = msg.presentation_context_id;
+        let (model, ts) = context_syntaxes(assoc, pc_id)?;
+        let index = self.index.as_deref().expect("C-FIND is only dispatched with an index");
+        assoc.cancelled.clear();
+        let result = read_identifier(msg, ts)
+            .and_then(|identifier| find(index, &model, &identifier, &self.args.ae_title));
+        let matches = match result {
+            Ok(matches) => matches,
+            Err(e) => {
+                eprintln!("[{}] C-FIND from {} failed: {}", self.args.ae_title, assoc.calling_ae, e.reason);
+                return assoc.send_message(pc_id, &dimse::response(&msg.command, e.status, false), None);
+            }
+        };
+        for identifier in &matches {
+            if self.cancel_requested(assoc, msg)? {
+                println!("[{}] C-FIND from {} cancelled", self.args.ae_title, assoc.calling_ae);
+                return assoc.send_message(pc_id, &dimse::response(&msg.command, status::CANCEL, false), None);
+            }
+            let mut data = Vec::new();
+            // 编码失败只结束本次查询, 不中断关联
+            if let Err(e) = identifier.write_dataset_with_ts(&mut data, ts) {
+                eprintln!("[{}] C-FIND from {} failed: {}", self.args.ae_title, assoc.calling_ae, e);
+                let rsp = dimse::response(&msg.command, status::UNABLE_TO_PROCESS, false);
+                return assoc.send_message(pc_id, &rsp, None);
+            }
+            assoc.send_message(pc_id, &dimse::response(&msg.command, status::PENDING, true), Some(&data))?;
+        }
+        assoc.send_message(pc_id, &dimse::response(&msg.command, status::SUCCESS, false), None)
+    }
+
+    /// 处理 C-GET-RQ / C-MOVE-RQ: C-GET 在同一关联上发送 C-STORE, C-MOVE 发往配置的目的地;
+    /// 每个子操作后返回带计数的 Pending 响应, 最后返回汇总状态与失败的实例
+    fn retrieve<S: Read + Write + PollRead>(&self, assoc: &mut Association<S>, msg: &Message) -> Result<(), NetError> {
+        let pc_id = msg.presentation_context_id;
+        let (model, ts) = context_syntaxes(assoc, pc_id)?;
+        let index = self.index.as_deref().expect("C-GET/C-MOVE is only dispatched with an index");
+        assoc.cancelled.clear();
+        let is_move = dimse::command_field(&msg.command) == Some(C_MOVE_RQ);
+        let operation = if is_move { "C-MOVE" } else { "C-GET" };
+        let destination = match is_move {
+            true => {
+                let ae_title = dimse::get_str(&msg.command, tags::MOVE_DESTINATION).unwrap_or_default();
+                match self.move_destinations.iter().find(|d| d.ae_title == ae_title) {
+                    Some(destination) => Some(destination),
+                    None => {
+                        eprintln!(
+                            "[{}] C-MOVE from {}: unknown destination '{}'",
+                            self.args.ae_title, assoc.calling_ae, ae_title
+                        );
+                        let rsp = dimse::response(&msg.command, status::MOVE_DESTINATION_UNKNOWN, false);
+                        return assoc.send_message(pc_id, &rsp, None);
+                    }
+                }
+            }
+            false => None,
+        };
+        let records = match read_identifier(msg, ts).and_then(|identifier| retrieve(index, &model, &identifier)) {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("[{}] {} from {} failed: {}", self.args.ae_title, operation, assoc.calling_ae, e.reason);
+                return assoc.send_message(pc_id, &dimse::response(&msg.command, e.status, false), None);
+            }
+        };
+        let items: Vec<SendItem> = records.iter().map(SendItem::from_record).collect();
+        let mut progress = Progress {
+            ops: SubOperations::new(items.len()),
+            failed: Vec::new(),
+        };
+        // 每个子操作之前检查 C-CANCEL-RQ
+        let mut cancelled = false;
+        match destination {
+            None => {
+                for (i, item) in items.iter().enumerate() {
+                    if self.cancel_requested(assoc, msg)? {
+                        cancelled = true;
+                        break;
+                    }
+                    let message_id = (i % u16::MAX as usize) as u16 + 1;
+                    let result = store_one(assoc, item, message_id, true, None);
+                    self.sub_operation_done(assoc, msg, &mut progress, item, result)?;
+                }
+            }
+            Some(destination) => {
+                let originator = assoc.calling_ae.clone();
+                let move_message_id = dimse::message_id(&msg.command).unwrap_or(0);
+                let args = self.destination_args(destination);
+                let mut message_id = 0u16;
+                for batch in plan_associations(items) {
+                    let mut options = args.request_options();
+                    options.contexts = batch.contexts.clone();
+                    // 连接失败或中途断开后, 本批余下的实例都记为失败
+                    let mut dest = args
+                        .connect()
+                        .and_then(|stream| Association::request(stream, &options))
+                        .map_err(|e| e.to_string());
+                    for item in &batch.items {
+                        if self.cancel_requested(assoc, msg)? {
+                            cancelled = true;
+                            break;
+                        }
+                        message_id = message_id.wrapping_add(1).max(1);
+                        let result = match &mut dest {
+                            Ok(dest) => store_one(dest, item, message_id, true, Some((&originator, move_message_id))),
+                            Err(reason) => Err(DcmToolsError::network(&item.path, reason.clone())),
+                        };
+                        if let Err(DcmToolsError::Network { source, .. }) = &result
+                            && let Ok(connected) = std::mem::replace(&mut dest, Err(source.to_string()))
+                        {
+                            let _ = connected.abort();
+                        }
+                        self.sub_operation_done(assoc, msg, &mut progress, item, result)?;
+                    }
+                    if let Ok(dest) = dest
+                        && let Err(e) = dest.release()
+                    {
+                        eprintln!("[{}] release with {} failed: {}", self.args.ae_title, args.called_ae, e);
+                    }
+                    if cancelled {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let ops = progress.ops;
+        println!(
+            "[{}] {} from {}: {} completed, {} failed, {} warning{}",
+            self.args.ae_title,
+            operation,
+            assoc.calling_ae,
+            ops.completed,
+            ops.failed,
+            ops.warning,
+            if cancelled { ", cancelled" } else { "" }
+        );
+        let has_failures = !progress.failed.is_empty();
+        let final_status = if cancelled { status::CANCEL } else { ops.final_status() };
+        let mut rsp = dimse::response(&msg.command, final_status, has_failures);
+        // 取消时带上未执行的子操作数
+        ops.put(&mut rsp, cancelled);
+        if !has_failures {
+            return assoc.send_message(pc_id, &rsp, None);
+        }
+        let mut identifier = InMemDicomObject::new_empty();
+        identifier.put(DataElement::new(
+            tags::FAILED_SOP_INSTANCE_UID_LIST,
+            VR::UI,
+            PrimitiveValue::Strs(progress.failed.into()),
+        ));
+        let mut data = Vec::new();
+        identifier
+            .write_dataset_with_ts(&mut data, ts)
+            .map_err(|e| NetError::Pdu(e.to_string()))?;
+        assoc.send_message(pc_id, &rsp, Some(&data))
+    }
+
+    /// C-MOVE 子操作连接目的地的参数; TLS 目的地使用本端的证书与 --tls-ca
+    fn destination_args(&self, destination: &MoveDestination) -> ScuArgs {
+        let tls = match destination.tls {
+            true => TlsArgs {
+                enabled: true,
+                server_name: None,
+                ..self.args.tls.clone()
+            },
+            false => TlsArgs::default(),
+        };
+        ScuArgs {
+            host: destination.host.clone(),
+            port: destination.port,
+            calling_ae: self.args.ae_title.clone(),
+            called_ae: destination.ae_title.clone(),
+            timeout: self.args.timeout,
+            max_pdu: self.args.max_pdu,
+            tls,
+        }
+    }
+
+    /// 不阻塞地读取已到达的消息, 返回发起方是否已用 C-CANCEL-RQ 取消该请求
+    fn cancel_requested<S: Read + Write + PollRead>(
+        &self,
+        assoc: &mut Association<S>,
+        msg: &Message,
+    ) -> Result<bool, NetError> {
+        while let Some(incoming) = assoc.poll_message()? {
+            match incoming {
+                Incoming::Message(other) if dimse::cancelled_message_id(&other.command).is_some() => {
+                    assoc.cancelled.extend(dimse::cancelled_message_id(&other.command));
+                }
+                _ => {
+                    return Err(NetError::Protocol(
+                        "unexpected message while a request is in progress".to_string(),
+                    ));
+                }
+            }
+        }
+        let message_id = dimse::message_id(&msg.command);
+        Ok(assoc.cancelled.iter().any(|id| Some(*id) == message_id))
+    }
+
+    /// 记录一个子操作的结果, 还有剩余子操作时发送 Pending 响应
+    fn sub_operation_done<S: Read + Write>(
+        &self,
+        assoc: &mut Association<S>,
+        msg: &Message,
+        progress: &mut Progress,
+        item: &SendItem,
+        result: Result<u16, DcmToolsError>,
+    ) -> Result<(), NetError> {
+        match result {
+            Ok(code) => progress.ops.record(Some(code)),
+            Err(e) => {
+                eprintln!("[{}] sub-operation for {} failed: {}", self.args.ae_title, assoc.calling_ae, e);
+                progress.ops.record(None);
+                progress.failed.push(item.sop_instance.clone());
+            }
+        }
+        if progress.ops.remaining == 0 {
+            return Ok(());
+        }
+        let mut rsp = dimse::response(&msg.command, status::PENDING, false);
+        progress.ops.put(&mut rsp, true);
+        assoc.send_message(msg.presentation_context_id, &rsp, None)
+    }
+}
+
+/// C-GET/C-MOVE 的子操作进度
+struct Progress {
+    ops: SubOperations,
+    failed: Vec<String>,
+}
+
+/// 表示上下文的抽象语法 (信息模型) 与传输语法
+fn context_syntaxes<S>(
+    assoc: &Association<S>,
+    pc_id: u8,
+) -> Result<(String, &'static TransferSyntax), NetError> {
+    let Some(pc) = assoc.contexts.iter().find(|pc| pc.id == pc_id) else {
+        return Err(NetError::Protocol(format!("unknown presentation context {}", pc_id)));
+    };
+    let Some(ts) = TransferSyntaxRegistry.get(&pc.transfer_syntax) else {
+        return Err(NetError::Protocol(format!("unknown transfer syntax {}", pc.transfer_syntax)));
+    };
+    Ok((pc.abstract_syntax.clone(), ts))
+}
+
+/// 按上下文的传输语法解码请求标识
+fn read_identifier(msg: &Message, ts: &TransferSyntax) -> Result<InMemDicomObject, QueryError> {
+    let data = msg.data.as_deref().ok_or_else(|| QueryError {
+        status: status::CANNOT_UNDERSTAND,
+        reason: "missing identifier".to_string(),
+    })?;
+    InMemDicomObject::read_dataset_with_ts(data, ts).map_err(|e| QueryError {
+        status: status::CANNOT_UNDERSTAND,
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scu::{ScuArgs, echo};
+    use clap::Parser;
+
+    #[derive(Parser)]
+    #[command(args_override_self = true)]
+    struct TestArgs {
+        #[command(flatten)]
+        scp: ScpArgs,
+    }
+
+    #[derive(Parser)]
+    #[command(args_override_self = true)]
+    struct TestScuArgs {
+        #[command(flatten)]
+        scu: ScuArgs,
+    }
+
+    /// 在随机端口启动 SCP, 返回端口
+    pub(crate) fn start_scp(args: &[&str]) -> u16 {
+        let mut argv = vec!["scp", "--bind", "127.0.0.1", "--port", "0", "--ae-title", "TEST-SCP"];
+        argv.extend_from_slice(args);
+        let scp = TestArgs::parse_from(argv).scp;
+        let listener = scp.listen().unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let service = Arc::new(ScpService::new(scp));
+        std::thread::spawn(move || service.serve(listener));
+        port
+    }
+
+    /// 启动带存储目录的 SCP
+    pub(crate) fn start_store_scp(dest: &std::path::Path) -> u16 {
+        start_store_scp_with(dest, &[])
+    }
+
+    fn start_store_scp_with(dest: &std::path::Path, args: &[&str]) -> u16 {
+        let mut argv = vec!["scp", "--bind", "127.0.0.1", "--port", "0", "--ae-title", "TEST-SCP"];
+        argv.extend_from_slice(args);
+        let scp = TestArgs::parse_from(argv).scp;
+        let listener = scp.listen().unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let service = Arc::new(ScpService::new(scp).with_storage(dest.to_path_buf()));
+        std::thread::spawn(move || service.serve(listener));
+        port
+    }
+
+    pub(crate) fn scu_args(port: u16, args: &[&str]) -> ScuArgs {
+        let port = port.to_string();
+        let mut argv = vec!["scu", "--port", port.as_str(), "--called-ae", "TEST-SCP", "--timeout", "5s"];
+        argv.extend_from_slice(args);
+        TestScuArgs::parse_from(argv).scu
+    }
+
+    #[test]
+    fn test_echo_on_localhost() {
+        let port = start_scp(&[]);
+        assert_eq!(echo(&scu_args(port, &[])).unwrap(), status::SUCCESS);
+    }
+
+    #[test]
+    fn test_strict_called_ae_is_rejected() {
+        let port = start_scp(&["--strict-ae"]);
+        let err = echo(&scu_args(port, &["--called-ae", "OTHER"])).unwrap_err();
+        assert!(matches!(err, NetError::Rejected(_)), "{}", err);
+        assert_eq!(echo(&scu_args(port, &[])).unwrap(), status::SUCCESS);
+    }
+
+    #[test]
+    fn test_calling_ae_allow_list() {
+        let port = start_scp(&["--allow-calling", "GOOD"]);
+        assert!(echo(&scu_args(port, &["--calling-ae", "BAD"])).is_err());
+        assert_eq!(echo(&scu_args(port, &["--calling-ae", "GOOD"])).unwrap(), status::SUCCESS);
+    }
+
+    #[test]
+    fn test_store_on_localhost() {
+        use crate::association::RequestOptions;
+        use dicom::dictionary_std::uids;
+
+        let dest = tempfile::tempdir().unwrap();
+        let port = start_store_scp(dest.path());
+        let obj = dicom_object::open_file(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+        )
+        .unwrap();
+        let sop_class = obj.meta().media_storage_sop_class_uid().to_string();
+        let sop_instance = obj.meta().media_storage_sop_instance_uid().to_string();
+        let mut data = Vec::new();
+        obj.write_dataset_with_ts(
+            &mut data,
+            &dicom_transfer_syntax_registry::entries::IMPLICIT_VR_LITTLE_ENDIAN.erased(),
+        )
+        .unwrap();
+
+        let scu = scu_args(port, &[]);
+        let options = RequestOptions::new("STORE-SCU", "TEST-SCP")
+            .with_context(&sop_class, &[uids::IMPLICIT_VR_LITTLE_ENDIAN]);
+        let mut assoc = Association::request(scu.connect().unwrap(), &options).unwrap();
+        let pc_id = assoc.find_context(&sop_class).unwrap().id;
+        let mut rq = dimse::request(C_STORE_RQ, &sop_class, 1, true);
+        rq.put(dicom::core::DataElement::new(
+            tags::AFFECTED_SOP_INSTANCE_UID,
+            dicom::core::VR::UI,
+            dicom::core::PrimitiveValue::from(sop_instance.as_str()),
+        ));
+        assoc.send_message(pc_id, &rq, Some(&data)).unwrap();
+        let Incoming::Message(rsp) = assoc.receive_message().unwrap() else {
+            panic!("expected C-STORE-RSP");
+        };
+        assert_eq!(dimse::status(&rsp.command), Some(status::SUCCESS));
+
+        // 数据集与命令中的 SOP Instance UID 不一致
+        rq.put(dicom::core::DataElement::new(
+            tags::AFFECTED_SOP_INSTANCE_UID,
+            dicom::core::VR::UI,
+            dicom::core::PrimitiveValue::from("1.2.3"),
+        ));
+        assoc.send_message(pc_id, &rq, Some(&data)).unwrap();
+        let Incoming::Message(rsp) = assoc.receive_message().unwrap() else {
+            panic!("expected C-STORE-RSP");
+        };
+        assert_eq!(dimse::status(&rsp.command), Some(status::DATA_SET_DOES_NOT_MATCH));
+        assoc.release().unwrap();
+
+        let stored = crate::dcmobj::walk_directory(dest.path()).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].ends_with(format!("{}.dcm", sop_instance)));
+        let copy = dicom_object::open_file(&stored[0]).unwrap();
+        assert_eq!(copy.meta().transfer_syntax(), uids::IMPLICIT_VR_LITTLE_ENDIAN);
+        assert_eq!(
+            crate::dcmobj::get_string(tags::SERIES_INSTANCE_UID, &copy),
+            crate::dcmobj::get_string(tags::SERIES_INSTANCE_UID, &obj)
+        );
+    }
+
+    #[test]
+    fn test_send_to_store_scp() {
+        use crate::scu::{SendItem, plan_associations, store_batch};
+
+        let dest = tempfile::tempdir().unwrap();
+        let port = start_store_scp(dest.path());
+        let item = SendItem::open(std::path::Path::new(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+        ))
+        .unwrap();
+        let unsupported = SendItem {
+            sop_class: "1.2.840.10008.9.9".to_string(),
+            ..item.clone()
+        };
+        let batches = plan_associations(vec![item.clone(), unsupported]);
+        assert_eq!(batches.len(), 1);
+        let results = store_batch(&scu_args(port, &[]), &batches[0], false);
+        assert_eq!(results.len(), 2);
+        assert_eq!(*results[0].as_ref().unwrap(), status::SUCCESS);
+        // 对端不接受的 SOP Class 单独记为失败, 不影响同一关联中的其他文件
+        assert!(matches!(
+            results[1],
+            Err(crate::error::DcmToolsError::Rejected { status: status::SOP_CLASS_NOT_SUPPORTED, .. })
+        ));
+        let stored = crate::dcmobj::walk_directory(dest.path()).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].ends_with(format!("{}.dcm", item.sop_instance)));
+
+        // 关联被拒绝时, 每个文件都得到失败结果而不是整体报错
+        let port = start_scp(&[]);
+        let batches = plan_associations(vec![item]);
+        let results = store_batch(&scu_args(port, &[]), &batches[0], false);
+        assert!(matches!(results[0], Err(crate::error::DcmToolsError::Network { .. })));
+    }
+
+    /// 生成 CA 以及由它签发的服务器/客户端证书, 返回目录
+    fn write_test_pki() -> tempfile::TempDir {
+        use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair};
+        let dir = tempfile::tempdir().unwrap();
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        std::fs::write(dir.path().join("ca.pem"), ca.pem()).unwrap();
+        let issuer = Issuer::from_params(&ca_params, &ca_key);
+        for name in ["server", "client"] {
+            let key = KeyPair::generate().unwrap();
+            let params = CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()]).unwrap();
+            let cert = params.signed_by(&key, &issuer).unwrap();
+            std::fs::write(dir.path().join(format!("{}.pem", name)), cert.pem()).unwrap();
+            std::fs::write(dir.path().join(format!("{}.key", name)), key.serialize_pem()).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_echo_over_tls() {
+        let pki = write_test_pki();
+        let file = |name: &str| pki.path().join(name).to_string_lossy().into_owned();
+        let (server_pem, server_key, ca) = (file("server.pem"), file("server.key"), file("ca.pem"));
+        let port = start_scp(&["--tls", "--tls-cert", &server_pem, "--tls-key", &server_key]);
+        assert_eq!(echo(&scu_args(port, &["--tls", "--tls-ca", &ca])).unwrap(), status::SUCCESS);
+        // 明文 SCU 连不上 TLS SCP
+        assert!(echo(&scu_args(port, &[])).is_err());
+        // 不信任的 CA
+        assert!(echo(&scu_args(port, &["--tls", "--tls-ca", &server_pem])).is_err());
+    }
+
+    #[test]
+    fn test_mutual_tls() {
+        let pki = write_test_pki();
+        let file = |name: &str| pki.path().join(name).to_string_lossy().into_owned();
+        let (server_pem, server_key, ca) = (file("server.pem"), file("server.key"), file("ca.pem"));
+        let (client_pem, client_key) = (file("client.pem"), file("client.key"));
+        let port = start_scp(&[
+            "--tls", "--tls-cert", &server_pem, "--tls-key", &server_key, "--tls-ca", &ca,
+        ]);
+        assert!(echo(&scu_args(port, &["--tls", "--tls-ca", &ca])).is_err());
+        let with_cert = ["--tls", "--tls-ca", &ca, "--tls-cert", &client_pem, "--tls-key", &client_key];
+        assert_eq!(echo(&scu_args(port, &with_cert)).unwrap(), status::SUCCESS);
+    }
+
+    #[test]
+    fn test_find_after_store() {
+        use crate::association::RequestOptions;
+        use crate::scu::{SendItem, plan_associations, store_batch};
+
+        let dest = tempfile::tempdir().unwrap();
+        let scp = TestArgs::parse_from(["scp", "--bind", "127.0.0.1", "--port", "0", "--ae-title", "TEST-SCP"]).scp;
+        let listener = scp.listen().unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let service = ScpService::new(scp)
+            .with_storage(dest.path().to_path_buf())
+            .with_index(Arc::new(MetadataIndex::default()));
+        std::thread::spawn(move || Arc::new(service).serve(listener));
+
+        // 新存储的实例立即可以查询到
+        let item = SendItem::open(std::path::Path::new(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+        ))
+        .unwrap();
+        let batches = plan_associations(vec![item.clone()]);
+        assert!(store_batch(&scu_args(port, &[]), &batches[0], false)[0].is_ok());
+
+        let options = RequestOptions::new("FIND-SCU", "TEST-SCP")
+            .with_context(STUDY_ROOT_FIND, crate::association::DEFAULT_TRANSFER_SYNTAXES);
+        let mut assoc = Association::request(scu_args(port, &[]).connect().unwrap(), &options).unwrap();
+        let pc = assoc.find_context(STUDY_ROOT_FIND).unwrap().clone();
+        let ts = TransferSyntaxRegistry.get(&pc.transfer_syntax).unwrap();
+        let mut identifier = InMemDicomObject::new_empty();
+        for (tag, vr, value) in [
+            (tags::QUERY_RETRIEVE_LEVEL, dicom::core::VR::CS, "IMAGE"),
+            (tags::SOP_INSTANCE_UID, dicom::core::VR::UI, ""),
+            (tags::SERIES_INSTANCE_UID, dicom::core::VR::UI, ""),
+        ] {
+            identifier.put(dicom::core::DataElement::new(tag, vr, dicom::core::PrimitiveValue::from(value)));
+        }
+        let mut data = Vec::new();
+        identifier.write_dataset_with_ts(&mut data, ts).unwrap();
+        assoc
+            .send_message(pc.id, &dimse::request(C_FIND_RQ, STUDY_ROOT_FIND, 1, true), Some(&data))
+            .unwrap();
+        let mut found = Vec::new();
+        loop {
+            let Incoming::Message(rsp) = assoc.receive_message().unwrap() else {
+                panic!("expected C-FIND-RSP");
+            };
+            match dimse::status(&rsp.command) {
+                Some(status::PENDING) => {
+                    let obj = InMemDicomObject::read_dataset_with_ts(rsp.data.unwrap().as_slice(), ts).unwrap();
+                    found.push(dimse::get_str(&obj, tags::SOP_INSTANCE_UID).unwrap());
+                }
+                Some(status::SUCCESS) => break,
+                other => panic!("unexpected status {:?}", other),
+            }
+        }
+        assoc.release().unwrap();
+        assert_eq!(found, [item.sop_instance]);
+    }
+
+    #[test]
+    fn test_parse_move_destination() {
+        assert_eq!(
+            parse_move_destination("VIEWER=10.0.0.5:11112").unwrap(),
+            MoveDestination {
+                ae_title: "VIEWER".to_string(),
+                host: "10.0.0.5".to_string(),
+                port: 11112,
+                tls: false,
+            }
+        );
+        let secure = parse_move_destination("VIEWER=tls://viewer.example:2762").unwrap();
+        assert_eq!((secure.host.as_str(), secure.port, secure.tls), ("viewer.example", 2762, true));
+        assert!(parse_move_destination("VIEWER").is_err());
+        assert!(parse_move_destination("VIEWER=host").is_err());
+        assert!(parse_move_destination("=host:104").is_err());
+        assert!(parse_move_destination("VIEWER=:104").is_err());
+    }
+
+    fn test_item() -> SendItem {
+        SendItem::open(std::path::Path::new(
+            "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm",
+        ))
+        .unwrap()
+    }
+
+    /// 启动 Q/R SCP: storage 中已有 test_data 的实例, 返回端口与存储目录
+    fn start_qr_scp(destinations: Vec<MoveDestination>) -> (u16, tempfile::TempDir, SendItem) {
+        let item = test_item();
+        let (port, storage) = start_qr_scp_with(&[], destinations, std::slice::from_ref(&item));
+        (port, storage, item)
+    }
+
+    /// 先通过明文的存储 SCP 存入 items, 再以 args 启动 Q/R SCP
+    fn start_qr_scp_with(
+        args: &[&str],
+        destinations: Vec<MoveDestination>,
+        items: &[SendItem],
+    ) -> (u16, tempfile::TempDir) {
+        let storage = tempfile::tempdir().unwrap();
+        let port = start_store_scp(storage.path());
+        for batch in plan_associations(items.to_vec()) {
+            assert!(crate::scu::store_batch(&scu_args(port, &[]), &batch, false).iter().all(Result::is_ok));
+        }
+
+        let mut argv = vec!["scp", "--bind", "127.0.0.1", "--port", "0", "--ae-title", "TEST-SCP"];
+        argv.extend_from_slice(args);
+        let scp = TestArgs::parse_from(argv).scp;
+        let listener = scp.listen().unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (index, _) = MetadataIndex::scan(storage.path()).unwrap();
+        let service = ScpService::new(scp)
+            .with_storage(storage.path().to_path_buf())
+            .with_index(Arc::new(index))
+            .with_move_destinations(destinations);
+        std::thread::spawn(move || Arc::new(service).serve(listener));
+        (port, storage)
+    }
+
+    /// Study 级别的 C-GET/C-MOVE 请求标识
+    fn study_identifier(item: &SendItem, ts: &TransferSyntax) -> Vec<u8> {
+        let obj = dicom_object::open_file(&item.path).unwrap();
+        let study_uid = crate::dcmobj::get_string(tags::STUDY_INSTANCE_UID, &obj);
+        let mut identifier = InMemDicomObject::new_empty();
+        identifier.put(DataElement::new(tags::QUERY_RETRIEVE_LEVEL, VR::CS, PrimitiveValue::from("STUDY")));
+        identifier.put(DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, PrimitiveValue::from(study_uid)));
+        let mut data = Vec::new();
+        identifier.write_dataset_with_ts(&mut data, ts).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_get_on_same_association() {
+        use crate::association::RequestOptions;
+
+        let (port, _storage, item) = start_qr_scp(Vec::new());
+        let mut options = RequestOptions::new("GET-SCU", "TEST-SCP")
+            .with_context(STUDY_ROOT_GET, crate::association::DEFAULT_TRANSFER_SYNTAXES)
+            .with_context(&item.sop_class, &[item.transfer_syntax.as_str()]);
+        options.scp_roles.push(item.sop_class.clone());
+        let mut assoc = Association::request(scu_args(port, &[]).connect().unwrap(), &options).unwrap();
+        let pc = assoc.find_context(STUDY_ROOT_GET).unwrap().clone();
+        let ts = TransferSyntaxRegistry.get(&pc.transfer_syntax).unwrap();
+        let data = study_identifier(&item, ts);
+        assoc
+            .send_message(pc.id, &dimse::request(C_GET_RQ, STUDY_ROOT_GET, 5, true), Some(&data))
+            .unwrap();
+
+        let mut received = Vec::new();
+        let final_rsp = loop {
+            let Incoming::Message(msg) = assoc.receive_message().unwrap() else {
+                panic!("expected DIMSE message");
+            };
+            match dimse::command_field(&msg.command) {
+                Some(C_STORE_RQ) => {
+                    received.push(dimse::get_str(&msg.command, tags::AFFECTED_SOP_INSTANCE_UID).unwrap());
+                    let rsp = dimse::response(&msg.command, status::SUCCESS, false);
+                    assoc.send_message(msg.presentation_context_id, &rsp, None).unwrap();
+                }
+                Some(field) if field == C_GET_RQ | dimse::RESPONSE_BIT => {
+                    assert_eq!(dimse::get_u16(&msg.command, tags::MESSAGE_ID_BEING_RESPONDED_TO), Some(5));
+                    if dimse::status(&msg.command) != Some(status::PENDING) {
+                        break msg.command;
+                    }
+                }
+                other => panic!("unexpected command {:?}", other),
+            }
+        };
+        assoc.release().unwrap();
+        assert_eq!(received, [item.sop_instance.as_str()]);
+        assert_eq!(dimse::status(&final_rsp), Some(status::SUCCESS));
+        assert_eq!(dimse::get_u16(&final_rsp, tags::NUMBER_OF_COMPLETED_SUBOPERATIONS), Some(1));
+        assert_eq!(dimse::get_u16(&final_rsp, tags::NUMBER_OF_FAILED_SUBOPERATIONS), Some(0));
+        assert!(final_rsp.get(tags::NUMBER_OF_REMAINING_SUBOPERATIONS).is_none());
+    }
+
+    /// 发送 C-MOVE-RQ, 返回最终响应命令集与数据集
+    fn send_move(port: u16, destination: &str, item: &SendItem) -> (InMemDicomObject, Option<InMemDicomObject>) {
+        send_move_with(port, &[], destination, item)
+    }
+
+    fn send_move_with(
+        port: u16,
+        scu: &[&str],
+        destination: &str,
+        item: &SendItem,
+    ) -> (InMemDicomObject, Option<InMemDicomObject>) {
+        use crate::association::RequestOptions;
+
+        let options = RequestOptions::new("MOVE-SCU", "TEST-SCP")
+            .with_context(STUDY_ROOT_MOVE, crate::association::DEFAULT_TRANSFER_SYNTAXES);
+        let mut assoc = Association::request(scu_args(port, scu).connect().unwrap(), &options).unwrap();
+        let pc = assoc.find_context(STUDY_ROOT_MOVE).unwrap().clone();
+        let ts = TransferSyntaxRegistry.get(&pc.transfer_syntax).unwrap();
+        let mut rq = dimse::request(C_MOVE_RQ, STUDY_ROOT_MOVE, 9, true);
+        rq.put(DataElement::new(tags::MOVE_DESTINATION, VR::AE, PrimitiveValue::from(destination)));
+        assoc.send_message(pc.id, &rq, Some(&study_identifier(item, ts))).unwrap();
+        let rsp = loop {
+            let Incoming::Message(msg) = assoc.receive_message().unwrap() else {
+                panic!("expected C-MOVE-RSP");
+            };
+            assert_eq!(dimse::command_field(&msg.command), Some(C_MOVE_RQ | dimse::RESPONSE_BIT));
+            if dimse::status(&msg.command) != Some(status::PENDING) {
+                break msg;
+            }
+        };
+        assoc.release().unwrap();
+        let data = rsp
+            .data
+            .map(|data| InMemDicomObject::read_dataset_with_ts(data.as_slice(), ts).unwrap());
+        (rsp.command, data)
+    }
+
+    #[test]
+    fn test_move_to_destination() {
+        let dest = tempfile::tempdir().unwrap();
+        let dest_port = start_store_scp(dest.path());
+        // 第二个目的地没有在监听, 子操作全部失败
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (port, _storage, item) = start_qr_scp(vec![
+            parse_move_destination(&format!("STORE=127.0.0.1:{}", dest_port)).unwrap(),
+            parse_move_destination(&format!("OFFLINE=127.0.0.1:{}", closed)).unwrap(),
+        ]);
+
+        let (rsp, _) = send_move(port, "STORE", &item);
+        assert_eq!(dimse::status(&rsp), Some(status::SUCCESS));
+        assert_eq!(dimse::get_u16(&rsp, tags::NUMBER_OF_COMPLETED_SUBOPERATIONS), Some(1));
+        let stored = crate::dcmobj::walk_directory(dest.path()).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].ends_with(format!("{}.dcm", item.sop_instance)));
+
+        let (rsp, data) = send_move(port, "OFFLINE", &item);
+        assert_eq!(dimse::status(&rsp), Some(status::UNABLE_TO_PERFORM_SUBOPERATIONS));
+        assert_eq!(dimse::get_u16(&rsp, tags::NUMBER_OF_FAILED_SUBOPERATIONS), Some(1));
+        assert_eq!(
+            dimse::get_str(&data.unwrap(), tags::FAILED_SOP_INSTANCE_UID_LIST),
+            Some(item.sop_instance.clone())
+        );
+
+        let (rsp, _) = send_move(port, "NOWHERE", &item);
+        assert_eq!(dimse::status(&rsp), Some(status::MOVE_DESTINATION_UNKNOWN));
+    }
+
+    #[test]
+    fn test_move_to_tls_destination() {
+        let pki = write_test_pki();
+        let file = |name: &str| pki.path().join(name).to_string_lossy().into_owned();
+        let (server_pem, server_key, ca) = (file("server.pem"), file("server.key"), file("ca.pem"));
+        let (client_pem, client_key) = (file("client.pem"), file("client.key"));
+        let dest = tempfile::tempdir().unwrap();
+        let dest_port = start_store_scp_with(dest.path(), &["--tls", "--tls-cert", &server_pem, "--tls-key", &server_key]);
+        let item = test_item();
+        let (port, _storage) = start_qr_scp_with(
+            &["--tls", "--tls-cert", &server_pem, "--tls-key", &server_key, "--tls-ca", &ca],
+            vec![
+                parse_move_destination(&format!("SECURE=tls://127.0.0.1:{}", dest_port)).unwrap(),
+                parse_move_destination(&format!("PLAIN=127.0.0.1:{}", dest_port)).unwrap(),
+            ],
+            std::slice::from_ref(&item),
+        );
+        let scu = ["--tls", "--tls-ca", &ca, "--tls-cert", &client_pem, "--tls-key", &client_key];
+
+        // 子操作以 SCP 自己的证书和 CA 通过 TLS 连接目的地
+        let (rsp, _) = send_move_with(port, &scu, "SECURE", &item);
+        assert_eq!(dimse::status(&rsp), Some(status::SUCCESS));
+        let stored = crate::dcmobj::walk_directory(dest.path()).unwrap();
+        assert_eq!(stored.len(), 1);
+        // 未标记 tls:// 的目的地按明文连接, 连不上 TLS SCP
+        let (rsp, _) = send_move_with(port, &scu, "PLAIN", &item);
+        assert_eq!(dimse::status(&rsp), Some(status::UNABLE_TO_PERFORM_SUBOPERATIONS));
+    }
+
+    /// C-CANCEL-RQ 命令集
+    fn cancel_request(message_id: u16) -> InMemDicomObject {
+        let mut cmd = InMemDicomObject::new_empty();
+        cmd.put(DataElement::new(tags::COMMAND_FIELD, VR::US, PrimitiveValue::from(dimse::C_CANCEL_RQ)));
+        cmd.put(DataElement::new(tags::MESSAGE_ID_BEING_RESPONDED_TO, VR::US, PrimitiveValue::from(message_id)));
+        cmd.put(DataElement::new(tags::COMMAND_DATA_SET_TYPE, VR::US, PrimitiveValue::from(dimse::NO_DATA_SET)));
+        cmd
+    }
+
+    #[test]
+    fn test_get_cancelled_between_sub_operations() {
+        use crate::association::RequestOptions;
+
+        // 同一检查的第二个实例: 新的 SOP Instance UID
+        let copies = tempfile::tempdir().unwrap();
+        let item = test_item();
+        let mut obj = dicom_object::open_file(&item.path).unwrap();
+        let sop_uid = dicom_gen_uid::gen_uid();
+        obj.put(DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, PrimitiveValue::from(sop_uid.as_str())));
+        obj.update_meta(|meta| meta.media_storage_sop_instance_uid = sop_uid);
+        let copy = copies.path().join("copy.dcm");
+        obj.write_to_file(&copy).unwrap();
+        let (port, _storage) = start_qr_scp_with(&[], Vec::new(), &[item.clone(), SendItem::open(&copy).unwrap()]);
+
+        let mut options = RequestOptions::new("GET-SCU", "TEST-SCP")
+            .with_context(STUDY_ROOT_GET, crate::association::DEFAULT_TRANSFER_SYNTAXES)
+            .with_context(&item.sop_class, &[item.transfer_syntax.as_str()]);
+        options.scp_roles.push(item.sop_class.clone());
+        let mut assoc = Association::request(scu_args(port, &[]).connect().unwrap(), &options).unwrap();
+        let pc = assoc.find_context(STUDY_ROOT_GET).unwrap().clone();
+        let ts = TransferSyntaxRegistry.get(&pc.transfer_syntax).unwrap();
+        let data = study_identifier(&item, ts);
+        assoc
+            .send_message(pc.id, &dimse::request(C_GET_RQ, STUDY_ROOT_GET, 5, true), Some(&data))
+            .unwrap();
+
+        let mut stores = 0;
+        let final_rsp = loop {
+            let Incoming::Message(msg) = assoc.receive_message().unwrap() else {
+                panic!("expected DIMSE message");
+            };
+            match dimse::command_field(&msg.command) {
+                Some(C_STORE_RQ) => {
+                    stores += 1;
+                    // 第一个子操作进行中取消, 之后再确认该 C-STORE
+                    assoc.send_message(pc.id, &cancel_request(5), None).unwrap();
+                    let rsp = dimse::response(&msg.command, status::SUCCESS, false);
+                    assoc.send_message(msg.presentation_context_id, &rsp, None).unwrap();
+                }
+                Some(field) if field == C_GET_RQ | dimse::RESPONSE_BIT => {
+                    if dimse::status(&msg.command) != Some(status::PENDING) {
+                        break msg.command;
+                    }
+                }
+                other => panic!("unexpected command {:?}", other),
+            }
+        };
+        assoc.release().unwrap();
+        assert_eq!(stores, 1);
+        assert_eq!(dimse::status(&final_rsp), Some(status::CANCEL));
+        assert_eq!(dimse::get_u16(&final_rsp, tags::NUMBER_OF_COMPLETED_SUBOPERATIONS), Some(1));
+        assert_eq!(dimse::get_u16(&final_rsp, tags::NUMBER_OF_REMAINING_SUBOPERATIONS), Some(1));
+    }
+}
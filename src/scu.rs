@@ -0,0 +1,372 @@
+use crate::association::{
+    Association, DEFAULT_TRANSFER_SYNTAXES, Incoming, NetError, PresentationContext,
+    RequestOptions, VERIFICATION_SOP_CLASS, parse_ae_title,
+};
+use crate::commands::parse_ttl;
+use crate::dimse::{self, C_ECHO_RQ, C_STORE_RQ, status};
+use crate::error::DcmToolsError;
+use crate::index::IndexRecord;
+use crate::tls::{NetStream, TlsArgs};
+use crate::transcode::TranscodeOptions;
+use clap::Args;
+use dicom::dictionary_std::tags;
+use dicom::object::DefaultDicomObject;
+use dicom_encoding::TransferSyntaxIndex;
+use dicom_object::{OpenFileOptions, open_file};
+use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+use dicom_ul::pdu::DEFAULT_MAX_PDU;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 一个关联最多 128 个表示上下文 (ID 为 1..=255 的奇数)
+pub const MAX_PRESENTATION_CONTEXTS: usize = 128;
+
+/// SCU 的连接参数
+#[derive(Debug, Clone, Args)]
+pub struct ScuArgs {
+    /// 对端主机名或 IP
+    #[arg(short = 'H', long = "host", default_value = "127.0.0.1")]
+    pub host: String,
+    /// 对端端口
+    #[arg(short = 'p', long = "port", default_value_t = 104)]
+    pub port: u16,
+    /// 本端 AE Title
+    #[arg(long = "calling-ae", default_value = "DCMTOOLS", value_parser = parse_ae_title)]
+    pub calling_ae: String,
+    /// 对端 AE Title
+    #[arg(long = "called-ae", default_value = "ANY-SCP", value_parser = parse_ae_title)]
+    pub called_ae: String,
+    /// 连接与读写超时, 如 30 / 30s / 2m
+    #[arg(long = "timeout", default_value = "30s", value_parser = parse_ttl)]
+    pub timeout: Duration,
+    /// 本端可接收的最大 PDU
+    #[arg(long = "max-pdu", default_value_t = DEFAULT_MAX_PDU)]
+    pub max_pdu: u32,
+    #[command(flatten)]
+    pub tls: TlsArgs,
+}
+
+impl ScuArgs {
+    /// 建立 TCP 连接, 设置读写超时, 启用 TLS 时完成握手
+    pub fn connect(&self) -> Result<NetStream, NetError> {
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| NetError::Protocol(format!("cannot resolve host {}", self.host)))?;
+        let stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+        if !self.tls.enabled {
+            return Ok(NetStream::Plain(stream));
+        }
+        let server_name = self.tls.server_name.as_deref().unwrap_or(&self.host);
+        NetStream::client(stream, self.tls.client_config()?, server_name)
+    }
+
+    pub fn request_options(&self) -> RequestOptions {
+        let mut options = RequestOptions::new(&self.calling_ae, &self.called_ae);
+        options.max_pdu = self.max_pdu;
+        options
+    }
+}
+
+/// C-ECHO: 建立关联, 发送 C-ECHO-RQ, 返回响应状态
+pub fn echo(args: &ScuArgs) -> Result<u16, NetError> {
+    let options = args
+        .request_options()
+        .with_context(VERIFICATION_SOP_CLASS, DEFAULT_TRANSFER_SYNTAXES);
+    let mut assoc = Association::request(args.connect()?, &options)?;
+    let pc_id = assoc
+        .find_context(VERIFICATION_SOP_CLASS)
+        .map(|pc| pc.id)
+        .ok_or_else(|| NetError::Rejected("verification not accepted".to_string()))?;
+    assoc.send_message(pc_id, &dimse::request(C_ECHO_RQ, VERIFICATION_SOP_CLASS, 1, false), None)?;
+    let status = match assoc.receive_message()? {
+        Incoming::Message(msg)
+            if dimse::command_field(&msg.command) == Some(C_ECHO_RQ | dimse::RESPONSE_BIT) =>
+        {
+            dimse::status(&msg.command).unwrap_or(dimse::status::PROCESSING_FAILURE)
+        }
+        _ => return Err(NetError::Protocol("expected C-ECHO-RSP".to_string())),
+    };
+    assoc.release()?;
+    Ok(status)
+}
+
+/// 未压缩的传输语法 (含 Explicit VR Big Endian), 可以直接用 dicom-rs 重新编码
+pub(crate) fn is_native(ts: &str) -> bool {
+    DEFAULT_TRANSFER_SYNTAXES.contains(&ts) || ts == "1.2.840.10008.1.2.2"
+}
+
+/// 待发送的文件, 只读取文件元信息
+#[derive(Debug, Clone)]
+pub struct SendItem {
+    pub path: PathBuf,
+    pub sop_class: String,
+    pub sop_instance: String,
+    pub transfer_syntax: String,
+}
+
+impl SendItem {
+    /// 索引中的实例, 不必重新读取文件
+    pub fn from_record(record: &IndexRecord) -> Self {
+        Self {
+            path: record.path.clone(),
+            sop_class: record.sop_class_uid.clone(),
+            sop_instance: record.image.sop_uid.clone(),
+            transfer_syntax: record.transfer_syntax.clone(),
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self, DcmToolsError> {
+        let obj = OpenFileOptions::new()
+            .read_until(tags::PATIENT_ID)
+            .open_file(path)
+            .map_err(|e| DcmToolsError::open(path, e))?;
+        let meta = obj.meta();
+        let trim = |s: &str| s.trim_end_matches(['\0', ' ']).to_string();
+        Ok(Self {
+            path: path.to_path_buf(),
+            sop_class: trim(meta.media_storage_sop_class_uid()),
+            sop_instance: trim(meta.media_storage_sop_instance_uid()),
+            transfer_syntax: trim(meta.transfer_syntax()),
+        })
+    }
+
+    /// 为该文件提议的上下文: 未压缩的文件提议全部未压缩语法;
+    /// 压缩的文件先提议原语法, 再单独提议未压缩语法作为转码回退
+    fn proposals(&self) -> Vec<(String, Vec<String>)> {
+        let mut native = vec![self.transfer_syntax.clone()];
+        if is_native(&self.transfer_syntax) {
+            native.extend(
+                DEFAULT_TRANSFER_SYNTAXES
+                    .iter()
+                    .filter(|ts| **ts != self.transfer_syntax)
+                    .map(|ts| ts.to_string()),
+            );
+            return vec![(self.sop_class.clone(), native)];
+        }
+        vec![
+            (self.sop_class.clone(), native),
+            (
+                self.sop_class.clone(),
+                DEFAULT_TRANSFER_SYNTAXES.iter().map(|ts| ts.to_string()).collect(),
+            ),
+        ]
+    }
+}
+
+/// 一个关联要提议的上下文及通过它发送的文件
+#[derive(Debug)]
+pub struct SendBatch {
+    pub contexts: Vec<(String, Vec<String>)>,
+    pub items: Vec<SendItem>,
+}
+
+/// 按表示上下文上限把文件分组, 每组一个关联
+pub fn plan_associations(items: Vec<SendItem>) -> Vec<SendBatch> {
+    let mut batches: Vec<SendBatch> = Vec::new();
+    for item in items {
+        let proposals = item.proposals();
+        let fits = batches.last().is_some_and(|batch| {
+            let new = proposals.iter().filter(|p| !batch.contexts.contains(p)).count();
+            batch.contexts.len() + new <= MAX_PRESENTATION_CONTEXTS
+        });
+        if !fits {
+            batches.push(SendBatch {
+                contexts: Vec::new(),
+                items: Vec::new(),
+            });
+        }
+        let batch = batches.last_mut().expect("batch just pushed");
+        for p in proposals {
+            if !batch.contexts.contains(&p) {
+                batch.contexts.push(p);
+            }
+        }
+        batch.items.push(item);
+    }
+    batches
+}
+
+/// 选择发送该文件的上下文: 优先原传输语法, 否则在允许时使用未压缩语法
+fn choose_context<'a>(
+    contexts: &'a [PresentationContext],
+    item: &SendItem,
+    transcode: bool,
+) -> Option<&'a PresentationContext> {
+    let same_class = || contexts.iter().filter(|pc| pc.abstract_syntax == item.sop_class);
+    same_class()
+        .find(|pc| pc.transfer_syntax == item.transfer_syntax)
+        .or_else(|| {
+            if is_native(&item.transfer_syntax) || transcode {
+                same_class().find(|pc| is_native(&pc.transfer_syntax))
+            } else {
+                None
+            }
+        })
+}
+
+/// 压缩的文件经 gdcm 解压为 Explicit VR Little Endian
+pub(crate) fn decompress(path: &Path, obj: &DefaultDicomObject) -> Result<DefaultDicomObject, DcmToolsError> {
+    let mut buffer = Vec::new();
+    obj.write_all(&mut buffer)
+        .map_err(|e| DcmToolsError::parse(path, e))?;
+    let options = TranscodeOptions::new(gdcm_conv::TransferSyntax::ExplicitVRLittleEndian);
+    let buffer = options
+        .run(buffer)
+        .map_err(|e| DcmToolsError::transcode(path, e))?;
+    // from_reader 不读取 128 字节的前导
+    let start = if buffer.get(128..132) == Some(b"DICM") { 128 } else { 0 };
+    dicom_object::from_reader(&buffer[start..]).map_err(|e| DcmToolsError::transcode(path, e))
+}
+
+/// 按上下文的传输语法编码数据集
+fn encode_data_set(
+    item: &SendItem,
+    pc: &PresentationContext,
+) -> Result<Vec<u8>, DcmToolsError> {
+    let path = item.path.as_path();
+    let mut obj = open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+    if pc.transfer_syntax != item.transfer_syntax && !is_native(&item.transfer_syntax) {
+        obj = decompress(path, &obj)?;
+    }
+    let ts = TransferSyntaxRegistry.get(&pc.transfer_syntax).ok_or_else(|| {
+        DcmToolsError::transcode(path, format!("unknown transfer syntax {}", pc.transfer_syntax))
+    })?;
+    let mut data = Vec::new();
+    obj.write_dataset_with_ts(&mut data, ts)
+        .map_err(|e| DcmToolsError::transcode(path, e))?;
+    Ok(data)
+}
+
+/// 发送一个文件, 返回对端的状态 (成功或警告);
+/// move_originator 为 C-MOVE 子操作的发起方 AE 与 Message ID
+pub fn store_one<S: std::io::Read + std::io::Write>(
+    assoc: &mut Association<S>,
+    item: &SendItem,
+    message_id: u16,
+    transcode: bool,
+    move_originator: Option<(&str, u16)>,
+) -> Result<u16, DcmToolsError> {
+    let pc = choose_context(&assoc.contexts, item, transcode)
+        .cloned()
+        .ok_or_else(|| DcmToolsError::rejected(&item.path, status::SOP_CLASS_NOT_SUPPORTED))?;
+    let data = encode_data_set(item, &pc)?;
+    let mut rq = dimse::store_request(&item.sop_class, &item.sop_instance, message_id);
+    if let Some((ae_title, move_message_id)) = move_originator {
+        dimse::set_move_originator(&mut rq, ae_title, move_message_id);
+    }
+    assoc
+        .send_message(pc.id, &rq, Some(&data))
+        .map_err(|e| DcmToolsError::network(&item.path, e))?;
+    let rsp = loop {
+        match assoc.receive_message() {
+            Ok(Incoming::Message(msg))
+                if dimse::command_field(&msg.command) == Some(C_STORE_RQ | dimse::RESPONSE_BIT) =>
+            {
+                break msg.command;
+            }
+            // C-GET 的子操作进行中, 发起方可能在同一关联上发来 C-CANCEL-RQ
+            Ok(Incoming::Message(msg)) if dimse::cancelled_message_id(&msg.command).is_some() => {
+                assoc.cancelled.extend(dimse::cancelled_message_id(&msg.command));
+            }
+            Ok(_) => return Err(DcmToolsError::network(&item.path, "expected C-STORE-RSP")),
+            Err(e) => return Err(DcmToolsError::network(&item.path, e)),
+        }
+    };
+    match dimse::status(&rsp).unwrap_or(status::PROCESSING_FAILURE) {
+        code if code == status::SUCCESS || dimse::is_warning(code) => Ok(code),
+        code => Err(DcmToolsError::rejected(&item.path, code)),
+    }
+}
+
+/// 通过一个关联发送一组文件, 每个文件一个结果; 关联失败时余下的文件都记为失败
+pub fn store_batch(args: &ScuArgs, batch: &SendBatch, transcode: bool) -> Vec<Result<u16, DcmToolsError>> {
+    let items = &batch.items;
+    let mut options = args.request_options();
+    options.contexts = batch.contexts.clone();
+    let mut assoc = match args.connect().and_then(|stream| Association::request(stream, &options)) {
+        Ok(assoc) => assoc,
+        Err(e) => {
+            let reason = e.to_string();
+            return items
+                .iter()
+                .map(|item| Err(DcmToolsError::network(&item.path, reason.clone())))
+                .collect();
+        }
+    };
+    let mut results = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let message_id = (i % u16::MAX as usize) as u16 + 1;
+        let result = store_one(&mut assoc, item, message_id, transcode, None);
+        if let Err(DcmToolsError::Network { source, .. }) = &result {
+            let reason = source.to_string();
+            results.push(result);
+            results.extend(
+                items[i + 1..]
+                    .iter()
+                    .map(|item| Err(DcmToolsError::network(&item.path, reason.clone()))),
+            );
+            let _ = assoc.abort();
+            return results;
+        }
+        results.push(result);
+    }
+    if let Err(e) = assoc.release() {
+        eprintln!("release failed: {}", e);
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(sop_class: &str, transfer_syntax: &str) -> SendItem {
+        SendItem {
+            path: PathBuf::from("a.dcm"),
+            sop_class: sop_class.to_string(),
+            sop_instance: "1.2.3".to_string(),
+            transfer_syntax: transfer_syntax.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_associations() {
+        // 未压缩的文件共用一个上下文, 压缩的文件另有未压缩的回退上下文
+        let batches = plan_associations(vec![
+            item("1.2.840.10008.5.1.4.1.1.2", "1.2.840.10008.1.2"),
+            item("1.2.840.10008.5.1.4.1.1.2", "1.2.840.10008.1.2"),
+            item("1.2.840.10008.5.1.4.1.1.2", "1.2.840.10008.1.2.4.90"),
+        ]);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].contexts.len(), 3);
+        assert_eq!(batches[0].items.len(), 3);
+
+        // 超过 128 个上下文时拆成多个关联
+        let items = (0..200)
+            .map(|i| item(&format!("1.2.3.{}", i), "1.2.840.10008.1.2.1"))
+            .collect();
+        let batches = plan_associations(items);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].contexts.len(), MAX_PRESENTATION_CONTEXTS);
+        assert_eq!(batches[1].items.len(), 200 - MAX_PRESENTATION_CONTEXTS);
+    }
+
+    #[test]
+    fn test_choose_context_fallback() {
+        let contexts = vec![PresentationContext {
+            id: 3,
+            abstract_syntax: "1.2.840.10008.5.1.4.1.1.2".to_string(),
+            transfer_syntax: "1.2.840.10008.1.2.1".to_string(),
+        }];
+        let jpeg = item("1.2.840.10008.5.1.4.1.1.2", "1.2.840.10008.1.2.4.50");
+        assert_eq!(choose_context(&contexts, &jpeg, true).map(|pc| pc.id), Some(3));
+        assert!(choose_context(&contexts, &jpeg, false).is_none());
+        let implicit = item("1.2.840.10008.5.1.4.1.1.2", "1.2.840.10008.1.2");
+        assert_eq!(choose_context(&contexts, &implicit, false).map(|pc| pc.id), Some(3));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::association::{IMPLEMENTATION_CLASS_UID, IMPLEMENTATION_VERSION_NAME};
+use crate::dimse::{self, status};
+use crate::transcode::SUPPORTED_SYNTAXES;
+use dicom::dictionary_std::tags;
+use dicom_encoding::TransferSyntaxIndex;
+use dicom_object::meta::FileMetaTableBuilder;
+use dicom_object::InMemDicomObject;
+use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Storage SOP Class 的 UID 前缀 (PS3.4 Annex B), 按前缀接受全部存储类
+pub const STORAGE_SOP_CLASS_ROOT: &str = "1.2.840.10008.5.1.4.1.1.";
+
+/// Storage SCP 接受的传输语法: transcode 支持的全部语法, 数据集原样落盘
+pub fn storage_transfer_syntaxes() -> Vec<String> {
+    SUPPORTED_SYNTAXES
+        .iter()
+        .map(|(_, _, uid)| uid.to_string())
+        .collect()
+}
+
+/// C-STORE 失败, 附带返回给 SCU 的状态码
+#[derive(Debug)]
+pub struct StoreFailure {
+    pub status: u16,
+    pub reason: String,
+}
+
+impl StoreFailure {
+    fn new(status: u16, reason: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for StoreFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04X}: {}", self.status, self.reason)
+    }
+}
+
+/// 目录名只保留一层, 防止 PatientID 等字段中的 ../ 或分隔符跳出目标目录
+fn path_component(value: &str) -> String {
+    let name: String = value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '\0' => '_',
+            c => c,
+        })
+        .collect();
+    match name.as_str() {
+        "" => "UNKNOWN".to_string(),
+        "." | ".." => name.replace('.', "_"),
+        _ => name,
+    }
+}
+
+/// 将 C-STORE 收到的数据集写入 dest/patient/study/series/sop.dcm
+///
+/// 数据集按协商的传输语法原样写入, 先写临时文件再重命名, 并发写同一实例时不会留下半个文件
+pub fn store_instance(
+    dest: &Path,
+    command: &InMemDicomObject,
+    ts_uid: &str,
+    data: &[u8],
+    source_ae: &str,
+) -> Result<PathBuf, StoreFailure> {
+    let sop_class = dimse::get_str(command, tags::AFFECTED_SOP_CLASS_UID)
+        .ok_or_else(|| StoreFailure::new(status::CANNOT_UNDERSTAND, "missing Affected SOP Class UID"))?;
+    let sop_instance = dimse::get_str(command, tags::AFFECTED_SOP_INSTANCE_UID).ok_or_else(|| {
+        StoreFailure::new(status::CANNOT_UNDERSTAND, "missing Affected SOP Instance UID")
+    })?;
+    let ts = TransferSyntaxRegistry.get(ts_uid).ok_or_else(|| {
+        StoreFailure::new(status::CANNOT_UNDERSTAND, format!("unknown transfer syntax {}", ts_uid))
+    })?;
+    let obj = InMemDicomObject::read_dataset_with_ts(data, ts)
+        .map_err(|e| StoreFailure::new(status::CANNOT_UNDERSTAND, e.to_string()))?;
+
+    let dataset_class = dimse::get_str(&obj, tags::SOP_CLASS_UID).unwrap_or_default();
+    let dataset_instance = dimse::get_str(&obj, tags::SOP_INSTANCE_UID).unwrap_or_default();
+    if dataset_class != sop_class || dataset_instance != sop_instance {
+        return Err(StoreFailure::new(
+            status::DATA_SET_DOES_NOT_MATCH,
+            format!(
+                "data set {} / {} does not match command {} / {}",
+                dataset_class, dataset_instance, sop_class, sop_instance
+            ),
+        ));
+    }
+    let study_uid = dimse::get_str(&obj, tags::STUDY_INSTANCE_UID).unwrap_or_default();
+    let series_uid = dimse::get_str(&obj, tags::SERIES_INSTANCE_UID).unwrap_or_default();
+    if study_uid.is_empty() || series_uid.is_empty() {
+        return Err(StoreFailure::new(
+            status::DATA_SET_DOES_NOT_MATCH,
+            "missing Study or Series Instance UID",
+        ));
+    }
+    let patient_id = dimse::get_str(&obj, tags::PATIENT_ID).unwrap_or_default();
+
+    let meta = FileMetaTableBuilder::new()
+        .media_storage_sop_class_uid(sop_class.as_str())
+        .media_storage_sop_instance_uid(sop_instance.as_str())
+        .transfer_syntax(ts_uid)
+        .implementation_class_uid(IMPLEMENTATION_CLASS_UID)
+        .implementation_version_name(IMPLEMENTATION_VERSION_NAME)
+        .source_application_entity_title(source_ae)
+        .build()
+        .map_err(|e| StoreFailure::new(status::PROCESSING_FAILURE, e.to_string()))?;
+
+    let target_dir = dest
+        .join(path_component(&patient_id))
+        .join(path_component(&study_uid))
+        .join(path_component(&series_uid));
+    let target_path = target_dir.join(format!("{}.dcm", path_component(&sop_instance)));
+    let out_of_resources =
+        |e: &dyn fmt::Display| StoreFailure::new(status::OUT_OF_RESOURCES, format!("{:?}: {}", target_path, e));
+    fs::create_dir_all(&target_dir).map_err(|e| out_of_resources(&e))?;
+    let mut file = tempfile::NamedTempFile::new_in(&target_dir).map_err(|e| out_of_resources(&e))?;
+    file.write_all(&[0u8; 128])
+        .and_then(|_| file.write_all(b"DICM"))
+        .map_err(|e| out_of_resources(&e))?;
+    meta.write(&mut file).map_err(|e| out_of_resources(&e))?;
+    file.write_all(data)
+        .and_then(|_| file.as_file().sync_all())
+        .map_err(|e| out_of_resources(&e))?;
+    file.persist(&target_path)
+        .map_err(|e| out_of_resources(&e.error))?;
+    Ok(target_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dimse::C_STORE_RQ;
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+
+    fn dataset(sop_instance: &str) -> Vec<u8> {
+        let mut obj = InMemDicomObject::new_empty();
+        for (tag, value) in [
+            (tags::SOP_CLASS_UID, "1.2.840.10008.5.1.4.1.1.7"),
+            (tags::SOP_INSTANCE_UID, sop_instance),
+            (tags::STUDY_INSTANCE_UID, "1.2.3"),
+            (tags::SERIES_INSTANCE_UID, "1.2.3.4"),
+        ] {
+            obj.put(DataElement::new(tag, VR::UI, PrimitiveValue::from(value)));
+        }
+        obj.put(DataElement::new(tags::PATIENT_ID, VR::LO, PrimitiveValue::from("../../etc")));
+        let mut data = Vec::new();
+        obj.write_dataset_with_ts(&mut data, &dicom_transfer_syntax_registry::entries::EXPLICIT_VR_LITTLE_ENDIAN.erased())
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_store_instance_layout_and_status() {
+        let dest = tempfile::tempdir().unwrap();
+        let mut command = dimse::request(C_STORE_RQ, "1.2.840.10008.5.1.4.1.1.7", 1, true);
+        command.put(DataElement::new(tags::AFFECTED_SOP_INSTANCE_UID, VR::UI, PrimitiveValue::from("1.2.3.4.5")));
+
+        let path = store_instance(dest.path(), &command, "1.2.840.10008.1.2.1", &dataset("1.2.3.4.5"), "SCU").unwrap();
+        assert_eq!(path, dest.path().join(".._.._etc").join("1.2.3").join("1.2.3.4").join("1.2.3.4.5.dcm"));
+        let obj = dicom_object::open_file(&path).unwrap();
+        assert_eq!(obj.meta().transfer_syntax(), "1.2.840.10008.1.2.1");
+        assert_eq!(obj.meta().media_storage_sop_instance_uid(), "1.2.3.4.5");
+
+        let err = store_instance(dest.path(), &command, "1.2.840.10008.1.2.1", &dataset("9.9"), "SCU").unwrap_err();
+        assert_eq!(err.status, status::DATA_SET_DOES_NOT_MATCH);
+        let err = store_instance(dest.path(), &command, "1.2.840.10008.1.2.1", b"garbage", "SCU").unwrap_err();
+        assert_eq!(err.status, status::CANNOT_UNDERSTAND);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::DcmToolsError;
+use crate::render::{RenderedFrame, Renderer, decode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+/// 缩略图默认的最长边 (像素)
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 128;
+/// JPEG 缩略图的质量
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Png,
+}
+
+impl ThumbnailFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpg",
+            ThumbnailFormat::Png => "png",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "image/jpeg",
+            ThumbnailFormat::Png => "image/png",
+        }
+    }
+}
+
+/// 缩略图写到目录 (清单中记录路径), 或以 data URI 内嵌在清单中
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThumbnailTarget {
+    Directory(PathBuf),
+    Inline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailOptions {
+    pub target: ThumbnailTarget,
+    pub format: ThumbnailFormat,
+    /// 最长边, 较小的图像不放大
+    pub size: u32,
+}
+
+impl ThumbnailOptions {
+    /// 渲染文件的中间帧 (按文件中的窗口) 并缩小, 返回编码后的图像
+    pub fn encode(&self, path: &Path) -> Result<Vec<u8>, DcmToolsError> {
+        let obj = dicom_object::open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+        let decoded = decode(path, &obj)?;
+        let frame = decoded.number_of_frames().max(1) / 2;
+        let rendered = Renderer::default()
+            .render_frame(&obj, &decoded, frame)
+            .map_err(|e| DcmToolsError::transcode(path, e))?;
+        let thumbnail = shrink(&rendered, self.size);
+        match self.format {
+            ThumbnailFormat::Jpeg => thumbnail.encode_jpeg(JPEG_QUALITY).map_err(|e| DcmToolsError::transcode(path, e)),
+            ThumbnailFormat::Png => {
+                let mut buffer = Vec::new();
+                thumbnail
+                    .encode_png(&mut buffer)
+                    .map_err(|e| DcmToolsError::transcode(path, e))?;
+                Ok(buffer)
+            }
+        }
+    }
+
+    /// 生成 series 的缩略图, 返回清单中引用它的字符串: 文件路径或 data URI
+    pub fn thumbnail(&self, path: &Path, series_uid: &str) -> Result<String, DcmToolsError> {
+        let image = self.encode(path)?;
+        match &self.target {
+            ThumbnailTarget::Directory(dir) => {
+                std::fs::create_dir_all(dir).map_err(|e| DcmToolsError::write(dir, e))?;
+                let target = dir.join(format!("{}.{}", series_uid, self.format.extension()));
+                std::fs::write(&target, image).map_err(|e| DcmToolsError::write(&target, e))?;
+                Ok(target.to_string_lossy().into_owned())
+            }
+            ThumbnailTarget::Inline => Ok(format!("data:{};base64,{}", self.format.mime_type(), STANDARD.encode(image))),
+        }
+    }
+}
+
+/// 按面积平均缩小到最长边不超过 size
+fn shrink(frame: &RenderedFrame, size: u32) -> RenderedFrame {
+    let longest = frame.width.max(frame.height);
+    if longest <= size {
+        return frame.clone();
+    }
+    let width = (frame.width as u64 * size as u64 / longest as u64).max(1) as u32;
+    let height = (frame.height as u64 * size as u64 / longest as u64).max(1) as u32;
+    let channels = frame.channels as usize;
+    let span = |out: u32, total_out: u32, total_in: u32| {
+        let start = out as u64 * total_in as u64 / total_out as u64;
+        let end = ((out as u64 + 1) * total_in as u64 / total_out as u64).max(start + 1);
+        start as usize..end as usize
+    };
+    let mut samples = Vec::with_capacity(width as usize * height as usize * channels);
+    for y in 0..height {
+        let rows = span(y, height, frame.height);
+        for x in 0..width {
+            let columns = span(x, width, frame.width);
+            for channel in 0..channels {
+                let mut sum = 0u64;
+                for row in rows.clone() {
+                    for column in columns.clone() {
+                        sum += frame.samples[(row * frame.width as usize + column) * channels + channel] as u64;
+                    }
+                }
+                let count = (rows.len() * columns.len()) as u64;
+                samples.push(((sum + count / 2) / count) as u16);
+            }
+        }
+    }
+    RenderedFrame {
+        width,
+        height,
+        channels: frame.channels,
+        bits: frame.bits,
+        samples,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shrink() {
+        let frame = RenderedFrame {
+            width: 4,
+            height: 2,
+            channels: 1,
+            bits: 8,
+            samples: vec![0, 10, 20, 30, 40, 50, 60, 70],
+        };
+        let small = shrink(&frame, 2);
+        assert_eq!((small.width, small.height), (2, 1));
+        assert_eq!(small.samples, vec![25, 45]);
+        assert_eq!(shrink(&frame, 8).samples, frame.samples);
+    }
+}
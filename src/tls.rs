@@ -0,0 +1,200 @@
+use crate::association::{NetError, PollRead};
+use clap::Args;
+use rustls::crypto::{CryptoProvider, ring};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    ClientConfig, ClientConnection, ConnectionCommon, RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// TLS 参数, SCU 与 SCP 共用
+#[derive(Debug, Clone, Default, Args)]
+pub struct TlsArgs {
+    /// 启用 TLS (BCP 195 profile: TLS 1.2/1.3, 仅 ECDHE + AEAD 套件)
+    #[arg(long = "tls")]
+    pub enabled: bool,
+    /// 本端证书链 (PEM), SCP 必需; SCU 指定时作为客户端证书 (双向 TLS)
+    #[arg(long = "tls-cert", requires_all = ["enabled", "key"])]
+    pub cert: Option<PathBuf>,
+    /// 本端私钥 (PEM)
+    #[arg(long = "tls-key", requires_all = ["enabled", "cert"])]
+    pub key: Option<PathBuf>,
+    /// 信任的 CA 证书 (PEM); SCU 用来验证对端, SCP 指定时要求并验证客户端证书
+    #[arg(long = "tls-ca", requires = "enabled")]
+    pub ca: Option<PathBuf>,
+    /// 验证服务器证书时使用的名称, 默认为 --host (仅 SCU)
+    #[arg(long = "tls-server-name", requires = "enabled")]
+    pub server_name: Option<String>,
+}
+
+/// BCP 195 (RFC 9325) 允许的密码套件
+fn bcp195_provider() -> Arc<CryptoProvider> {
+    use ring::cipher_suite::*;
+    Arc::new(CryptoProvider {
+        cipher_suites: vec![
+            TLS13_AES_256_GCM_SHA384,
+            TLS13_AES_128_GCM_SHA256,
+            TLS13_CHACHA20_POLY1305_SHA256,
+            TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+            TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+            TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+        ],
+        ..ring::default_provider()
+    })
+}
+
+fn tls_error(path: &Path, e: impl std::fmt::Display) -> NetError {
+    NetError::Tls(format!("{}: {}", path.display(), e))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, NetError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| tls_error(path, e))?;
+    if certs.is_empty() {
+        return Err(tls_error(path, "no certificate found"));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, NetError> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| tls_error(path, e))
+}
+
+fn load_roots(path: &Path) -> Result<Arc<RootCertStore>, NetError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| tls_error(path, e))?;
+    }
+    Ok(Arc::new(roots))
+}
+
+impl TlsArgs {
+    /// 本端证书与私钥
+    fn identity(&self) -> Result<Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>, NetError> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => Ok(Some((load_certs(cert)?, load_key(key)?))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>, NetError> {
+        let ca = self
+            .ca
+            .as_deref()
+            .ok_or_else(|| NetError::Tls("--tls-ca is required to verify the peer".to_string()))?;
+        let builder = ClientConfig::builder_with_provider(bcp195_provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| NetError::Tls(e.to_string()))?
+            .with_root_certificates(load_roots(ca)?);
+        let config = match self.identity()? {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| NetError::Tls(e.to_string()))?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Arc::new(config))
+    }
+
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>, NetError> {
+        let (certs, key) = self
+            .identity()?
+            .ok_or_else(|| NetError::Tls("--tls-cert and --tls-key are required".to_string()))?;
+        let provider = bcp195_provider();
+        let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| NetError::Tls(e.to_string()))?;
+        let builder = match &self.ca {
+            Some(ca) => builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder_with_provider(load_roots(ca)?, provider)
+                    .build()
+                    .map_err(|e| NetError::Tls(e.to_string()))?,
+            ),
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| NetError::Tls(e.to_string()))?;
+        Ok(Arc::new(config))
+    }
+}
+
+/// 关联使用的字节流: 明文 TCP 或 TLS
+pub enum NetStream {
+    Plain(TcpStream),
+    Client(Box<StreamOwned<ClientConnection, TcpStream>>),
+    Server(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+impl NetStream {
+    /// SCU: 在已建立的 TCP 连接上完成 TLS 握手
+    pub fn client(mut sock: TcpStream, config: Arc<ClientConfig>, server_name: &str) -> Result<Self, NetError> {
+        let name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| NetError::Tls(format!("{}: {}", server_name, e)))?;
+        let mut conn = ClientConnection::new(config, name).map_err(|e| NetError::Tls(e.to_string()))?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut sock)?;
+        }
+        Ok(NetStream::Client(Box::new(StreamOwned::new(conn, sock))))
+    }
+
+    /// SCP: 在接受的 TCP 连接上完成 TLS 握手
+    pub fn server(mut sock: TcpStream, config: Arc<ServerConfig>) -> Result<Self, NetError> {
+        let mut conn = ServerConnection::new(config).map_err(|e| NetError::Tls(e.to_string()))?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut sock)?;
+        }
+        Ok(NetStream::Server(Box::new(StreamOwned::new(conn, sock))))
+    }
+}
+
+impl Read for NetStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            NetStream::Plain(s) => s.read(buf),
+            NetStream::Client(s) => s.read(buf),
+            NetStream::Server(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for NetStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            NetStream::Plain(s) => s.write(buf),
+            NetStream::Client(s) => s.write(buf),
+            NetStream::Server(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            NetStream::Plain(s) => s.flush(),
+            NetStream::Client(s) => s.flush(),
+            NetStream::Server(s) => s.flush(),
+        }
+    }
+}
+
+/// rustls 中已解密但还未读取的数据
+fn has_plaintext<D>(conn: &mut ConnectionCommon<D>) -> io::Result<bool> {
+    conn.process_new_packets()
+        .map(|state| state.plaintext_bytes_to_read() > 0)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl PollRead for NetStream {
+    fn has_input(&mut self) -> io::Result<bool> {
+        match self {
+            NetStream::Plain(s) => s.has_input(),
+            NetStream::Client(s) => Ok(has_plaintext(&mut s.conn)? || s.sock.has_input()?),
+            NetStream::Server(s) => Ok(has_plaintext(&mut s.conn)? || s.sock.has_input()?),
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use clap::{Args, ValueEnum};
+use gdcm_conv::{GDCMError, PhotometricInterpretation, TransferSyntax};
+
+/// 未指定时 JPEG 有损压缩使用的质量
+pub const DEFAULT_JPEG_QUALITY: u32 = 90;
+/// 未指定时 JPEG-LS near-lossless 允许的误差
+pub const DEFAULT_JPEG_LS_ALLOW_ERROR: u32 = 2;
+
+/// 支持的传输语法: (名称, 别名, UID)
+pub const SUPPORTED_SYNTAXES: &[(&str, &[&str], &str)] = &[
+    ("implicit-vr-little-endian", &["implicit"], "1.2.840.10008.1.2"),
+    ("explicit-vr-little-endian", &["explicit"], "1.2.840.10008.1.2.1"),
+    ("explicit-vr-big-endian", &["big-endian"], "1.2.840.10008.1.2.2"),
+    ("rle-lossless", &["rle"], "1.2.840.10008.1.2.5"),
+    ("jpeg-baseline", &["jpeg"], "1.2.840.10008.1.2.4.50"),
+    ("jpeg-extended", &[], "1.2.840.10008.1.2.4.51"),
+    ("jpeg-lossless", &[], "1.2.840.10008.1.2.4.57"),
+    ("jpeg-lossless-sv1", &[], "1.2.840.10008.1.2.4.70"),
+    ("jpeg-ls-lossless", &["jls"], "1.2.840.10008.1.2.4.80"),
+    ("jpeg-ls-near-lossless", &["jls-near"], "1.2.840.10008.1.2.4.81"),
+    ("jpeg2000-lossless", &["j2k-lossless"], "1.2.840.10008.1.2.4.90"),
+    ("jpeg2000", &["j2k"], "1.2.840.10008.1.2.4.91"),
+    ("jpeg2000-part2-lossless", &["j2k-part2-lossless"], "1.2.840.10008.1.2.4.92"),
+    ("jpeg2000-part2", &["j2k-part2"], "1.2.840.10008.1.2.4.93"),
+    ("mpeg2-main-profile", &["mpeg2"], "1.2.840.10008.1.2.4.100"),
+];
+
+/// 按名称或 UID 解析传输语法, 有损参数取默认值 (用作 clap value_parser)
+pub fn parse_transfer_syntax(value: &str) -> Result<TransferSyntax, String> {
+    let key = value.trim().to_ascii_lowercase().replace(['_', ' '], "-");
+    if key == "none" {
+        return Ok(TransferSyntax::None);
+    }
+    let name = SUPPORTED_SYNTAXES
+        .iter()
+        .find(|(name, aliases, uid)| *name == key || aliases.contains(&key.as_str()) || *uid == key)
+        .map(|(name, _, _)| *name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = SUPPORTED_SYNTAXES.iter().map(|(n, _, _)| *n).collect();
+            format!(
+                "unsupported transfer syntax: {} (expected one of: {})",
+                value,
+                names.join(", ")
+            )
+        })?;
+    let ts = match name {
+        "implicit-vr-little-endian" => TransferSyntax::ImplicitVRLittleEndian,
+        "explicit-vr-little-endian" => TransferSyntax::ExplicitVRLittleEndian,
+        "explicit-vr-big-endian" => TransferSyntax::ExplicitVRBigEndian,
+        "rle-lossless" => TransferSyntax::RLELossless,
+        "jpeg-baseline" => TransferSyntax::JPEGBaselineProcess1(DEFAULT_JPEG_QUALITY),
+        "jpeg-extended" => TransferSyntax::JPEGExtendedProcess2_4(DEFAULT_JPEG_QUALITY),
+        "jpeg-lossless" => TransferSyntax::JPEGLosslessProcess14,
+        "jpeg-lossless-sv1" => TransferSyntax::JPEGLosslessProcess14_1,
+        "jpeg-ls-lossless" => TransferSyntax::JPEGLSLossless,
+        "jpeg-ls-near-lossless" => TransferSyntax::JPEGLSNearLossless(DEFAULT_JPEG_LS_ALLOW_ERROR),
+        "jpeg2000-lossless" => TransferSyntax::JPEG2000Lossless,
+        "jpeg2000" => TransferSyntax::JPEG2000(0, 0, 0, false),
+        "jpeg2000-part2-lossless" => TransferSyntax::JPEG2000Part2Lossless,
+        "jpeg2000-part2" => TransferSyntax::JPEG2000Part2(0, 0, 0, false),
+        _ => TransferSyntax::MPEG2MainProfile,
+    };
+    Ok(ts)
+}
+
+/// 传输语法对应的 UID, `TransferSyntax::None` 返回 None
+pub fn transfer_syntax_uid(ts: TransferSyntax) -> Option<&'static str> {
+    let name = match ts {
+        TransferSyntax::None => return None,
+        TransferSyntax::ImplicitVRLittleEndian => "implicit-vr-little-endian",
+        TransferSyntax::ExplicitVRLittleEndian => "explicit-vr-little-endian",
+        TransferSyntax::ExplicitVRBigEndian => "explicit-vr-big-endian",
+        TransferSyntax::RLELossless => "rle-lossless",
+        TransferSyntax::JPEGBaselineProcess1(_) => "jpeg-baseline",
+        TransferSyntax::JPEGExtendedProcess2_4(_) => "jpeg-extended",
+        TransferSyntax::JPEGLosslessProcess14 => "jpeg-lossless",
+        TransferSyntax::JPEGLosslessProcess14_1 => "jpeg-lossless-sv1",
+        TransferSyntax::JPEGLSLossless => "jpeg-ls-lossless",
+        TransferSyntax::JPEGLSNearLossless(_) => "jpeg-ls-near-lossless",
+        TransferSyntax::JPEG2000Lossless => "jpeg2000-lossless",
+        TransferSyntax::JPEG2000(..) => "jpeg2000",
+        TransferSyntax::JPEG2000Part2Lossless => "jpeg2000-part2-lossless",
+        TransferSyntax::JPEG2000Part2(..) => "jpeg2000-part2",
+        TransferSyntax::MPEG2MainProfile => "mpeg2-main-profile",
+    };
+    SUPPORTED_SYNTAXES
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, _, uid)| *uid)
+}
+
+/// 有损压缩参数, 未设置的保持解析时的默认值
+#[derive(Debug, Default, Clone, Copy, Args)]
+pub struct LossyParams {
+    /// JPEG 质量, 或 JPEG 2000 的第一层质量
+    #[arg(long = "quality")]
+    pub quality: Option<u32>,
+    /// JPEG 2000 第二层质量
+    #[arg(long = "quality2")]
+    pub quality2: Option<u32>,
+    /// JPEG 2000 第三层质量
+    #[arg(long = "quality3")]
+    pub quality3: Option<u32>,
+    /// JPEG 2000 使用不可逆小波
+    #[arg(long = "irreversible")]
+    pub irreversible: bool,
+    /// JPEG-LS near-lossless 允许的误差
+    #[arg(long = "allow-error")]
+    pub allow_error: Option<u32>,
+}
+
+impl LossyParams {
+    pub fn apply(&self, ts: TransferSyntax) -> TransferSyntax {
+        match ts {
+            TransferSyntax::JPEGBaselineProcess1(q) => {
+                TransferSyntax::JPEGBaselineProcess1(self.quality.unwrap_or(q))
+            }
+            TransferSyntax::JPEGExtendedProcess2_4(q) => {
+                TransferSyntax::JPEGExtendedProcess2_4(self.quality.unwrap_or(q))
+            }
+            TransferSyntax::JPEGLSNearLossless(e) => {
+                TransferSyntax::JPEGLSNearLossless(self.allow_error.unwrap_or(e))
+            }
+            TransferSyntax::JPEG2000(q1, q2, q3, irr) => TransferSyntax::JPEG2000(
+                self.quality.unwrap_or(q1),
+                self.quality2.unwrap_or(q2),
+                self.quality3.unwrap_or(q3),
+                irr || self.irreversible,
+            ),
+            TransferSyntax::JPEG2000Part2(q1, q2, q3, irr) => TransferSyntax::JPEG2000Part2(
+                self.quality.unwrap_or(q1),
+                self.quality2.unwrap_or(q2),
+                self.quality3.unwrap_or(q3),
+                irr || self.irreversible,
+            ),
+            other => other,
+        }
+    }
+}
+
+/// 是否为带有损参数的传输语法
+fn has_lossy_params(ts: TransferSyntax) -> bool {
+    matches!(
+        ts,
+        TransferSyntax::JPEGBaselineProcess1(_)
+            | TransferSyntax::JPEGExtendedProcess2_4(_)
+            | TransferSyntax::JPEGLSNearLossless(_)
+            | TransferSyntax::JPEG2000(..)
+            | TransferSyntax::JPEG2000Part2(..)
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Photometric {
+    Monochrome1,
+    Monochrome2,
+    #[value(alias = "palette_color")]
+    PaletteColor,
+    Rgb,
+    Hsv,
+    Argb,
+    Cmyk,
+    #[value(alias = "ybr_full")]
+    YbrFull,
+    #[value(name = "ybr-full-422", alias = "ybr_full_422")]
+    YbrFull422,
+    #[value(name = "ybr-partial-422", alias = "ybr_partial_422")]
+    YbrPartial422,
+    #[value(name = "ybr-partial-420", alias = "ybr_partial_420")]
+    YbrPartial420,
+    #[value(alias = "ybr_ict")]
+    YbrIct,
+    #[value(alias = "ybr_rct")]
+    YbrRct,
+}
+
+impl Photometric {
+    pub fn to_gdcm(self) -> PhotometricInterpretation {
+        match self {
+            Photometric::Monochrome1 => PhotometricInterpretation::Monochrome1,
+            Photometric::Monochrome2 => PhotometricInterpretation::Monochrome2,
+            Photometric::PaletteColor => PhotometricInterpretation::PaletteColor,
+            Photometric::Rgb => PhotometricInterpretation::RGB,
+            Photometric::Hsv => PhotometricInterpretation::HSV,
+            Photometric::Argb => PhotometricInterpretation::ARGB,
+            Photometric::Cmyk => PhotometricInterpretation::CMYK,
+            Photometric::YbrFull => PhotometricInterpretation::YbrFull,
+            Photometric::YbrFull422 => PhotometricInterpretation::YbrFull422,
+            Photometric::YbrPartial422 => PhotometricInterpretation::YbrPartial422,
+            Photometric::YbrPartial420 => PhotometricInterpretation::YbrPartial420,
+            Photometric::YbrIct => PhotometricInterpretation::YbrIct,
+            Photometric::YbrRct => PhotometricInterpretation::YbrRct,
+        }
+    }
+}
+
+/// gdcm_conv::pipeline 的三段参数: 传输语法 -> 光度解释 -> 第二传输语法
+#[derive(Debug, Clone, Copy)]
+pub struct TranscodeOptions {
+    pub transfer_syntax: TransferSyntax,
+    pub photometric: Option<Photometric>,
+    pub second_transfer_syntax: TransferSyntax,
+}
+
+impl Default for TranscodeOptions {
+    fn default() -> Self {
+        Self::new(TransferSyntax::JPEG2000Lossless)
+    }
+}
+
+impl TranscodeOptions {
+    pub fn new(transfer_syntax: TransferSyntax) -> Self {
+        Self {
+            transfer_syntax,
+            photometric: None,
+            second_transfer_syntax: TransferSyntax::None,
+        }
+    }
+
+    /// 检查参数组合.
+    /// gdcm_conv 只从最后一段传输语法读取有损参数, 第一段的有损参数在有第二段时会被忽略.
+    pub fn validate(&self) -> Result<(), String> {
+        if matches!(self.transfer_syntax, TransferSyntax::None) {
+            return Err("the first transfer syntax must not be none".to_string());
+        }
+        if has_lossy_params(self.transfer_syntax)
+            && !matches!(self.second_transfer_syntax, TransferSyntax::None)
+        {
+            return Err(format!(
+                "lossy parameters of {:?} are ignored when a second transfer syntax is set",
+                self.transfer_syntax
+            ));
+        }
+        Ok(())
+    }
+
+    /// 实际传给 pipeline 的 (pre, photometric, post).
+    /// 只有一段有损转换时放到 post 段执行, 这样 quality 等参数才会生效;
+    /// 同时转换光度解释时, pre 段先解压为 Explicit VR Little Endian.
+    fn stages(&self) -> (TransferSyntax, PhotometricInterpretation, TransferSyntax) {
+        let photometric = self
+            .photometric
+            .map(Photometric::to_gdcm)
+            .unwrap_or(PhotometricInterpretation::None);
+        if matches!(self.second_transfer_syntax, TransferSyntax::None)
+            && has_lossy_params(self.transfer_syntax)
+        {
+            let pre = match self.photometric {
+                Some(_) => TransferSyntax::ExplicitVRLittleEndian,
+                None => TransferSyntax::None,
+            };
+            return (pre, photometric, self.transfer_syntax);
+        }
+        (self.transfer_syntax, photometric, self.second_transfer_syntax)
+    }
+
+    /// 最终输出文件的传输语法 UID
+    pub fn output_uid(&self) -> Option<&'static str> {
+        transfer_syntax_uid(self.second_transfer_syntax)
+            .or_else(|| transfer_syntax_uid(self.transfer_syntax))
+    }
+
+    pub fn run(&self, input_buffer: Vec<u8>) -> Result<Vec<u8>, GDCMError> {
+        let (pre, photometric, post) = self.stages();
+        gdcm_conv::pipeline(
+            // Input DICOM file buffer
+            input_buffer,
+            // Estimated Length
+            None,
+            // First Transfer Syntax conversion
+            pre,
+            // Photometric conversion
+            photometric,
+            // Second Transfer Syntax conversion
+            post,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_by_name_alias_and_uid() {
+        for (name, aliases, uid) in SUPPORTED_SYNTAXES {
+            let by_name = parse_transfer_syntax(name).unwrap();
+            let by_uid = parse_transfer_syntax(uid).unwrap();
+            assert_eq!(by_name.to_id(), by_uid.to_id());
+            assert_eq!(transfer_syntax_uid(by_name), Some(*uid));
+            for alias in aliases.iter() {
+                assert_eq!(parse_transfer_syntax(alias).unwrap().to_id(), by_name.to_id());
+            }
+        }
+        assert!(matches!(
+            parse_transfer_syntax("JPEG2000_Lossless").unwrap(),
+            TransferSyntax::JPEG2000Lossless
+        ));
+        assert!(matches!(parse_transfer_syntax("none").unwrap(), TransferSyntax::None));
+        assert!(parse_transfer_syntax("1.2.3.4").is_err());
+    }
+
+    #[test]
+    fn lossy_params_are_applied() {
+        let params = LossyParams {
+            quality: Some(75),
+            quality2: Some(40),
+            irreversible: true,
+            ..Default::default()
+        };
+        let ts = params.apply(parse_transfer_syntax("jpeg-baseline").unwrap());
+        assert!(matches!(ts, TransferSyntax::JPEGBaselineProcess1(75)));
+        let ts = params.apply(parse_transfer_syntax("j2k").unwrap());
+        assert!(matches!(ts, TransferSyntax::JPEG2000(75, 40, 0, true)));
+        let ts = LossyParams::default().apply(parse_transfer_syntax("jls-near").unwrap());
+        assert!(matches!(
+            ts,
+            TransferSyntax::JPEGLSNearLossless(DEFAULT_JPEG_LS_ALLOW_ERROR)
+        ));
+        let ts = params.apply(TransferSyntax::RLELossless);
+        assert!(matches!(ts, TransferSyntax::RLELossless));
+    }
+
+    #[test]
+    fn single_lossy_stage_runs_as_post() {
+        let options = TranscodeOptions::new(TransferSyntax::JPEGBaselineProcess1(80));
+        let (pre, _, post) = options.stages();
+        assert!(matches!(pre, TransferSyntax::None));
+        assert!(matches!(post, TransferSyntax::JPEGBaselineProcess1(80)));
+
+        let options = TranscodeOptions {
+            transfer_syntax: TransferSyntax::ExplicitVRLittleEndian,
+            photometric: Some(Photometric::Rgb),
+            second_transfer_syntax: TransferSyntax::JPEG2000Lossless,
+        };
+        let (pre, _, post) = options.stages();
+        assert!(matches!(pre, TransferSyntax::ExplicitVRLittleEndian));
+        assert!(matches!(post, TransferSyntax::JPEG2000Lossless));
+        assert_eq!(options.output_uid(), Some("1.2.840.10008.1.2.4.90"));
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn single_lossy_stage_with_photometric_decompresses_first() {
+        let options = TranscodeOptions {
+            transfer_syntax: TransferSyntax::JPEGBaselineProcess1(50),
+            photometric: Some(Photometric::Rgb),
+            second_transfer_syntax: TransferSyntax::None,
+        };
+        let (pre, photometric, post) = options.stages();
+        assert!(matches!(pre, TransferSyntax::ExplicitVRLittleEndian));
+        assert!(matches!(photometric, PhotometricInterpretation::RGB));
+        assert!(matches!(post, TransferSyntax::JPEGBaselineProcess1(50)));
+        assert_eq!(options.output_uid(), Some("1.2.840.10008.1.2.4.50"));
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn lossy_first_stage_with_second_stage_is_rejected() {
+        let options = TranscodeOptions {
+            transfer_syntax: TransferSyntax::JPEGBaselineProcess1(80),
+            photometric: None,
+            second_transfer_syntax: TransferSyntax::ExplicitVRLittleEndian,
+        };
+        assert!(options.validate().is_err());
+        assert!(TranscodeOptions::new(TransferSyntax::None).validate().is_err());
+    }
+}
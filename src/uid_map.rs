@@ -0,0 +1,234 @@
+use crate::anonymize::{Action, profile_entry};
+use crate::error::DcmToolsError;
+use dicom::core::value::{DataSetSequence, PrimitiveValue, Value};
+use dicom::core::{DataElement, Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS uid_map (
+    original TEXT PRIMARY KEY,
+    replacement TEXT NOT NULL UNIQUE
+);
+";
+
+#[derive(Debug, Default)]
+struct Inner {
+    forward: HashMap<String, String>,
+    reverse: HashMap<String, String>,
+    /// 映射文件; 每个文件处理完后在一个事务中写入新的映射, 中途失败也不会丢失已输出文件的映射
+    store: Option<(PathBuf, Connection)>,
+    /// 尚未写入映射文件的新映射
+    pending: Vec<(String, String)>,
+}
+
+/// 原 UID 与新 UID 的一一映射: 同一个原 UID 总是得到同一个新 UID;
+/// 使用映射文件 (SQLite) 时跨多次运行保持一致, 也可由新 UID 查回原 UID
+#[derive(Debug, Default)]
+pub struct UidMap {
+    inner: Mutex<Inner>,
+}
+
+impl UidMap {
+    /// 打开或创建映射文件, 载入已有的映射
+    pub fn open(path: &Path) -> Result<Self, DcmToolsError> {
+        let conn = Connection::open(path).map_err(|e| DcmToolsError::database(path, e))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| DcmToolsError::database(path, e))?;
+        let mut inner = Inner::default();
+        {
+            let mut stmt = conn
+                .prepare("SELECT original, replacement FROM uid_map")
+                .map_err(|e| DcmToolsError::database(path, e))?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+                .map_err(|e| DcmToolsError::database(path, e))?;
+            for row in rows {
+                let (original, replacement) = row.map_err(|e| DcmToolsError::database(path, e))?;
+                inner.reverse.insert(replacement.clone(), original.clone());
+                inner.forward.insert(original, replacement);
+            }
+        }
+        inner.store = Some((path.to_path_buf(), conn));
+        Ok(Self { inner: Mutex::new(inner) })
+    }
+
+    /// 原 UID 对应的新 UID, 没有时用 dicom-gen-uid 生成并记录
+    pub fn map(&self, uid: &str) -> Result<String, DcmToolsError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(replacement) = inner.forward.get(uid) {
+            return Ok(replacement.clone());
+        }
+        let replacement = dicom_gen_uid::gen_uid();
+        if inner.store.is_some() {
+            inner.pending.push((uid.to_string(), replacement.clone()));
+        }
+        inner.reverse.insert(replacement.clone(), uid.to_string());
+        inner.forward.insert(uid.to_string(), replacement.clone());
+        Ok(replacement)
+    }
+
+    /// 在一个事务中把新的映射写入映射文件
+    pub fn flush(&self) -> Result<(), DcmToolsError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let Inner { store, pending, .. } = &mut *inner;
+        let Some((path, conn)) = store else { return Ok(()) };
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let db_error = |e: rusqlite::Error| DcmToolsError::database(path.as_path(), e);
+        let tx = conn.transaction().map_err(db_error)?;
+        for (original, replacement) in pending.iter() {
+            tx.execute(
+                "INSERT INTO uid_map (original, replacement) VALUES (?1, ?2)",
+                params![original, replacement],
+            )
+            .map_err(db_error)?;
+        }
+        tx.commit().map_err(db_error)?;
+        pending.clear();
+        Ok(())
+    }
+
+    /// 新 UID 对应的原 UID
+    pub fn original(&self, uid: &str) -> Option<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.reverse.get(uid).cloned()
+    }
+
+    /// 替换数据集 (含嵌套序列) 中 Table E.1-1 动作为 U 的 UID 属性, 如
+    /// Study/Series/SOP Instance UID, Frame of Reference UID, Referenced SOP Instance UID;
+    /// reverse 时换回原 UID, 映射中没有的 UID 保持不变
+    pub fn remap_dataset(&self, obj: &mut InMemDicomObject, reverse: bool) -> Result<(), DcmToolsError> {
+        let element_tags: Vec<Tag> = obj.tags().collect();
+        for tag in element_tags {
+            let Ok(element) = obj.take_element(tag) else { continue };
+            let vr = element.vr();
+            let element = if vr == VR::SQ {
+                let mut items = match element.into_value() {
+                    Value::Sequence(seq) => seq.into_items().into_vec(),
+                    _ => Vec::new(),
+                };
+                for item in &mut items {
+                    self.remap_dataset(item, reverse)?;
+                }
+                DataElement::new(tag, vr, DataSetSequence::from(items))
+            } else if vr == VR::UI && profile_entry(tag).is_some_and(|entry| entry.action == Action::U) {
+                let mut uids = Vec::new();
+                for uid in element.to_multi_str().map(|uids| uids.to_vec()).unwrap_or_default() {
+                    let uid = uid.trim_end_matches('\0');
+                    uids.push(if reverse {
+                        self.original(uid).unwrap_or_else(|| uid.to_string())
+                    } else {
+                        self.map(uid)?
+                    });
+                }
+                DataElement::new(tag, vr, PrimitiveValue::Strs(uids.into()))
+            } else {
+                element
+            };
+            obj.put(element);
+        }
+        Ok(())
+    }
+
+    /// 替换文件中的 UID, 文件元信息中的 SOP Instance UID 随之更新
+    pub fn remap_file(&self, mut obj: DefaultDicomObject, reverse: bool) -> Result<DefaultDicomObject, DcmToolsError> {
+        self.remap_dataset(&mut obj, reverse)?;
+        let sop_uid = obj
+            .element_opt(tags::SOP_INSTANCE_UID)
+            .ok()
+            .flatten()
+            .and_then(|e| e.to_str().ok())
+            .map(|s| s.trim_end_matches('\0').to_string());
+        if let Some(uid) = sop_uid {
+            obj.update_meta(|meta| meta.media_storage_sop_instance_uid = uid);
+        }
+        self.flush()?;
+        Ok(obj)
+    }
+}
+
+impl Drop for UidMap {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("Failed to save UID mappings: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persisted_uid_map() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("uids.db");
+        let first = UidMap::open(&path).unwrap();
+        let study = first.map("1.2.3").unwrap();
+        assert_eq!(first.map("1.2.3").unwrap(), study);
+        assert_ne!(first.map("1.2.4").unwrap(), study);
+        drop(first);
+
+        // 再次运行得到同样的新 UID, 并可查回原 UID
+        let second = UidMap::open(&path).unwrap();
+        assert_eq!(second.map("1.2.3").unwrap(), study);
+        assert_eq!(second.original(&study).as_deref(), Some("1.2.3"));
+        assert_eq!(second.original("9.9.9"), None);
+    }
+
+    #[test]
+    fn test_mappings_written_on_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("uids.db");
+        let uids = UidMap::open(&path).unwrap();
+        let study = uids.map("1.2.3").unwrap();
+        let series = uids.map("1.2.3.1").unwrap();
+        let count = |conn: &Connection| conn.query_row("SELECT COUNT(*) FROM uid_map", [], |row| row.get::<_, i64>(0)).unwrap();
+        let reader = Connection::open(&path).unwrap();
+        assert_eq!(count(&reader), 0);
+        uids.flush().unwrap();
+        assert_eq!(count(&reader), 2);
+        // 再次 flush 不会重复写入
+        uids.flush().unwrap();
+        assert_eq!(count(&reader), 2);
+        drop(uids);
+        let reopened = UidMap::open(&path).unwrap();
+        assert_eq!(reopened.original(&study).as_deref(), Some("1.2.3"));
+        assert_eq!(reopened.original(&series).as_deref(), Some("1.2.3.1"));
+    }
+
+    #[test]
+    fn test_remap_file_round_trip() {
+        let mut obj = dicom_object::open_file("./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm").unwrap();
+        let referenced = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::REFERENCED_SOP_CLASS_UID, VR::UI, PrimitiveValue::from("1.2.840.10008.5.1.4.1.1.2")),
+            DataElement::new(tags::REFERENCED_SOP_INSTANCE_UID, VR::UI, PrimitiveValue::from("1.2.3.4")),
+        ]);
+        obj.put(DataElement::new(tags::REFERENCED_IMAGE_SEQUENCE, VR::SQ, DataSetSequence::from(vec![referenced])));
+        let text = |obj: &InMemDicomObject, tag| obj.element(tag).unwrap().to_str().unwrap().trim_end_matches('\0').to_string();
+        let sop_uid = text(&obj, tags::SOP_INSTANCE_UID);
+        let sop_class = text(&obj, tags::SOP_CLASS_UID);
+
+        let uids = UidMap::default();
+        let remapped = uids.remap_file(obj, false).unwrap();
+        assert_eq!(text(&remapped, tags::SOP_INSTANCE_UID), uids.map(&sop_uid).unwrap());
+        assert_eq!(remapped.meta().media_storage_sop_instance_uid(), uids.map(&sop_uid).unwrap());
+        assert_eq!(text(&remapped, tags::SOP_CLASS_UID), sop_class);
+        let item = &remapped.element(tags::REFERENCED_IMAGE_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(text(item, tags::REFERENCED_SOP_INSTANCE_UID), uids.map("1.2.3.4").unwrap());
+        assert_eq!(text(item, tags::REFERENCED_SOP_CLASS_UID), "1.2.840.10008.5.1.4.1.1.2");
+
+        let restored = uids.remap_file(remapped, true).unwrap();
+        assert_eq!(text(&restored, tags::SOP_INSTANCE_UID), sop_uid);
+        assert_eq!(restored.meta().media_storage_sop_instance_uid(), sop_uid);
+        let item = &restored.element(tags::REFERENCED_IMAGE_SEQUENCE).unwrap().items().unwrap()[0];
+        assert_eq!(text(item, tags::REFERENCED_SOP_INSTANCE_UID), "1.2.3.4");
+    }
+}
@@ -0,0 +1,998 @@
+use crate::dcm_meta::DcmMapMeta;
+use crate::error::DcmToolsError;
+use dicom::core::dictionary::{DataDictionary, DataDictionaryEntry, VirtualVr};
+use dicom::core::value::{PrimitiveValue, Value};
+use dicom::core::header::Header;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags, uids};
+use dicom_object::mem::InMemElement;
+use dicom_object::InMemDicomObject;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// 一条检查结果; tag 形如 (0008,1140)[0].(0008,1150), 指向嵌套序列中的属性
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<&'static str>,
+    pub message: String,
+}
+
+/// 单个文件的检查报告
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub path: PathBuf,
+    pub sop_class_uid: String,
+    /// 按 SOP Class 选用的 IOD, 不支持的 SOP Class 只检查通用模块
+    pub iod: Option<&'static str>,
+    pub errors: usize,
+    pub warnings: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    fn push(&mut self, severity: Severity, tag: Option<String>, module: Option<&'static str>, message: String) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        self.findings.push(Finding { severity, tag, module, message });
+    }
+
+    fn error(&mut self, tag: Tag, module: &'static str, message: String) {
+        self.push(Severity::Error, Some(tag_label(tag)), Some(module), message);
+    }
+}
+
+type Condition = fn(&InMemDicomObject) -> bool;
+
+/// 属性类型 (PS3.5 7.4); 1C/2C 的条件成立时分别按 1/2 检查
+#[derive(Clone, Copy)]
+enum Type {
+    One,
+    OneC(Condition),
+    Two,
+    TwoC(Condition),
+    Three,
+}
+
+impl Type {
+    fn label(self) -> &'static str {
+        match self {
+            Type::One => "1",
+            Type::OneC(_) => "1C",
+            Type::Two => "2",
+            Type::TwoC(_) => "2C",
+            Type::Three => "3",
+        }
+    }
+}
+
+/// 模块中的属性: 类型, VM 范围 (u32::MAX 表示 n) 和枚举值
+struct Attribute {
+    tag: Tag,
+    kind: Type,
+    vm: (u32, u32),
+    values: &'static [&'static str],
+}
+
+const N: u32 = u32::MAX;
+
+const fn a(tag: Tag, kind: Type) -> Attribute {
+    Attribute { tag, kind, vm: (1, 1), values: &[] }
+}
+
+impl Attribute {
+    const fn vm(self, min: u32, max: u32) -> Self {
+        Attribute { vm: (min, max), ..self }
+    }
+
+    const fn values(self, values: &'static [&'static str]) -> Self {
+        Attribute { values, ..self }
+    }
+}
+
+struct Module {
+    name: &'static str,
+    attributes: &'static [Attribute],
+}
+
+struct Iod {
+    name: &'static str,
+    sop_classes: &'static [&'static str],
+    /// General Series Module 中 Modality 的限定值
+    modality: Option<&'static str>,
+    modules: &'static [&'static Module],
+}
+
+const MONOCHROME: &[&str] = &["MONOCHROME1", "MONOCHROME2"];
+
+static PATIENT: Module = Module {
+    name: "Patient",
+    attributes: &[
+        a(tags::PATIENT_NAME, Type::Two),
+        a(tags::PATIENT_ID, Type::Two),
+        a(tags::PATIENT_BIRTH_DATE, Type::Two),
+        a(tags::PATIENT_SEX, Type::Two).values(&["M", "F", "O"]),
+        a(tags::PATIENT_IDENTITY_REMOVED, Type::Three).values(&["YES", "NO"]),
+    ],
+};
+
+static GENERAL_STUDY: Module = Module {
+    name: "General Study",
+    attributes: &[
+        a(tags::STUDY_INSTANCE_UID, Type::One),
+        a(tags::STUDY_DATE, Type::Two),
+        a(tags::STUDY_TIME, Type::Two),
+        a(tags::REFERRING_PHYSICIAN_NAME, Type::Two),
+        a(tags::STUDY_ID, Type::Two),
+        a(tags::ACCESSION_NUMBER, Type::Two),
+    ],
+};
+
+static GENERAL_SERIES: Module = Module {
+    name: "General Series",
+    attributes: &[
+        a(tags::MODALITY, Type::One),
+        a(tags::SERIES_INSTANCE_UID, Type::One),
+        a(tags::SERIES_NUMBER, Type::Two),
+        a(tags::LATERALITY, Type::Three).values(&["R", "L"]),
+        a(tags::PATIENT_POSITION, Type::TwoC(is_ct_or_mr)).values(&[
+            "HFP", "HFS", "HFDR", "HFDL", "HFV", "HFI", "FFDR", "FFDL", "FFP", "FFS", "FFV", "FFI", "LFP", "LFS",
+            "RFP", "RFS", "AFDR", "AFDL", "PFDR", "PFDL",
+        ]),
+    ],
+};
+
+static FRAME_OF_REFERENCE: Module = Module {
+    name: "Frame of Reference",
+    attributes: &[
+        a(tags::FRAME_OF_REFERENCE_UID, Type::One),
+        a(tags::POSITION_REFERENCE_INDICATOR, Type::Two),
+    ],
+};
+
+static GENERAL_EQUIPMENT: Module = Module {
+    name: "General Equipment",
+    attributes: &[a(tags::MANUFACTURER, Type::Two)],
+};
+
+static GENERAL_IMAGE: Module = Module {
+    name: "General Image",
+    attributes: &[
+        a(tags::INSTANCE_NUMBER, Type::Two),
+        a(tags::PATIENT_ORIENTATION, Type::TwoC(no_image_orientation)).vm(2, 2),
+        a(tags::IMAGE_TYPE, Type::Three).vm(2, N),
+        a(tags::IMAGE_LATERALITY, Type::Three).values(&["R", "L", "U", "B"]),
+        a(tags::BURNED_IN_ANNOTATION, Type::Three).values(&["YES", "NO"]),
+        a(tags::LOSSY_IMAGE_COMPRESSION, Type::Three).values(&["00", "01"]),
+        a(tags::PRESENTATION_LUT_SHAPE, Type::Three).values(&["IDENTITY", "INVERSE"]),
+    ],
+};
+
+static IMAGE_PLANE: Module = Module {
+    name: "Image Plane",
+    attributes: &[
+        a(tags::PIXEL_SPACING, Type::One).vm(2, 2),
+        a(tags::IMAGE_ORIENTATION_PATIENT, Type::One).vm(6, 6),
+        a(tags::IMAGE_POSITION_PATIENT, Type::One).vm(3, 3),
+        a(tags::SLICE_THICKNESS, Type::Two),
+    ],
+};
+
+static IMAGE_PIXEL: Module = Module {
+    name: "Image Pixel",
+    attributes: &[
+        a(tags::SAMPLES_PER_PIXEL, Type::One),
+        a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(&[
+            "MONOCHROME1",
+            "MONOCHROME2",
+            "PALETTE COLOR",
+            "RGB",
+            "YBR_FULL",
+            "YBR_FULL_422",
+            "YBR_PARTIAL_420",
+            "YBR_ICT",
+            "YBR_RCT",
+            "XYB",
+        ]),
+        a(tags::ROWS, Type::One),
+        a(tags::COLUMNS, Type::One),
+        a(tags::BITS_ALLOCATED, Type::One),
+        a(tags::BITS_STORED, Type::One),
+        a(tags::HIGH_BIT, Type::One),
+        a(tags::PIXEL_REPRESENTATION, Type::One).values(&["0", "1"]),
+        a(tags::PIXEL_DATA, Type::OneC(no_pixel_data_provider)),
+        a(tags::PLANAR_CONFIGURATION, Type::OneC(has_multiple_samples)).values(&["0", "1"]),
+        a(tags::PIXEL_ASPECT_RATIO, Type::Three).vm(2, 2),
+        a(tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR, Type::OneC(is_palette_color)).vm(3, 3),
+        a(tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR, Type::OneC(is_palette_color)).vm(3, 3),
+        a(tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DESCRIPTOR, Type::OneC(is_palette_color)).vm(3, 3),
+        a(tags::RED_PALETTE_COLOR_LOOKUP_TABLE_DATA, Type::OneC(is_palette_color)),
+        a(tags::GREEN_PALETTE_COLOR_LOOKUP_TABLE_DATA, Type::OneC(is_palette_color)),
+        a(tags::BLUE_PALETTE_COLOR_LOOKUP_TABLE_DATA, Type::OneC(is_palette_color)),
+    ],
+};
+
+static SOP_COMMON: Module = Module {
+    name: "SOP Common",
+    attributes: &[
+        a(tags::SOP_CLASS_UID, Type::One),
+        a(tags::SOP_INSTANCE_UID, Type::One),
+        a(tags::SPECIFIC_CHARACTER_SET, Type::OneC(has_non_ascii_text)).vm(1, N),
+    ],
+};
+
+static CT_IMAGE: Module = Module {
+    name: "CT Image",
+    attributes: &[
+        a(tags::IMAGE_TYPE, Type::One).vm(2, N),
+        a(tags::SAMPLES_PER_PIXEL, Type::One).values(&["1"]),
+        a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(MONOCHROME),
+        a(tags::BITS_ALLOCATED, Type::One).values(&["16"]),
+        a(tags::BITS_STORED, Type::One).values(&["12", "13", "14", "15", "16"]),
+        a(tags::HIGH_BIT, Type::One).values(&["11", "12", "13", "14", "15"]),
+        a(tags::RESCALE_INTERCEPT, Type::One),
+        a(tags::RESCALE_SLOPE, Type::One),
+        a(tags::KVP, Type::Two),
+        a(tags::ACQUISITION_NUMBER, Type::Two),
+    ],
+};
+
+static MR_IMAGE: Module = Module {
+    name: "MR Image",
+    attributes: &[
+        a(tags::IMAGE_TYPE, Type::One).vm(2, N),
+        a(tags::SAMPLES_PER_PIXEL, Type::One).values(&["1"]),
+        a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(MONOCHROME),
+        a(tags::BITS_ALLOCATED, Type::One).values(&["16"]),
+        a(tags::SCANNING_SEQUENCE, Type::One).vm(1, N).values(&["SE", "IR", "GR", "EP", "RM"]),
+        a(tags::SEQUENCE_VARIANT, Type::One)
+            .vm(1, N)
+            .values(&["SK", "MTC", "SS", "TRSS", "SP", "MP", "OSP", "NONE"]),
+        a(tags::SCAN_OPTIONS, Type::Two).vm(1, N),
+        a(tags::MR_ACQUISITION_TYPE, Type::Two).values(&["2D", "3D"]),
+        a(tags::REPETITION_TIME, Type::TwoC(not_echo_planar)),
+        a(tags::ECHO_TIME, Type::Two),
+        a(tags::ECHO_TRAIN_LENGTH, Type::Two),
+        a(tags::INVERSION_TIME, Type::TwoC(is_inversion_recovery)),
+    ],
+};
+
+static CR_SERIES: Module = Module {
+    name: "CR Series",
+    attributes: &[a(tags::BODY_PART_EXAMINED, Type::Two), a(tags::VIEW_POSITION, Type::Two)],
+};
+
+static CR_IMAGE: Module = Module {
+    name: "CR Image",
+    attributes: &[a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(MONOCHROME)],
+};
+
+static SC_EQUIPMENT: Module = Module {
+    name: "SC Equipment",
+    attributes: &[a(tags::CONVERSION_TYPE, Type::One)],
+};
+
+static US_IMAGE: Module = Module {
+    name: "US Image",
+    attributes: &[
+        a(tags::SAMPLES_PER_PIXEL, Type::One).values(&["1", "3"]),
+        a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(&[
+            "MONOCHROME2",
+            "PALETTE COLOR",
+            "RGB",
+            "YBR_FULL",
+            "YBR_FULL_422",
+            "YBR_PARTIAL_420",
+            "YBR_ICT",
+            "YBR_RCT",
+        ]),
+        a(tags::BITS_ALLOCATED, Type::One).values(&["8", "16"]),
+        a(tags::PIXEL_REPRESENTATION, Type::One).values(&["0"]),
+        a(tags::IMAGE_TYPE, Type::Two).vm(2, 4),
+    ],
+};
+
+static MULTI_FRAME: Module = Module {
+    name: "Multi-frame",
+    attributes: &[
+        a(tags::NUMBER_OF_FRAMES, Type::One),
+        a(tags::FRAME_INCREMENT_POINTER, Type::One).vm(1, N),
+    ],
+};
+
+static DX_SERIES: Module = Module {
+    name: "DX Series",
+    attributes: &[a(tags::PRESENTATION_INTENT_TYPE, Type::One).values(&["FOR PRESENTATION", "FOR PROCESSING"])],
+};
+
+static DX_IMAGE: Module = Module {
+    name: "DX Image",
+    attributes: &[
+        a(tags::IMAGE_TYPE, Type::One).vm(2, N),
+        a(tags::SAMPLES_PER_PIXEL, Type::One).values(&["1"]),
+        a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(MONOCHROME),
+        a(tags::BITS_ALLOCATED, Type::One).values(&["8", "16"]),
+        a(tags::PIXEL_REPRESENTATION, Type::One).values(&["0"]),
+        a(tags::RESCALE_INTERCEPT, Type::One),
+        a(tags::RESCALE_SLOPE, Type::One),
+        a(tags::RESCALE_TYPE, Type::One),
+        a(tags::LOSSY_IMAGE_COMPRESSION, Type::One).values(&["00", "01"]),
+        a(tags::PRESENTATION_LUT_SHAPE, Type::OneC(is_for_presentation)).values(&["IDENTITY", "INVERSE"]),
+    ],
+};
+
+static DX_DETECTOR: Module = Module {
+    name: "DX Detector",
+    attributes: &[
+        a(tags::DETECTOR_TYPE, Type::Two).values(&["DIRECT", "SCINTILLATOR", "STORAGE", "FILM"]),
+        a(tags::IMAGER_PIXEL_SPACING, Type::One).vm(2, 2),
+    ],
+};
+
+static PET_SERIES: Module = Module {
+    name: "PET Series",
+    attributes: &[
+        a(tags::SERIES_DATE, Type::One),
+        a(tags::SERIES_TIME, Type::One),
+        a(tags::UNITS, Type::One),
+        a(tags::COUNTS_SOURCE, Type::One).values(&["EMISSION", "TRANSMISSION"]),
+        a(tags::SERIES_TYPE, Type::One).vm(2, 2),
+        a(tags::CORRECTED_IMAGE, Type::Two).vm(1, N),
+        a(tags::DECAY_CORRECTION, Type::One).values(&["NONE", "START", "ADMIN"]),
+        a(tags::NUMBER_OF_SLICES, Type::One),
+    ],
+};
+
+static PET_ISOTOPE: Module = Module {
+    name: "PET Isotope",
+    attributes: &[a(tags::RADIOPHARMACEUTICAL_INFORMATION_SEQUENCE, Type::Two)],
+};
+
+static PET_IMAGE: Module = Module {
+    name: "PET Image",
+    attributes: &[
+        a(tags::IMAGE_TYPE, Type::One).vm(2, 2),
+        a(tags::SAMPLES_PER_PIXEL, Type::One).values(&["1"]),
+        a(tags::PHOTOMETRIC_INTERPRETATION, Type::One).values(&["MONOCHROME2"]),
+        a(tags::BITS_ALLOCATED, Type::One).values(&["16"]),
+        a(tags::RESCALE_INTERCEPT, Type::One),
+        a(tags::RESCALE_SLOPE, Type::One),
+        a(tags::FRAME_REFERENCE_TIME, Type::One),
+        a(tags::IMAGE_INDEX, Type::One),
+        a(tags::ACQUISITION_DATE, Type::Two),
+        a(tags::ACQUISITION_TIME, Type::Two),
+        a(tags::ACTUAL_FRAME_DURATION, Type::Two),
+        a(tags::DECAY_FACTOR, Type::OneC(is_decay_corrected)),
+    ],
+};
+
+/// 各 IOD 的必选 (M) 模块; 可选 (U) 与条件 (C) 模块不检查
+static IODS: &[Iod] = &[
+    Iod {
+        name: "CT Image",
+        sop_classes: &[uids::CT_IMAGE_STORAGE],
+        modality: Some("CT"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &FRAME_OF_REFERENCE,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PLANE,
+            &IMAGE_PIXEL,
+            &CT_IMAGE,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "MR Image",
+        sop_classes: &[uids::MR_IMAGE_STORAGE],
+        modality: Some("MR"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &FRAME_OF_REFERENCE,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PLANE,
+            &IMAGE_PIXEL,
+            &MR_IMAGE,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "Computed Radiography Image",
+        sop_classes: &[uids::COMPUTED_RADIOGRAPHY_IMAGE_STORAGE],
+        modality: Some("CR"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &CR_SERIES,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PIXEL,
+            &CR_IMAGE,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "Secondary Capture Image",
+        sop_classes: &[uids::SECONDARY_CAPTURE_IMAGE_STORAGE],
+        modality: None,
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &SC_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PIXEL,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "US Image",
+        sop_classes: &[uids::ULTRASOUND_IMAGE_STORAGE],
+        modality: Some("US"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PIXEL,
+            &US_IMAGE,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "US Multi-frame Image",
+        sop_classes: &[uids::ULTRASOUND_MULTI_FRAME_IMAGE_STORAGE],
+        modality: Some("US"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PIXEL,
+            &MULTI_FRAME,
+            &US_IMAGE,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "Digital X-Ray Image",
+        sop_classes: &[
+            uids::DIGITAL_X_RAY_IMAGE_STORAGE_FOR_PRESENTATION,
+            uids::DIGITAL_X_RAY_IMAGE_STORAGE_FOR_PROCESSING,
+        ],
+        modality: Some("DX"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &DX_SERIES,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PIXEL,
+            &DX_IMAGE,
+            &DX_DETECTOR,
+            &SOP_COMMON,
+        ],
+    },
+    Iod {
+        name: "PET Image",
+        sop_classes: &[uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE],
+        modality: Some("PT"),
+        modules: &[
+            &PATIENT,
+            &GENERAL_STUDY,
+            &GENERAL_SERIES,
+            &PET_SERIES,
+            &PET_ISOTOPE,
+            &FRAME_OF_REFERENCE,
+            &GENERAL_EQUIPMENT,
+            &GENERAL_IMAGE,
+            &IMAGE_PLANE,
+            &IMAGE_PIXEL,
+            &PET_IMAGE,
+            &SOP_COMMON,
+        ],
+    },
+];
+
+/// 不支持的 SOP Class 只检查各 IOD 共有的模块
+static COMMON_MODULES: &[&Module] = &[&PATIENT, &GENERAL_STUDY, &GENERAL_SERIES, &GENERAL_EQUIPMENT, &SOP_COMMON];
+
+fn text(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.element_opt(tag)
+        .ok()
+        .flatten()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).trim_start().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn int(obj: &InMemDicomObject, tag: Tag) -> Option<u64> {
+    text(obj, tag).and_then(|s| s.parse().ok())
+}
+
+fn is_ct_or_mr(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::SOP_CLASS_UID).is_some_and(|uid| uid == uids::CT_IMAGE_STORAGE || uid == uids::MR_IMAGE_STORAGE)
+}
+
+fn no_image_orientation(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::IMAGE_ORIENTATION_PATIENT).is_none()
+}
+
+fn no_pixel_data_provider(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::PIXEL_DATA_PROVIDER_URL).is_none()
+}
+
+fn has_multiple_samples(obj: &InMemDicomObject) -> bool {
+    int(obj, tags::SAMPLES_PER_PIXEL).is_some_and(|samples| samples > 1)
+}
+
+fn is_palette_color(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::PHOTOMETRIC_INTERPRETATION).is_some_and(|pi| pi == "PALETTE COLOR")
+}
+
+fn not_echo_planar(obj: &InMemDicomObject) -> bool {
+    !text(obj, tags::SCANNING_SEQUENCE).is_some_and(|s| s.split('\\').any(|v| v.trim() == "EP"))
+}
+
+fn is_inversion_recovery(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::SCANNING_SEQUENCE).is_some_and(|s| s.split('\\').any(|v| v.trim() == "IR"))
+}
+
+fn is_for_presentation(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::PRESENTATION_INTENT_TYPE).is_some_and(|s| s == "FOR PRESENTATION")
+}
+
+fn is_decay_corrected(obj: &InMemDicomObject) -> bool {
+    text(obj, tags::DECAY_CORRECTION).is_some_and(|s| s != "NONE")
+}
+
+fn is_text_vr(vr: VR) -> bool {
+    matches!(vr, VR::SH | VR::LO | VR::ST | VR::LT | VR::UT | VR::UC | VR::PN)
+}
+
+/// 存在非 ASCII 文本时必须给出 Specific Character Set
+fn has_non_ascii_text(obj: &InMemDicomObject) -> bool {
+    obj.iter().any(|element| match element.value() {
+        Value::Sequence(seq) => seq.items().iter().any(has_non_ascii_text),
+        _ => is_text_vr(element.vr()) && element.to_str().is_ok_and(|s| !s.is_ascii()),
+    })
+}
+
+fn tag_label(tag: Tag) -> String {
+    format!("({:04X},{:04X})", tag.group(), tag.element())
+}
+
+fn alias(tag: Tag) -> String {
+    StandardDataDictionary
+        .by_tag(tag)
+        .map(|entry| entry.alias().to_string())
+        .unwrap_or_else(|| tag_label(tag))
+}
+
+fn values(element: &InMemElement) -> Vec<String> {
+    element
+        .to_multi_str()
+        .map(|values| values.iter().map(|v| v.trim_matches(['\0', ' ']).to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// 没有值: 长度为 0, 空序列, 或字符串只有填充
+fn is_empty(element: &InMemElement) -> bool {
+    match element.value() {
+        Value::Primitive(PrimitiveValue::Empty) => true,
+        Value::Primitive(PrimitiveValue::Str(_) | PrimitiveValue::Strs(_)) => values(element).iter().all(|v| v.is_empty()),
+        Value::Primitive(_) => false,
+        Value::Sequence(seq) => seq.items().is_empty(),
+        Value::PixelSequence(_) => false,
+    }
+}
+
+fn is_binary_vr(vr: VR) -> bool {
+    matches!(vr, VR::OB | VR::OW | VR::OF | VR::OD | VR::OL | VR::OV | VR::UN | VR::SQ)
+}
+
+fn check_module(obj: &InMemDicomObject, module: &'static Module, report: &mut Report) {
+    for attribute in module.attributes {
+        let required = match attribute.kind {
+            Type::One => Some(1),
+            Type::OneC(condition) if condition(obj) => Some(1),
+            Type::Two => Some(2),
+            Type::TwoC(condition) if condition(obj) => Some(2),
+            _ => None,
+        };
+        let Some(element) = obj.element_opt(attribute.tag).ok().flatten() else {
+            if required.is_some() {
+                let message = format!("Type {} attribute {} is missing", attribute.kind.label(), alias(attribute.tag));
+                report.error(attribute.tag, module.name, message);
+            }
+            continue;
+        };
+        if is_empty(element) {
+            if required == Some(1) {
+                let message = format!("Type {} attribute {} has no value", attribute.kind.label(), alias(attribute.tag));
+                report.error(attribute.tag, module.name, message);
+            }
+            continue;
+        }
+        if is_binary_vr(element.vr()) {
+            continue;
+        }
+        let values = values(element);
+        let (min, max) = attribute.vm;
+        let count = values.len() as u32;
+        if count < min || count > max {
+            let expected = match (min, max) {
+                (min, max) if min == max => min.to_string(),
+                (min, N) => format!("{}-n", min),
+                (min, max) => format!("{}-{}", min, max),
+            };
+            let message = format!("{} has VM {}, expected {}", alias(attribute.tag), count, expected);
+            report.error(attribute.tag, module.name, message);
+        }
+        if !attribute.values.is_empty() {
+            for value in values.iter().filter(|v| !attribute.values.contains(&v.as_str())) {
+                let message = format!(
+                    "{} value {:?} is not one of {}",
+                    alias(attribute.tag),
+                    value,
+                    attribute.values.join(", ")
+                );
+                report.error(attribute.tag, module.name, message);
+            }
+        }
+    }
+}
+
+fn vr_matches(expected: VirtualVr, vr: VR) -> bool {
+    match expected {
+        VirtualVr::Exact(expected) => expected == vr,
+        VirtualVr::Xs => matches!(vr, VR::US | VR::SS),
+        VirtualVr::Ox | VirtualVr::Px => matches!(vr, VR::OB | VR::OW),
+        VirtualVr::Lt => matches!(vr, VR::US | VR::SS | VR::OW),
+        _ => true,
+    }
+}
+
+/// 单个值的最大长度 (PS3.5 Table 6.2-1)
+fn max_length(vr: VR) -> Option<usize> {
+    match vr {
+        VR::AE | VR::CS | VR::DS | VR::SH => Some(16),
+        VR::AS => Some(4),
+        VR::DA => Some(8),
+        VR::IS => Some(12),
+        VR::TM => Some(14),
+        VR::DT => Some(26),
+        VR::LO | VR::UI => Some(64),
+        VR::ST => Some(1024),
+        VR::LT => Some(10240),
+        _ => None,
+    }
+}
+
+fn is_uid(value: &str) -> bool {
+    value
+        .split('.')
+        .all(|c| !c.is_empty() && c.bytes().all(|b| b.is_ascii_digit()) && (c == "0" || !c.starts_with('0')))
+}
+
+fn is_time(value: &str) -> bool {
+    let (hms, fraction) = match value.split_once('.') {
+        Some((hms, fraction)) => (hms, Some(fraction)),
+        None => (value, None),
+    };
+    if !hms.bytes().all(|b| b.is_ascii_digit()) || !matches!(hms.len(), 2 | 4 | 6) {
+        return false;
+    }
+    if let Some(fraction) = fraction
+        && (hms.len() != 6 || fraction.is_empty() || fraction.len() > 6 || !fraction.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return false;
+    }
+    let part = |i: usize| hms.get(i..i + 2).map(|s| s.parse::<u32>().unwrap_or(99));
+    part(0).is_some_and(|h| h < 24) && part(2).is_none_or(|m| m < 60) && part(4).is_none_or(|s| s <= 60)
+}
+
+/// 按 VR 检查单个值的格式, 返回问题描述
+fn check_format(vr: VR, value: &str) -> Option<String> {
+    if let Some(max) = max_length(vr)
+        && value.chars().count() > max
+    {
+        return Some(format!("value {:?} exceeds {} characters allowed for {}", value, max, vr));
+    }
+    let valid = match vr {
+        VR::CS => value.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b' ' || b == b'_'),
+        VR::UI => is_uid(value),
+        VR::DA => chrono::NaiveDate::parse_from_str(value, "%Y%m%d").is_ok() && value.len() == 8,
+        VR::TM => is_time(value),
+        VR::AS => {
+            // 按字节检查, 非 ASCII 的输入不会在字符边界之外切片
+            let bytes = value.as_bytes();
+            bytes.len() == 4
+                && bytes[..3].iter().all(u8::is_ascii_digit)
+                && matches!(bytes[3], b'D' | b'W' | b'M' | b'Y')
+        }
+        VR::IS => value.parse::<i64>().is_ok_and(|v| i32::try_from(v).is_ok()),
+        VR::DS => {
+            value.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'))
+                && value.parse::<f64>().is_ok_and(f64::is_finite)
+        }
+        _ => true,
+    };
+    (!valid).then(|| format!("invalid {} value {:?}", vr, value))
+}
+
+/// 检查所有标准属性 (含嵌套序列) 的 VR 与值格式; 私有属性与组长度不检查
+fn check_elements(obj: &InMemDicomObject, prefix: &str, report: &mut Report) {
+    for element in obj {
+        let tag = element.tag();
+        if tag.group() % 2 == 1 || tag.element() == 0 {
+            continue;
+        }
+        let label = format!("{}{}", prefix, tag_label(tag));
+        let vr = element.vr();
+        if let Some(entry) = StandardDataDictionary.by_tag(tag)
+            && !vr_matches(entry.vr(), vr)
+        {
+            let expected = entry.vr().exact().map(|vr| vr.to_string().to_owned()).unwrap_or_else(|| format!("{:?}", entry.vr()));
+            let severity = if vr == VR::UN { Severity::Warning } else { Severity::Error };
+            let message = format!("{} has VR {}, expected {}", entry.alias(), vr, expected);
+            report.push(severity, Some(label.clone()), None, message);
+        }
+        match element.value() {
+            Value::Sequence(seq) => {
+                for (i, item) in seq.items().iter().enumerate() {
+                    check_elements(item, &format!("{}[{}].", label, i), report);
+                }
+            }
+            Value::Primitive(PrimitiveValue::Str(_) | PrimitiveValue::Strs(_)) => {
+                for value in values(element).iter().filter(|v| !v.is_empty()) {
+                    if let Some(message) = check_format(vr, value) {
+                        report.push(Severity::Error, Some(label.clone()), None, format!("{}: {}", alias(tag), message));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 检查 Image Pixel 属性之间以及与像素数据长度的一致性
+fn check_pixels(obj: &InMemDicomObject, report: &mut Report) {
+    const MODULE: &str = "Image Pixel";
+    let (Some(rows), Some(columns), Some(samples), Some(bits_allocated), Some(bits_stored), Some(high_bit)) = (
+        int(obj, tags::ROWS),
+        int(obj, tags::COLUMNS),
+        int(obj, tags::SAMPLES_PER_PIXEL),
+        int(obj, tags::BITS_ALLOCATED),
+        int(obj, tags::BITS_STORED),
+        int(obj, tags::HIGH_BIT),
+    ) else {
+        return;
+    };
+    let meta = DcmMapMeta {
+        bit_allocated: bits_allocated as u16,
+        bits_stored: bits_stored as u16,
+        high_bit: high_bit as u16,
+    };
+    if let Err(e) = meta.validate_bits() {
+        report.error(tags::BITS_ALLOCATED, MODULE, e);
+        return;
+    }
+    let photometric = text(obj, tags::PHOTOMETRIC_INTERPRETATION).unwrap_or_default();
+    let expected_samples = match photometric.as_str() {
+        "MONOCHROME1" | "MONOCHROME2" | "PALETTE COLOR" => Some(1),
+        "" => None,
+        _ => Some(3),
+    };
+    if let Some(expected) = expected_samples
+        && samples != expected
+    {
+        let message = format!("SamplesPerPixel is {} but {} requires {}", samples, photometric, expected);
+        report.error(tags::SAMPLES_PER_PIXEL, MODULE, message);
+        return;
+    }
+    let Some(pixel_data) = obj.element_opt(tags::PIXEL_DATA).ok().flatten() else {
+        return;
+    };
+    let frames = int(obj, tags::NUMBER_OF_FRAMES).unwrap_or(1).max(1);
+    match pixel_data.value() {
+        Value::Primitive(value) => {
+            // YBR_FULL_422 未压缩时每两个像素共用一对色差样本
+            let samples = if photometric == "YBR_FULL_422" { 2 } else { samples };
+            let Some(bits) = [columns, samples, frames, bits_allocated]
+                .into_iter()
+                .try_fold(rows, |product, factor| product.checked_mul(factor))
+            else {
+                let message = format!(
+                    "Pixel Data size overflows (rows {} x columns {} x samples {} x bits allocated {} x frames {})",
+                    rows, columns, samples, bits_allocated, frames
+                );
+                report.error(tags::PIXEL_DATA, MODULE, message);
+                return;
+            };
+            let expected = bits.div_ceil(8);
+            let actual = value.calculate_byte_len() as u64;
+            if actual < expected {
+                let message = format!(
+                    "Pixel Data has {} bytes, expected {} (rows {} x columns {} x samples {} x bits allocated {} x frames {})",
+                    actual, expected, rows, columns, samples, bits_allocated, frames
+                );
+                report.error(tags::PIXEL_DATA, MODULE, message);
+            } else if actual > expected + expected % 2 {
+                let message = format!("Pixel Data has {} bytes, {} more than expected", actual, actual - expected);
+                report.push(Severity::Warning, Some(tag_label(tags::PIXEL_DATA)), Some(MODULE), message);
+            }
+        }
+        Value::PixelSequence(seq) => {
+            if seq.fragments().is_empty() {
+                report.error(tags::PIXEL_DATA, MODULE, "encapsulated Pixel Data has no fragments".to_string());
+            }
+        }
+        Value::Sequence(_) => {}
+    }
+}
+
+/// 按 SOP Class 对应的 IOD 检查数据集
+pub fn validate(path: &Path, obj: &InMemDicomObject) -> Report {
+    let sop_class_uid = text(obj, tags::SOP_CLASS_UID).unwrap_or_default();
+    let iod = IODS.iter().find(|iod| iod.sop_classes.contains(&sop_class_uid.as_str()));
+    let mut report = Report {
+        path: path.to_path_buf(),
+        sop_class_uid,
+        iod: iod.map(|iod| iod.name),
+        errors: 0,
+        warnings: 0,
+        findings: Vec::new(),
+    };
+    match iod {
+        Some(iod) => {
+            for module in iod.modules {
+                check_module(obj, module, &mut report);
+            }
+            if let (Some(expected), Some(modality)) = (iod.modality, text(obj, tags::MODALITY))
+                && modality != expected
+            {
+                let message = format!("Modality {:?} does not match {} IOD, expected {}", modality, iod.name, expected);
+                report.error(tags::MODALITY, "General Series", message);
+            }
+        }
+        None => {
+            let message = format!("SOP Class {:?} is not supported, checking common modules only", report.sop_class_uid);
+            report.push(Severity::Warning, Some(tag_label(tags::SOP_CLASS_UID)), None, message);
+            for module in COMMON_MODULES {
+                check_module(obj, module, &mut report);
+            }
+            if obj.element_opt(tags::PIXEL_DATA).ok().flatten().is_some() {
+                check_module(obj, &IMAGE_PIXEL, &mut report);
+            }
+        }
+    }
+    check_pixels(obj, &mut report);
+    check_elements(obj, "", &mut report);
+    report
+}
+
+/// 打开并检查单个文件; 文件元信息中的 SOP Class/Instance UID 需与数据集一致
+pub fn validate_file(path: &Path) -> Result<Report, DcmToolsError> {
+    let obj = dicom_object::open_file(path).map_err(|e| DcmToolsError::open(path, e))?;
+    let mut report = validate(path, &obj);
+    let meta = obj.meta();
+    let pairs = [
+        (tags::SOP_CLASS_UID, meta.media_storage_sop_class_uid(), "MediaStorageSOPClassUID"),
+        (tags::SOP_INSTANCE_UID, meta.media_storage_sop_instance_uid(), "MediaStorageSOPInstanceUID"),
+    ];
+    for (tag, meta_uid, name) in pairs {
+        let meta_uid = meta_uid.trim_end_matches(['\0', ' ']);
+        if let Some(uid) = text(&obj, tag)
+            && uid != meta_uid
+        {
+            let message = format!("{} {:?} in file meta does not match {} {:?}", name, meta_uid, alias(tag), uid);
+            report.error(tag, "SOP Common", message);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::{DataElement, dicom_value};
+
+    const TEST_FILE: &str = "./test_data/1.2.840.113713.55902.1.642704.2248.1270609320.4549.dcm";
+
+    fn messages(report: &Report) -> Vec<String> {
+        report.findings.iter().map(|f| format!("{} {}", f.tag.as_deref().unwrap_or(""), f.message)).collect()
+    }
+
+    #[test]
+    fn test_validate_ct_image() {
+        let report = validate_file(Path::new(TEST_FILE)).unwrap();
+        assert_eq!(report.iod, Some("CT Image"));
+        // 测试文件的 StudyTime/ContentTime 等是合法值, 不应报告像素或必选属性问题
+        assert!(
+            !report.findings.iter().any(|f| f.module == Some("Image Pixel") || f.module == Some("Patient")),
+            "{:?}",
+            messages(&report)
+        );
+    }
+
+    #[test]
+    fn test_validate_broken_instance() {
+        let mut obj = dicom_object::open_file(TEST_FILE).unwrap().into_inner();
+        obj.remove_element(tags::STUDY_INSTANCE_UID);
+        obj.remove_element(tags::PATIENT_ID);
+        obj.put(DataElement::new(tags::PATIENT_SEX, VR::CS, dicom_value!(Str, "MALE")));
+        obj.put(DataElement::new(tags::IMAGE_POSITION_PATIENT, VR::DS, dicom_value!(Strs, ["1", "2"])));
+        obj.put(DataElement::new(tags::STUDY_DATE, VR::DA, dicom_value!(Str, "20100230")));
+        obj.put(DataElement::new(tags::ROWS, VR::US, dicom_value!(U16, [1024])));
+        let report = validate(Path::new("broken.dcm"), &obj);
+        let messages = messages(&report);
+        let has = |needle: &str| messages.iter().any(|m| m.contains(needle));
+        assert!(has("(0020,000D) Type 1 attribute StudyInstanceUID is missing"), "{:?}", messages);
+        assert!(has("(0010,0020) Type 2 attribute PatientID is missing"), "{:?}", messages);
+        assert!(has("PatientSex value \"MALE\" is not one of M, F, O"), "{:?}", messages);
+        assert!(has("ImagePositionPatient has VM 2, expected 3"), "{:?}", messages);
+        assert!(has("invalid DA value \"20100230\""), "{:?}", messages);
+        assert!(has("(7FE0,0010) Pixel Data has 524288 bytes, expected 1048576"), "{:?}", messages);
+        assert_eq!(report.errors, report.findings.iter().filter(|f| f.severity == Severity::Error).count());
+    }
+
+    #[test]
+    fn test_pixel_length_overflow() {
+        // 帧数过大时像素数据长度溢出, 报告错误而不是 panic
+        let mut obj = dicom_object::open_file(TEST_FILE).unwrap().into_inner();
+        obj.put(DataElement::new(tags::NUMBER_OF_FRAMES, VR::IS, dicom_value!(Str, "2147483647")));
+        obj.put(DataElement::new(tags::ROWS, VR::US, dicom_value!(U16, [65535])));
+        obj.put(DataElement::new(tags::COLUMNS, VR::US, dicom_value!(U16, [65535])));
+        obj.put(DataElement::new(tags::SAMPLES_PER_PIXEL, VR::US, dicom_value!(U16, [3])));
+        obj.put(DataElement::new(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, dicom_value!(Str, "RGB")));
+        obj.put(DataElement::new(tags::BITS_ALLOCATED, VR::US, dicom_value!(U16, [32])));
+        obj.put(DataElement::new(tags::BITS_STORED, VR::US, dicom_value!(U16, [32])));
+        obj.put(DataElement::new(tags::HIGH_BIT, VR::US, dicom_value!(U16, [31])));
+        let messages = messages(&validate(Path::new("huge.dcm"), &obj));
+        assert!(messages.iter().any(|m| m.contains("Pixel Data size overflows")), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_check_format() {
+        assert_eq!(check_format(VR::UI, "1.2.840.10008.5.1.4.1.1.2"), None);
+        assert!(check_format(VR::UI, "1.2.03").is_some());
+        assert_eq!(check_format(VR::TM, "083139.0"), None);
+        assert_eq!(check_format(VR::TM, "08"), None);
+        assert!(check_format(VR::TM, "08:31:39").is_some());
+        assert_eq!(check_format(VR::AS, "053Y"), None);
+        assert!(check_format(VR::AS, "53Y").is_some());
+        // 非 ASCII 的值按字节检查, 不会 panic
+        assert!(check_format(VR::AS, "岁Y").is_some());
+        assert!(check_format(VR::AS, "0岁").is_some());
+        // 长度按字符计
+        assert_eq!(check_format(VR::LO, &"张".repeat(64)), None);
+        assert!(check_format(VR::LO, &"张".repeat(65)).is_some());
+        assert!(check_format(VR::CS, "ct").is_some());
+        assert!(check_format(VR::IS, "4294967296").is_some());
+        assert!(check_format(VR::DS, "NaN").is_some());
+        assert_eq!(check_format(VR::DS, "-1.5e3"), None);
+    }
+}